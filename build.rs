@@ -0,0 +1,16 @@
+// Copyright 2026 Aptove
+// SPDX-License-Identifier: Apache-2.0
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // Use the vendored protoc so builds don't depend on a system install
+    if std::env::var_os("PROTOC").is_none() {
+        std::env::set_var("PROTOC", protoc_bin_vendored::protoc_bin_path()?);
+    }
+
+    tonic_build::configure()
+        // Submission requests are serialized into the audit log
+        .type_attribute("xcbridge.v1.BuildRequest", "#[derive(serde::Serialize)]")
+        .type_attribute("xcbridge.v1.TestRequest", "#[derive(serde::Serialize)]")
+        .compile_protos(&["proto/xcbridge.proto"], &["proto"])?;
+    Ok(())
+}
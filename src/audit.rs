@@ -0,0 +1,150 @@
+// Copyright 2026 Aptove
+// SPDX-License-Identifier: Apache-2.0
+
+//! Append-only audit log of mutating requests
+//!
+//! Entries are written as JSON lines straight to the audit file, independent
+//! of the tracing subscriber, so log-level filters can't suppress them.
+
+use crate::state::Namespace;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::Value;
+use std::path::Path;
+use tokio::fs::{File, OpenOptions};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
+
+/// Request fields whose values are never written to the audit log
+const SECRET_FIELDS: &[&str] = &["environment", "password", "secret", "token", "api_key"];
+
+/// One audited request
+#[derive(Debug, Serialize)]
+pub struct AuditEntry {
+    pub timestamp: DateTime<Utc>,
+    /// Label of the API key used (none when authentication is disabled)
+    pub key_label: Option<String>,
+    /// e.g. "POST /build" or "grpc StartBuild"
+    pub endpoint: String,
+    /// Request parameters with secrets redacted
+    pub params: Value,
+    /// HTTP status of the response (if known)
+    pub status: Option<u16>,
+}
+
+impl AuditEntry {
+    pub fn new(namespace: &Namespace, endpoint: String, params: Value) -> Self {
+        Self {
+            timestamp: Utc::now(),
+            key_label: (!namespace.0.is_empty()).then(|| namespace.0.clone()),
+            endpoint,
+            params: redact(params),
+            status: None,
+        }
+    }
+}
+
+/// Audit file opened for appending
+pub struct AuditLog {
+    file: Mutex<File>,
+}
+
+impl AuditLog {
+    /// Open (or create) the audit file for appending
+    pub async fn open(path: &Path) -> std::io::Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .await?;
+        Ok(Self {
+            file: Mutex::new(file),
+        })
+    }
+
+    /// Append an entry, logging (rather than failing the request) on error
+    pub async fn record(&self, entry: &AuditEntry) {
+        let mut line = match serde_json::to_vec(entry) {
+            Ok(line) => line,
+            Err(e) => {
+                tracing::error!("Failed to serialize audit entry: {}", e);
+                return;
+            }
+        };
+        line.push(b'\n');
+
+        let mut file = self.file.lock().await;
+        if let Err(e) = file.write_all(&line).await.and(file.flush().await) {
+            tracing::error!("Failed to write audit log: {}", e);
+        }
+    }
+}
+
+/// Replace the values of secret fields, at any depth, with "[redacted]"
+pub fn redact(value: Value) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(
+            map.into_iter()
+                .map(|(key, value)| {
+                    let secret = SECRET_FIELDS
+                        .iter()
+                        .any(|field| key.to_lowercase().contains(field));
+                    if secret {
+                        (key, Value::String("[redacted]".to_string()))
+                    } else {
+                        (key, redact(value))
+                    }
+                })
+                .collect(),
+        ),
+        Value::Array(values) => Value::Array(values.into_iter().map(redact).collect()),
+        value => value,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_redact() {
+        let params = json!({
+            "project": "/src/MyApp.xcodeproj",
+            "environment": {"SIGNING_TOKEN": "abc"},
+            "options": [{"auth_token": "abc", "scheme": "MyApp"}]
+        });
+
+        assert_eq!(
+            redact(params),
+            json!({
+                "project": "/src/MyApp.xcodeproj",
+                "environment": "[redacted]",
+                "options": [{"auth_token": "[redacted]", "scheme": "MyApp"}]
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_record_appends_json_lines() {
+        let path =
+            std::env::temp_dir().join(format!("xcbridge-audit-{}.jsonl", uuid::Uuid::new_v4()));
+        let log = AuditLog::open(&path).await.unwrap();
+
+        for endpoint in ["POST /build", "DELETE /build/abc"] {
+            let entry = AuditEntry::new(&Namespace::new("ci"), endpoint.to_string(), json!({}));
+            log.record(&entry).await;
+        }
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        let entries: Vec<Value> = contents
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0]["key_label"], "ci");
+        assert_eq!(entries[1]["endpoint"], "DELETE /build/abc");
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,96 @@
+// Copyright 2026 Aptove
+// SPDX-License-Identifier: Apache-2.0
+
+//! Build fingerprinting for skipping redundant builds
+
+use crate::xcode::xcodebuild::BuildParams;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::path::Path;
+
+/// Directories that hold build output rather than sources
+const IGNORED_DIRS: &[&str] = &["DerivedData", "build", ".build", "xcuserdata"];
+
+/// Hash the source tree under `root`
+///
+/// Uses each file's relative path, size and modification time rather than
+/// its contents, so large projects can be fingerprinted quickly. Hidden
+/// directories (e.g. `.git`) and build output directories are skipped.
+pub fn hash_directory(root: &Path) -> io::Result<u64> {
+    let mut files = Vec::new();
+    collect_files(root, root, &mut files)?;
+    files.sort();
+
+    let mut hasher = DefaultHasher::new();
+    files.hash(&mut hasher);
+    Ok(hasher.finish())
+}
+
+fn collect_files(root: &Path, dir: &Path, files: &mut Vec<(String, u64, u128)>) -> io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        let file_type = entry.file_type()?;
+
+        if file_type.is_dir() {
+            if name.starts_with('.') || IGNORED_DIRS.contains(&name.as_ref()) {
+                continue;
+            }
+            collect_files(root, &entry.path(), files)?;
+        } else if file_type.is_file() {
+            let metadata = entry.metadata()?;
+            let modified = metadata
+                .modified()?
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|d| d.as_nanos())
+                .unwrap_or_default();
+            let relative = entry
+                .path()
+                .strip_prefix(root)
+                .unwrap_or(&entry.path())
+                .to_string_lossy()
+                .to_string();
+            files.push((relative, metadata.len(), modified));
+        }
+    }
+    Ok(())
+}
+
+/// Combine the build parameters with a source fingerprint into a cache key
+pub fn build_fingerprint(params: &BuildParams, source: &str) -> String {
+    let mut hasher = DefaultHasher::new();
+    params.to_args().hash(&mut hasher);
+    let mut environment: Vec<_> = params.environment.iter().collect();
+    environment.sort_unstable();
+    environment.hash(&mut hasher);
+    params.working_directory.hash(&mut hasher);
+    source.hash(&mut hasher);
+    format!("{:016x}", hasher.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hash_directory_tracks_source_changes() {
+        let dir = std::env::temp_dir().join(format!("xcbridge-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(dir.join("Sources")).unwrap();
+        std::fs::create_dir_all(dir.join("DerivedData")).unwrap();
+        std::fs::write(dir.join("Sources/App.swift"), "struct App {}").unwrap();
+
+        let initial = hash_directory(&dir).unwrap();
+
+        // Build output doesn't affect the fingerprint
+        std::fs::write(dir.join("DerivedData/out.o"), "object").unwrap();
+        assert_eq!(hash_directory(&dir).unwrap(), initial);
+
+        // New sources do
+        std::fs::write(dir.join("Sources/View.swift"), "struct View {}").unwrap();
+        assert_ne!(hash_directory(&dir).unwrap(), initial);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -3,8 +3,9 @@
 
 //! Configuration module for xcbridge
 
+use crate::state::Namespace;
 use clap::Parser;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 /// Xcode bridge service for containerized iOS development
 #[derive(Parser, Debug, Clone)]
@@ -21,10 +22,28 @@ pub struct Config {
     #[arg(short = 'H', long, default_value = "127.0.0.1", env = "XCBRIDGE_HOST")]
     pub host: String,
 
+    /// Allow binding to a non-loopback address without an API key, exposing
+    /// an unauthenticated build server to the network
+    #[arg(long, env = "XCBRIDGE_INSECURE")]
+    pub insecure: bool,
+
+    /// Port for the optional gRPC interface (disabled if not set)
+    #[arg(long, env = "XCBRIDGE_GRPC_PORT")]
+    pub grpc_port: Option<u16>,
+
     /// API key for authentication (optional)
     #[arg(long, env = "XCBRIDGE_API_KEY")]
     pub api_key: Option<String>,
 
+    /// Additional API keys as `label:key` pairs, each seeing only the builds
+    /// it started
+    #[arg(long, env = "XCBRIDGE_API_KEYS", value_delimiter = ',')]
+    pub api_keys: Option<Vec<String>>,
+
+    /// API key for admin operations such as resetting CoreSimulatorService
+    #[arg(long, env = "XCBRIDGE_ADMIN_KEY")]
+    pub admin_key: Option<String>,
+
     /// Log level (trace, debug, info, warn, error)
     #[arg(short, long, default_value = "info", env = "XCBRIDGE_LOG_LEVEL")]
     pub log_level: String,
@@ -32,6 +51,127 @@ pub struct Config {
     /// Allowed paths for build operations (security restriction)
     #[arg(long, env = "XCBRIDGE_ALLOWED_PATHS", value_delimiter = ',')]
     pub allowed_paths: Option<Vec<PathBuf>>,
+
+    /// Base directory for per-build DerivedData (defaults to a temp directory)
+    #[arg(long, env = "XCBRIDGE_DERIVED_DATA_BASE")]
+    pub derived_data_base: Option<PathBuf>,
+
+    /// Xcode to use (`Xcode.app` or its `Contents/Developer` directory);
+    /// defaults to discovering it from PATH and `xcode-select`
+    #[arg(long, env = "DEVELOPER_DIR")]
+    pub xcode_path: Option<PathBuf>,
+
+    /// Retries of transient simctl failures when booting, installing and launching
+    #[arg(long, default_value = "2", env = "XCBRIDGE_SIMCTL_RETRIES")]
+    pub simctl_retries: u32,
+
+    /// Simulators (UDIDs or device names) booted by `POST /simulator/prewarm`
+    #[arg(long, env = "XCBRIDGE_PREWARM_SIMULATORS", value_delimiter = ',')]
+    pub prewarm_simulators: Option<Vec<String>>,
+
+    /// Boot the prewarm simulators when the service starts
+    #[arg(long, env = "XCBRIDGE_PREWARM_ON_START")]
+    pub prewarm_on_start: bool,
+
+    /// Origins allowed to make browser requests, comma-separated, or `*` for
+    /// any (defaults to localhost on any port)
+    #[arg(long, env = "XCBRIDGE_CORS_ORIGINS", value_delimiter = ',')]
+    pub cors_origins: Option<Vec<String>>,
+
+    /// File to append a JSON-lines audit record of mutating requests to
+    #[arg(long, env = "XCBRIDGE_AUDIT_LOG")]
+    pub audit_log: Option<PathBuf>,
+
+    /// Number of completed builds to keep before the oldest are removed
+    #[arg(long, default_value = "100", env = "XCBRIDGE_MAX_COMPLETED_BUILDS")]
+    pub max_completed_builds: usize,
+
+    /// Maximum number of jobs tracked at once, running or completed; new
+    /// jobs are rejected with 503 until some are removed (0 for no limit)
+    #[arg(long, default_value = "1000", env = "XCBRIDGE_MAX_BUILDS")]
+    pub max_builds: usize,
+
+    /// Maximum number of builds and test runs running at once; later ones
+    /// wait in a queue for a free slot (0 for no limit)
+    #[arg(long, default_value = "0", env = "XCBRIDGE_MAX_CONCURRENT_JOBS")]
+    pub max_concurrent_jobs: usize,
+
+    /// Seconds xcodebuild searches for a build or test destination before
+    /// failing, unless a request sets its own (0 leaves xcodebuild's default)
+    #[arg(long, default_value = "30", env = "XCBRIDGE_DESTINATION_TIMEOUT")]
+    pub destination_timeout: u32,
+
+    /// Seconds between refreshes of the cached Xcode version, SDK and
+    /// runtime lists (0 refreshes only at startup and on `POST /refresh`)
+    #[arg(long, default_value = "600", env = "XCBRIDGE_HOST_REFRESH_INTERVAL")]
+    pub host_refresh_interval: u64,
+
+    /// Niceness (0-20) xcodebuild runs at, so builds yield the CPU to other
+    /// work on shared hosts (0 leaves builds at normal priority)
+    #[arg(
+        long,
+        default_value = "0",
+        env = "XCBRIDGE_BUILD_NICE",
+        value_parser = clap::value_parser!(u32).range(0..=20)
+    )]
+    pub build_nice: u32,
+
+    /// Concurrent build tasks per build or test run (`-jobs`), unless a
+    /// request sets its own
+    #[arg(
+        long,
+        env = "XCBRIDGE_BUILD_JOBS",
+        value_parser = clap::value_parser!(u32).range(1..)
+    )]
+    pub build_jobs: Option<u32>,
+
+    /// `devicectl device` subcommands `POST /device/exec` may run,
+    /// comma-separated, e.g. "info lockState" (defaults to the read-only
+    /// `info` subcommands)
+    #[arg(long, env = "XCBRIDGE_DEVICECTL_EXEC_ALLOW", value_delimiter = ',')]
+    pub devicectl_exec_allow: Option<Vec<String>>,
+
+    /// Seconds between probes of the CoreSimulator service (0 disables them)
+    #[arg(long, default_value = "60", env = "XCBRIDGE_SIMULATOR_HEALTH_INTERVAL")]
+    pub simulator_health_interval: u64,
+
+    /// Directory uploaded project archives are extracted under (defaults to
+    /// a temp directory); must be within `--allowed-paths` if that is set
+    #[arg(long, env = "XCBRIDGE_UPLOAD_DIR")]
+    pub upload_dir: Option<PathBuf>,
+
+    /// Largest project archive accepted by `/build/upload` and
+    /// `/test/upload`, in MB
+    #[arg(long, default_value = "512", env = "XCBRIDGE_MAX_UPLOAD_SIZE")]
+    pub max_upload_size: u64,
+
+    /// Largest total size an uploaded archive may extract to, in MB
+    #[arg(long, default_value = "4096", env = "XCBRIDGE_MAX_EXTRACTED_SIZE")]
+    pub max_extracted_size: u64,
+
+    /// Largest single file an uploaded archive may extract, in MB
+    #[arg(long, default_value = "1024", env = "XCBRIDGE_MAX_EXTRACTED_FILE_SIZE")]
+    pub max_extracted_file_size: u64,
+
+    /// Milliseconds between checks for job completion in log streams (new
+    /// lines are pushed as they are logged)
+    #[arg(
+        long,
+        default_value = "100",
+        env = "XCBRIDGE_LOG_POLL_INTERVAL",
+        value_parser = clap::value_parser!(u64).range(10..=10_000)
+    )]
+    pub log_poll_interval: u64,
+
+    /// Seconds a synchronous simulator request (boot, install, launch, ...)
+    /// may take before it fails with a timeout (0 for no limit)
+    #[arg(long, default_value = "300", env = "XCBRIDGE_SIMULATOR_TIMEOUT")]
+    pub simulator_timeout: u64,
+
+    /// Seconds a device request (install, launch, exec, ...) may take before
+    /// it fails with a timeout (0 for no limit)
+    #[arg(long, default_value = "600", env = "XCBRIDGE_DEVICE_TIMEOUT")]
+    pub device_timeout: u64,
 }
 
 impl Config {
@@ -40,8 +180,67 @@ impl Config {
         Config::parse()
     }
 
+    /// `-destination-timeout` for requests that don't set one
+    pub fn default_destination_timeout(&self) -> Option<u32> {
+        (self.destination_timeout > 0).then_some(self.destination_timeout)
+    }
+
+    /// Whether requests must present an API key
+    pub fn auth_required(&self) -> bool {
+        self.api_key.is_some() || self.api_keys.is_some() || self.admin_key.is_some()
+    }
+
+    /// Whether `--host` only accepts connections from this machine
+    pub fn is_loopback_host(&self) -> bool {
+        let host = self.host.trim_start_matches('[').trim_end_matches(']');
+        host.eq_ignore_ascii_case("localhost")
+            || host
+                .parse::<std::net::IpAddr>()
+                .is_ok_and(|ip| ip.is_loopback())
+    }
+
+    /// Whether the service would be reachable from the network without
+    /// authentication
+    pub fn exposed_without_auth(&self) -> bool {
+        !self.auth_required() && !self.is_loopback_host()
+    }
+
+    /// Whether a request's API key may use admin operations
+    ///
+    /// Admin operations are open when authentication is disabled, like
+    /// everything else.
+    pub fn is_admin_key(&self, key: Option<&str>) -> bool {
+        if !self.auth_required() {
+            return true;
+        }
+        key.is_some() && self.admin_key.as_deref() == key
+    }
+
+    /// Resolve the namespace for a request's API key
+    ///
+    /// Returns `None` if the key is required but missing or unknown. The
+    /// `--api-key` key maps to the "default" namespace and each `--api-keys`
+    /// entry to its label.
+    pub fn namespace_for_key(&self, key: Option<&str>) -> Option<Namespace> {
+        if !self.auth_required() {
+            return Some(Namespace::default());
+        }
+
+        let key = key?;
+        if self.api_key.as_deref() == Some(key) {
+            return Some(Namespace::new("default"));
+        }
+        if self.admin_key.as_deref() == Some(key) {
+            return Some(Namespace::new("admin"));
+        }
+        self.api_keys.iter().flatten().find_map(|entry| {
+            let (label, entry_key) = entry.split_once(':')?;
+            (entry_key == key).then(|| Namespace::new(label))
+        })
+    }
+
     /// Check if a path is allowed for build operations
-    pub fn is_path_allowed(&self, path: &PathBuf) -> bool {
+    pub fn is_path_allowed(&self, path: &Path) -> bool {
         match &self.allowed_paths {
             Some(allowed) => {
                 let canonical = path.canonicalize().ok();
@@ -59,6 +258,64 @@ impl Config {
         }
     }
 
+    /// Check if a path that may not exist yet, such as an output directory,
+    /// is allowed
+    ///
+    /// The nearest existing ancestor is checked, since that is where the path
+    /// will be created. Paths with `..` components are refused, as they could
+    /// leave that ancestor once the missing directories are created.
+    pub fn is_output_path_allowed(&self, path: &Path) -> bool {
+        if path
+            .components()
+            .any(|c| matches!(c, std::path::Component::ParentDir))
+        {
+            return false;
+        }
+        match path.ancestors().find(|ancestor| ancestor.exists()) {
+            Some(existing) => self.is_path_allowed(existing),
+            None => false,
+        }
+    }
+
+    /// Directory under which per-build DerivedData directories are created
+    pub fn derived_data_base(&self) -> PathBuf {
+        self.derived_data_base
+            .clone()
+            .unwrap_or_else(|| std::env::temp_dir().join("xcbridge").join("DerivedData"))
+    }
+
+    /// How often log streams check whether their job has finished
+    pub fn log_poll_interval(&self) -> std::time::Duration {
+        std::time::Duration::from_millis(self.log_poll_interval)
+    }
+
+    /// Directory under which uploaded projects are extracted
+    pub fn upload_dir(&self) -> PathBuf {
+        self.upload_dir
+            .clone()
+            .unwrap_or_else(|| std::env::temp_dir().join("xcbridge").join("Uploads"))
+    }
+
+    /// `--max-upload-size` in bytes
+    pub fn max_upload_bytes(&self) -> usize {
+        usize::try_from(self.max_upload_size.saturating_mul(1024 * 1024)).unwrap_or(usize::MAX)
+    }
+
+    /// Directory for build result bundles
+    pub fn result_bundle_dir(&self) -> PathBuf {
+        std::env::temp_dir().join("xcbridge").join("ResultBundles")
+    }
+
+    /// Directory attachments are exported to from test result bundles
+    pub fn attachment_dir(&self) -> PathBuf {
+        std::env::temp_dir().join("xcbridge").join("Attachments")
+    }
+
+    /// Directory for screenshots of failed tests
+    pub fn screenshot_dir(&self) -> PathBuf {
+        std::env::temp_dir().join("xcbridge").join("Screenshots")
+    }
+
     /// Get the socket address to bind to
     pub fn socket_addr(&self) -> String {
         format!("{}:{}", self.host, self.port)
@@ -74,10 +331,88 @@ mod tests {
         let config = Config {
             port: 9090,
             host: "127.0.0.1".to_string(),
+            insecure: false,
+            grpc_port: None,
             api_key: None,
+            api_keys: None,
+            admin_key: None,
             log_level: "info".to_string(),
             allowed_paths: None,
+            derived_data_base: None,
+            xcode_path: None,
+            simctl_retries: 2,
+            prewarm_simulators: None,
+            prewarm_on_start: false,
+            cors_origins: None,
+            audit_log: None,
+            max_completed_builds: 100,
+            max_builds: 1000,
+            max_concurrent_jobs: 0,
+            destination_timeout: 30,
+            host_refresh_interval: 600,
+            build_nice: 0,
+            build_jobs: None,
+            devicectl_exec_allow: None,
+            simulator_health_interval: 60,
+            upload_dir: None,
+            max_upload_size: 512,
+            max_extracted_size: 4096,
+            max_extracted_file_size: 1024,
+            log_poll_interval: 100,
+            simulator_timeout: 300,
+            device_timeout: 600,
         };
         assert_eq!(config.socket_addr(), "127.0.0.1:9090");
     }
+
+    #[test]
+    fn test_namespace_for_key() {
+        let config = Config::parse_from([
+            "xcbridge",
+            "--api-key",
+            "main-key",
+            "--api-keys",
+            "team-a:key-a,team-b:key-b",
+        ]);
+
+        assert_eq!(
+            config.namespace_for_key(Some("main-key")),
+            Some(Namespace::new("default"))
+        );
+        assert_eq!(
+            config.namespace_for_key(Some("key-b")),
+            Some(Namespace::new("team-b"))
+        );
+        assert_eq!(config.namespace_for_key(Some("team-a")), None);
+        assert_eq!(config.namespace_for_key(None), None);
+
+        let open = Config::parse_from(["xcbridge"]);
+        assert_eq!(open.namespace_for_key(None), Some(Namespace::default()));
+    }
+
+    #[test]
+    fn test_exposed_without_auth() {
+        let config = |args: &[&str]| Config::parse_from([&["xcbridge"], args].concat());
+
+        assert!(!config(&[]).exposed_without_auth());
+        assert!(!config(&["--host", "localhost"]).exposed_without_auth());
+        assert!(!config(&["--host", "::1"]).exposed_without_auth());
+        assert!(config(&["--host", "0.0.0.0"]).exposed_without_auth());
+        assert!(config(&["--host", "192.168.1.20"]).exposed_without_auth());
+        assert!(!config(&["--host", "0.0.0.0", "--api-key", "secret"]).exposed_without_auth());
+    }
+
+    #[test]
+    fn test_is_output_path_allowed() {
+        let dir = std::env::temp_dir().join(format!("xcbridge-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let config = Config::parse_from(["xcbridge", "--allowed-paths", dir.to_str().unwrap()]);
+
+        assert!(config.is_output_path_allowed(&dir));
+        assert!(config.is_output_path_allowed(&dir.join("exports/MyApp")));
+        assert!(!config.is_output_path_allowed(&dir.join("new/../../escape")));
+        assert!(!config.is_output_path_allowed(Path::new("/var/elsewhere/out")));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
 }
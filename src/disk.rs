@@ -0,0 +1,97 @@
+// Copyright 2026 Aptove
+// SPDX-License-Identifier: Apache-2.0
+
+//! Disk space checks for recognizing builds that failed on a full disk
+
+use std::path::Path;
+use tokio::process::Command;
+
+/// Output fragments that indicate the disk filled up during a job
+const DISK_FULL_PATTERNS: &[&str] = &["No space left on device", "ENOSPC"];
+
+/// Whether a log line reports that the disk is full
+pub fn is_disk_full_error(line: &str) -> bool {
+    DISK_FULL_PATTERNS
+        .iter()
+        .any(|pattern| line.contains(pattern))
+}
+
+/// Free space in bytes on the volume containing `path`
+///
+/// Uses `df` so that it works for any existing path, walking up to the
+/// nearest existing ancestor if `path` itself has been removed.
+pub async fn free_space(path: &Path) -> Option<u64> {
+    let existing = path.ancestors().find(|p| p.exists())?;
+    let output = Command::new("df")
+        .arg("-Pk")
+        .arg(existing)
+        .output()
+        .await
+        .ok()?;
+
+    if !output.status.success() {
+        return None;
+    }
+
+    parse_df_available(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// Parse the available space from POSIX `df -Pk` output
+///
+/// ```text
+/// Filesystem     1024-blocks      Used Available Capacity  Mounted on
+/// /dev/disk3s5     971350180 901234567  40123456    96%    /System/Volumes/Data
+/// ```
+fn parse_df_available(output: &str) -> Option<u64> {
+    let line = output.lines().nth(1)?;
+    let available_kb: u64 = line.split_whitespace().nth(3)?.parse().ok()?;
+    Some(available_kb * 1024)
+}
+
+/// Format a byte count for error messages
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: &[&str] = &["bytes", "KB", "MB", "GB", "TB"];
+
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{} bytes", bytes)
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_disk_full_error() {
+        assert!(is_disk_full_error(
+            "error: unable to write file '/tmp/DerivedData/Foo.o': No space left on device"
+        ));
+        assert!(is_disk_full_error(
+            "clang: error: ENOSPC while writing output"
+        ));
+        assert!(!is_disk_full_error("error: cannot find 'Foo' in scope"));
+    }
+
+    #[test]
+    fn test_parse_df_available() {
+        let output = "Filesystem     1024-blocks      Used Available Capacity  Mounted on\n\
+                      /dev/disk3s5     971350180 901234567  40123456    96%    /System/Volumes/Data\n";
+        assert_eq!(parse_df_available(output), Some(40123456 * 1024));
+        assert_eq!(parse_df_available(""), None);
+    }
+
+    #[test]
+    fn test_format_bytes() {
+        assert_eq!(format_bytes(512), "512 bytes");
+        assert_eq!(format_bytes(1536 * 1024 * 1024), "1.5 GB");
+    }
+}
@@ -3,13 +3,59 @@
 
 //! Error types for xcbridge
 
+use crate::xcode::signing::{CodeSigningIssue, SIGNING_HINT};
 use axum::{
-    http::StatusCode,
+    http::{header, StatusCode},
     response::{IntoResponse, Response},
     Json,
 };
 use serde::Serialize;
 
+/// Details of an external command (simctl, devicectl) that exited unsuccessfully
+#[derive(Debug, Clone, Serialize)]
+pub struct CommandFailure {
+    /// Full command line
+    pub command: String,
+    /// Exit code (None if terminated by a signal)
+    pub exit_code: Option<i32>,
+    /// Captured stderr
+    pub stderr: String,
+}
+
+impl CommandFailure {
+    pub fn new(program: &str, args: &[&str], output: &std::process::Output) -> Self {
+        Self {
+            command: std::iter::once(program)
+                .chain(args.iter().copied())
+                .collect::<Vec<_>>()
+                .join(" "),
+            exit_code: output.status.code(),
+            stderr: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        }
+    }
+}
+
+/// Where and why a JSON request body couldn't be read
+#[derive(Debug, Clone, Serialize)]
+pub struct BodyError {
+    /// Path to the offending field, e.g. `simulators[1]` (None if the body
+    /// isn't valid JSON)
+    pub field: Option<String>,
+    /// What the field should hold, e.g. "a string"
+    pub expected: Option<String>,
+    /// Description from the JSON parser
+    pub reason: String,
+}
+
+impl std::fmt::Display for BodyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.field {
+            Some(field) => write!(f, "`{}`: {}", field, self.reason),
+            None => f.write_str(&self.reason),
+        }
+    }
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum XcbridgeError {
     #[error("Xcode not found. Please install Xcode and run xcode-select.")]
@@ -21,30 +67,74 @@ pub enum XcbridgeError {
     #[error("Test failed: {0}")]
     TestFailed(String),
 
-    #[error("Simulator not found: {0}")]
+    #[error("Simulator not found: {0}. Create one with `xcrun simctl create` or pick one from /simulator/list")]
     SimulatorNotFound(String),
 
+    #[error("Simulator {simulator} is unavailable: {reason}. Install its runtime (e.g. `xcodebuild -downloadPlatform iOS`) or use another simulator")]
+    SimulatorUnavailable { simulator: String, reason: String },
+
     #[error("Simulator error: {0}")]
     SimulatorError(String),
 
+    #[error("Simulator error: {}", .0.stderr)]
+    SimulatorCommandFailed(CommandFailure),
+
     #[error("Device not found: {0}")]
     DeviceNotFound(String),
 
     #[error("Device error: {0}")]
     DeviceError(String),
 
+    #[error("Device error: {}", .0.stderr)]
+    DeviceCommandFailed(CommandFailure),
+
+    #[error("devicectl is not available on this host. Physical devices require Xcode 15 or later")]
+    DevicectlUnavailable,
+
+    #[error("Code signing failed: {}. {}", .0.message.trim_end_matches('.'), SIGNING_HINT)]
+    CodeSigningError(CodeSigningIssue),
+
+    #[error("Toolchain not found: {0}")]
+    ToolchainNotFound(String),
+
     #[error("Path not allowed: {0}")]
     PathNotAllowed(String),
 
+    #[error("Command not allowed: {0}")]
+    CommandNotAllowed(String),
+
     #[error("Command execution failed: {0}")]
     CommandFailed(String),
 
     #[error("Invalid request: {0}")]
     InvalidRequest(String),
 
+    #[error("Invalid request body: {0}")]
+    InvalidBody(BodyError),
+
     #[error("Build not found: {0}")]
     BuildNotFound(String),
 
+    #[error(
+        "Lease not found: {0}. It may have expired; lease a simulator again with /simulator/lease"
+    )]
+    LeaseNotFound(String),
+
+    #[error("Test plan not found: {0}")]
+    TestPlanNotFound(String),
+
+    #[error("Upload is larger than the {0} MB limit")]
+    UploadTooLarge(u64),
+
+    #[error("Uploaded archive is too large once extracted: {0}")]
+    ExtractedTooLarge(String),
+
+    #[error("Timed out: {0}")]
+    Timeout(String),
+
+    #[error("Too busy: {0} jobs are already tracked. Retry once some finish and are cleaned up")]
+    TooBusy(usize),
+
     #[error("Internal error: {0}")]
     Internal(String),
 
@@ -56,6 +146,18 @@ pub enum XcbridgeError {
 struct ErrorResponse {
     error: String,
     message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    command: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    exit_code: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stderr: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    code_signing: Option<CodeSigningIssue>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    field: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    expected: Option<String>,
 }
 
 impl IntoResponse for XcbridgeError {
@@ -65,24 +167,170 @@ impl IntoResponse for XcbridgeError {
             XcbridgeError::BuildFailed(_) => (StatusCode::BAD_REQUEST, "build_failed"),
             XcbridgeError::TestFailed(_) => (StatusCode::BAD_REQUEST, "test_failed"),
             XcbridgeError::SimulatorNotFound(_) => (StatusCode::NOT_FOUND, "simulator_not_found"),
+            XcbridgeError::SimulatorUnavailable { .. } => {
+                (StatusCode::CONFLICT, "simulator_unavailable")
+            }
             XcbridgeError::SimulatorError(_) => (StatusCode::BAD_REQUEST, "simulator_error"),
+            XcbridgeError::SimulatorCommandFailed(_) => {
+                (StatusCode::BAD_REQUEST, "simulator_error")
+            }
             XcbridgeError::DeviceNotFound(_) => (StatusCode::NOT_FOUND, "device_not_found"),
             XcbridgeError::DeviceError(_) => (StatusCode::BAD_REQUEST, "device_error"),
+            XcbridgeError::DeviceCommandFailed(_) => (StatusCode::BAD_REQUEST, "device_error"),
+            XcbridgeError::DevicectlUnavailable => {
+                (StatusCode::NOT_IMPLEMENTED, "devicectl_unavailable")
+            }
+            XcbridgeError::CodeSigningError(_) => (StatusCode::BAD_REQUEST, "code_signing_error"),
+            XcbridgeError::ToolchainNotFound(_) => (StatusCode::BAD_REQUEST, "toolchain_not_found"),
             XcbridgeError::PathNotAllowed(_) => (StatusCode::FORBIDDEN, "path_not_allowed"),
+            XcbridgeError::CommandNotAllowed(_) => (StatusCode::FORBIDDEN, "command_not_allowed"),
             XcbridgeError::CommandFailed(_) => (StatusCode::INTERNAL_SERVER_ERROR, "command_failed"),
             XcbridgeError::InvalidRequest(_) => (StatusCode::BAD_REQUEST, "invalid_request"),
+            XcbridgeError::InvalidBody(_) => (StatusCode::UNPROCESSABLE_ENTITY, "invalid_body"),
             XcbridgeError::BuildNotFound(_) => (StatusCode::NOT_FOUND, "build_not_found"),
+            XcbridgeError::LeaseNotFound(_) => (StatusCode::NOT_FOUND, "lease_not_found"),
+            XcbridgeError::TestPlanNotFound(_) => (StatusCode::NOT_FOUND, "test_plan_not_found"),
+            XcbridgeError::UploadTooLarge(_) | XcbridgeError::ExtractedTooLarge(_) => {
+                (StatusCode::PAYLOAD_TOO_LARGE, "upload_too_large")
+            }
+            XcbridgeError::Timeout(_) => (StatusCode::GATEWAY_TIMEOUT, "timeout"),
+            XcbridgeError::TooBusy(_) => (StatusCode::SERVICE_UNAVAILABLE, "too_busy"),
             XcbridgeError::Internal(_) => (StatusCode::INTERNAL_SERVER_ERROR, "internal_error"),
             XcbridgeError::Unauthorized => (StatusCode::UNAUTHORIZED, "unauthorized"),
         };
 
+        let failure = match &self {
+            XcbridgeError::SimulatorCommandFailed(failure)
+            | XcbridgeError::DeviceCommandFailed(failure) => Some(failure.clone()),
+            _ => None,
+        };
+        let code_signing = match &self {
+            XcbridgeError::CodeSigningError(issue) => Some(issue.clone()),
+            _ => None,
+        };
+        let body_error = match &self {
+            XcbridgeError::InvalidBody(error) => Some(error.clone()),
+            _ => None,
+        };
+
         let body = Json(ErrorResponse {
             error: error_type.to_string(),
             message: self.to_string(),
+            command: failure.as_ref().map(|f| f.command.clone()),
+            exit_code: failure.as_ref().and_then(|f| f.exit_code),
+            stderr: failure.map(|f| f.stderr),
+            code_signing,
+            field: body_error.as_ref().and_then(|e| e.field.clone()),
+            expected: body_error.and_then(|e| e.expected),
         });
 
-        (status, body).into_response()
+        let text = ErrorText(format!("{}: {}\n", error_type, self));
+        let mut response = (status, body).into_response();
+        response.extensions_mut().insert(text);
+        response
     }
 }
 
+/// Plain-text body of an error response, for clients that ask for text
+#[derive(Debug, Clone)]
+pub struct ErrorText(String);
+
+/// Whether an `Accept` header prefers plain text to JSON
+///
+/// `text/event-stream` counts as text, so SSE clients get readable errors.
+/// JSON wins ties, including `*/*` and no header at all.
+pub fn prefers_text(accept: &str) -> bool {
+    let quality = |is_match: fn(&str) -> bool| {
+        accept
+            .split(',')
+            .filter_map(|range| {
+                let mut params = range.split(';');
+                let media = params.next()?.trim().to_ascii_lowercase();
+                if !is_match(&media) {
+                    return None;
+                }
+                let q = params
+                    .filter_map(|p| p.trim().strip_prefix("q="))
+                    .find_map(|q| q.parse::<f32>().ok())
+                    .unwrap_or(1.0);
+                Some(q)
+            })
+            .fold(0.0, f32::max)
+    };
+    let text = quality(|m| matches!(m, "text/plain" | "text/event-stream" | "text/*"));
+    let json = quality(|m| matches!(m, "application/json" | "application/*" | "*/*"));
+    text > json
+}
+
+/// Replace an error response's JSON body with its plain-text form
+///
+/// Responses that aren't errors from `XcbridgeError` are returned as is.
+pub fn into_text_response(response: Response) -> Response {
+    let Some(ErrorText(text)) = response.extensions().get::<ErrorText>().cloned() else {
+        return response;
+    };
+    let (mut parts, _) = response.into_parts();
+    parts.headers.insert(
+        header::CONTENT_TYPE,
+        header::HeaderValue::from_static("text/plain; charset=utf-8"),
+    );
+    parts.headers.remove(header::CONTENT_LENGTH);
+    Response::from_parts(parts, text.into())
+}
+
 pub type Result<T> = std::result::Result<T, XcbridgeError>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_command_failure_details_in_response() {
+        let err = XcbridgeError::SimulatorCommandFailed(CommandFailure {
+            command: "xcrun simctl boot ABC".to_string(),
+            exit_code: Some(149),
+            stderr: "Unable to boot device in current state: Booted".to_string(),
+        });
+
+        let response = err.into_response();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["error"], "simulator_error");
+        assert_eq!(json["command"], "xcrun simctl boot ABC");
+        assert_eq!(json["exit_code"], 149);
+        assert_eq!(
+            json["stderr"],
+            "Unable to boot device in current state: Booted"
+        );
+    }
+
+    #[test]
+    fn test_prefers_text() {
+        assert!(prefers_text("text/plain"));
+        assert!(prefers_text("text/event-stream"));
+        assert!(prefers_text("text/plain, application/json;q=0.5"));
+        assert!(!prefers_text("*/*"));
+        assert!(!prefers_text("application/json, text/plain"));
+        assert!(!prefers_text("text/plain;q=0.2, */*;q=0.8"));
+        assert!(!prefers_text(""));
+    }
+
+    #[tokio::test]
+    async fn test_into_text_response() {
+        let response =
+            into_text_response(XcbridgeError::BuildNotFound("abc".into()).into_response());
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(
+            response.headers()[header::CONTENT_TYPE],
+            "text/plain; charset=utf-8"
+        );
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(body, "build_not_found: Build not found: abc\n");
+    }
+}
@@ -0,0 +1,466 @@
+// Copyright 2026 Aptove
+// SPDX-License-Identifier: Apache-2.0
+
+//! Optional gRPC interface for xcbridge
+//!
+//! Exposes build/test submission and a server-streaming logs RPC over the
+//! same state as the REST API. Enabled with `--grpc-port`.
+
+use crate::audit::AuditEntry;
+use crate::error::XcbridgeError;
+use crate::handlers::{self, JsonBody};
+use crate::log_stream::{self, LogUpdate};
+use crate::models;
+use crate::state::{BuildStatus, Namespace, SharedState};
+use crate::xcode::bundle::AppInfo;
+use crate::xcode::signing::CodeSigningIssue;
+use crate::xcode::xcresult::{BuildDiagnostics, BuildIssue};
+use axum::extract::{Path, Query, State};
+use axum::Json;
+use futures::stream::Stream;
+use std::collections::HashSet;
+use std::pin::Pin;
+use tokio::net::TcpListener;
+use tonic::transport::server::TcpIncoming;
+use tonic::{Request, Response, Status};
+
+pub mod proto {
+    tonic::include_proto!("xcbridge.v1");
+}
+
+use proto::xcbridge_server::{Xcbridge, XcbridgeServer};
+use proto::{log_event, LogEvent};
+
+impl From<XcbridgeError> for Status {
+    fn from(err: XcbridgeError) -> Self {
+        let message = err.to_string();
+        match err {
+            XcbridgeError::XcodeNotFound => Status::unavailable(message),
+            XcbridgeError::DevicectlUnavailable => Status::unimplemented(message),
+            XcbridgeError::SimulatorNotFound(_)
+            | XcbridgeError::DeviceNotFound(_)
+            | XcbridgeError::BuildNotFound(_)
+            | XcbridgeError::LeaseNotFound(_)
+            | XcbridgeError::TestPlanNotFound(_) => Status::not_found(message),
+            XcbridgeError::PathNotAllowed(_) | XcbridgeError::CommandNotAllowed(_) => {
+                Status::permission_denied(message)
+            }
+            XcbridgeError::InvalidRequest(_)
+            | XcbridgeError::InvalidBody(_)
+            | XcbridgeError::ToolchainNotFound(_) => Status::invalid_argument(message),
+            XcbridgeError::Unauthorized => Status::unauthenticated(message),
+            XcbridgeError::Timeout(_) => Status::deadline_exceeded(message),
+            XcbridgeError::UploadTooLarge(_)
+            | XcbridgeError::ExtractedTooLarge(_)
+            | XcbridgeError::TooBusy(_) => Status::resource_exhausted(message),
+            XcbridgeError::BuildFailed(_)
+            | XcbridgeError::TestFailed(_)
+            | XcbridgeError::SimulatorError(_)
+            | XcbridgeError::SimulatorUnavailable { .. }
+            | XcbridgeError::SimulatorCommandFailed(_)
+            | XcbridgeError::DeviceError(_)
+            | XcbridgeError::DeviceCommandFailed(_)
+            | XcbridgeError::CodeSigningError(_) => Status::failed_precondition(message),
+            XcbridgeError::CommandFailed(_) | XcbridgeError::Internal(_) => {
+                Status::internal(message)
+            }
+        }
+    }
+}
+
+impl From<proto::BuildRequest> for models::BuildRequest {
+    fn from(req: proto::BuildRequest) -> Self {
+        Self {
+            project: req.project,
+            workspace: req.workspace,
+            scheme: req.scheme,
+            target: req.target,
+            configuration: req.configuration.unwrap_or_else(|| "Debug".to_string()),
+            destination: req.destination,
+            lease: req.lease,
+            derived_data_path: req.derived_data_path,
+            cleanup_derived_data: req.cleanup_derived_data,
+            toolchain: req.toolchain,
+            extra_args: req.extra_args,
+            use_cache: req.use_cache,
+            fingerprint: req.fingerprint,
+            result_bundle: req.result_bundle,
+            keep_result_bundle: req.keep_result_bundle,
+            environment: req.environment,
+            working_directory: req.working_directory,
+            destination_timeout: req.destination_timeout,
+            resolve_packages: req.resolve_packages,
+            skip_plugin_validation: req.skip_plugin_validation,
+            jobs: req.jobs,
+            retry_clean_on_failure: req.retry_clean_on_failure,
+            timing_summary: req.timing_summary,
+            output_dir: req.output_dir,
+            name: req.name,
+        }
+    }
+}
+
+impl From<proto::TestRequest> for models::TestRequest {
+    fn from(req: proto::TestRequest) -> Self {
+        Self {
+            project: req.project,
+            workspace: req.workspace,
+            scheme: req.scheme,
+            destination: req.destination,
+            lease: req.lease,
+            test_plan: req.test_plan,
+            only_test_configuration: req.only_test_configuration,
+            skip_test_configuration: req.skip_test_configuration,
+            test_language: req.test_language,
+            test_region: req.test_region,
+            toolchain: req.toolchain,
+            only_testing: req.only_testing,
+            skip_testing: req.skip_testing,
+            parallel_testing: req.parallel_testing,
+            parallel_workers: req.parallel_workers,
+            result_bundle: req.result_bundle,
+            keep_result_bundle: req.keep_result_bundle,
+            capture_runtime_logs: req.capture_runtime_logs,
+            erase_after: req.erase_after,
+            destination_timeout: req.destination_timeout,
+            xctestrun_path: req.xctestrun_path,
+            jobs: req.jobs,
+            fail_fast: req.fail_fast,
+            screenshot_on_failure: req.screenshot_on_failure,
+            name: req.name,
+        }
+    }
+}
+
+impl From<models::BuildStartedResponse> for proto::BuildStartedResponse {
+    fn from(res: models::BuildStartedResponse) -> Self {
+        Self {
+            build_id: res.build_id,
+            status: res.status,
+            logs_url: res.logs_url,
+            cache_hit: res.cache_hit,
+            artifacts: res.artifacts.unwrap_or_default(),
+            exported_artifacts: res.exported_artifacts.unwrap_or_default(),
+            derived_data_path: res.derived_data_path,
+            name: res.name,
+        }
+    }
+}
+
+impl From<models::BuildStatusResponse> for proto::BuildStatusResponse {
+    fn from(res: models::BuildStatusResponse) -> Self {
+        Self {
+            build_id: res.build_id,
+            status: res.status,
+            exit_code: res.exit_code,
+            artifacts: res.artifacts.unwrap_or_default(),
+            exported_artifacts: res.exported_artifacts.unwrap_or_default(),
+            error: res.error,
+            logs: res.logs.unwrap_or_default(),
+            derived_data_path: res.derived_data_path,
+            apps: res
+                .apps
+                .unwrap_or_default()
+                .into_iter()
+                .map(Into::into)
+                .collect(),
+            diagnostics: res.diagnostics.map(Into::into),
+            failure_reason: res.failure_reason.map(|r| r.name().to_string()),
+            result_bundle_path: res.result_bundle_path,
+            code_signing: res.code_signing.map(Into::into),
+            script_phase: res.script_phase.map(|failure| proto::ScriptPhaseFailure {
+                script: failure.script,
+                target: failure.target,
+                output: failure.output,
+            }),
+            name: res.name,
+            retried_clean: res.retried_clean,
+            timing_summary: res
+                .timing_summary
+                .unwrap_or_default()
+                .into_iter()
+                .map(|timing| proto::BuildPhaseTiming {
+                    phase: timing.phase,
+                    tasks: timing.tasks,
+                    seconds: timing.seconds,
+                })
+                .collect(),
+            duration_secs: res.duration_secs,
+            queue_position: res.queue_position.map(|position| position as u32),
+            eta_secs: res.eta_secs,
+            cpu_time: res.cpu_time.map(|cpu| proto::CpuTime {
+                user_secs: cpu.user_secs,
+                system_secs: cpu.system_secs,
+            }),
+        }
+    }
+}
+
+impl From<CodeSigningIssue> for proto::CodeSigningIssue {
+    fn from(issue: CodeSigningIssue) -> Self {
+        Self {
+            kind: issue.kind,
+            message: issue.message,
+            bundle_id: issue.bundle_id,
+            team_id: issue.team_id,
+            target: issue.target,
+        }
+    }
+}
+
+impl From<BuildDiagnostics> for proto::BuildDiagnostics {
+    fn from(diagnostics: BuildDiagnostics) -> Self {
+        Self {
+            error_count: diagnostics.error_count,
+            warning_count: diagnostics.warning_count,
+            analyzer_warning_count: diagnostics.analyzer_warning_count,
+            errors: diagnostics.errors.into_iter().map(Into::into).collect(),
+            warnings: diagnostics.warnings.into_iter().map(Into::into).collect(),
+            duration_secs: diagnostics.duration_secs,
+        }
+    }
+}
+
+impl From<BuildIssue> for proto::BuildIssue {
+    fn from(issue: BuildIssue) -> Self {
+        Self {
+            issue_type: issue.issue_type,
+            message: issue.message,
+            file: issue.file,
+            line: issue.line,
+        }
+    }
+}
+
+impl From<models::ArtifactEvent> for proto::ArtifactEvent {
+    fn from(event: models::ArtifactEvent) -> Self {
+        Self {
+            path: event.path,
+            target: event.target,
+        }
+    }
+}
+
+impl From<AppInfo> for proto::AppInfo {
+    fn from(info: AppInfo) -> Self {
+        Self {
+            path: info.path,
+            bundle_id: info.bundle_id,
+            name: info.name,
+            version: info.version,
+            build: info.build,
+            executable: info.executable,
+            minimum_os_version: info.minimum_os_version,
+        }
+    }
+}
+
+/// gRPC service backed by the shared application state
+pub struct XcbridgeService {
+    state: SharedState,
+}
+
+/// Namespace resolved by the auth interceptor
+fn request_namespace<T>(request: &Request<T>) -> Namespace {
+    request
+        .extensions()
+        .get::<Namespace>()
+        .cloned()
+        .unwrap_or_default()
+}
+
+impl XcbridgeService {
+    /// Record a mutating call in the audit log, if one is configured
+    async fn audit<T: serde::Serialize>(&self, namespace: &Namespace, method: &str, req: &T) {
+        if let Some(audit_log) = &self.state.audit_log {
+            let params = serde_json::to_value(req).unwrap_or_default();
+            let entry = AuditEntry::new(namespace, format!("grpc {}", method), params);
+            audit_log.record(&entry).await;
+        }
+    }
+}
+
+type LogStream = Pin<Box<dyn Stream<Item = Result<LogEvent, Status>> + Send>>;
+
+#[tonic::async_trait]
+impl Xcbridge for XcbridgeService {
+    async fn start_build(
+        &self,
+        request: Request<proto::BuildRequest>,
+    ) -> Result<Response<proto::BuildStartedResponse>, Status> {
+        let namespace = request_namespace(&request);
+        let req = request.into_inner();
+        self.audit(&namespace, "StartBuild", &req).await;
+        let req = models::BuildRequest::from(req);
+        let Json(res) =
+            handlers::build::start_build(State(self.state.clone()), namespace, JsonBody(req))
+                .await?;
+        Ok(Response::new(res.into()))
+    }
+
+    async fn start_test(
+        &self,
+        request: Request<proto::TestRequest>,
+    ) -> Result<Response<proto::BuildStartedResponse>, Status> {
+        let namespace = request_namespace(&request);
+        let req = request.into_inner();
+        self.audit(&namespace, "StartTest", &req).await;
+        let req = models::TestRequest::from(req);
+        let Json(res) =
+            handlers::test::start_test(State(self.state.clone()), namespace, JsonBody(req)).await?;
+        Ok(Response::new(res.into()))
+    }
+
+    async fn get_build(
+        &self,
+        request: Request<proto::GetBuildRequest>,
+    ) -> Result<Response<proto::BuildStatusResponse>, Status> {
+        let namespace = request_namespace(&request);
+        let build_id = request.into_inner().build_id;
+        let Json(res) = handlers::build::get_build(
+            State(self.state.clone()),
+            namespace,
+            Path(build_id),
+            Query(models::StatusQuery::default()),
+        )
+        .await?;
+        Ok(Response::new(res.into()))
+    }
+
+    type StreamLogsStream = LogStream;
+
+    async fn stream_logs(
+        &self,
+        request: Request<proto::StreamLogsRequest>,
+    ) -> Result<Response<Self::StreamLogsStream>, Status> {
+        let namespace = request_namespace(&request);
+        let build_id = request.into_inner().build_id;
+        let state = self.state.clone();
+
+        // Verify build exists
+        if state.get_owned_build(&build_id, &namespace).await.is_none() {
+            return Err(XcbridgeError::BuildNotFound(build_id).into());
+        }
+
+        let updates = log_stream::follow(state, build_id);
+        let stream = async_stream::stream! {
+            let mut sent_artifacts = HashSet::new();
+            for await update in updates {
+                match update {
+                    LogUpdate::Line(line) => {
+                        let progress = handlers::test::parse_test_case_line(&line);
+                        let artifact = handlers::build::parse_product_line(&line);
+                        yield Ok(LogEvent {
+                            event: Some(log_event::Event::Line(line)),
+                        });
+
+                        if let Some(progress) = progress {
+                            yield Ok(LogEvent {
+                                event: Some(log_event::Event::Test(proto::TestCaseEvent {
+                                    suite: progress.suite,
+                                    test: progress.test,
+                                    status: progress.status,
+                                    duration: progress.duration,
+                                })),
+                            });
+                        }
+
+                        if let Some(artifact) = artifact {
+                            if sent_artifacts.insert(artifact.path.clone()) {
+                                yield Ok(LogEvent {
+                                    event: Some(log_event::Event::Artifact(artifact.into())),
+                                });
+                            }
+                        }
+                    }
+                    LogUpdate::Complete(build) => {
+                        if let BuildStatus::Success { artifacts, .. } = &build {
+                            for path in artifacts.iter().filter(|p| !sent_artifacts.contains(*p)) {
+                                yield Ok(LogEvent {
+                                    event: Some(log_event::Event::Artifact(proto::ArtifactEvent {
+                                        path: path.clone(),
+                                        target: None,
+                                    })),
+                                });
+                            }
+                        }
+                        yield Ok(LogEvent {
+                            event: Some(log_event::Event::Complete(build.name().to_string())),
+                        });
+                    }
+                    LogUpdate::Queued(queue) => {
+                        yield Ok(LogEvent {
+                            event: Some(log_event::Event::Queued(proto::QueueEvent {
+                                position: queue.position as u32,
+                                eta_secs: queue.eta_secs,
+                            })),
+                        });
+                    }
+                }
+            }
+        };
+
+        Ok(Response::new(Box::pin(stream)))
+    }
+}
+
+/// Serve the gRPC interface on a bound listener until the process exits
+#[allow(clippy::result_large_err)] // tonic interceptors must return `Status`
+pub async fn serve(
+    state: SharedState,
+    listener: TcpListener,
+) -> Result<(), tonic::transport::Error> {
+    let config = state.config.clone();
+
+    // Same X-API-Key check as the REST auth middleware, via request metadata
+    let auth = move |mut request: Request<()>| {
+        let key = request
+            .metadata()
+            .get("x-api-key")
+            .and_then(|v| v.to_str().ok());
+        match config.namespace_for_key(key) {
+            Some(namespace) => {
+                request.extensions_mut().insert(namespace);
+                Ok(request)
+            }
+            None => Err(Status::unauthenticated("Unauthorized")),
+        }
+    };
+
+    let service = XcbridgeServer::with_interceptor(XcbridgeService { state }, auth);
+
+    // Wrapping a bound listener can't fail
+    let incoming =
+        TcpIncoming::from_listener(listener, true, None).expect("listener is already bound");
+
+    tonic::transport::Server::builder()
+        .add_service(service)
+        .serve_with_incoming(incoming)
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::state::AppState;
+    use clap::Parser;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_get_unknown_build_is_not_found() {
+        let config = Config::parse_from(["xcbridge"]);
+        let service = XcbridgeService {
+            state: Arc::new(AppState::new(config, "15.0".to_string())),
+        };
+
+        let status = service
+            .get_build(Request::new(proto::GetBuildRequest {
+                build_id: "missing".to_string(),
+            }))
+            .await
+            .unwrap_err();
+
+        assert_eq!(status.code(), tonic::Code::NotFound);
+    }
+}
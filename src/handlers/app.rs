@@ -0,0 +1,75 @@
+// Copyright 2026 Aptove
+// SPDX-License-Identifier: Apache-2.0
+
+//! App bundle handlers
+
+use super::JsonBody;
+use crate::error::{Result, XcbridgeError};
+use crate::models::{AppInfoQuery, AppValidateRequest};
+use crate::state::{Namespace, SharedState};
+use crate::xcode::bundle::{self, AppInfo};
+use crate::xcode::validation::{self, AppCompatibility, AppValidation};
+use axum::{
+    extract::{Query, State},
+    Json,
+};
+use std::path::PathBuf;
+
+/// GET /app/info - Read bundle metadata from a built app
+pub async fn info(
+    State(state): State<SharedState>,
+    namespace: Namespace,
+    Query(query): Query<AppInfoQuery>,
+) -> Result<Json<AppInfo>> {
+    let path = PathBuf::from(&query.path);
+    if !state.is_product_path_allowed(&path, &namespace).await {
+        return Err(XcbridgeError::PathNotAllowed(query.path));
+    }
+
+    let info = bundle::read_app_info(&path)?;
+    Ok(Json(info))
+}
+
+/// GET /app/compatibility - Report the minimum OS, device families and
+/// architectures a built app supports
+pub async fn compatibility(
+    State(state): State<SharedState>,
+    namespace: Namespace,
+    Query(query): Query<AppInfoQuery>,
+) -> Result<Json<AppCompatibility>> {
+    let path = PathBuf::from(&query.path);
+    if !state.is_product_path_allowed(&path, &namespace).await {
+        return Err(XcbridgeError::PathNotAllowed(query.path));
+    }
+    if !path.is_dir() {
+        return Err(XcbridgeError::InvalidRequest(format!(
+            "Not an app bundle: {}",
+            query.path
+        )));
+    }
+
+    let compatibility = validation::app_compatibility(&path).await?;
+    Ok(Json(compatibility))
+}
+
+/// POST /app/validate - Check an app bundle's Info.plist, architectures and
+/// code signature before installing it
+pub async fn validate(
+    State(state): State<SharedState>,
+    namespace: Namespace,
+    JsonBody(req): JsonBody<AppValidateRequest>,
+) -> Result<Json<AppValidation>> {
+    let path = PathBuf::from(&req.path);
+    if !state.is_product_path_allowed(&path, &namespace).await {
+        return Err(XcbridgeError::PathNotAllowed(req.path));
+    }
+    if !path.is_dir() {
+        return Err(XcbridgeError::InvalidRequest(format!(
+            "Not an app bundle: {}",
+            req.path
+        )));
+    }
+
+    let validation = validation::validate_app(&path, req.platform.as_deref()).await;
+    Ok(Json(validation))
+}
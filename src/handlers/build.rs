@@ -3,83 +3,843 @@
 
 //! Build handler
 
+use super::JsonBody;
+use crate::cache;
+use crate::disk;
 use crate::error::{Result, XcbridgeError};
-use crate::models::{BuildRequest, BuildStartedResponse, BuildStatusResponse};
-use crate::state::{BuildStatus, SharedState};
-use crate::xcode::xcodebuild::{self, BuildParams};
+use crate::log_stream::{self, LogUpdate};
+use crate::models::{
+    ArtifactEvent, BatchBuildRequest, BuildCancelAllResponse, BuildCancelResponse,
+    BuildCleanupResponse, BuildRequest, BuildStartedResponse, BuildStatusResponse,
+    CancelBuildsQuery, LogDownloadQuery, LogExport, LogFormat, LogQuery, RecentBuild,
+    RecentBuildsQuery, RecentBuildsResponse, StatusQuery,
+};
+use crate::state::{
+    self, BatchStep, BatchStepStatus, BuildRecord, BuildStatus, FailureReason, Namespace,
+    QueueStatus, SharedState,
+};
+use crate::upload;
+use crate::xcode::bundle;
+use crate::xcode::simctl;
+use crate::xcode::xcodebuild::{self, BuildParams, CancelSignal};
+use crate::xcode::xcresult;
 use axum::{
-    extract::{Path, State},
-    response::sse::{Event, Sse},
+    extract::{Multipart, Path, Query, State},
+    http::header,
+    response::{
+        sse::{Event, Sse},
+        IntoResponse,
+    },
     Json,
 };
 use futures::stream::Stream;
+use regex::Regex;
+use std::collections::HashSet;
 use std::convert::Infallible;
 use std::path::PathBuf;
 use std::sync::Arc;
 use tokio::sync::mpsc;
+use tracing::Instrument;
 use uuid::Uuid;
 
 /// POST /build - Start a new build
 pub async fn start_build(
     State(state): State<SharedState>,
-    Json(req): Json<BuildRequest>,
+    namespace: Namespace,
+    JsonBody(req): JsonBody<BuildRequest>,
 ) -> Result<Json<BuildStartedResponse>> {
-    // Validate project/workspace path
-    let project_path = req
-        .project
-        .as_ref()
-        .or(req.workspace.as_ref())
-        .ok_or_else(|| {
-            XcbridgeError::InvalidRequest("Either project or workspace must be specified".into())
-        })?;
+    submit_build(state, namespace, req, None).await.map(Json)
+}
 
-    let path = PathBuf::from(project_path);
-    if !state.config.is_path_allowed(&path) {
-        return Err(XcbridgeError::PathNotAllowed(project_path.clone()));
+/// POST /build/upload - Build a project uploaded as an archive
+///
+/// Takes a multipart body with the project archive in `archive` and a build
+/// request in `request`, whose paths are relative to the archive root.
+pub async fn upload_build(
+    State(state): State<SharedState>,
+    namespace: Namespace,
+    multipart: Multipart,
+) -> Result<Json<BuildStartedResponse>> {
+    let upload = upload::receive(&state.config, multipart).await?;
+    let started = async {
+        let mut req: BuildRequest = super::parse_json_body(&upload.request)?;
+        req.project = super::resolve_upload_path(&upload, req.project)?;
+        req.workspace = super::resolve_upload_path(&upload, req.workspace)?;
+        req.working_directory = super::resolve_upload_path(&upload, req.working_directory)?;
+        submit_build(state, namespace, req, Some(upload.dir.clone())).await
     }
+    .await;
+
+    // The workspace goes with the build, unless no build will run in it
+    if !matches!(&started, Ok(started) if started.cache_hit != Some(true)) {
+        state::remove_upload(&upload.dir).await;
+    }
+    started.map(Json)
+}
+
+/// DerivedData directory for a new build, and whether it is removed with
+/// the build
+///
+/// Each build gets its own directory unless the caller chose one. Only
+/// server-assigned directories are removed by default, so a caller's shared
+/// cache survives unless `cleanup` asks otherwise.
+fn derived_data_for(
+    state: &SharedState,
+    build_id: &str,
+    requested: Option<&str>,
+    cleanup: Option<bool>,
+) -> (PathBuf, bool) {
+    match requested {
+        Some(path) => (PathBuf::from(path), cleanup.unwrap_or(false)),
+        None => (
+            state.config.derived_data_base().join(build_id),
+            cleanup.unwrap_or(true),
+        ),
+    }
+}
+
+/// Validate a build request and start it in the background
+///
+/// `upload_dir` is an uploaded project's workspace, removed once the build
+/// finishes.
+async fn submit_build(
+    state: SharedState,
+    namespace: Namespace,
+    req: BuildRequest,
+    upload_dir: Option<PathBuf>,
+) -> Result<BuildStartedResponse> {
+    let path = validate_build_request(&state, &req)?;
+    let (destination, auto_destination) = resolve_destination(&state, &namespace, &req).await?;
 
     // Generate build ID
     let build_id = Uuid::new_v4().to_string();
-    
-    // Create build entry
-    state.create_build(&build_id).await;
-
-    // Convert request to build params
-    let params = BuildParams {
-        project: req.project,
-        workspace: req.workspace,
-        scheme: req.scheme,
-        configuration: req.configuration,
-        destination: req.destination,
-        derived_data_path: req.derived_data_path,
-        extra_args: req.extra_args,
+    let configuration = req.configuration.to_lowercase();
+    let name = super::job_name(
+        req.name.clone(),
+        &[
+            "build",
+            req.scheme
+                .as_deref()
+                .or(req.target.as_deref())
+                .unwrap_or_default(),
+            &configuration,
+        ],
+        &build_id,
+    )?;
+
+    let (derived_data_path, owns_derived_data) = derived_data_for(
+        &state,
+        &build_id,
+        req.derived_data_path.as_deref(),
+        req.cleanup_derived_data,
+    );
+
+    let result_bundle_path = req.result_bundle.then(|| {
+        state
+            .config
+            .result_bundle_dir()
+            .join(format!("{}.xcresult", build_id))
+            .to_string_lossy()
+            .to_string()
+    });
+    let params = build_params(
+        &state,
+        &req,
+        destination,
+        &derived_data_path,
+        result_bundle_path,
+    );
+
+    // Reuse a previous build if nothing has changed. The per-build DerivedData
+    // and result bundle paths are excluded from the fingerprint since they
+    // differ on every build.
+    let fingerprint = if req.use_cache {
+        let source = match req.fingerprint {
+            Some(fingerprint) => fingerprint,
+            None => hash_project_dir(&path).await?,
+        };
+        let mut cache_params = params.clone();
+        cache_params.result_bundle_path = None;
+        if req.derived_data_path.is_none() {
+            cache_params.derived_data_path = None;
+        }
+        let fingerprint = cache::build_fingerprint(&cache_params, &source);
+
+        if let Some((build_id, artifacts)) = state.get_cached_build(&fingerprint, &namespace).await
+        {
+            tracing::info!(
+                "Build cache hit for {}: reusing {}",
+                params.name(),
+                build_id
+            );
+            let record = state.get_build_record(&build_id).await;
+            let derived_data_path = record
+                .as_ref()
+                .and_then(|record| record.derived_data_path.as_ref())
+                .map(|path| path.to_string_lossy().to_string());
+            let exported_artifacts = match &req.output_dir {
+                Some(dir) => {
+                    Some(bundle::export_products(&artifacts, std::path::Path::new(dir)).await?)
+                }
+                None => None,
+            };
+            return Ok(BuildStartedResponse {
+                build_id: build_id.clone(),
+                name: record.and_then(|record| record.name),
+                status: "success".to_string(),
+                logs_url: format!("/build/{}/logs", build_id),
+                cache_hit: Some(true),
+                artifacts: Some(artifacts),
+                exported_artifacts,
+                derived_data_path,
+            });
+        }
+        Some(fingerprint)
+    } else {
+        None
     };
+    let cache_hit = fingerprint.as_ref().map(|_| false);
+    let cancel = CancelSignal::default();
+
+    // Create build entry
+    state
+        .insert_build(
+            &build_id,
+            BuildRecord {
+                derived_data_path: Some(derived_data_path.clone()),
+                owns_derived_data,
+                namespace,
+                name: Some(name.clone()),
+                // Target builds are listed under the target name
+                scheme: Some(params.name().to_string()),
+                result_bundle_path: params.result_bundle_path.as_ref().map(PathBuf::from),
+                keep_result_bundle: req.keep_result_bundle,
+                cancel: cancel.clone(),
+                ..BuildRecord::default()
+            },
+        )
+        .await?;
+
+    if let Some(message) = auto_destination {
+        state.append_build_log(&build_id, message).await;
+    }
 
     // Spawn build task
     let state_clone = Arc::clone(&state);
     let build_id_clone = build_id.clone();
-    tokio::spawn(async move {
-        run_build(state_clone, build_id_clone, params).await;
-    });
+    let resolve_packages = req.resolve_packages;
+    let retry_clean = req.retry_clean_on_failure;
+    let output_dir = req.output_dir.map(PathBuf::from);
+    let span = tracing::info_span!("build", id = %build_id, name = %name);
+    tokio::spawn(
+        async move {
+            if let Some(_slot) = state_clone.acquire_job_slot(&build_id_clone, &cancel).await {
+                run_build(
+                    state_clone,
+                    build_id_clone,
+                    params,
+                    resolve_packages,
+                    retry_clean,
+                    fingerprint,
+                    output_dir,
+                    cancel,
+                )
+                .await;
+            }
+            if let Some(dir) = upload_dir {
+                state::remove_upload(&dir).await;
+            }
+        }
+        .instrument(span),
+    );
 
-    Ok(Json(BuildStartedResponse {
+    Ok(BuildStartedResponse {
         build_id: build_id.clone(),
+        name: Some(name),
         status: "running".to_string(),
         logs_url: format!("/build/{}/logs", build_id),
-    }))
+        cache_hit,
+        artifacts: None,
+        exported_artifacts: None,
+        derived_data_path: Some(derived_data_path.to_string_lossy().to_string()),
+    })
 }
 
-/// Run the actual build
-async fn run_build(state: SharedState, build_id: String, params: BuildParams) {
+/// POST /build/batch - Build several schemes one after another as one job
+///
+/// The builds share one DerivedData directory, so later schemes reuse the
+/// modules earlier ones built. Each build is framed in the log by
+/// `=== [n/total] ... ===` lines, and `GET /build/{id}` reports each one's
+/// status and artifacts under `batch`. By default the first failure skips
+/// the remaining builds; with `continue_on_error` they run anyway. The job
+/// fails if any build failed.
+pub async fn start_batch_build(
+    State(state): State<SharedState>,
+    namespace: Namespace,
+    JsonBody(req): JsonBody<BatchBuildRequest>,
+) -> Result<Json<BuildStartedResponse>> {
+    if req.builds.is_empty() {
+        return Err(XcbridgeError::InvalidRequest(
+            "builds must list at least one build".into(),
+        ));
+    }
+    for build in &req.builds {
+        validate_build_request(&state, build)?;
+        if build.name.is_some()
+            || build.use_cache
+            || build.result_bundle
+            || build.keep_result_bundle
+            || build.timing_summary
+            || build.derived_data_path.is_some()
+            || build.cleanup_derived_data.is_some()
+            || build.output_dir.is_some()
+        {
+            return Err(XcbridgeError::InvalidRequest(
+                "name, use_cache, result_bundle, keep_result_bundle, timing_summary, \
+                 derived_data_path, cleanup_derived_data and output_dir can't be set on the \
+                 builds in a batch"
+                    .into(),
+            ));
+        }
+    }
+    for build in &req.builds {
+        let project =
+            super::project_or_workspace(build.project.as_ref(), build.workspace.as_ref())?;
+        check_derived_data_path(
+            &state,
+            req.derived_data_path.as_deref(),
+            req.cleanup_derived_data,
+            std::path::Path::new(project),
+        )?;
+    }
+
+    let build_id = Uuid::new_v4().to_string();
+    let schemes: Vec<&str> = req
+        .builds
+        .iter()
+        .map(|build| {
+            build
+                .scheme
+                .as_deref()
+                .or(build.target.as_deref())
+                .unwrap_or_default()
+        })
+        .collect();
+    let name = super::job_name(req.name, &["batch", schemes[0]], &build_id)?;
+    let (derived_data_path, owns_derived_data) = derived_data_for(
+        &state,
+        &build_id,
+        req.derived_data_path.as_deref(),
+        req.cleanup_derived_data,
+    );
+
+    let mut steps = Vec::new();
+    let mut messages = Vec::new();
+    for build in &req.builds {
+        let (destination, auto_destination) =
+            resolve_destination(&state, &namespace, build).await?;
+        messages.extend(auto_destination);
+        steps.push(BatchBuild {
+            params: build_params(&state, build, destination, &derived_data_path, None),
+            resolve_packages: build.resolve_packages,
+            retry_clean: build.retry_clean_on_failure,
+        });
+    }
+    let cancel = CancelSignal::default();
+
+    state
+        .insert_build(
+            &build_id,
+            BuildRecord {
+                derived_data_path: Some(derived_data_path.clone()),
+                owns_derived_data,
+                namespace,
+                name: Some(name.clone()),
+                scheme: Some(schemes[0].to_string()),
+                batch: Some(
+                    schemes
+                        .iter()
+                        .map(|scheme| BatchStep::pending(scheme))
+                        .collect(),
+                ),
+                cancel: cancel.clone(),
+                ..BuildRecord::default()
+            },
+        )
+        .await?;
+    for message in messages {
+        state.append_build_log(&build_id, message).await;
+    }
+
     let state_clone = Arc::clone(&state);
     let build_id_clone = build_id.clone();
+    let continue_on_error = req.continue_on_error;
+    let span = tracing::info_span!("build", id = %build_id, name = %name);
+    tokio::spawn(
+        async move {
+            if let Some(_slot) = state_clone.acquire_job_slot(&build_id_clone, &cancel).await {
+                run_batch(
+                    state_clone,
+                    build_id_clone,
+                    steps,
+                    continue_on_error,
+                    cancel,
+                )
+                .await;
+            }
+        }
+        .instrument(span),
+    );
+
+    Ok(Json(BuildStartedResponse {
+        build_id: build_id.clone(),
+        name: Some(name),
+        status: "running".to_string(),
+        logs_url: format!("/build/{}/logs", build_id),
+        cache_hit: None,
+        artifacts: None,
+        exported_artifacts: None,
+        derived_data_path: Some(derived_data_path.to_string_lossy().to_string()),
+    }))
+}
+
+/// One build of a batch
+struct BatchBuild {
+    params: BuildParams,
+    resolve_packages: bool,
+    retry_clean: bool,
+}
+
+/// Run a batch's builds in order, then settle the job
+async fn run_batch(
+    state: SharedState,
+    build_id: String,
+    builds: Vec<BatchBuild>,
+    continue_on_error: bool,
+    cancel: CancelSignal,
+) {
+    let total = builds.len();
+    let mut artifacts = Vec::new();
+    let mut failure = None;
+
+    for (index, build) in builds.into_iter().enumerate() {
+        let mut step = BatchStep::pending(build.params.name());
+        let label = format!("[{}/{}] {}", index + 1, total, step.scheme);
+
+        if cancel.is_cancelled() || (failure.is_some() && !continue_on_error) {
+            step.status = BatchStepStatus::Skipped;
+            state.set_batch_step(&build_id, index, step).await;
+            continue;
+        }
+
+        state
+            .append_build_log(&build_id, format!("=== {} building ===", label))
+            .await;
+        step.status = BatchStepStatus::Running;
+        state.set_batch_step(&build_id, index, step.clone()).await;
+
+        let started = std::time::Instant::now();
+        let outcome = execute_build(
+            &state,
+            &build_id,
+            &build.params,
+            build.resolve_packages,
+            build.retry_clean,
+            &cancel,
+        )
+        .await;
+        step.duration_secs = Some(started.elapsed().as_secs_f64());
+
+        match outcome {
+            BuildOutcome::Success { artifacts: built } => {
+                state
+                    .append_build_log(&build_id, format!("=== {} succeeded ===", label))
+                    .await;
+                step.status = BatchStepStatus::Success;
+                step.artifacts = built.clone();
+                artifacts.extend(built);
+            }
+            BuildOutcome::Failed {
+                error,
+                exit_code,
+                reason,
+            } => {
+                state
+                    .append_build_log(&build_id, format!("=== {} failed: {} ===", label, error))
+                    .await;
+                step.status = BatchStepStatus::Failed;
+                step.error = Some(error.clone());
+                if failure.is_none() {
+                    failure = Some((format!("{}: {}", step.scheme, error), exit_code, reason));
+                }
+            }
+        }
+        state.set_batch_step(&build_id, index, step).await;
+    }
+
+    match failure {
+        None => state.complete_build(&build_id, artifacts).await,
+        Some((error, exit_code, reason)) => {
+            state
+                .fail_build_with_reason(&build_id, error, exit_code, reason)
+                .await;
+            remove_owned_derived_data(&state, &build_id).await;
+        }
+    }
+}
+
+/// Check a build request's paths, scheme or target and options, returning
+/// the project or workspace path
+fn validate_build_request(state: &SharedState, req: &BuildRequest) -> Result<PathBuf> {
+    let project_path = super::project_or_workspace(req.project.as_ref(), req.workspace.as_ref())?;
+
+    let path = PathBuf::from(project_path);
+    if !state.config.is_path_allowed(&path) {
+        return Err(XcbridgeError::PathNotAllowed(project_path.clone()));
+    }
+
+    if let Some(toolchain) = &req.toolchain {
+        if xcodebuild::find_toolchain(toolchain).is_none() {
+            return Err(XcbridgeError::ToolchainNotFound(toolchain.clone()));
+        }
+    }
+    if req.jobs == Some(0) {
+        return Err(XcbridgeError::InvalidRequest(
+            "jobs must be at least 1".into(),
+        ));
+    }
+
+    match (&req.scheme, &req.target) {
+        (Some(_), Some(_)) => {
+            return Err(XcbridgeError::InvalidRequest(
+                "Specify either scheme or target, not both".into(),
+            ))
+        }
+        (None, None) => {
+            return Err(XcbridgeError::InvalidRequest(
+                "Either scheme or target must be specified".into(),
+            ))
+        }
+        (None, Some(_)) if req.workspace.is_some() => {
+            return Err(XcbridgeError::InvalidRequest(
+                "Targets can only be built from a project, not a workspace".into(),
+            ))
+        }
+        _ => {}
+    }
+
+    xcodebuild::validate_environment(&req.environment)?;
+
+    if let Some(dir) = &req.working_directory {
+        let dir_path = PathBuf::from(dir);
+        if !state.config.is_path_allowed(&dir_path) {
+            return Err(XcbridgeError::PathNotAllowed(dir.clone()));
+        }
+        if !dir_path.is_dir() {
+            return Err(XcbridgeError::InvalidRequest(format!(
+                "Working directory not found: {}",
+                dir
+            )));
+        }
+    }
+    if let Some(dir) = &req.output_dir {
+        if !state
+            .config
+            .is_output_path_allowed(std::path::Path::new(dir))
+        {
+            return Err(XcbridgeError::PathNotAllowed(dir.clone()));
+        }
+    }
+    check_derived_data_path(
+        state,
+        req.derived_data_path.as_deref(),
+        req.cleanup_derived_data,
+        &path,
+    )?;
+    Ok(path)
+}
+
+/// Check a caller-chosen DerivedData directory before a build writes to it,
+/// and maybe removes it
+///
+/// It must be within the allowed paths and must not hold the project, which
+/// removing it with the build would delete. Removal is only honored under
+/// `--derived-data-base`, so `cleanup_derived_data` can't delete arbitrary
+/// directories.
+fn check_derived_data_path(
+    state: &SharedState,
+    requested: Option<&str>,
+    cleanup: Option<bool>,
+    project: &std::path::Path,
+) -> Result<()> {
+    let Some(requested) = requested else {
+        return Ok(());
+    };
+    let path = std::path::Path::new(requested);
+    if !state.config.is_output_path_allowed(path) {
+        return Err(XcbridgeError::PathNotAllowed(requested.to_string()));
+    }
+
+    let resolved = resolve_existing(path);
+    let project_dir = resolve_existing(project.parent().unwrap_or(project));
+    if project_dir.starts_with(&resolved) {
+        return Err(XcbridgeError::InvalidRequest(format!(
+            "derived_data_path must not contain the project: {}",
+            requested
+        )));
+    }
+    let base = resolve_existing(&state.config.derived_data_base());
+    if cleanup == Some(true) && !resolved.starts_with(base) {
+        return Err(XcbridgeError::InvalidRequest(format!(
+            "cleanup_derived_data can only remove directories under --derived-data-base: {}",
+            requested
+        )));
+    }
+    Ok(())
+}
+
+/// `path` with its existing part canonicalized, so symlinks and relative
+/// paths compare correctly with other paths
+fn resolve_existing(path: &std::path::Path) -> PathBuf {
+    let absolute = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    let Some(existing) = absolute.ancestors().find(|ancestor| ancestor.exists()) else {
+        return absolute;
+    };
+    let rest = absolute
+        .strip_prefix(existing)
+        .unwrap_or(std::path::Path::new(""));
+    existing
+        .canonicalize()
+        .unwrap_or_else(|_| existing.to_path_buf())
+        .join(rest)
+}
+
+/// The request's destination, or its lease's simulator
+///
+/// Without either (or a destination in `extra_args`), builds go to the only
+/// booted simulator, along with a log line saying so.
+async fn resolve_destination(
+    state: &SharedState,
+    namespace: &Namespace,
+    req: &BuildRequest,
+) -> Result<(Option<String>, Option<String>)> {
+    let destination = super::lease_destination(
+        state,
+        namespace,
+        req.lease.as_deref(),
+        req.destination.clone(),
+    )
+    .await?;
+    if destination.is_some() || has_destination_arg(&req.extra_args) {
+        return Ok((destination, None));
+    }
+
+    // Default to the booted simulator, as the simulator handlers do
+    let (destination, simulator) = booted_simulator_destination().await?;
+    let message = format!(
+        "No destination specified, using booted simulator {}: {}",
+        simulator, destination
+    );
+    tracing::info!("{}", message);
+    Ok((Some(destination), Some(message)))
+}
+
+/// xcodebuild parameters for a validated build request
+fn build_params(
+    state: &SharedState,
+    req: &BuildRequest,
+    destination: Option<String>,
+    derived_data_path: &std::path::Path,
+    result_bundle_path: Option<String>,
+) -> BuildParams {
+    BuildParams {
+        project: req.project.clone(),
+        workspace: req.workspace.clone(),
+        scheme: req.scheme.clone(),
+        target: req.target.clone(),
+        configuration: req.configuration.clone(),
+        destination,
+        destination_timeout: req
+            .destination_timeout
+            .or(state.config.default_destination_timeout()),
+        jobs: req.jobs.or(state.config.build_jobs),
+        timing_summary: req.timing_summary,
+        derived_data_path: Some(derived_data_path.to_string_lossy().to_string()),
+        result_bundle_path,
+        toolchain: req.toolchain.clone(),
+        extra_args: req.extra_args.clone(),
+        environment: req.environment.clone(),
+        working_directory: req.working_directory.clone(),
+        skip_plugin_validation: req.skip_plugin_validation,
+    }
+}
+
+/// Whether extra xcodebuild arguments already choose a destination or SDK
+fn has_destination_arg(extra_args: &[String]) -> bool {
+    extra_args
+        .iter()
+        .any(|arg| arg == "-destination" || arg == "-sdk")
+}
+
+/// Destination for the only booted simulator, along with its name
+async fn booted_simulator_destination() -> Result<(String, String)> {
+    let mut booted = simctl::booted_simulators().await?;
+    match booted.len() {
+        1 => {
+            let simulator = booted.remove(0);
+            Ok((
+                format!("platform=iOS Simulator,id={}", simulator.udid),
+                simulator.name,
+            ))
+        }
+        0 => Err(XcbridgeError::InvalidRequest(
+            "No destination specified and no simulator is booted; \
+             pass a destination or boot a simulator first"
+                .into(),
+        )),
+        n => Err(XcbridgeError::InvalidRequest(format!(
+            "No destination specified and {} simulators are booted ({}); \
+             pass a destination such as \"platform=iOS Simulator,id=<udid>\"",
+            n,
+            booted
+                .iter()
+                .map(|s| format!("{} {}", s.name, s.udid))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ))),
+    }
+}
+
+/// Product bundles in the build's DerivedData, falling back to the build
+/// directory reported in the log
+fn build_artifacts(derived_data_path: Option<&str>, build_dir: Option<String>) -> Vec<String> {
+    let products = derived_data_path
+        .map(|path| bundle::find_products(std::path::Path::new(path)))
+        .unwrap_or_default();
+    if products.is_empty() {
+        return build_dir.into_iter().collect();
+    }
+    products
+        .into_iter()
+        .map(|path| path.to_string_lossy().to_string())
+        .collect()
+}
+
+/// Fingerprint the directory containing the project or workspace
+async fn hash_project_dir(project_path: &std::path::Path) -> Result<String> {
+    let dir = project_path.parent().unwrap_or(project_path).to_path_buf();
+
+    let hash = tokio::task::spawn_blocking(move || cache::hash_directory(&dir))
+        .await
+        .map_err(|e| XcbridgeError::Internal(e.to_string()))?
+        .map_err(|e| {
+            XcbridgeError::InvalidRequest(format!("Failed to fingerprint project: {}", e))
+        })?;
+
+    Ok(format!("{:016x}", hash))
+}
+
+/// Run the actual build, then copy its products to `output_dir` if asked
+#[allow(clippy::too_many_arguments)]
+async fn run_build(
+    state: SharedState,
+    build_id: String,
+    params: BuildParams,
+    resolve_packages: bool,
+    retry_clean: bool,
+    fingerprint: Option<String>,
+    output_dir: Option<PathBuf>,
+    cancel: CancelSignal,
+) {
+    let mut outcome = execute_build(
+        &state,
+        &build_id,
+        &params,
+        resolve_packages,
+        retry_clean,
+        &cancel,
+    )
+    .await;
+    if let (BuildOutcome::Success { artifacts }, Some(dir)) = (&outcome, &output_dir) {
+        state
+            .append_build_log(
+                &build_id,
+                format!("=== Exporting build products to {} ===", dir.display()),
+            )
+            .await;
+        match bundle::export_products(artifacts, dir).await {
+            Ok(exported) => state.set_exported_artifacts(&build_id, exported).await,
+            Err(e) => {
+                outcome = BuildOutcome::Failed {
+                    error: format!("Failed to export build products: {}", e),
+                    exit_code: None,
+                    reason: None,
+                }
+            }
+        }
+    }
+
+    match outcome {
+        BuildOutcome::Success { artifacts } => {
+            state.complete_build(&build_id, artifacts).await;
+            if let Some(fingerprint) = fingerprint {
+                state.cache_build(fingerprint, &build_id).await;
+            }
+        }
+        BuildOutcome::Failed {
+            error,
+            exit_code,
+            reason,
+        } => {
+            state
+                .fail_build_with_reason(&build_id, error, exit_code, reason)
+                .await;
+            remove_owned_derived_data(&state, &build_id).await;
+        }
+    }
+}
+
+/// How a build's xcodebuild run ended
+enum BuildOutcome {
+    Success {
+        artifacts: Vec<String>,
+    },
+    Failed {
+        error: String,
+        exit_code: Option<i32>,
+        reason: Option<FailureReason>,
+    },
+}
+
+/// Resolve packages if asked, run xcodebuild (retrying from clean on stale
+/// DerivedData if asked) and attach its diagnostics and timings to the job,
+/// leaving the job's status to the caller
+async fn execute_build(
+    state: &SharedState,
+    build_id: &str,
+    params: &BuildParams,
+    resolve_packages: bool,
+    retry_clean: bool,
+    cancel: &CancelSignal,
+) -> BuildOutcome {
+    if resolve_packages {
+        if let Err((error, exit_code)) =
+            resolve_package_dependencies(state, build_id, params, cancel).await
+        {
+            return BuildOutcome::Failed {
+                error,
+                exit_code,
+                reason: Some(FailureReason::PackageResolution),
+            };
+        }
+    }
 
     let (tx, mut rx) = mpsc::channel::<String>(100);
 
     // Spawn log collector
-    let state_for_logs = Arc::clone(&state);
-    let build_id_for_logs = build_id.clone();
-    tokio::spawn(async move {
+    let state_for_logs = Arc::clone(state);
+    let build_id_for_logs = build_id.to_string();
+    let collector = tokio::spawn(async move {
         while let Some(line) = rx.recv().await {
             state_for_logs
                 .append_build_log(&build_id_for_logs, line)
@@ -87,52 +847,294 @@ async fn run_build(state: SharedState, build_id: String, params: BuildParams) {
         }
     });
 
+    let result_bundle_path = params.result_bundle_path.clone().map(PathBuf::from);
+    if let Some(parent) = result_bundle_path.as_deref().and_then(|p| p.parent()) {
+        let _ = tokio::fs::create_dir_all(parent).await;
+    }
+
     // Run xcodebuild
-    let result = xcodebuild::run_xcodebuild(params.to_args(), move |line| {
-        let _ = tx.try_send(line);
-    })
+    let run = |args: Vec<String>| {
+        let tx = tx.clone();
+        xcodebuild::run_xcodebuild_with_env(
+            args,
+            &params.environment,
+            params
+                .working_directory
+                .as_deref()
+                .map(std::path::Path::new),
+            cancel,
+            move |line| {
+                let _ = tx.try_send(line);
+            },
+        )
+    };
+    let mut result = run(params.to_args()).await;
+    if let Ok(output) = &result {
+        state.add_cpu_time(build_id, output.cpu_time).await;
+    }
+
+    // Stale modules and precompiled headers are fixed by a clean build
+    let stale = matches!(&result, Ok(output) if !output.success
+        && xcodebuild::is_stale_build_failure(&output.logs));
+    if let Some(args) = params
+        .clean_build_args()
+        .filter(|_| retry_clean && stale && !cancel.is_cancelled())
+    {
+        tracing::info!("Build failed on stale DerivedData, retrying with clean");
+        let _ = tx
+            .send("=== Stale build cache detected; retrying with clean ===".to_string())
+            .await;
+        if let Some(path) = &result_bundle_path {
+            state::remove_result_bundle(path).await;
+        }
+        state.mark_retried_clean(build_id).await;
+        result = run(args).await;
+        if let Ok(output) = &result {
+            state.add_cpu_time(build_id, output.cpu_time).await;
+        }
+    }
+    drop(tx);
+    // Batches log their own lines after the build's, so let these land first
+    let _ = collector.await;
+
+    // Attach diagnostics before the build is marked complete, so they are
+    // available as soon as clients see the final status
+    if let Some(result_bundle_path) = result_bundle_path {
+        match xcresult::build_results(&result_bundle_path).await {
+            Ok(diagnostics) => state.set_build_diagnostics(build_id, diagnostics).await,
+            Err(e) => tracing::warn!("Failed to read result bundle: {}", e),
+        }
+    }
+
+    if let Some(timings) = result
+        .as_ref()
+        .ok()
+        .and_then(|output| xcodebuild::parse_timing_summary(&output.logs))
+    {
+        state.set_timing_summary(build_id, timings).await;
+    }
+
+    match result {
+        Ok(output) if output.success => BuildOutcome::Success {
+            artifacts: build_artifacts(params.derived_data_path.as_deref(), output.build_dir),
+        },
+        Ok(output) => BuildOutcome::Failed {
+            error: output
+                .logs
+                .iter()
+                .rev()
+                .find(|l| l.contains("error:"))
+                .cloned()
+                .unwrap_or_else(|| "Build failed".to_string()),
+            exit_code: Some(output.exit_code),
+            reason: None,
+        },
+        Err(e) => BuildOutcome::Failed {
+            error: e.to_string(),
+            exit_code: None,
+            reason: None,
+        },
+    }
+}
+
+/// Run `xcodebuild -resolvePackageDependencies`, streaming its output into
+/// the build log ahead of the build's own
+async fn resolve_package_dependencies(
+    state: &SharedState,
+    build_id: &str,
+    params: &BuildParams,
+    cancel: &CancelSignal,
+) -> std::result::Result<(), (String, Option<i32>)> {
+    state
+        .append_build_log(
+            build_id,
+            "=== Resolving package dependencies ===".to_string(),
+        )
+        .await;
+
+    let (tx, mut rx) = mpsc::unbounded_channel::<String>();
+    let log_state = Arc::clone(state);
+    let log_build_id = build_id.to_string();
+    let collector = tokio::spawn(async move {
+        while let Some(line) = rx.recv().await {
+            log_state.append_build_log(&log_build_id, line).await;
+        }
+    });
+
+    let result = xcodebuild::run_xcodebuild_with_env(
+        params.resolve_packages_args(),
+        &params.environment,
+        params
+            .working_directory
+            .as_deref()
+            .map(std::path::Path::new),
+        cancel,
+        |line| {
+            let _ = tx.send(line);
+        },
+    )
     .await;
+    let _ = collector.await;
+    if let Ok(output) = &result {
+        state.add_cpu_time(build_id, output.cpu_time).await;
+    }
 
     match result {
+        Ok(output) if output.success => {
+            state
+                .append_build_log(
+                    build_id,
+                    "=== Package dependencies resolved ===".to_string(),
+                )
+                .await;
+            Ok(())
+        }
         Ok(output) => {
-            if output.success {
-                let artifacts = output
-                    .build_dir
-                    .map(|d| vec![d])
-                    .unwrap_or_default();
-                state_clone.complete_build(&build_id_clone, artifacts).await;
-            } else {
-                let error = output
-                    .logs
-                    .iter()
-                    .rev()
-                    .find(|l| l.contains("error:"))
-                    .cloned()
-                    .unwrap_or_else(|| "Build failed".to_string());
-                state_clone
-                    .fail_build(&build_id_clone, error, Some(output.exit_code))
-                    .await;
+            let error = output
+                .logs
+                .iter()
+                .rev()
+                .find(|l| l.contains("error:"))
+                .cloned()
+                .unwrap_or_else(|| "Failed to resolve package dependencies".to_string());
+            Err((
+                format!("Package resolution failed: {}", error),
+                Some(output.exit_code),
+            ))
+        }
+        Err(e) => Err((format!("Package resolution failed: {}", e), None)),
+    }
+}
+
+/// Remove a failed build's server-assigned DerivedData right away
+///
+/// Successful builds keep theirs until the build is reaped, since their
+/// artifacts (and any cache entry) live inside it.
+async fn remove_owned_derived_data(state: &SharedState, build_id: &str) {
+    if let Some(BuildRecord {
+        derived_data_path: Some(path),
+        owns_derived_data: true,
+        ..
+    }) = state.get_build_record(build_id).await
+    {
+        state::remove_derived_data(&path).await;
+    }
+}
+
+/// GET /build/:id/logs/download - Download a build's log as a file
+///
+/// Plain text by default, or JSON with `?format=json`, served as an
+/// attachment named after the build.
+pub async fn download_logs(
+    State(state): State<SharedState>,
+    namespace: Namespace,
+    Path(build_id): Path<String>,
+    Query(query): Query<LogDownloadQuery>,
+) -> Result<impl IntoResponse> {
+    let filter = super::log_filter(query.grep.as_deref())?;
+    let record = state
+        .get_owned_build(&build_id, &namespace)
+        .await
+        .ok_or_else(|| XcbridgeError::BuildNotFound(build_id.clone()))?;
+    let logs = super::filter_logs(record.status.logs().to_vec(), filter.as_ref());
+    let file_stem = log_file_stem(record.name.as_deref().unwrap_or(&build_id));
+
+    let (content_type, extension, body) = match query.format {
+        LogFormat::Text => {
+            let mut text = logs.join("\n");
+            if !text.is_empty() {
+                text.push('\n');
             }
+            ("text/plain; charset=utf-8", "log", text)
         }
-        Err(e) => {
-            state_clone
-                .fail_build(&build_id_clone, e.to_string(), None)
-                .await;
+        LogFormat::Json => {
+            let export = LogExport {
+                build_id,
+                name: record.name,
+                status: record.status.name().to_string(),
+                logs,
+            };
+            let json = serde_json::to_string_pretty(&export)
+                .map_err(|e| XcbridgeError::Internal(format!("Failed to encode log: {}", e)))?;
+            ("application/json", "json", json)
         }
-    }
+    };
+    let disposition = format!("attachment; filename=\"{}.{}\"", file_stem, extension);
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, content_type.to_string()),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        body,
+    ))
+}
+
+/// A job name made safe for a `Content-Disposition` filename
+fn log_file_stem(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-') {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect()
 }
 
 /// GET /build/:id - Get build status
 pub async fn get_build(
     State(state): State<SharedState>,
+    namespace: Namespace,
     Path(build_id): Path<String>,
+    Query(query): Query<StatusQuery>,
 ) -> Result<Json<BuildStatusResponse>> {
-    let build = state
-        .get_build(&build_id)
+    let filter = super::log_filter(query.grep.as_deref())?;
+    let record = state
+        .get_owned_build(&build_id, &namespace)
         .await
         .ok_or_else(|| XcbridgeError::BuildNotFound(build_id.clone()))?;
+    let queue = state.queue_status(&build_id).await;
 
-    let (status, exit_code, artifacts, error, logs) = match build {
+    Ok(Json(status_response(
+        build_id,
+        record,
+        queue,
+        query.include_logs,
+        filter.as_ref(),
+    )))
+}
+
+/// Describe a build record the way `GET /build/:id` reports it
+///
+/// Logs are only included when `include_logs` is set, filtered by `filter`.
+fn status_response(
+    build_id: String,
+    record: BuildRecord,
+    queue: Option<QueueStatus>,
+    include_logs: bool,
+    filter: Option<&Regex>,
+) -> BuildStatusResponse {
+    // Report the bundle ids of built apps so clients can install and launch
+    // them without reading Info.plist themselves
+    let apps = match (&record.status, &record.derived_data_path) {
+        (BuildStatus::Success { .. }, Some(derived_data)) => Some(
+            bundle::find_apps(derived_data)
+                .iter()
+                .filter_map(|app| bundle::read_app_info(app).ok())
+                .collect(),
+        ),
+        _ => None,
+    };
+
+    let duration_secs = record.duration_secs();
+    let derived_data_path = record
+        .derived_data_path
+        .map(|path| path.to_string_lossy().to_string());
+
+    let (status, exit_code, artifacts, error, logs) = match record.status {
+        BuildStatus::Running { logs } if queue.is_some() => ("queued", None, None, None, logs),
         BuildStatus::Running { logs } => ("running", None, None, None, logs),
         BuildStatus::Success { logs, artifacts } => {
             ("success", Some(0), Some(artifacts), None, logs)
@@ -145,55 +1147,193 @@ pub async fn get_build(
         BuildStatus::Cancelled => ("cancelled", None, None, None, vec![]),
     };
 
-    Ok(Json(BuildStatusResponse {
+    BuildStatusResponse {
         build_id,
+        name: record.name,
         status: status.to_string(),
         exit_code,
         artifacts,
+        exported_artifacts: record.exported_artifacts,
         error,
-        logs,
-    }))
+        logs: include_logs.then(|| super::filter_logs(logs, filter)),
+        derived_data_path,
+        apps,
+        diagnostics: record.diagnostics,
+        result_bundle_path: record
+            .result_bundle_path
+            .map(|path| path.to_string_lossy().to_string()),
+        failure_reason: record.failure_reason,
+        code_signing: record.code_signing,
+        script_phase: record.script_phase,
+        retried_clean: record.retried_clean,
+        timing_summary: record.timing_summary,
+        batch: record.batch,
+        duration_secs,
+        cpu_time: record.cpu_time,
+        queue_position: queue.as_ref().map(|queue| queue.position),
+        eta_secs: queue.and_then(|queue| queue.eta_secs),
+    }
+}
+
+/// GET /builds/recent - Most recent build and test jobs for a scheme
+pub async fn recent_builds(
+    State(state): State<SharedState>,
+    namespace: Namespace,
+    Query(query): Query<RecentBuildsQuery>,
+) -> Json<RecentBuildsResponse> {
+    let builds = state
+        .recent_builds(&namespace, &query.scheme, query.limit)
+        .await
+        .into_iter()
+        .map(|(build_id, record)| {
+            let error = match &record.status {
+                BuildStatus::Failed { error, .. } => Some(error.clone()),
+                _ => None,
+            };
+            RecentBuild {
+                build_id,
+                name: record.name.clone(),
+                kind: record.kind,
+                status: record.status.name().to_string(),
+                error,
+                failure_reason: record.failure_reason,
+                started_at: record.started_at,
+                duration_secs: record.duration_secs(),
+            }
+        })
+        .collect();
+
+    Json(RecentBuildsResponse {
+        scheme: query.scheme,
+        builds,
+    })
+}
+
+/// POST /builds/cleanup - Remove the caller's completed jobs and their DerivedData
+///
+/// Frees disk space after a job fails with the "disk_full" failure reason.
+pub async fn cleanup_builds(
+    State(state): State<SharedState>,
+    namespace: Namespace,
+) -> Json<BuildCleanupResponse> {
+    let removed = state.cleanup_completed_builds(&namespace).await;
+    let free_bytes = disk::free_space(&state.config.derived_data_base()).await;
+
+    Json(BuildCleanupResponse {
+        removed,
+        free_bytes,
+    })
+}
+
+/// POST /builds/cancel - Cancel the namespace's running jobs by scheme or name
+///
+/// For clients that lost a job's id, e.g. after crashing right after
+/// submitting it. At least one of `scheme` and `name` is required; a job
+/// must match every one given.
+pub async fn cancel_builds(
+    State(state): State<SharedState>,
+    namespace: Namespace,
+    Query(query): Query<CancelBuildsQuery>,
+) -> Result<Json<BuildCancelResponse>> {
+    if query.scheme.is_none() && query.name.is_none() {
+        return Err(XcbridgeError::InvalidRequest(
+            "Set scheme or name to choose the jobs to cancel".into(),
+        ));
+    }
+    let cancelled = state
+        .cancel_matching_builds(&namespace, query.scheme.as_deref(), query.name.as_deref())
+        .await;
+    tracing::info!(
+        "Cancelled {} running jobs by scheme or name",
+        cancelled.len()
+    );
+
+    Ok(Json(BuildCancelResponse { cancelled }))
+}
+
+/// POST /builds/cancel-all - Cancel every running build and test (admin only)
+///
+/// Stops each job's xcodebuild the same way `DELETE /build/:id` does.
+pub async fn cancel_all_builds(State(state): State<SharedState>) -> Json<BuildCancelAllResponse> {
+    let cancelled = state.cancel_all_builds().await;
+    tracing::info!("Cancelled all running jobs ({})", cancelled);
+
+    Json(BuildCancelAllResponse { cancelled })
+}
+
+/// Parse the xcodebuild step that finishes a product bundle, e.g.
+/// `CodeSign /path/Build/Products/Debug-iphonesimulator/MyApp.app (in target 'MyApp' from project 'MyApp')`
+///
+/// Only bundles that the final `artifacts` list would include are reported.
+pub(crate) fn parse_product_line(line: &str) -> Option<ArtifactEvent> {
+    let rest = line
+        .strip_prefix("CodeSign ")
+        .or_else(|| line.strip_prefix("Touch "))?;
+    let (path, target) = match rest.split_once(" (in target '") {
+        Some((path, target)) => (path, target.split_once('\'').map(|(t, _)| t.to_string())),
+        None => (rest.trim_end(), None),
+    };
+    // xcodebuild escapes spaces in paths
+    let path = path.replace("\\ ", " ");
+
+    bundle::is_product(std::path::Path::new(&path)).then_some(ArtifactEvent { path, target })
 }
 
 /// GET /build/:id/logs - Stream build logs via SSE
+///
+/// Product bundles are sent as `artifact` events as soon as they're signed,
+/// and any remaining artifacts just before `complete`.
 pub async fn build_logs(
     State(state): State<SharedState>,
+    namespace: Namespace,
     Path(build_id): Path<String>,
+    Query(query): Query<LogQuery>,
 ) -> Result<Sse<impl Stream<Item = std::result::Result<Event, Infallible>>>> {
+    let filter = super::log_filter(query.grep.as_deref())?;
+
     // Verify build exists
-    if state.get_build(&build_id).await.is_none() {
+    if state.get_owned_build(&build_id, &namespace).await.is_none() {
         return Err(XcbridgeError::BuildNotFound(build_id));
     }
 
+    let updates = log_stream::follow(state, build_id);
     let stream = async_stream::stream! {
-        let mut last_index = 0;
-        
-        loop {
-            if let Some(build) = state.get_build(&build_id).await {
-                let logs = build.logs();
-                
-                // Send new log lines
-                for line in logs.iter().skip(last_index) {
-                    yield Ok(Event::default().data(line.clone()));
+        let mut sent_artifacts = HashSet::new();
+        for await update in updates {
+            match update {
+                LogUpdate::Line(line) => {
+                    // `artifact` events are sent whether or not the line matches
+                    let artifact = parse_product_line(&line);
+                    if filter.as_ref().is_none_or(|filter| filter.is_match(&line)) {
+                        yield Ok(Event::default().data(line));
+                    }
+
+                    if let Some(artifact) = artifact {
+                        if sent_artifacts.insert(artifact.path.clone()) {
+                            if let Ok(event) = Event::default().event("artifact").json_data(&artifact) {
+                                yield Ok(event);
+                            }
+                        }
+                    }
                 }
-                last_index = logs.len();
-
-                // Check if build is complete
-                if build.is_complete() {
-                    let status = match &build {
-                        BuildStatus::Success { .. } => "success",
-                        BuildStatus::Failed { .. } => "failed",
-                        BuildStatus::Cancelled => "cancelled",
-                        _ => "unknown",
-                    };
-                    yield Ok(Event::default().event("complete").data(status));
-                    break;
+                LogUpdate::Complete(build) => {
+                    // Reconcile with the final artifact list
+                    if let BuildStatus::Success { artifacts, .. } = &build {
+                        for path in artifacts.iter().filter(|p| !sent_artifacts.contains(*p)) {
+                            let artifact = ArtifactEvent { path: path.clone(), target: None };
+                            if let Ok(event) = Event::default().event("artifact").json_data(&artifact) {
+                                yield Ok(event);
+                            }
+                        }
+                    }
+                    yield Ok(Event::default().event("complete").data(build.name()));
+                }
+                LogUpdate::Queued(queue) => {
+                    if let Ok(event) = Event::default().event("queued").json_data(&queue) {
+                        yield Ok(event);
+                    }
                 }
-            } else {
-                break;
             }
-
-            tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
         }
     };
 
@@ -203,20 +1343,199 @@ pub async fn build_logs(
 /// DELETE /build/:id - Cancel a build
 pub async fn cancel_build(
     State(state): State<SharedState>,
+    namespace: Namespace,
     Path(build_id): Path<String>,
 ) -> Result<Json<BuildStatusResponse>> {
-    let cancelled = state.cancel_build(&build_id).await;
+    let cancelled = state.cancel_build(&build_id, &namespace).await;
     
     if !cancelled {
         return Err(XcbridgeError::BuildNotFound(build_id));
     }
+    let record = state
+        .get_build_record(&build_id)
+        .await
+        .ok_or_else(|| XcbridgeError::BuildNotFound(build_id.clone()))?;
 
-    Ok(Json(BuildStatusResponse {
-        build_id,
-        status: "cancelled".to_string(),
-        exit_code: None,
-        artifacts: None,
-        error: None,
-        logs: vec![],
-    }))
+    Ok(Json(status_response(build_id, record, None, true, None)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::xcode::runner::{self, MockRunner};
+    use clap::Parser;
+
+    #[test]
+    fn test_derived_data_cleanup_decision() {
+        let state = Arc::new(crate::state::AppState::new(
+            crate::config::Config::parse_from(["xcbridge", "--derived-data-base", "/tmp/dd"]),
+            String::new(),
+        ));
+
+        let assigned = derived_data_for(&state, "b1", None, None);
+        assert_eq!(assigned, (PathBuf::from("/tmp/dd/b1"), true));
+        let kept = derived_data_for(&state, "b1", None, Some(false));
+        assert_eq!(kept, (PathBuf::from("/tmp/dd/b1"), false));
+
+        let shared = derived_data_for(&state, "b1", Some("/cache/dd"), None);
+        assert_eq!(shared, (PathBuf::from("/cache/dd"), false));
+        let removed = derived_data_for(&state, "b1", Some("/cache/dd"), Some(true));
+        assert_eq!(removed, (PathBuf::from("/cache/dd"), true));
+    }
+
+    #[test]
+    fn test_check_derived_data_path() {
+        let dir = std::env::temp_dir().join(format!("xcbridge-{}", Uuid::new_v4()));
+        let project = dir.join("src/MyApp/MyApp.xcodeproj");
+        std::fs::create_dir_all(&project).unwrap();
+        let base = dir.join("dd");
+        let state = Arc::new(crate::state::AppState::new(
+            crate::config::Config::parse_from([
+                "xcbridge",
+                "--allowed-paths",
+                dir.to_str().unwrap(),
+                "--derived-data-base",
+                base.to_str().unwrap(),
+            ]),
+            String::new(),
+        ));
+        let check = |path: &std::path::Path, cleanup| {
+            check_derived_data_path(&state, path.to_str(), cleanup, &project)
+        };
+
+        assert!(check(&dir.join("cache"), None).is_ok());
+        assert!(check(&base.join("shared"), Some(true)).is_ok());
+        assert!(matches!(
+            check(std::path::Path::new("/var/elsewhere"), None),
+            Err(XcbridgeError::PathNotAllowed(_))
+        ));
+        // Removing these would delete the project
+        assert!(matches!(
+            check(&dir.join("src"), None),
+            Err(XcbridgeError::InvalidRequest(_))
+        ));
+        assert!(matches!(
+            check(&dir.join("src/MyApp"), None),
+            Err(XcbridgeError::InvalidRequest(_))
+        ));
+        assert!(matches!(
+            check(&dir.join("cache"), Some(true)),
+            Err(XcbridgeError::InvalidRequest(_))
+        ));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_batch_skips_remaining_builds_after_failure() {
+        let state = Arc::new(crate::state::AppState::new(
+            crate::config::Config::parse_from(["xcbridge"]),
+            String::new(),
+        ));
+        let build_id = Uuid::new_v4().to_string();
+        let dir = std::env::temp_dir().join(format!("xcbridge-{}", build_id));
+        let req: BuildRequest = serde_json::from_value(serde_json::json!({
+            "project": dir.join("Missing.xcodeproj"),
+            "scheme": "Core",
+            "destination": "generic/platform=iOS",
+        }))
+        .unwrap();
+        let build = |scheme: &str| {
+            let mut params = build_params(&state, &req, req.destination.clone(), &dir, None);
+            params.scheme = Some(scheme.to_string());
+            BatchBuild {
+                params,
+                resolve_packages: false,
+                retry_clean: false,
+            }
+        };
+        state
+            .insert_build(
+                &build_id,
+                BuildRecord {
+                    batch: Some(vec![BatchStep::pending("Core"), BatchStep::pending("App")]),
+                    ..BuildRecord::default()
+                },
+            )
+            .await
+            .unwrap();
+
+        let mock = Arc::new(MockRunner::new().fail(&["xcodebuild"], "error: Core failed"));
+        let builds = vec![build("Core"), build("App")];
+        runner::with_runner(
+            mock.clone(),
+            run_batch(
+                Arc::clone(&state),
+                build_id.clone(),
+                builds,
+                false,
+                CancelSignal::default(),
+            ),
+        )
+        .await;
+
+        // Only Core ran
+        let calls = mock.calls();
+        assert_eq!(calls.len(), 1);
+        assert!(calls[0].windows(2).any(|pair| pair == ["-scheme", "Core"]));
+
+        let record = state.get_build_record(&build_id).await.unwrap();
+        let batch = record.batch.unwrap();
+        assert_eq!(batch[0].status, BatchStepStatus::Failed);
+        assert_eq!(batch[1].status, BatchStepStatus::Skipped);
+        match record.status {
+            BuildStatus::Failed { error, logs, .. } => {
+                assert!(error.starts_with("Core: "));
+                assert_eq!(logs[0], "=== [1/2] Core building ===");
+                assert!(logs.last().unwrap().starts_with("=== [1/2] Core failed: "));
+            }
+            other => panic!("unexpected status: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_log_file_stem() {
+        assert_eq!(
+            log_file_stem("build-MyApp-debug-550e"),
+            "build-MyApp-debug-550e"
+        );
+        assert_eq!(
+            log_file_stem("nightly \"release\"/v2"),
+            "nightly--release--v2"
+        );
+    }
+
+    #[test]
+    fn test_parse_product_line() {
+        let line = "CodeSign /Users/me/DerivedData/Build/Products/Debug-iphonesimulator/My\\ App.app (in target 'My App' from project 'MyApp')";
+        assert_eq!(
+            parse_product_line(line),
+            Some(ArtifactEvent {
+                path: "/Users/me/DerivedData/Build/Products/Debug-iphonesimulator/My App.app"
+                    .to_string(),
+                target: Some("My App".to_string()),
+            })
+        );
+
+        let line = "Touch /tmp/dd/Build/Products/Release/MyKit.framework";
+        assert_eq!(
+            parse_product_line(line).map(|a| a.path),
+            Some("/tmp/dd/Build/Products/Release/MyKit.framework".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse_product_line_ignores_nested_and_intermediate_paths() {
+        // Embedded bundles are reported with their container
+        let nested = "CodeSign /tmp/dd/Build/Products/Debug-iphoneos/MyApp.app/PlugIns/Widget.appex (in target 'Widget' from project 'MyApp')";
+        assert_eq!(parse_product_line(nested), None);
+
+        let intermediate = "Touch /tmp/dd/Build/Intermediates.noindex/MyApp.build/Debug/MyApp.app (in target 'MyApp' from project 'MyApp')";
+        assert_eq!(parse_product_line(intermediate), None);
+
+        assert_eq!(
+            parse_product_line("CompileSwift normal arm64 /tmp/App.swift"),
+            None
+        );
+    }
 }
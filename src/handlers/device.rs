@@ -3,14 +3,24 @@
 
 //! Device handlers for physical iOS devices
 
-use crate::error::Result;
+use super::JsonBody;
+use crate::error::{Result, XcbridgeError};
 use crate::models::{
-    DeviceInfo, DeviceInstallRequest, DeviceLaunchRequest, DeviceListResponse,
-    DeviceUninstallRequest, SuccessResponse,
+    DeviceExecRequest, DeviceIds, DeviceInfo, DeviceInstallRequest, DeviceInstallResponse,
+    DeviceInstallResult, DeviceLaunchRequest, DeviceListResponse, DeviceUninstallRequest,
+    SuccessResponse, UninstallResponse,
 };
 use crate::state::SharedState;
-use crate::xcode::devicectl;
-use axum::{extract::State, Json};
+use crate::xcode::devicectl::{self, Device, ExecOutput};
+use crate::xcode::validation::{self, AppCompatibility};
+use axum::{extract::State, http::StatusCode, Json};
+use futures::future::join_all;
+use std::path::Path;
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+
+/// Maximum number of devices installed to at once
+const MAX_CONCURRENT_INSTALLS: usize = 4;
 
 /// GET /device/list - List all connected physical devices
 pub async fn list(State(_state): State<SharedState>) -> Result<Json<DeviceListResponse>> {
@@ -23,23 +33,187 @@ pub async fn list(State(_state): State<SharedState>) -> Result<Json<DeviceListRe
     Ok(Json(DeviceListResponse { devices }))
 }
 
-/// POST /device/install - Install an app on a physical device
+/// POST /device/install - Install an app on one or more physical devices
+///
+/// A single `device_id` fails the request if the install fails. A list of
+/// ids installs concurrently and returns per-device results, with
+/// `207 Multi-Status` when any device failed.
+///
+/// Devices whose OS is older than the app's minimum, or whose family the
+/// app doesn't support, are refused before installing unless
+/// `skip_compatibility_check` is set.
 pub async fn install(
     State(_state): State<SharedState>,
-    Json(req): Json<DeviceInstallRequest>,
-) -> Result<Json<SuccessResponse>> {
-    devicectl::install(&req.device_id, &req.app_path).await?;
+    JsonBody(req): JsonBody<DeviceInstallRequest>,
+) -> Result<(StatusCode, Json<DeviceInstallResponse>)> {
+    if matches!(&req.device_id, DeviceIds::Many(ids) if ids.is_empty()) {
+        return Err(XcbridgeError::InvalidRequest(
+            "device_id must list at least one device".into(),
+        ));
+    }
+    let check = CompatibilityCheck::new(&req.app_path, req.skip_compatibility_check).await;
 
-    Ok(Json(SuccessResponse::new(format!(
-        "App installed to device {}",
-        req.device_id
-    ))))
+    if let DeviceIds::One(device_id) = req.device_id {
+        check.check(&device_id)?;
+        devicectl::install(&device_id, &req.app_path).await?;
+
+        return Ok((
+            StatusCode::OK,
+            Json(DeviceInstallResponse {
+                success: true,
+                message: format!("App installed to device {}", device_id),
+                results: vec![DeviceInstallResult {
+                    device_id,
+                    success: true,
+                    error: None,
+                }],
+            }),
+        ));
+    }
+
+    let semaphore = Arc::new(Semaphore::new(MAX_CONCURRENT_INSTALLS));
+    let installs = req.device_id.into_vec().into_iter().map(|device_id| {
+        let semaphore = Arc::clone(&semaphore);
+        let app_path = req.app_path.clone();
+        let compatible = check.check(&device_id);
+        async move {
+            let result = match compatible {
+                Ok(()) => {
+                    let _permit = semaphore.acquire().await;
+                    devicectl::install(&device_id, &app_path).await
+                }
+                Err(e) => Err(e),
+            };
+            DeviceInstallResult {
+                device_id,
+                success: result.is_ok(),
+                error: result.err().map(|e| e.to_string()),
+            }
+        }
+    });
+    let results = join_all(installs).await;
+
+    let succeeded = results.iter().filter(|r| r.success).count();
+    let all_succeeded = succeeded == results.len();
+    let status = if all_succeeded {
+        StatusCode::OK
+    } else {
+        StatusCode::MULTI_STATUS
+    };
+
+    Ok((
+        status,
+        Json(DeviceInstallResponse {
+            success: all_succeeded,
+            message: format!(
+                "App installed to {} of {} devices",
+                succeeded,
+                results.len()
+            ),
+            results,
+        }),
+    ))
+}
+
+/// An app's requirements and the connected devices, read once per install
+struct CompatibilityCheck {
+    app: Option<AppCompatibility>,
+    devices: Vec<Device>,
+    enforce: bool,
+}
+
+impl CompatibilityCheck {
+    /// Read the app's requirements and list devices. Either lookup failing
+    /// skips the check, leaving devicectl to report the problem.
+    async fn new(app_path: &str, skip: bool) -> Self {
+        let app = match validation::app_compatibility(Path::new(app_path)).await {
+            Ok(app) => Some(app),
+            Err(e) => {
+                tracing::debug!("Skipping compatibility check for {}: {}", app_path, e);
+                None
+            }
+        };
+        let devices = match &app {
+            Some(_) => devicectl::list_devices().await.unwrap_or_else(|e| {
+                tracing::warn!(
+                    "Skipping compatibility check, listing devices failed: {}",
+                    e
+                );
+                Vec::new()
+            }),
+            None => Vec::new(),
+        };
+
+        Self {
+            app,
+            devices,
+            enforce: !skip,
+        }
+    }
+
+    fn check(&self, device_id: &str) -> Result<()> {
+        let Some(app) = &self.app else {
+            return Ok(());
+        };
+        let Some(device) = self
+            .devices
+            .iter()
+            .find(|d| d.udid.eq_ignore_ascii_case(device_id))
+        else {
+            return Ok(());
+        };
+        let Some(reason) = validation::device_incompatibility(app, device) else {
+            return Ok(());
+        };
+
+        if self.enforce {
+            Err(XcbridgeError::DeviceError(format!(
+                "App is not compatible with device {}: {}",
+                device_id, reason
+            )))
+        } else {
+            tracing::warn!(
+                "Installing to incompatible device {}: {}",
+                device_id,
+                reason
+            );
+            Ok(())
+        }
+    }
+}
+
+/// POST /device/exec - Run an allowlisted `devicectl device` subcommand
+///
+/// Returns the command's exit code and output whether or not it succeeded.
+/// Only subcommands in `--devicectl-exec-allow` (by default the read-only
+/// `info` queries) may run.
+pub async fn exec(
+    State(state): State<SharedState>,
+    JsonBody(req): JsonBody<DeviceExecRequest>,
+) -> Result<Json<ExecOutput>> {
+    if req.command.is_empty() {
+        return Err(XcbridgeError::InvalidRequest(
+            "command must name a devicectl device subcommand".into(),
+        ));
+    }
+    if !devicectl::is_exec_command_allowed(
+        state.config.devicectl_exec_allow.as_deref(),
+        &req.command,
+    ) {
+        return Err(XcbridgeError::CommandNotAllowed(format!(
+            "devicectl device {}",
+            req.command.join(" ")
+        )));
+    }
+
+    let output = devicectl::exec(&req.device_id, &req.command, &req.args, req.json).await?;
+    Ok(Json(output))
 }
 
 /// POST /device/launch - Launch an app on a physical device
 pub async fn launch(
     State(_state): State<SharedState>,
-    Json(req): Json<DeviceLaunchRequest>,
+    JsonBody(req): JsonBody<DeviceLaunchRequest>,
 ) -> Result<Json<SuccessResponse>> {
     devicectl::launch(&req.device_id, &req.bundle_id).await?;
 
@@ -50,14 +224,29 @@ pub async fn launch(
 }
 
 /// POST /device/uninstall - Uninstall an app from a physical device
+///
+/// Succeeds with `was_installed: false` if the app was not installed.
 pub async fn uninstall(
     State(_state): State<SharedState>,
-    Json(req): Json<DeviceUninstallRequest>,
-) -> Result<Json<SuccessResponse>> {
-    devicectl::uninstall(&req.device_id, &req.bundle_id).await?;
+    JsonBody(req): JsonBody<DeviceUninstallRequest>,
+) -> Result<Json<UninstallResponse>> {
+    let was_installed = devicectl::uninstall(&req.device_id, &req.bundle_id).await?;
 
-    Ok(Json(SuccessResponse::new(format!(
-        "App {} uninstalled from device {}",
-        req.bundle_id, req.device_id
-    ))))
+    let message = if was_installed {
+        format!(
+            "App {} uninstalled from device {}",
+            req.bundle_id, req.device_id
+        )
+    } else {
+        format!(
+            "App {} was not installed on device {}",
+            req.bundle_id, req.device_id
+        )
+    };
+
+    Ok(Json(UninstallResponse {
+        success: true,
+        message,
+        was_installed,
+    }))
 }
@@ -0,0 +1,225 @@
+// Copyright 2026 Aptove
+// SPDX-License-Identifier: Apache-2.0
+
+//! Localization export and import handlers
+//!
+//! Both run xcodebuild as a job, so progress streams from `/build/:id/logs`
+//! and the .xcloc paths are reported as the job's artifacts by `/build/:id`.
+
+use super::JsonBody;
+use crate::error::{Result, XcbridgeError};
+use crate::models::{BuildStartedResponse, LocalizationExportRequest, LocalizationImportRequest};
+use crate::state::{BuildRecord, JobKind, Namespace, SharedState};
+use crate::xcode::xcodebuild::{self, CancelSignal};
+use axum::{extract::State, Json};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+/// POST /localizations/export - Export a project's strings as .xcloc bundles
+pub async fn export(
+    State(state): State<SharedState>,
+    namespace: Namespace,
+    JsonBody(req): JsonBody<LocalizationExportRequest>,
+) -> Result<Json<BuildStartedResponse>> {
+    check_project(&state, &req.project)?;
+    if !state
+        .config
+        .is_output_path_allowed(Path::new(&req.localization_path))
+    {
+        return Err(XcbridgeError::PathNotAllowed(req.localization_path));
+    }
+    if let Some(language) = req.languages.iter().find(|l| !is_language_code(l)) {
+        return Err(XcbridgeError::InvalidRequest(format!(
+            "Invalid language: {}",
+            language
+        )));
+    }
+
+    let args = xcodebuild::export_localizations_args(
+        &req.project,
+        &req.localization_path,
+        &req.languages,
+        req.include_screenshots,
+    );
+    let languages = req.languages.join("-");
+    let job = LocalizationJob::Export(PathBuf::from(&req.localization_path));
+    start(
+        state,
+        namespace,
+        req.name,
+        &["export", &languages],
+        args,
+        job,
+    )
+    .await
+}
+
+/// POST /localizations/import - Import a translated .xcloc bundle
+pub async fn import(
+    State(state): State<SharedState>,
+    namespace: Namespace,
+    JsonBody(req): JsonBody<LocalizationImportRequest>,
+) -> Result<Json<BuildStartedResponse>> {
+    check_project(&state, &req.project)?;
+    let bundle = Path::new(&req.localization_path);
+    if !state.config.is_path_allowed(bundle) {
+        return Err(XcbridgeError::PathNotAllowed(req.localization_path));
+    }
+    if !bundle.exists() {
+        return Err(XcbridgeError::InvalidRequest(format!(
+            "Localization not found: {}",
+            req.localization_path
+        )));
+    }
+
+    let args =
+        xcodebuild::import_localizations_args(&req.project, &req.localization_path, req.merge);
+    let language = bundle
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let job = LocalizationJob::Import(req.localization_path.clone());
+    start(
+        state,
+        namespace,
+        req.name,
+        &["import", &language],
+        args,
+        job,
+    )
+    .await
+}
+
+fn check_project(state: &SharedState, project: &str) -> Result<()> {
+    if !state.config.is_path_allowed(Path::new(project)) {
+        return Err(XcbridgeError::PathNotAllowed(project.to_string()));
+    }
+    Ok(())
+}
+
+/// Language codes like "fr", "pt-BR" or "zh-Hans"
+fn is_language_code(language: &str) -> bool {
+    !language.is_empty()
+        && !language.starts_with('-')
+        && language
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+enum LocalizationJob {
+    /// Directory the .xcloc bundles are exported to
+    Export(PathBuf),
+    /// The imported .xcloc bundle
+    Import(String),
+}
+
+async fn start(
+    state: SharedState,
+    namespace: Namespace,
+    name: Option<String>,
+    name_parts: &[&str],
+    args: Vec<String>,
+    job: LocalizationJob,
+) -> Result<Json<BuildStartedResponse>> {
+    let job_id = Uuid::new_v4().to_string();
+    let mut parts = vec!["localization"];
+    parts.extend(name_parts.iter().filter(|part| !part.is_empty()));
+    let name = super::job_name(name, &parts, &job_id)?;
+    let cancel = CancelSignal::default();
+    state
+        .insert_build(
+            &job_id,
+            BuildRecord {
+                namespace,
+                kind: JobKind::Localization,
+                name: Some(name.clone()),
+                cancel: cancel.clone(),
+                ..BuildRecord::default()
+            },
+        )
+        .await?;
+
+    let job_state = Arc::clone(&state);
+    let id = job_id.clone();
+    tokio::spawn(async move {
+        run_localization(job_state, id, args, job, cancel).await;
+    });
+
+    Ok(Json(BuildStartedResponse {
+        build_id: job_id.clone(),
+        name: Some(name),
+        status: "running".to_string(),
+        logs_url: format!("/build/{}/logs", job_id),
+        cache_hit: None,
+        artifacts: None,
+        exported_artifacts: None,
+        derived_data_path: None,
+    }))
+}
+
+async fn run_localization(
+    state: SharedState,
+    job_id: String,
+    args: Vec<String>,
+    job: LocalizationJob,
+    cancel: CancelSignal,
+) {
+    let (tx, mut rx) = mpsc::unbounded_channel::<String>();
+    let log_state = Arc::clone(&state);
+    let log_id = job_id.clone();
+    let collector = tokio::spawn(async move {
+        while let Some(line) = rx.recv().await {
+            log_state.append_build_log(&log_id, line).await;
+        }
+    });
+
+    let result =
+        xcodebuild::run_xcodebuild_with_env(args, &HashMap::new(), None, &cancel, |line| {
+            let _ = tx.send(line);
+        })
+        .await;
+    let _ = collector.await;
+    if let Ok(output) = &result {
+        state.add_cpu_time(&job_id, output.cpu_time).await;
+    }
+
+    match result {
+        Ok(output) if output.success => {
+            let artifacts = match job {
+                LocalizationJob::Export(dir) => xcloc_bundles(&dir),
+                LocalizationJob::Import(bundle) => vec![bundle],
+            };
+            state.complete_build(&job_id, artifacts).await;
+        }
+        Ok(output) => {
+            let error = output
+                .logs
+                .iter()
+                .rev()
+                .find(|l| l.contains("error"))
+                .cloned()
+                .unwrap_or_else(|| "Localization failed".to_string());
+            state
+                .fail_build(&job_id, error, Some(output.exit_code))
+                .await;
+        }
+        Err(e) => state.fail_build(&job_id, e.to_string(), None).await,
+    }
+}
+
+/// The .xcloc bundles in an export directory, sorted by path
+fn xcloc_bundles(dir: &Path) -> Vec<String> {
+    let mut bundles: Vec<String> = std::fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "xcloc"))
+        .map(|path| path.to_string_lossy().to_string())
+        .collect();
+    bundles.sort();
+    bundles
+}
@@ -3,8 +3,330 @@
 
 //! HTTP request handlers
 
+pub mod app;
 pub mod build;
 pub mod device;
+pub mod localization;
+pub mod project;
+pub mod provisioning;
+pub mod run;
+pub mod signing;
 pub mod simulator;
 pub mod status;
 pub mod test;
+
+use crate::error::{BodyError, Result, XcbridgeError};
+use crate::state::{AppState, Namespace};
+use crate::upload::{self, Upload};
+use axum::body::Bytes;
+use axum::extract::{FromRequest, Request};
+use axum::http::header::CONTENT_TYPE;
+use regex::{Regex, RegexBuilder};
+use serde::de::DeserializeOwned;
+
+/// Longest `grep` pattern accepted for filtering job logs
+const MAX_GREP_PATTERN_LEN: usize = 1024;
+
+/// Compiled size limit for `grep` patterns, so a pathological pattern can't
+/// use unbounded memory
+const GREP_SIZE_LIMIT: usize = 1 << 20;
+
+/// A job's destination: the leased simulator if `lease` (a lease id or
+/// tag) is set, renewing the lease, else `destination`
+pub(crate) async fn lease_destination(
+    state: &AppState,
+    namespace: &Namespace,
+    lease: Option<&str>,
+    destination: Option<String>,
+) -> Result<Option<String>> {
+    let Some(lease) = lease else {
+        return Ok(destination);
+    };
+    if destination.is_some() {
+        return Err(XcbridgeError::InvalidRequest(
+            "Specify either lease or destination, not both".into(),
+        ));
+    }
+
+    let lease = state
+        .renew_lease(lease, namespace)
+        .await
+        .ok_or_else(|| XcbridgeError::LeaseNotFound(lease.to_string()))?;
+    Ok(Some(format!("id={}", lease.udid)))
+}
+
+/// Longest job name a client may choose
+const MAX_JOB_NAME_LEN: usize = 100;
+
+/// The job name the client asked for, or a readable one made from `parts`
+/// and the start of the job id, e.g. `build-MyApp-debug-3f2a`
+pub(crate) fn job_name(requested: Option<String>, parts: &[&str], job_id: &str) -> Result<String> {
+    if let Some(name) = requested {
+        if name.trim().is_empty()
+            || name.chars().count() > MAX_JOB_NAME_LEN
+            || name.chars().any(char::is_control)
+        {
+            return Err(XcbridgeError::InvalidRequest(format!(
+                "name must be 1 to {} characters without control characters",
+                MAX_JOB_NAME_LEN
+            )));
+        }
+        return Ok(name);
+    }
+
+    let slug = |part: &str| -> String {
+        part.chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '.' || c == '_' {
+                    c
+                } else {
+                    '-'
+                }
+            })
+            .collect()
+    };
+    Ok(parts
+        .iter()
+        .map(|part| slug(part))
+        .filter(|part| !part.is_empty())
+        .chain(std::iter::once(job_id.chars().take(4).collect()))
+        .collect::<Vec<_>>()
+        .join("-"))
+}
+
+/// Path of the project or workspace a request targets
+///
+/// Exactly one must be given, since xcodebuild rejects `-project` combined
+/// with `-workspace`.
+pub(crate) fn project_or_workspace<'a>(
+    project: Option<&'a String>,
+    workspace: Option<&'a String>,
+) -> Result<&'a String> {
+    match (project, workspace) {
+        (Some(_), Some(_)) => Err(XcbridgeError::InvalidRequest(
+            "Specify either project or workspace, not both".into(),
+        )),
+        (Some(path), None) | (None, Some(path)) => Ok(path),
+        (None, None) => Err(XcbridgeError::InvalidRequest(
+            "Either project or workspace must be specified".into(),
+        )),
+    }
+}
+
+/// Compile a job log `grep` pattern, if one was given
+pub(crate) fn log_filter(grep: Option<&str>) -> Result<Option<Regex>> {
+    let Some(pattern) = grep else {
+        return Ok(None);
+    };
+    if pattern.len() > MAX_GREP_PATTERN_LEN {
+        return Err(XcbridgeError::InvalidRequest(format!(
+            "grep pattern is longer than {} characters",
+            MAX_GREP_PATTERN_LEN
+        )));
+    }
+
+    RegexBuilder::new(pattern)
+        .size_limit(GREP_SIZE_LIMIT)
+        .build()
+        .map(Some)
+        .map_err(|e| XcbridgeError::InvalidRequest(format!("Invalid grep pattern: {}", e)))
+}
+
+/// JSON request body extractor
+///
+/// Like `axum::Json`, but malformed bodies are rejected with a `422`
+/// [`XcbridgeError::InvalidBody`] naming the offending field and what it
+/// should hold, rather than a plain-text rejection.
+pub struct JsonBody<T>(pub T);
+
+#[axum::async_trait]
+impl<T, S> FromRequest<S> for JsonBody<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = XcbridgeError;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self> {
+        let is_json = req
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|v| v.starts_with("application/json"));
+        if !is_json {
+            return Err(XcbridgeError::InvalidRequest(
+                "Expected a JSON body with `Content-Type: application/json`".into(),
+            ));
+        }
+
+        let bytes = Bytes::from_request(req, state)
+            .await
+            .map_err(|e| XcbridgeError::InvalidRequest(e.body_text()))?;
+        parse_json_body(&bytes).map(JsonBody)
+    }
+}
+
+/// Resolve a path from an uploaded request within its extracted project
+fn resolve_upload_path(upload: &Upload, path: Option<String>) -> Result<Option<String>> {
+    path.map(|path| upload::resolve(&upload.dir, &path))
+        .transpose()
+}
+
+/// Deserialize a JSON request body, reporting the field that failed
+pub(crate) fn parse_json_body<T: DeserializeOwned>(bytes: &[u8]) -> Result<T> {
+    let deserializer = &mut serde_json::Deserializer::from_slice(bytes);
+    serde_path_to_error::deserialize(deserializer).map_err(|e| {
+        let path = e.path().to_string();
+        let inner = e.into_inner();
+        XcbridgeError::InvalidBody(body_error(&path, &inner))
+    })
+}
+
+fn body_error(path: &str, error: &serde_json::Error) -> BodyError {
+    let message = error.to_string();
+    // Drop serde_json's " at line N column M" suffix
+    let reason = match message.rfind(" at line ") {
+        Some(i) if error.line() > 0 => message[..i].to_string(),
+        _ => message,
+    };
+    if error.is_syntax() || error.is_eof() {
+        return BodyError {
+            field: None,
+            expected: None,
+            reason: format!(
+                "{} (line {}, column {})",
+                reason,
+                error.line(),
+                error.column()
+            ),
+        };
+    }
+
+    let parent = (path != ".").then_some(path);
+    let missing = reason
+        .strip_prefix("missing field `")
+        .and_then(|rest| rest.strip_suffix('`'));
+    let field = match (parent, missing) {
+        (Some(parent), Some(name)) => Some(format!("{}.{}", parent, name)),
+        (None, Some(name)) => Some(name.to_string()),
+        (parent, None) => parent.map(str::to_string),
+    };
+    let expected = reason
+        .split_once(", expected ")
+        .map(|(_, expected)| expected.to_string());
+
+    BodyError {
+        field,
+        expected,
+        reason,
+    }
+}
+
+/// Keep only the log lines matching the filter (all lines without one)
+pub(crate) fn filter_logs(logs: Vec<String>, filter: Option<&Regex>) -> Vec<String> {
+    match filter {
+        Some(filter) => logs
+            .into_iter()
+            .filter(|line| filter.is_match(line))
+            .collect(),
+        None => logs,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_job_name() {
+        let id = "3f2a9c1e-0000-4000-8000-000000000000";
+        assert_eq!(
+            job_name(None, &["build", "MyApp", "debug"], id).unwrap(),
+            "build-MyApp-debug-3f2a"
+        );
+        assert_eq!(
+            job_name(None, &["test", "My App Tests"], id).unwrap(),
+            "test-My-App-Tests-3f2a"
+        );
+        assert_eq!(
+            job_name(Some("nightly ui tests".to_string()), &["test"], id).unwrap(),
+            "nightly ui tests"
+        );
+        assert!(job_name(Some(" ".to_string()), &["test"], id).is_err());
+        assert!(job_name(Some("a\nb".to_string()), &["test"], id).is_err());
+    }
+
+    #[test]
+    fn test_project_or_workspace() {
+        let project = "/src/MyApp.xcodeproj".to_string();
+        let workspace = "/src/MyApp.xcworkspace".to_string();
+
+        assert_eq!(
+            project_or_workspace(Some(&project), None).unwrap(),
+            &project
+        );
+        assert_eq!(
+            project_or_workspace(None, Some(&workspace)).unwrap(),
+            &workspace
+        );
+        assert!(matches!(
+            project_or_workspace(Some(&project), Some(&workspace)),
+            Err(XcbridgeError::InvalidRequest(_))
+        ));
+        assert!(matches!(
+            project_or_workspace(None, None),
+            Err(XcbridgeError::InvalidRequest(_))
+        ));
+    }
+
+    #[test]
+    fn test_parse_json_body_reports_field() {
+        #[derive(Debug, serde::Deserialize)]
+        #[allow(dead_code)]
+        struct Body {
+            scheme: String,
+            #[serde(default)]
+            simulators: Vec<String>,
+        }
+
+        let error = |json: &str| match parse_json_body::<Body>(json.as_bytes()) {
+            Err(XcbridgeError::InvalidBody(error)) => error,
+            other => panic!("unexpected result: {other:?}"),
+        };
+
+        let e = error(r#"{"scheme": 1}"#);
+        assert_eq!(e.field.as_deref(), Some("scheme"));
+        assert_eq!(e.expected.as_deref(), Some("a string"));
+
+        let e = error(r#"{"scheme": "App", "simulators": ["a", false]}"#);
+        assert_eq!(e.field.as_deref(), Some("simulators[1]"));
+        assert_eq!(e.expected.as_deref(), Some("a string"));
+
+        let e = error(r#"{"simulators": []}"#);
+        assert_eq!(e.field.as_deref(), Some("scheme"));
+        assert_eq!(e.reason, "missing field `scheme`");
+
+        let e = error(r#"{"scheme": "App""#);
+        assert_eq!(e.field, None);
+        assert!(e.reason.contains("line 1"));
+
+        assert!(parse_json_body::<Body>(br#"{"scheme": "App"}"#).is_ok());
+    }
+
+    #[test]
+    fn test_log_filter() {
+        let logs = vec![
+            "CompileSwift normal arm64 App.swift".to_string(),
+            "App.swift:3:5: error: cannot find 'x' in scope".to_string(),
+            "** BUILD FAILED **".to_string(),
+        ];
+
+        let filter = log_filter(Some("error:|FAILED")).unwrap();
+        assert_eq!(filter_logs(logs.clone(), filter.as_ref()), logs[1..]);
+        assert_eq!(filter_logs(logs.clone(), None), logs);
+
+        assert!(log_filter(Some("(unclosed")).is_err());
+        assert!(log_filter(Some(&"a".repeat(MAX_GREP_PATTERN_LEN + 1))).is_err());
+        assert!(log_filter(Some("\\w{1000}{1000}")).is_err());
+    }
+}
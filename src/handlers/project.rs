@@ -0,0 +1,100 @@
+// Copyright 2026 Aptove
+// SPDX-License-Identifier: Apache-2.0
+
+//! Project inspection handlers
+
+use crate::error::{Result, XcbridgeError};
+use crate::models::{DestinationListResponse, SchemeQuery, TestPlanListResponse};
+use crate::state::SharedState;
+use crate::xcode::test_plans::{self, TestPlan};
+use crate::xcode::xcodebuild;
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+use std::path::PathBuf;
+
+/// GET /project/destinations - List valid build destinations for a scheme
+pub async fn destinations(
+    State(state): State<SharedState>,
+    Query(query): Query<SchemeQuery>,
+) -> Result<Json<DestinationListResponse>> {
+    // Validate project/workspace path
+    let project_path =
+        super::project_or_workspace(query.project.as_ref(), query.workspace.as_ref())?;
+
+    let path = PathBuf::from(project_path);
+    if !state.config.is_path_allowed(&path) {
+        return Err(XcbridgeError::PathNotAllowed(project_path.clone()));
+    }
+
+    let destinations = xcodebuild::show_destinations(
+        query.project.as_deref(),
+        query.workspace.as_deref(),
+        &query.scheme,
+    )
+    .await?;
+
+    Ok(Json(DestinationListResponse {
+        scheme: query.scheme,
+        destinations,
+    }))
+}
+
+/// GET /project/test-plans - List the test plans referenced by a scheme
+pub async fn test_plans(
+    State(state): State<SharedState>,
+    Query(query): Query<SchemeQuery>,
+) -> Result<Json<TestPlanListResponse>> {
+    // Validate project/workspace path
+    let project_path =
+        super::project_or_workspace(query.project.as_ref(), query.workspace.as_ref())?;
+
+    let path = PathBuf::from(project_path);
+    if !state.config.is_path_allowed(&path) {
+        return Err(XcbridgeError::PathNotAllowed(project_path.clone()));
+    }
+
+    let test_plans = test_plans::scheme_test_plans(&path, &query.scheme)?;
+
+    Ok(Json(TestPlanListResponse {
+        scheme: query.scheme,
+        test_plans,
+    }))
+}
+
+/// GET /project/test-plans/:name - Read one of a scheme's test plans
+pub async fn test_plan(
+    State(state): State<SharedState>,
+    Path(name): Path<String>,
+    Query(query): Query<SchemeQuery>,
+) -> Result<Json<TestPlan>> {
+    let project_path =
+        super::project_or_workspace(query.project.as_ref(), query.workspace.as_ref())?;
+
+    let path = PathBuf::from(project_path);
+    if !state.config.is_path_allowed(&path) {
+        return Err(XcbridgeError::PathNotAllowed(project_path.clone()));
+    }
+
+    // Plans are found by name, never by a path into the project
+    if name.is_empty() || name.contains(['/', '\\', '\0']) || name.starts_with('.') {
+        return Err(XcbridgeError::InvalidRequest(format!(
+            "Invalid test plan name: {:?}",
+            name
+        )));
+    }
+    let plan = test_plans::scheme_test_plans(&path, &query.scheme)?
+        .into_iter()
+        .find(|plan| plan.name == name)
+        .ok_or_else(|| {
+            XcbridgeError::TestPlanNotFound(format!("{} in scheme {}", name, query.scheme))
+        })?;
+    // Schemes can reference plans outside the project's directory
+    let plan_path = PathBuf::from(&plan.path);
+    if !state.config.is_path_allowed(&plan_path) {
+        return Err(XcbridgeError::PathNotAllowed(plan.path));
+    }
+
+    Ok(Json(test_plans::read_test_plan_contents(&plan_path)?))
+}
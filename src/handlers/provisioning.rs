@@ -0,0 +1,50 @@
+// Copyright 2026 Aptove
+// SPDX-License-Identifier: Apache-2.0
+
+//! Provisioning profile handlers
+
+use super::JsonBody;
+use crate::error::{Result, XcbridgeError};
+use crate::models::{ProvisioningInstallRequest, ProvisioningListResponse};
+use crate::state::SharedState;
+use crate::xcode::provisioning::{self, ProvisioningProfile};
+use axum::{extract::State, Json};
+use std::path::PathBuf;
+
+/// GET /provisioning/list - List installed provisioning profiles
+pub async fn list(State(_state): State<SharedState>) -> Result<Json<ProvisioningListResponse>> {
+    let profiles = tokio::task::spawn_blocking(provisioning::list_profiles)
+        .await
+        .map_err(|e| XcbridgeError::Internal(e.to_string()))??;
+
+    Ok(Json(ProvisioningListResponse { profiles }))
+}
+
+/// POST /provisioning/install - Install a .mobileprovision for device signing
+pub async fn install(
+    State(state): State<SharedState>,
+    JsonBody(req): JsonBody<ProvisioningInstallRequest>,
+) -> Result<Json<ProvisioningProfile>> {
+    let path = PathBuf::from(&req.path);
+    if !state.config.is_path_allowed(&path) {
+        return Err(XcbridgeError::PathNotAllowed(req.path));
+    }
+    if path.extension().is_none_or(|ext| ext != "mobileprovision") {
+        return Err(XcbridgeError::InvalidRequest(format!(
+            "Not a .mobileprovision file: {}",
+            req.path
+        )));
+    }
+
+    let profile = tokio::task::spawn_blocking(move || provisioning::install_profile(&path))
+        .await
+        .map_err(|e| XcbridgeError::Internal(e.to_string()))??;
+
+    tracing::info!(
+        "Installed provisioning profile {} ({})",
+        profile.uuid,
+        profile.name.as_deref().unwrap_or("unnamed")
+    );
+
+    Ok(Json(profile))
+}
@@ -0,0 +1,289 @@
+// Copyright 2026 Aptove
+// SPDX-License-Identifier: Apache-2.0
+
+//! Combined build, install and launch handler
+
+use super::JsonBody;
+use crate::error::{Result, XcbridgeError};
+use crate::models::{RunPhaseEvent, SimulatorRunRequest};
+use crate::state::{self, BuildRecord, Namespace, SharedState};
+use crate::xcode::bundle;
+use crate::xcode::simctl::{self, Simulator};
+use crate::xcode::xcodebuild::{self, BuildParams, CancelSignal};
+use axum::{
+    extract::State,
+    response::sse::{Event, Sse},
+};
+use futures::stream::Stream;
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::path::{Path, PathBuf};
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+/// Progress of a build-and-run session
+enum RunEvent {
+    Log(String),
+    Phase(RunPhaseEvent),
+    Complete(&'static str),
+}
+
+/// Sends phase markers and log lines to the SSE stream
+#[derive(Clone)]
+struct RunReporter {
+    tx: mpsc::UnboundedSender<RunEvent>,
+}
+
+impl RunReporter {
+    fn phase(&self, phase: &str, status: &str, message: Option<String>) {
+        let _ = self.tx.send(RunEvent::Phase(RunPhaseEvent {
+            phase: phase.to_string(),
+            status: status.to_string(),
+            message,
+            build_id: None,
+        }));
+    }
+
+    fn log(&self, line: String) {
+        let _ = self.tx.send(RunEvent::Log(line));
+    }
+
+    /// Report a failed phase and return false so callers can bail out
+    fn failed(&self, phase: &str, message: String) -> bool {
+        self.phase(phase, "failed", Some(message));
+        false
+    }
+}
+
+/// POST /simulator/run - Build, boot, install and launch as one SSE session
+///
+/// Emits a `phase` event with a JSON [`RunPhaseEvent`] at the start and end of
+/// each phase (build, boot, install, launch), raw build log lines as data
+/// events, and a final `complete` event with "success" or "failed". The build
+/// is also tracked under `/build/{id}`.
+pub async fn run(
+    State(state): State<SharedState>,
+    namespace: Namespace,
+    JsonBody(req): JsonBody<SimulatorRunRequest>,
+) -> Result<Sse<impl Stream<Item = std::result::Result<Event, Infallible>>>> {
+    // Validate project/workspace path
+    let project_path = super::project_or_workspace(req.project.as_ref(), req.workspace.as_ref())?;
+
+    let path = PathBuf::from(project_path);
+    if !state.config.is_path_allowed(&path) {
+        return Err(XcbridgeError::PathNotAllowed(project_path.clone()));
+    }
+
+    // Resolve the target simulator up front so the build can target it
+    let simulator = if let Some(udid) = &req.udid {
+        simctl::get_simulator(udid).await?
+    } else if let Some(device_type) = &req.device_type {
+        simctl::find_simulator(device_type, None).await?
+    } else {
+        simctl::get_booted_simulator().await?.ok_or_else(|| {
+            XcbridgeError::InvalidRequest(
+                "Either udid or device_type must be specified when no simulator is booted".into(),
+            )
+        })?
+    };
+
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let reporter = RunReporter { tx };
+
+    tokio::spawn(async move {
+        let success = run_on_simulator(&state, &reporter, namespace, req, simulator).await;
+        let _ = reporter.tx.send(RunEvent::Complete(if success {
+            "success"
+        } else {
+            "failed"
+        }));
+    });
+
+    let stream = async_stream::stream! {
+        while let Some(event) = rx.recv().await {
+            match event {
+                RunEvent::Log(line) => yield Ok(Event::default().data(line)),
+                RunEvent::Phase(phase) => {
+                    if let Ok(event) = Event::default().event("phase").json_data(&phase) {
+                        yield Ok(event);
+                    }
+                }
+                RunEvent::Complete(status) => {
+                    yield Ok(Event::default().event("complete").data(status));
+                    break;
+                }
+            }
+        }
+    };
+
+    Ok(Sse::new(stream))
+}
+
+/// Run each phase in order, stopping at the first failure
+async fn run_on_simulator(
+    state: &SharedState,
+    reporter: &RunReporter,
+    namespace: Namespace,
+    req: SimulatorRunRequest,
+    simulator: Simulator,
+) -> bool {
+    let udid = simulator.udid;
+
+    // Build
+    let Some(app_path) = build(state, reporter, namespace, &req, &udid).await else {
+        return false;
+    };
+
+    // Hold the simulator from boot through launch
+    let _lock = state.lock_simulator(&udid).await;
+
+    // Boot
+    reporter.phase("boot", "started", None);
+    if let Err(e) = simctl::boot(&udid).await {
+        return reporter.failed("boot", e.to_string());
+    }
+    reporter.phase("boot", "success", Some(udid.clone()));
+
+    // Install
+    reporter.phase("install", "started", None);
+    let app_path = app_path.to_string_lossy().to_string();
+    if let Err(e) = simctl::install(&udid, &app_path).await {
+        return reporter.failed("install", e.to_string());
+    }
+    reporter.phase("install", "success", Some(app_path.clone()));
+
+    // Launch
+    reporter.phase("launch", "started", None);
+    let bundle_id = match req.bundle_id {
+        Some(bundle_id) => bundle_id,
+        None => match bundle::read_app_info(Path::new(&app_path)) {
+            Ok(info) => info.bundle_id,
+            Err(e) => return reporter.failed("launch", e.to_string()),
+        },
+    };
+    match simctl::launch(&udid, &bundle_id, &req.arguments, true).await {
+        Ok(pid) => {
+            let message = pid.map(|pid| format!("pid {}", pid));
+            reporter.phase("launch", "success", message);
+            true
+        }
+        Err(e) => reporter.failed("launch", e.to_string()),
+    }
+}
+
+/// Build for the simulator, returning the built app bundle
+async fn build(
+    state: &SharedState,
+    reporter: &RunReporter,
+    namespace: Namespace,
+    req: &SimulatorRunRequest,
+    udid: &str,
+) -> Option<PathBuf> {
+    let build_id = Uuid::new_v4().to_string();
+    let derived_data_path = state.config.derived_data_base().join(&build_id);
+    let cancel = CancelSignal::default();
+
+    let inserted = state
+        .insert_build(
+            &build_id,
+            BuildRecord {
+                derived_data_path: Some(derived_data_path.clone()),
+                owns_derived_data: true,
+                namespace,
+                scheme: Some(req.scheme.clone()),
+                cancel: cancel.clone(),
+                ..BuildRecord::default()
+            },
+        )
+        .await;
+    if let Err(e) = inserted {
+        reporter.failed("build", e.to_string());
+        return None;
+    }
+
+    let _ = reporter.tx.send(RunEvent::Phase(RunPhaseEvent {
+        phase: "build".to_string(),
+        status: "started".to_string(),
+        message: None,
+        build_id: Some(build_id.clone()),
+    }));
+
+    // Wait in the same `--max-concurrent-jobs` queue as other builds
+    let Some(_slot) = state.acquire_job_slot(&build_id, &cancel).await else {
+        reporter.failed("build", "Build was cancelled while queued".to_string());
+        return None;
+    };
+
+    let params = BuildParams {
+        project: req.project.clone(),
+        workspace: req.workspace.clone(),
+        scheme: Some(req.scheme.clone()),
+        target: None,
+        configuration: req.configuration.clone(),
+        destination: Some(format!("platform=iOS Simulator,id={}", udid)),
+        destination_timeout: state.config.default_destination_timeout(),
+        derived_data_path: Some(derived_data_path.to_string_lossy().to_string()),
+        result_bundle_path: None,
+        toolchain: None,
+        extra_args: vec![],
+        environment: HashMap::new(),
+        working_directory: None,
+        skip_plugin_validation: false,
+        jobs: state.config.build_jobs,
+        timing_summary: false,
+    };
+
+    // Mirror log lines into the build's state as well as the stream
+    let (log_tx, mut log_rx) = mpsc::unbounded_channel::<String>();
+    let log_state = state.clone();
+    let log_build_id = build_id.clone();
+    let collector = tokio::spawn(async move {
+        while let Some(line) = log_rx.recv().await {
+            log_state.append_build_log(&log_build_id, line).await;
+        }
+    });
+
+    let line_reporter = reporter.clone();
+    let result = xcodebuild::run_xcodebuild_with_env(
+        params.to_args(),
+        &params.environment,
+        None,
+        &cancel,
+        move |line| {
+            let _ = log_tx.send(line.clone());
+            line_reporter.log(line);
+        },
+    )
+    .await;
+    let _ = collector.await;
+    if let Ok(output) = &result {
+        state.add_cpu_time(&build_id, output.cpu_time).await;
+    }
+
+    let error = match result {
+        Ok(output) if output.success => {
+            match xcodebuild::find_simulator_app(&derived_data_path, &req.configuration) {
+                Some(app_path) => {
+                    let artifacts = vec![app_path.to_string_lossy().to_string()];
+                    state.complete_build(&build_id, artifacts).await;
+                    reporter.phase("build", "success", None);
+                    return Some(app_path);
+                }
+                None => "Build succeeded but no .app bundle was found".to_string(),
+            }
+        }
+        Ok(output) => output
+            .logs
+            .iter()
+            .rev()
+            .find(|l| l.contains("error:"))
+            .cloned()
+            .unwrap_or_else(|| "Build failed".to_string()),
+        Err(e) => e.to_string(),
+    };
+
+    state.fail_build(&build_id, error.clone(), None).await;
+    state::remove_derived_data(&derived_data_path).await;
+    reporter.failed("build", error);
+    None
+}
@@ -0,0 +1,56 @@
+// Copyright 2026 Aptove
+// SPDX-License-Identifier: Apache-2.0
+
+//! Code-signing setup handler
+
+use crate::error::{Result, XcbridgeError};
+use crate::models::SigningStatusResponse;
+use crate::state::SharedState;
+use crate::xcode::{keychain, provisioning};
+use axum::{extract::State, Json};
+
+/// GET /signing/status - Check the keychain, signing identities and
+/// provisioning profiles before a device or archive build
+pub async fn status(State(_state): State<SharedState>) -> Result<Json<SigningStatusResponse>> {
+    let (keychain, identities, profiles) = tokio::join!(
+        keychain::login_keychain_status(),
+        keychain::signing_identities(),
+        tokio::task::spawn_blocking(provisioning::list_profiles),
+    );
+    let keychain = keychain?;
+    let identities = identities?;
+    let (expired, profiles): (Vec<_>, Vec<_>) = profiles
+        .map_err(|e| XcbridgeError::Internal(e.to_string()))??
+        .into_iter()
+        .partition(|profile| profile.expired);
+
+    let mut problems = Vec::new();
+    if !keychain.unlocked {
+        problems.push(match &keychain.path {
+            Some(path) => format!(
+                "The login keychain is locked; unlock it with `security unlock-keychain {}`",
+                path
+            ),
+            None => "There is no login keychain".to_string(),
+        });
+    }
+    if !identities.iter().any(|identity| identity.valid) {
+        problems.push("No valid code-signing identity is installed".to_string());
+    }
+    if profiles.is_empty() {
+        problems.push(
+            "No unexpired provisioning profile is installed; install one with \
+             POST /provisioning/install"
+                .to_string(),
+        );
+    }
+
+    Ok(Json(SigningStatusResponse {
+        ready: problems.is_empty(),
+        keychain,
+        identities,
+        profiles,
+        expired_profiles: expired.len(),
+        problems,
+    }))
+}
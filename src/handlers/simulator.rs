@@ -3,15 +3,39 @@
 
 //! Simulator handlers
 
+use super::JsonBody;
 use crate::error::{Result, XcbridgeError};
 use crate::models::{
-    SimulatorBootRequest, SimulatorBootResponse, SimulatorInstallRequest, SimulatorLaunchRequest,
-    SimulatorListResponse, SimulatorShutdownRequest, SimulatorUninstallRequest, SimulatorInfo,
-    SuccessResponse,
+    BootSettingFailure, BuildStartedResponse, InstallResponse, LaunchResponse,
+    ResetServiceResponse, RuntimeInstallRequest, SimulatorAppQuery, SimulatorBootRequest,
+    SimulatorBootResponse, SimulatorButtonRequest, SimulatorDetails, SimulatorInfo,
+    SimulatorInstallRequest, SimulatorLaunchRequest, SimulatorLeaseRequest, SimulatorLeaseResponse,
+    SimulatorListResponse, SimulatorLocaleRequest, SimulatorLogTailQuery, SimulatorLogTailResponse,
+    SimulatorPrewarmRequest, SimulatorPrewarmResponse, SimulatorPrewarmResult,
+    SimulatorReleaseRequest, SimulatorShutdownRequest, SimulatorSignalRequest, SimulatorTapRequest,
+    SimulatorTypeRequest, SimulatorTypeResponse, SimulatorUninstallRequest,
+    SimulatorWaitReadyRequest, SuccessResponse, UninstallResponse,
 };
-use crate::state::SharedState;
+use crate::state::{BuildRecord, JobKind, Namespace, SharedState};
+use crate::xcode::bundle;
 use crate::xcode::simctl;
-use axum::{extract::State, Json};
+use crate::xcode::ui_input::{self, TextInputMethod};
+use crate::xcode::xcodebuild::{self, CancelSignal};
+use axum::{
+    body::Bytes,
+    extract::{Path, Query, State},
+    http::{header, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use futures::future::join_all;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use uuid::Uuid;
+#[cfg(feature = "screen-text")]
+use {crate::models::ScreenTextResponse, crate::xcode::screen_text};
 
 /// GET /simulator/list - List all available simulators
 pub async fn list(State(_state): State<SharedState>) -> Result<Json<SimulatorListResponse>> {
@@ -24,24 +48,66 @@ pub async fn list(State(_state): State<SharedState>) -> Result<Json<SimulatorLis
     Ok(Json(SimulatorListResponse { simulators }))
 }
 
+/// GET /simulator/:udid - Full details of one simulator, including its
+/// data and log paths
+pub async fn details(
+    State(_state): State<SharedState>,
+    Path(udid): Path<String>,
+) -> Result<Json<SimulatorDetails>> {
+    let simulator = simctl::simulator_details(&udid).await?;
+    Ok(Json(SimulatorDetails::from(simulator)))
+}
+
+/// GET /simulator/booted - List booted simulators
+pub async fn booted(State(_state): State<SharedState>) -> Result<Json<SimulatorListResponse>> {
+    let simulators = simctl::booted_simulators()
+        .await?
+        .into_iter()
+        .map(SimulatorInfo::from)
+        .collect();
+
+    Ok(Json(SimulatorListResponse { simulators }))
+}
+
+/// How long to wait for a booted simulator to become ready before applying
+/// the settings requested with the boot
+const BOOT_SETTINGS_TIMEOUT: Duration = Duration::from_secs(180);
+
 /// POST /simulator/boot - Boot a simulator
+///
+/// Appearance, locale and status bar settings in the request are applied
+/// once `simctl bootstatus` completes, in that order. A setting that fails
+/// is reported in `failed_settings` rather than failing the boot.
 pub async fn boot(
-    State(_state): State<SharedState>,
-    Json(req): Json<SimulatorBootRequest>,
+    State(state): State<SharedState>,
+    JsonBody(req): JsonBody<SimulatorBootRequest>,
 ) -> Result<Json<SimulatorBootResponse>> {
-    // Find the simulator
-    let simulator = if let Some(udid) = req.udid {
-        simctl::get_simulator(&udid).await?
-    } else if let Some(device_type) = req.device_type {
-        simctl::find_simulator(&device_type, req.runtime.as_deref()).await?
+    let locale = match (&req.language, &req.locale) {
+        (Some(language), Some(locale)) => Some((language.as_str(), locale.as_str())),
+        (None, None) => None,
+        _ => {
+            return Err(XcbridgeError::InvalidRequest(
+                "language and locale must be specified together".into(),
+            ))
+        }
+    };
+
+    // Find the simulator, reusing a booted one that matches the device type
+    let simulator = if let Some(udid) = &req.udid {
+        simctl::get_simulator(udid).await?
+    } else if let Some(device_type) = &req.device_type {
+        simctl::find_simulator(device_type, req.runtime.as_deref()).await?
     } else {
         return Err(XcbridgeError::InvalidRequest(
             "Either udid or device_type must be specified".into(),
         ));
     };
 
-    // Boot the simulator
-    simctl::boot(&simulator.udid).await?;
+    // Boot the simulator. Concurrent boots of it, e.g. for the same device
+    // type, wait here for the first to finish and find it already booted
+    let _lock = state.lock_simulator(&simulator.udid).await;
+    let already_booted = simctl::boot(&simulator.udid).await?;
+    let failed_settings = apply_boot_settings(&simulator.udid, &req, locale).await;
 
     // Get updated status
     let booted = simctl::get_simulator(&simulator.udid).await?;
@@ -50,18 +116,599 @@ pub async fn boot(
         udid: booted.udid,
         name: booted.name,
         status: booted.state,
+        already_booted,
+        failed_settings,
+    }))
+}
+
+/// Apply the settings requested with a boot, returning those that failed
+async fn apply_boot_settings(
+    udid: &str,
+    req: &SimulatorBootRequest,
+    locale: Option<(&str, &str)>,
+) -> Vec<BootSettingFailure> {
+    if req.appearance.is_none() && locale.is_none() && req.status_bar.is_none() {
+        return Vec::new();
+    }
+
+    // Each setting fails with the same error if the simulator never gets ready
+    let ready =
+        match tokio::time::timeout(BOOT_SETTINGS_TIMEOUT, simctl::wait_until_ready(udid, false))
+            .await
+        {
+            Ok(Ok(())) => Ok(()),
+            Ok(Err(e)) => Err(e.to_string()),
+            Err(_) => Err(format!(
+                "Simulator {} was not ready after {}s",
+                udid,
+                BOOT_SETTINGS_TIMEOUT.as_secs()
+            )),
+        };
+
+    let mut failed = Vec::new();
+    if let Some(appearance) = req.appearance {
+        let set = simctl::set_appearance(udid, appearance);
+        failed.extend(apply_boot_setting(udid, "appearance", &ready, set).await);
+    }
+    if let Some((language, locale)) = locale {
+        let set = simctl::set_locale(udid, language, locale);
+        failed.extend(apply_boot_setting(udid, "locale", &ready, set).await);
+    }
+    if let Some(overrides) = &req.status_bar {
+        let set = simctl::override_status_bar(udid, overrides);
+        failed.extend(apply_boot_setting(udid, "status_bar", &ready, set).await);
+    }
+    failed
+}
+
+/// Run `set` if the simulator is ready, returning the failure if either fails
+async fn apply_boot_setting(
+    udid: &str,
+    setting: &str,
+    ready: &std::result::Result<(), String>,
+    set: impl std::future::Future<Output = Result<()>>,
+) -> Option<BootSettingFailure> {
+    let error = match ready {
+        Ok(()) => set.await.err()?.to_string(),
+        Err(e) => e.clone(),
+    };
+    tracing::warn!("Failed to set {} on simulator {}: {}", setting, udid, error);
+    Some(BootSettingFailure {
+        setting: setting.to_string(),
+        error,
+    })
+}
+
+/// Longest a simulator lease may run between renewals
+const MAX_LEASE_TTL_SECS: u64 = 24 * 60 * 60;
+
+/// POST /simulator/lease - Reserve a booted simulator for a session
+///
+/// Returns and renews the tag's existing lease if it has one. Otherwise
+/// leases an unleased simulator matching `udid` or `device_type`,
+/// preferring one already booted, and boots it. Builds and tests pass the
+/// lease id or tag as `lease` to run on it, which also renews it. Leases
+/// only coordinate clients that use them; see [`SimulatorLease`].
+///
+/// [`SimulatorLease`]: crate::state::SimulatorLease
+pub async fn lease(
+    State(state): State<SharedState>,
+    namespace: Namespace,
+    JsonBody(req): JsonBody<SimulatorLeaseRequest>,
+) -> Result<Json<SimulatorLeaseResponse>> {
+    if req.tag.trim().is_empty() {
+        return Err(XcbridgeError::InvalidRequest(
+            "tag must not be empty".into(),
+        ));
+    }
+    if req.ttl_secs == 0 || req.ttl_secs > MAX_LEASE_TTL_SECS {
+        return Err(XcbridgeError::InvalidRequest(format!(
+            "ttl_secs must be between 1 and {}",
+            MAX_LEASE_TTL_SECS
+        )));
+    }
+
+    let simulators = simctl::list_all_devices().await?;
+    let ttl = chrono::Duration::seconds(req.ttl_secs as i64);
+    let (lease, new) = state
+        .acquire_lease(&req.tag, &namespace, ttl, |leased| {
+            simctl::select_for_lease(
+                simulators,
+                req.udid.as_deref(),
+                req.device_type.as_deref(),
+                req.runtime.as_deref(),
+                leased,
+            )
+        })
+        .await?;
+
+    let booted = {
+        let _lock = state.lock_simulator(&lease.udid).await;
+        simctl::boot(&lease.udid).await
+    };
+    if let Err(e) = booted {
+        if new {
+            state.release_lease(&lease.lease_id, &namespace).await;
+        }
+        return Err(e);
+    }
+    let simulator = simctl::get_simulator(&lease.udid).await?;
+
+    Ok(Json(SimulatorLeaseResponse {
+        lease_id: lease.lease_id,
+        tag: lease.tag,
+        udid: simulator.udid,
+        name: simulator.name,
+        status: simulator.state,
+        expires_at: lease.expires_at,
+    }))
+}
+
+/// POST /simulator/release - End a simulator lease
+///
+/// The simulator stays booted for the next lease unless `shutdown` is set.
+pub async fn release(
+    State(state): State<SharedState>,
+    namespace: Namespace,
+    JsonBody(req): JsonBody<SimulatorReleaseRequest>,
+) -> Result<Json<SuccessResponse>> {
+    let lease = state
+        .release_lease(&req.lease, &namespace)
+        .await
+        .ok_or_else(|| XcbridgeError::LeaseNotFound(req.lease.clone()))?;
+
+    if req.shutdown {
+        let _lock = state.lock_simulator(&lease.udid).await;
+        simctl::shutdown(&lease.udid).await?;
+    }
+
+    Ok(Json(SuccessResponse::new(format!(
+        "Released lease {} on simulator {}",
+        lease.lease_id, lease.udid
+    ))))
+}
+
+/// POST /simulator/wait-ready - Wait until a simulator is booted and responsive
+///
+/// Blocks until `simctl bootstatus` completes and SpringBoard is running, or
+/// the timeout elapses.
+pub async fn wait_ready(
+    State(_state): State<SharedState>,
+    JsonBody(req): JsonBody<SimulatorWaitReadyRequest>,
+) -> Result<Json<SimulatorBootResponse>> {
+    // Fail fast on unknown simulators rather than waiting out the timeout
+    let already_booted = simctl::get_simulator(&req.udid).await?.state == "Booted";
+
+    tokio::time::timeout(
+        Duration::from_secs(req.timeout_secs),
+        simctl::wait_until_ready(&req.udid, req.boot),
+    )
+    .await
+    .map_err(|_| {
+        XcbridgeError::Timeout(format!(
+            "Simulator {} was not ready after {}s",
+            req.udid, req.timeout_secs
+        ))
+    })??;
+
+    let simulator = simctl::get_simulator(&req.udid).await?;
+
+    Ok(Json(SimulatorBootResponse {
+        udid: simulator.udid,
+        name: simulator.name,
+        status: simulator.state,
+        already_booted,
+        failed_settings: Vec::new(),
+    }))
+}
+
+/// How long to wait for each simulator to become ready when prewarming
+const PREWARM_TIMEOUT: Duration = Duration::from_secs(180);
+
+/// POST /simulator/prewarm - Boot simulators so later requests find them ready
+///
+/// Boots the requested simulators, or the `--prewarm-simulators` list if the
+/// body is omitted, and waits until each is ready. Responds with 207 Multi-Status
+/// if any failed.
+pub async fn prewarm(
+    State(state): State<SharedState>,
+    body: Bytes,
+) -> Result<(StatusCode, Json<SimulatorPrewarmResponse>)> {
+    let req: SimulatorPrewarmRequest = if body.is_empty() {
+        SimulatorPrewarmRequest::default()
+    } else {
+        super::parse_json_body(&body)?
+    };
+    let simulators = if req.simulators.is_empty() {
+        state.config.prewarm_simulators.clone().unwrap_or_default()
+    } else {
+        req.simulators
+    };
+
+    if simulators.is_empty() {
+        return Err(XcbridgeError::InvalidRequest(
+            "No simulators specified and none configured with --prewarm-simulators".into(),
+        ));
+    }
+
+    let results = prewarm_simulators(&state, simulators).await;
+    let success = results.iter().all(|r| r.success);
+    let status = if success {
+        StatusCode::OK
+    } else {
+        StatusCode::MULTI_STATUS
+    };
+
+    Ok((status, Json(SimulatorPrewarmResponse { success, results })))
+}
+
+/// Boot each simulator (by UDID or device name) and wait until it is ready
+pub(crate) async fn prewarm_simulators(
+    state: &SharedState,
+    simulators: Vec<String>,
+) -> Vec<SimulatorPrewarmResult> {
+    let prewarms = simulators.into_iter().map(|simulator| async move {
+        let result = prewarm_simulator(state, &simulator).await;
+        SimulatorPrewarmResult {
+            simulator,
+            udid: result.as_ref().ok().cloned(),
+            success: result.is_ok(),
+            error: result.err().map(|e| e.to_string()),
+        }
+    });
+    join_all(prewarms).await
+}
+
+async fn prewarm_simulator(state: &SharedState, simulator: &str) -> Result<String> {
+    let udid = match simctl::get_simulator(simulator).await {
+        Ok(sim) => sim.udid,
+        Err(XcbridgeError::SimulatorNotFound(_)) => {
+            simctl::find_simulator(simulator, None).await?.udid
+        }
+        Err(e) => return Err(e),
+    };
+
+    let _lock = state.lock_simulator(&udid).await;
+    tokio::time::timeout(PREWARM_TIMEOUT, simctl::wait_until_ready(&udid, true))
+        .await
+        .map_err(|_| {
+            XcbridgeError::Timeout(format!(
+                "Simulator {} was not ready after {}s",
+                udid,
+                PREWARM_TIMEOUT.as_secs()
+            ))
+        })??;
+
+    Ok(udid)
+}
+
+/// How long to wait for CoreSimulatorService to come back after a reset
+const SERVICE_RESTART_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// POST /simulator/reset-service - Restart CoreSimulatorService (admin only)
+pub async fn reset_service(
+    State(_state): State<SharedState>,
+) -> Result<Json<ResetServiceResponse>> {
+    let was_running = simctl::kill_core_simulator_service().await?;
+
+    tokio::time::timeout(SERVICE_RESTART_TIMEOUT, simctl::wait_for_service())
+        .await
+        .map_err(|_| {
+            XcbridgeError::Timeout(format!(
+                "CoreSimulatorService did not respond after {}s",
+                SERVICE_RESTART_TIMEOUT.as_secs()
+            ))
+        })?;
+
+    Ok(Json(ResetServiceResponse {
+        success: true,
+        was_running,
+    }))
+}
+
+/// POST /simulator/runtimes/install - Download or add a simulator runtime (admin only)
+///
+/// Runs as a job, so progress streams from `/build/:id/logs` and the outcome
+/// is reported by `/build/:id`.
+pub async fn install_runtime(
+    State(state): State<SharedState>,
+    namespace: Namespace,
+    JsonBody(req): JsonBody<RuntimeInstallRequest>,
+) -> Result<Json<BuildStartedResponse>> {
+    let version = req.version.clone().unwrap_or_default();
+    let source = match req.image {
+        Some(image) => {
+            if req.version.is_some() {
+                return Err(XcbridgeError::InvalidRequest(
+                    "Specify either image or version, not both".into(),
+                ));
+            }
+            if !state.config.is_path_allowed(std::path::Path::new(&image)) {
+                return Err(XcbridgeError::PathNotAllowed(image));
+            }
+            if !std::path::Path::new(&image).is_file() {
+                return Err(XcbridgeError::InvalidRequest(format!(
+                    "Runtime image not found: {}",
+                    image
+                )));
+            }
+            RuntimeSource::Image(image)
+        }
+        None => {
+            if !xcodebuild::DOWNLOADABLE_PLATFORMS.contains(&req.platform.as_str()) {
+                return Err(XcbridgeError::InvalidRequest(format!(
+                    "Unknown platform '{}', expected one of: {}",
+                    req.platform,
+                    xcodebuild::DOWNLOADABLE_PLATFORMS.join(", ")
+                )));
+            }
+            RuntimeSource::Download(xcodebuild::download_platform_args(
+                &req.platform,
+                req.version.as_deref(),
+            ))
+        }
+    };
+
+    let job_id = Uuid::new_v4().to_string();
+    let name = super::job_name(None, &["runtime", &req.platform, &version], &job_id)?;
+    let cancel = CancelSignal::default();
+    state
+        .insert_build(
+            &job_id,
+            BuildRecord {
+                namespace,
+                kind: JobKind::Runtime,
+                name: Some(name.clone()),
+                cancel: cancel.clone(),
+                ..BuildRecord::default()
+            },
+        )
+        .await?;
+
+    let job_state = Arc::clone(&state);
+    let id = job_id.clone();
+    tokio::spawn(async move {
+        run_runtime_install(job_state, id, source, cancel).await;
+    });
+
+    Ok(Json(BuildStartedResponse {
+        build_id: job_id.clone(),
+        name: Some(name),
+        status: "running".to_string(),
+        logs_url: format!("/build/{}/logs", job_id),
+        cache_hit: None,
+        artifacts: None,
+        exported_artifacts: None,
+        derived_data_path: None,
+    }))
+}
+
+enum RuntimeSource {
+    /// `xcodebuild -downloadPlatform` arguments
+    Download(Vec<String>),
+    /// Disk image passed to `simctl runtime add`
+    Image(String),
+}
+
+async fn run_runtime_install(
+    state: SharedState,
+    job_id: String,
+    source: RuntimeSource,
+    cancel: CancelSignal,
+) {
+    let result = match source {
+        RuntimeSource::Download(args) => {
+            let (tx, mut rx) = mpsc::unbounded_channel::<String>();
+            let log_state = Arc::clone(&state);
+            let log_id = job_id.clone();
+            let collector = tokio::spawn(async move {
+                while let Some(line) = rx.recv().await {
+                    log_state.append_build_log(&log_id, line).await;
+                }
+            });
+
+            let result =
+                xcodebuild::run_xcodebuild_with_env(args, &HashMap::new(), None, &cancel, |line| {
+                    let _ = tx.send(line);
+                })
+                .await;
+            let _ = collector.await;
+            if let Ok(output) = &result {
+                state.add_cpu_time(&job_id, output.cpu_time).await;
+            }
+
+            match result {
+                Ok(output) if output.success => Ok(()),
+                Ok(output) => Err((
+                    output
+                        .logs
+                        .iter()
+                        .rev()
+                        .find(|l| l.contains("error"))
+                        .cloned()
+                        .unwrap_or_else(|| "Runtime download failed".to_string()),
+                    Some(output.exit_code),
+                )),
+                Err(e) => Err((e.to_string(), None)),
+            }
+        }
+        RuntimeSource::Image(image) => match simctl::add_runtime(&image).await {
+            Ok(output) => {
+                for line in output.lines() {
+                    state.append_build_log(&job_id, line.to_string()).await;
+                }
+                Ok(())
+            }
+            Err(e) => Err((e.to_string(), None)),
+        },
+    };
+
+    match result {
+        Ok(()) => {
+            // Make the new runtime visible to /capabilities right away
+            state.refresh_host_info().await;
+            state.complete_build(&job_id, vec![]).await;
+        }
+        Err((error, exit_code)) => state.fail_build(&job_id, error, exit_code).await,
+    }
+}
+
+/// GET /simulator/:udid/documents - Download an app's Documents directory
+///
+/// `udid` may be "booted" for the booted simulator. Responds with an
+/// uncompressed tarball whose entries are under `Documents/`.
+pub async fn documents(
+    State(_state): State<SharedState>,
+    Path(udid): Path<String>,
+    Query(query): Query<SimulatorAppQuery>,
+) -> Result<impl IntoResponse> {
+    let archive = simctl::archive_documents(&udid, &query.bundle_id).await?;
+    let disposition = format!("attachment; filename=\"{}-Documents.tar\"", query.bundle_id);
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/x-tar".to_string()),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        archive,
+    ))
+}
+
+/// GET /simulator/:udid/logs/tail - The latest lines of a simulator's log
+///
+/// A one-shot `log show` over the last few minutes, for quick diagnostics
+/// without holding a stream open. `udid` may be "booted".
+pub async fn log_tail(
+    State(_state): State<SharedState>,
+    Path(udid): Path<String>,
+    Query(query): Query<SimulatorLogTailQuery>,
+) -> Result<Json<SimulatorLogTailResponse>> {
+    let (lines, truncated) =
+        simctl::log_tail(&udid, query.lines, query.minutes, query.process.as_deref()).await?;
+
+    Ok(Json(SimulatorLogTailResponse {
+        udid,
+        lines,
+        truncated,
+    }))
+}
+
+/// Longest text `POST /simulator/:udid/type` accepts
+const MAX_TYPE_TEXT_LEN: usize = 10_000;
+
+/// POST /simulator/:udid/type - Type text into the focused field
+///
+/// `udid` may be "booted". Without idb the text is put on the simulator's
+/// pasteboard instead, and `method` is "pasteboard".
+pub async fn type_text(
+    State(_state): State<SharedState>,
+    Path(udid): Path<String>,
+    JsonBody(req): JsonBody<SimulatorTypeRequest>,
+) -> Result<Json<SimulatorTypeResponse>> {
+    if req.text.is_empty() || req.text.chars().count() > MAX_TYPE_TEXT_LEN {
+        return Err(XcbridgeError::InvalidRequest(format!(
+            "text must be 1 to {} characters",
+            MAX_TYPE_TEXT_LEN
+        )));
+    }
+    let udid = booted_or_udid(udid).await?;
+    let method = ui_input::type_text(&udid, &req.text).await?;
+
+    let message = match method {
+        TextInputMethod::Typed => format!("Typed {} characters", req.text.chars().count()),
+        TextInputMethod::Pasteboard => {
+            "idb isn't installed, so the text was put on the pasteboard instead of typed".into()
+        }
+    };
+    Ok(Json(SimulatorTypeResponse {
+        udid,
+        method,
+        message,
+    }))
+}
+
+/// POST /simulator/:udid/tap - Tap a point on the screen (requires idb)
+///
+/// `udid` may be "booted".
+pub async fn tap(
+    State(_state): State<SharedState>,
+    Path(udid): Path<String>,
+    JsonBody(req): JsonBody<SimulatorTapRequest>,
+) -> Result<Json<SuccessResponse>> {
+    let valid = |value: f64| value.is_finite() && value >= 0.0;
+    if !valid(req.x) || !valid(req.y) || !req.duration_secs.is_none_or(valid) {
+        return Err(XcbridgeError::InvalidRequest(
+            "x, y and duration_secs must be non-negative numbers".into(),
+        ));
+    }
+    let udid = booted_or_udid(udid).await?;
+    ui_input::tap(&udid, req.x, req.y, req.duration_secs).await?;
+
+    Ok(Json(SuccessResponse::new(format!(
+        "Tapped ({}, {}) on {}",
+        req.x, req.y, udid
+    ))))
+}
+
+/// POST /simulator/:udid/button - Press a hardware button or shake the device
+///
+/// `udid` may be "booted". Shakes work without idb; other buttons need it.
+pub async fn button(
+    State(_state): State<SharedState>,
+    Path(udid): Path<String>,
+    JsonBody(req): JsonBody<SimulatorButtonRequest>,
+) -> Result<Json<SuccessResponse>> {
+    let udid = booted_or_udid(udid).await?;
+    ui_input::press_button(&udid, req.button).await?;
+
+    Ok(Json(SuccessResponse::new(format!(
+        "Pressed {} on {}",
+        req.button.name(),
+        udid
+    ))))
+}
+
+/// The booted simulator's UDID for "booted", which idb doesn't understand
+async fn booted_or_udid(udid: String) -> Result<String> {
+    if !udid.eq_ignore_ascii_case("booted") {
+        return Ok(udid);
+    }
+    simctl::get_booted_simulator()
+        .await?
+        .map(|simulator| simulator.udid)
+        .ok_or_else(|| XcbridgeError::SimulatorNotFound("No booted simulator".into()))
+}
+
+/// GET /simulator/:udid/screen-text - Recognize the text on a simulator's screen
+///
+/// `udid` may be "booted" for the booted simulator.
+#[cfg(feature = "screen-text")]
+pub async fn screen_text(
+    State(_state): State<SharedState>,
+    Path(udid): Path<String>,
+) -> Result<Json<ScreenTextResponse>> {
+    let path = std::env::temp_dir().join(format!("xcbridge-screen-{}.png", uuid::Uuid::new_v4()));
+    simctl::screenshot(&udid, &path.to_string_lossy()).await?;
+
+    let result = screen_text::recognize_text(&path).await;
+    let _ = tokio::fs::remove_file(&path).await;
+
+    Ok(Json(ScreenTextResponse {
+        udid,
+        texts: result?,
     }))
 }
 
 /// POST /simulator/shutdown - Shutdown a simulator
 pub async fn shutdown(
-    State(_state): State<SharedState>,
-    Json(req): Json<SimulatorShutdownRequest>,
+    State(state): State<SharedState>,
+    JsonBody(req): JsonBody<SimulatorShutdownRequest>,
 ) -> Result<Json<SuccessResponse>> {
     if req.all {
         simctl::shutdown_all().await?;
         Ok(Json(SuccessResponse::new("All simulators shut down")))
     } else if let Some(udid) = req.udid {
+        let _lock = state.lock_simulator(&udid).await;
         simctl::shutdown(&udid).await?;
         Ok(Json(SuccessResponse::new(format!(
             "Simulator {} shut down",
@@ -76,9 +723,9 @@ pub async fn shutdown(
 
 /// POST /simulator/install - Install an app on a simulator
 pub async fn install(
-    State(_state): State<SharedState>,
-    Json(req): Json<SimulatorInstallRequest>,
-) -> Result<Json<SuccessResponse>> {
+    State(state): State<SharedState>,
+    JsonBody(req): JsonBody<SimulatorInstallRequest>,
+) -> Result<Json<InstallResponse>> {
     // Get the target simulator
     let udid = if let Some(udid) = req.udid {
         udid
@@ -92,20 +739,34 @@ pub async fn install(
             .udid
     };
 
+    // Read the bundle id up front, so a bad bundle fails before installing
+    let bundle_id = if req.data_container {
+        Some(bundle::read_app_info(std::path::Path::new(&req.app_path))?.bundle_id)
+    } else {
+        None
+    };
+
     // Install the app
+    let _lock = state.lock_simulator(&udid).await;
     simctl::install(&udid, &req.app_path).await?;
+    let data_container = match &bundle_id {
+        Some(bundle_id) => Some(simctl::get_app_container(&udid, bundle_id, "data").await?),
+        None => None,
+    };
 
-    Ok(Json(SuccessResponse::new(format!(
-        "App installed to simulator {}",
-        udid
-    ))))
+    Ok(Json(InstallResponse {
+        success: true,
+        message: format!("App installed to simulator {}", udid),
+        bundle_id,
+        data_container,
+    }))
 }
 
 /// POST /simulator/launch - Launch an app on a simulator
 pub async fn launch(
-    State(_state): State<SharedState>,
-    Json(req): Json<SimulatorLaunchRequest>,
-) -> Result<Json<SuccessResponse>> {
+    State(state): State<SharedState>,
+    JsonBody(req): JsonBody<SimulatorLaunchRequest>,
+) -> Result<Json<LaunchResponse>> {
     // Get the target simulator
     let udid = if let Some(udid) = req.udid {
         udid
@@ -120,18 +781,98 @@ pub async fn launch(
     };
 
     // Launch the app
-    simctl::launch(&udid, &req.bundle_id, &req.arguments).await?;
+    let _lock = state.lock_simulator(&udid).await;
+    let pid = simctl::launch(&udid, &req.bundle_id, &req.arguments, req.terminate_running).await?;
 
-    Ok(Json(SuccessResponse::new(format!(
-        "App {} launched on simulator {}",
-        req.bundle_id, udid
-    ))))
+    Ok(Json(LaunchResponse {
+        success: true,
+        message: format!("App {} launched on simulator {}", req.bundle_id, udid),
+        pid,
+    }))
 }
 
 /// POST /simulator/uninstall - Uninstall an app from a simulator
+///
+/// Succeeds with `was_installed: false` if the app was not installed.
 pub async fn uninstall(
+    State(state): State<SharedState>,
+    JsonBody(req): JsonBody<SimulatorUninstallRequest>,
+) -> Result<Json<UninstallResponse>> {
+    // Get the target simulator
+    let udid = if let Some(udid) = req.udid {
+        udid
+    } else {
+        // Use the currently booted simulator
+        simctl::get_booted_simulator()
+            .await?
+            .ok_or_else(|| {
+                XcbridgeError::SimulatorError("No simulator is currently booted".into())
+            })?
+            .udid
+    };
+
+    // Uninstall the app
+    let _lock = state.lock_simulator(&udid).await;
+    let was_installed = simctl::uninstall(&udid, &req.bundle_id).await?;
+
+    let message = if was_installed {
+        format!("App {} uninstalled from simulator {}", req.bundle_id, udid)
+    } else {
+        format!(
+            "App {} was not installed on simulator {}",
+            req.bundle_id, udid
+        )
+    };
+
+    Ok(Json(UninstallResponse {
+        success: true,
+        message,
+        was_installed,
+    }))
+}
+
+/// POST /simulator/locale - Set a simulator's language and region
+///
+/// The simulator must already be booted. SpringBoard is restarted to apply
+/// the change, so the simulator briefly resprings.
+pub async fn locale(
+    State(state): State<SharedState>,
+    JsonBody(req): JsonBody<SimulatorLocaleRequest>,
+) -> Result<Json<SuccessResponse>> {
+    // Get the target simulator
+    let simulator = if let Some(udid) = req.udid {
+        simctl::get_simulator(&udid).await?
+    } else {
+        // Use the currently booted simulator
+        simctl::get_booted_simulator().await?.ok_or_else(|| {
+            XcbridgeError::SimulatorError("No simulator is currently booted".into())
+        })?
+    };
+
+    let _lock = state.lock_simulator(&simulator.udid).await;
+    if simulator.state != "Booted" {
+        return Err(XcbridgeError::SimulatorError(format!(
+            "Simulator {} must be booted to change its locale",
+            simulator.udid
+        )));
+    }
+
+    if let Some(enabled) = req.hardware_keyboard {
+        simctl::set_hardware_keyboard(enabled).await?;
+    }
+
+    simctl::set_locale(&simulator.udid, &req.language, &req.locale).await?;
+
+    Ok(Json(SuccessResponse::new(format!(
+        "Simulator {} locale set to {} ({})",
+        simulator.udid, req.locale, req.language
+    ))))
+}
+
+/// POST /simulator/signal - Send a signal to a process in a simulator
+pub async fn signal(
     State(_state): State<SharedState>,
-    Json(req): Json<SimulatorUninstallRequest>,
+    JsonBody(req): JsonBody<SimulatorSignalRequest>,
 ) -> Result<Json<SuccessResponse>> {
     // Get the target simulator
     let udid = if let Some(udid) = req.udid {
@@ -146,11 +887,10 @@ pub async fn uninstall(
             .udid
     };
 
-    // Uninstall the app
-    simctl::uninstall(&udid, &req.bundle_id).await?;
+    simctl::signal(&udid, req.pid, &req.signal).await?;
 
     Ok(Json(SuccessResponse::new(format!(
-        "App {} uninstalled from simulator {}",
-        req.bundle_id, udid
+        "Sent {} to pid {} on simulator {}",
+        req.signal, req.pid, udid
     ))))
 }
@@ -4,10 +4,17 @@
 //! Status handler
 
 use crate::error::Result;
-use crate::models::{DeviceInfo, SimulatorInfo, StatusResponse};
-use crate::state::SharedState;
-use crate::xcode::{devicectl, simctl};
+use crate::models::{
+    DeviceInfo, LastCommandsResponse, SimulatorInfo, StatusResponse, SystemResponse,
+    VersionResponse,
+};
+use crate::state::{HostInfo, JobKind, SharedState};
+use crate::xcode::capabilities::Capabilities;
+use crate::xcode::{devicectl, runner, simctl};
+use axum::http::header;
+use axum::response::IntoResponse;
 use axum::{extract::State, Json};
+use std::fmt::Write;
 
 /// GET /status - Health check and status information
 pub async fn status(State(state): State<SharedState>) -> Result<Json<StatusResponse>> {
@@ -18,17 +25,134 @@ pub async fn status(State(state): State<SharedState>) -> Result<Json<StatusRespo
         .map(SimulatorInfo::from)
         .collect();
 
-    let devices = devicectl::list_devices()
-        .await
-        .unwrap_or_default()
-        .into_iter()
-        .map(DeviceInfo::from)
-        .collect();
+    // Hosts without devicectl simply have no devices to report
+    let devicectl_available = state.capabilities().await.devicectl;
+    let devices = if devicectl_available {
+        devicectl::list_devices()
+            .await
+            .unwrap_or_default()
+            .into_iter()
+            .map(DeviceInfo::from)
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    let host = state.host_info().await;
 
     Ok(Json(StatusResponse {
         healthy: true,
-        xcode_version: state.xcode_version.clone(),
+        xcode_version: host.xcode_version,
+        sdks: host.sdks,
         simulators,
         connected_devices: devices,
+        devicectl_available,
+        simulator_service: state.simulator_health().await,
     }))
 }
+
+/// GET /version - Versions of xcbridge, Xcode and the Command Line Tools
+///
+/// Served without an API key so clients can check compatibility first.
+pub async fn version(State(state): State<SharedState>) -> Json<VersionResponse> {
+    let host = state.host_info().await;
+    Json(VersionResponse {
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        xcode_version: host.xcode_version,
+        command_line_tools_version: host.command_line_tools_version,
+    })
+}
+
+/// GET /system - Health of the host's subsystems
+///
+/// Reports the periodic CoreSimulator probe, so schedulers can route around
+/// a host whose simulator service is wedged.
+pub async fn system(State(state): State<SharedState>) -> Json<SystemResponse> {
+    Json(SystemResponse {
+        simulator_service: state.simulator_health().await,
+    })
+}
+
+/// GET /capabilities - Optional features available on this host
+///
+/// Probed on the first request and cached with the rest of the host info.
+pub async fn capabilities(State(state): State<SharedState>) -> Json<Capabilities> {
+    Json(state.capabilities().await)
+}
+
+/// GET /metrics - Job counts and time spent, in Prometheus text format
+///
+/// Totals cover every job finished since the server started, including
+/// ones already removed by cleanup.
+pub async fn metrics(State(state): State<SharedState>) -> impl IntoResponse {
+    let usage = state.job_usage().await;
+    let kinds = [
+        JobKind::Build,
+        JobKind::Test,
+        JobKind::Runtime,
+        JobKind::Localization,
+    ];
+    let mut body = String::new();
+
+    body.push_str(
+        "# HELP xcbridge_jobs_finished_total Jobs that completed, failed or were cancelled\n",
+    );
+    body.push_str("# TYPE xcbridge_jobs_finished_total counter\n");
+    for kind in kinds {
+        let finished = usage.get(&kind).map_or(0, |u| u.finished);
+        let _ = writeln!(
+            body,
+            "xcbridge_jobs_finished_total{{kind=\"{}\"}} {}",
+            kind.as_str(),
+            finished
+        );
+    }
+
+    body.push_str("# HELP xcbridge_job_wall_seconds_total Wall-clock time of finished jobs\n");
+    body.push_str("# TYPE xcbridge_job_wall_seconds_total counter\n");
+    for kind in kinds {
+        let secs = usage.get(&kind).map_or(0.0, |u| u.wall_secs);
+        let _ = writeln!(
+            body,
+            "xcbridge_job_wall_seconds_total{{kind=\"{}\"}} {}",
+            kind.as_str(),
+            secs
+        );
+    }
+
+    body.push_str(
+        "# HELP xcbridge_job_cpu_seconds_total CPU time of xcodebuild and the tools it ran\n",
+    );
+    body.push_str("# TYPE xcbridge_job_cpu_seconds_total counter\n");
+    for kind in kinds {
+        let cpu = usage.get(&kind).map(|u| u.cpu_time).unwrap_or_default();
+        for (mode, secs) in [("user", cpu.user_secs), ("system", cpu.system_secs)] {
+            let _ = writeln!(
+                body,
+                "xcbridge_job_cpu_seconds_total{{kind=\"{}\",mode=\"{}\"}} {}",
+                kind.as_str(),
+                mode,
+                secs
+            );
+        }
+    }
+
+    ([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], body)
+}
+
+/// GET /debug/last-commands - Recent simctl, devicectl and other commands
+///
+/// Lists the last commands the bridge ran, newest first, with their exit
+/// codes and the end of their stderr, for diagnosing a misbehaving host
+/// without shell access. Streamed builds and tests aren't included; their
+/// output is in the job's logs.
+pub async fn last_commands() -> Json<LastCommandsResponse> {
+    Json(LastCommandsResponse {
+        commands: runner::recent_commands(),
+    })
+}
+
+/// POST /refresh - Re-query the cached Xcode version, SDKs and runtimes
+pub async fn refresh(State(state): State<SharedState>) -> Json<HostInfo> {
+    Json(state.refresh_host_info().await)
+}
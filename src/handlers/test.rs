@@ -3,77 +3,401 @@
 
 //! Test handler
 
+use super::JsonBody;
 use crate::error::{Result, XcbridgeError};
-use crate::models::{BuildStartedResponse, TestRequest, TestResultResponse};
-use crate::state::{BuildStatus, SharedState};
-use crate::xcode::xcodebuild::{self, TestParams};
+use crate::junit;
+use crate::log_stream::{self, LogUpdate};
+use crate::models::{
+    BuildStartedResponse, LogQuery, StatusQuery, TestAttachmentInfo, TestAttachmentsResponse,
+    TestCaseEvent, TestCaseResult, TestFailure, TestRequest, TestResultResponse,
+};
+use crate::state::{self, BuildRecord, BuildStatus, JobKind, Namespace, SharedState};
+use crate::upload;
+use crate::xcode::runner;
+use crate::xcode::runtime_logs::RuntimeLogCapture;
+use crate::xcode::simctl::{self, SimulatorErase};
+use crate::xcode::xcodebuild::{self, CancelSignal, TestParams};
+use crate::xcode::xcresult::{self, TestAttachment, TestSummary};
 use axum::{
-    extract::{Path, State},
-    response::sse::{Event, Sse},
+    extract::{Multipart, Path, Query, State},
+    http::header,
+    response::{
+        sse::{Event, Sse},
+        IntoResponse,
+    },
     Json,
 };
+use chrono::{DateTime, Utc};
 use futures::stream::Stream;
+use std::collections::HashMap;
 use std::convert::Infallible;
 use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::mpsc;
+use tracing::Instrument;
 use uuid::Uuid;
 
 /// POST /test - Start a test run
 pub async fn start_test(
     State(state): State<SharedState>,
-    Json(req): Json<TestRequest>,
+    namespace: Namespace,
+    JsonBody(req): JsonBody<TestRequest>,
+) -> Result<Json<BuildStartedResponse>> {
+    submit_test(state, namespace, req, None).await.map(Json)
+}
+
+/// POST /test/upload - Test a project uploaded as an archive
+///
+/// Takes the same multipart body as `/build/upload`, with a test request.
+pub async fn upload_test(
+    State(state): State<SharedState>,
+    namespace: Namespace,
+    multipart: Multipart,
 ) -> Result<Json<BuildStartedResponse>> {
-    // Validate project/workspace path
-    let project_path = req
-        .project
-        .as_ref()
-        .or(req.workspace.as_ref())
-        .ok_or_else(|| {
-            XcbridgeError::InvalidRequest("Either project or workspace must be specified".into())
-        })?;
+    let upload = upload::receive(&state.config, multipart).await?;
+    let started = async {
+        let mut req: TestRequest = super::parse_json_body(&upload.request)?;
+        req.project = super::resolve_upload_path(&upload, req.project)?;
+        req.workspace = super::resolve_upload_path(&upload, req.workspace)?;
+        req.xctestrun_path = super::resolve_upload_path(&upload, req.xctestrun_path)?;
+        submit_test(state, namespace, req, Some(upload.dir.clone())).await
+    }
+    .await;
+
+    if started.is_err() {
+        state::remove_upload(&upload.dir).await;
+    }
+    started.map(Json)
+}
+
+/// Validate a test request and start it in the background
+///
+/// `upload_dir` is an uploaded project's workspace, removed once the run
+/// finishes.
+async fn submit_test(
+    state: SharedState,
+    namespace: Namespace,
+    req: TestRequest,
+    upload_dir: Option<PathBuf>,
+) -> Result<BuildStartedResponse> {
+    // Validate the project/workspace or xctestrun path
+    let project_path = match &req.xctestrun_path {
+        Some(xctestrun) => {
+            if req.project.is_some()
+                || req.workspace.is_some()
+                || req.scheme.is_some()
+                || req.test_plan.is_some()
+            {
+                return Err(XcbridgeError::InvalidRequest(
+                    "xctestrun_path can't be combined with project, workspace, scheme or test_plan"
+                        .into(),
+                ));
+            }
+            xctestrun
+        }
+        None => {
+            if req.scheme.is_none() {
+                return Err(XcbridgeError::InvalidRequest(
+                    "scheme is required unless xctestrun_path is set".into(),
+                ));
+            }
+            super::project_or_workspace(req.project.as_ref(), req.workspace.as_ref())?
+        }
+    };
 
     let path = PathBuf::from(project_path);
     if !state.config.is_path_allowed(&path) {
         return Err(XcbridgeError::PathNotAllowed(project_path.clone()));
     }
+    if req.xctestrun_path.is_some()
+        && (!path.is_file() || path.extension().is_none_or(|ext| ext != "xctestrun"))
+    {
+        return Err(XcbridgeError::InvalidRequest(format!(
+            "xctestrun_path is not an .xctestrun file: {}",
+            project_path
+        )));
+    }
+
+    if let Some(toolchain) = &req.toolchain {
+        if xcodebuild::find_toolchain(toolchain).is_none() {
+            return Err(XcbridgeError::ToolchainNotFound(toolchain.clone()));
+        }
+    }
+
+    if req.test_plan.is_none()
+        && !(req.only_test_configuration.is_empty() && req.skip_test_configuration.is_empty())
+    {
+        return Err(XcbridgeError::InvalidRequest(
+            "only_test_configuration and skip_test_configuration require test_plan".into(),
+        ));
+    }
+
+    xcodebuild::validate_test_locale(req.test_language.as_deref(), req.test_region.as_deref())?;
+
+    if req.parallel_workers.is_some() && !req.parallel_testing {
+        return Err(XcbridgeError::InvalidRequest(
+            "parallel_workers requires parallel_testing".into(),
+        ));
+    }
+    if req.parallel_workers == Some(0) {
+        return Err(XcbridgeError::InvalidRequest(
+            "parallel_workers must be at least 1".into(),
+        ));
+    }
+    if req.jobs == Some(0) {
+        return Err(XcbridgeError::InvalidRequest(
+            "jobs must be at least 1".into(),
+        ));
+    }
+    // Clones log separately from the simulator they were cloned from, and
+    // show their own screens
+    if req.capture_runtime_logs && req.parallel_testing {
+        return Err(XcbridgeError::InvalidRequest(
+            "capture_runtime_logs can't be combined with parallel_testing".into(),
+        ));
+    }
+    if req.screenshot_on_failure && req.parallel_testing {
+        return Err(XcbridgeError::InvalidRequest(
+            "screenshot_on_failure can't be combined with parallel_testing".into(),
+        ));
+    }
+
+    let destination =
+        super::lease_destination(&state, &namespace, req.lease.as_deref(), req.destination).await?;
+
+    let simulator_udid = if req.capture_runtime_logs || req.erase_after || req.screenshot_on_failure
+    {
+        Some(destination_simulator(destination.as_deref()).await?)
+    } else {
+        None
+    };
+
+    // Never erase a simulator someone else booted: only one that is shut down
+    // (so this run boots it) or that this service booted
+    let erase = match (&simulator_udid, req.erase_after) {
+        (Some(udid), true) => {
+            let simulator = simctl::get_simulator(udid).await?;
+            Some(EraseTarget {
+                udid: udid.clone(),
+                allowed: simulator.state == "Shutdown" || simctl::is_service_booted(udid),
+            })
+        }
+        _ => None,
+    };
+    let fail_fast = req.fail_fast;
+    let hooks = SimulatorHooks {
+        runtime_log_udid: simulator_udid.clone().filter(|_| req.capture_runtime_logs),
+        screenshot_udid: simulator_udid.filter(|_| req.screenshot_on_failure),
+        erase,
+    };
 
     // Generate test ID
     let test_id = Uuid::new_v4().to_string();
-    
-    // Create test entry (reusing build state)
-    state.create_build(&test_id).await;
+    let subject = match (&req.scheme, &req.xctestrun_path) {
+        (Some(scheme), _) => scheme.clone(),
+        (None, Some(xctestrun)) => std::path::Path::new(xctestrun)
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_default(),
+        (None, None) => String::new(),
+    };
+    let name = super::job_name(req.name, &["test", &subject], &test_id)?;
 
     // Convert request to test params
     let params = TestParams {
         project: req.project,
         workspace: req.workspace,
         scheme: req.scheme,
-        destination: req.destination,
+        xctestrun_path: req.xctestrun_path.clone(),
+        destination,
+        destination_timeout: req
+            .destination_timeout
+            .or(state.config.default_destination_timeout()),
         test_plan: req.test_plan,
+        only_test_configuration: req.only_test_configuration,
+        skip_test_configuration: req.skip_test_configuration,
+        test_language: req.test_language,
+        test_region: req.test_region,
+        toolchain: req.toolchain,
+        jobs: req.jobs.or(state.config.build_jobs),
         only_testing: req.only_testing,
         skip_testing: req.skip_testing,
+        parallel_testing: req.parallel_testing,
+        parallel_workers: req.parallel_workers,
+        // Parallel workers interleave their output, so counts are read from
+        // the result bundle instead of the log. xctestrun runs use it to list
+        // the tests that ran.
+        result_bundle_path: (req.result_bundle
+            || req.parallel_testing
+            || req.xctestrun_path.is_some())
+        .then(|| {
+            state
+                .config
+                .result_bundle_dir()
+                .join(format!("{}.xcresult", test_id))
+                .to_string_lossy()
+                .to_string()
+        }),
     };
 
+    // Create test entry (reusing build state)
+    let cancel = CancelSignal::default();
+    state
+        .insert_build(
+            &test_id,
+            BuildRecord {
+                namespace,
+                kind: JobKind::Test,
+                name: Some(name.clone()),
+                scheme: params.scheme.clone(),
+                result_bundle_path: params.result_bundle_path.as_ref().map(PathBuf::from),
+                keep_result_bundle: req.keep_result_bundle,
+                screenshot_dir: hooks
+                    .screenshot_udid
+                    .as_ref()
+                    .map(|_| state.config.screenshot_dir().join(&test_id)),
+                cancel: cancel.clone(),
+                ..BuildRecord::default()
+            },
+        )
+        .await?;
+
     // Spawn test task
     let state_clone = Arc::clone(&state);
     let test_id_clone = test_id.clone();
-    tokio::spawn(async move {
-        run_test(state_clone, test_id_clone, params).await;
-    });
+    let span = tracing::info_span!("test", id = %test_id, name = %name);
+    tokio::spawn(
+        async move {
+            if let Some(_slot) = state_clone.acquire_job_slot(&test_id_clone, &cancel).await {
+                run_test(state_clone, test_id_clone, params, hooks, fail_fast, cancel).await;
+            }
+            if let Some(dir) = upload_dir {
+                state::remove_upload(&dir).await;
+            }
+        }
+        .instrument(span),
+    );
 
-    Ok(Json(BuildStartedResponse {
+    Ok(BuildStartedResponse {
         build_id: test_id.clone(),
+        name: Some(name),
         status: "running".to_string(),
         logs_url: format!("/test/{}/logs", test_id),
-    }))
+        cache_hit: None,
+        artifacts: None,
+        exported_artifacts: None,
+        derived_data_path: None,
+    })
+}
+
+/// Work done on the destination simulator around a test run
+struct SimulatorHooks {
+    /// Simulator whose log is captured
+    runtime_log_udid: Option<String>,
+    /// Simulator to screenshot as tests fail
+    screenshot_udid: Option<String>,
+    /// Simulator to erase once the run finishes
+    erase: Option<EraseTarget>,
+}
+
+struct EraseTarget {
+    udid: String,
+    /// Whether this service may erase it
+    allowed: bool,
+}
+
+/// Simulator a test destination runs on
+///
+/// Uses the destination's `id=`, else the simulator named by `name=`
+/// (preferring a booted one), else the booted simulator.
+async fn destination_simulator(destination: Option<&str>) -> Result<String> {
+    let field = |key: &str| {
+        destination?
+            .split(',')
+            .find_map(|part| part.trim().strip_prefix(key))
+            .map(str::to_string)
+    };
+
+    if let Some(udid) = field("id=") {
+        return Ok(udid);
+    }
+    if let Some(name) = field("name=") {
+        let booted = simctl::booted_simulators().await?;
+        if let Some(simulator) = booted.into_iter().find(|s| s.name == name) {
+            return Ok(simulator.udid);
+        }
+        return Ok(simctl::find_simulator(&name, None).await?.udid);
+    }
+    if destination.is_none() {
+        if let Some(simulator) = simctl::get_booted_simulator().await? {
+            return Ok(simulator.udid);
+        }
+    }
+    Err(XcbridgeError::InvalidRequest(
+        "capture_runtime_logs, erase_after and screenshot_on_failure require a simulator destination with an id or name"
+            .into(),
+    ))
 }
 
+/// How long to wait for a simulator booted ahead of a test run to become
+/// ready
+const PRE_BOOT_TIMEOUT: Duration = Duration::from_secs(180);
+
 /// Run the actual test
-async fn run_test(state: SharedState, test_id: String, params: TestParams) {
+///
+/// A run cancelled while its simulator boots stops there, leaving the
+/// record to the cancel.
+async fn run_test(
+    state: SharedState,
+    test_id: String,
+    params: TestParams,
+    hooks: SimulatorHooks,
+    fail_fast: bool,
+    cancel: CancelSignal,
+) {
     let state_clone = Arc::clone(&state);
     let test_id_clone = test_id.clone();
 
+    // The log can only be streamed from a booted simulator, so boot it ahead
+    // of xcodebuild
+    let mut runtime_logs = None;
+    if let Some(udid) = hooks.runtime_log_udid {
+        let ready = tokio::select! {
+            _ = cancel.cancelled() => return,
+            ready = tokio::time::timeout(PRE_BOOT_TIMEOUT, simctl::wait_until_ready(&udid, true)) => ready,
+        };
+        let capture = match ready {
+            Ok(Ok(())) => RuntimeLogCapture::start(&udid),
+            Ok(Err(e)) => Err(e),
+            Err(_) => Err(XcbridgeError::Timeout(format!(
+                "Simulator {} was not ready after {}s",
+                udid,
+                PRE_BOOT_TIMEOUT.as_secs()
+            ))),
+        };
+        match capture {
+            Ok(capture) => runtime_logs = Some(capture),
+            Err(e) => tracing::warn!("Not capturing runtime logs for {}: {}", udid, e),
+        }
+    }
+    let mut screenshots = None;
+    if let Some(udid) = hooks.screenshot_udid {
+        let dir = state.config.screenshot_dir().join(&test_id);
+        match tokio::fs::create_dir_all(&dir).await {
+            Ok(()) => screenshots = Some(FailureScreenshots::new(udid, dir)),
+            Err(e) => tracing::warn!("Not taking failure screenshots: {}", e),
+        }
+    }
+    let screenshots = Arc::new(std::sync::Mutex::new(screenshots));
+    let screenshots_for_lines = Arc::clone(&screenshots);
+    let failed_tests = Arc::new(std::sync::Mutex::new(FailedTestTimes::default()));
+    let failed_tests_for_lines = Arc::clone(&failed_tests);
+    let first_failure = Arc::new(std::sync::Mutex::new(
+        fail_fast.then(|| FirstFailure::new(cancel.clone())),
+    ));
+    let first_failure_for_lines = Arc::clone(&first_failure);
+
     let (tx, mut rx) = mpsc::channel::<String>(100);
 
     // Spawn log collector
@@ -87,13 +411,96 @@ async fn run_test(state: SharedState, test_id: String, params: TestParams) {
         }
     });
 
+    let result_bundle_path = params.result_bundle_path.clone().map(PathBuf::from);
+    if let Some(parent) = result_bundle_path.as_deref().and_then(|p| p.parent()) {
+        let _ = tokio::fs::create_dir_all(parent).await;
+    }
+
     // Run xcodebuild test
-    let result = xcodebuild::run_xcodebuild(params.to_args(), move |line| {
-        let _ = tx.try_send(line);
-    })
+    let result = xcodebuild::run_xcodebuild_with_env(
+        params.to_args(),
+        &HashMap::new(),
+        None,
+        &cancel,
+        move |line| {
+            if let Some(screenshots) = screenshots_for_lines.lock().unwrap().as_mut() {
+                screenshots.observe(&line);
+            }
+            let event = parse_test_case_line(&line);
+            if let Some(event) = &event {
+                failed_tests_for_lines.lock().unwrap().record(event);
+            }
+            let _ = tx.try_send(line);
+            let stopped = event.as_ref().and_then(|event| {
+                first_failure_for_lines
+                    .lock()
+                    .unwrap()
+                    .as_mut()?
+                    .observe(event)
+            });
+            if let Some(test) = stopped {
+                let _ = tx.try_send(format!(
+                    "Stopping the run after the first failing test, {} (fail_fast)",
+                    test
+                ));
+            }
+        },
+    )
     .await;
+    if let Ok(output) = &result {
+        state_clone
+            .add_cpu_time(&test_id_clone, output.cpu_time)
+            .await;
+    }
+
+    if let Some(capture) = runtime_logs {
+        let buffer = capture.finish().await;
+        let windows = std::mem::take(&mut failed_tests.lock().unwrap().failed)
+            .into_iter()
+            .map(|(name, (start, end))| (name, buffer.window(start, end)))
+            .collect();
+        state_clone.set_runtime_logs(&test_id_clone, windows).await;
+    }
+
+    let screenshots = screenshots.lock().unwrap().take();
+    if let Some(screenshots) = screenshots {
+        let taken = screenshots.finish().await;
+        state_clone
+            .set_failure_screenshots(&test_id_clone, taken)
+            .await;
+    }
+
+    if let Some(result_bundle_path) = result_bundle_path {
+        match xcresult::test_summary(&result_bundle_path).await {
+            Ok(summary) => state_clone.set_test_summary(&test_id_clone, summary).await,
+            Err(e) => tracing::warn!("Failed to read test result bundle: {}", e),
+        }
+    }
+
+    if let Some(target) = hooks.erase {
+        let erase = erase_simulator(&state_clone, target).await;
+        state_clone.set_simulator_erase(&test_id_clone, erase).await;
+    }
+
+    let stopped_at = first_failure
+        .lock()
+        .unwrap()
+        .take()
+        .and_then(|first| first.stopped_at);
+    if stopped_at.is_some() {
+        state_clone.mark_truncated(&test_id_clone).await;
+    }
 
     match result {
+        Ok(output) if stopped_at.is_some() => {
+            let error = format!(
+                "Stopped at the first failing test, {}; later tests didn't run",
+                stopped_at.unwrap_or_default()
+            );
+            state_clone
+                .fail_build(&test_id_clone, error, Some(output.exit_code))
+                .await;
+        }
         Ok(output) => {
             if output.success {
                 state_clone.complete_build(&test_id_clone, vec![]).await;
@@ -118,17 +525,44 @@ async fn run_test(state: SharedState, test_id: String, params: TestParams) {
     }
 }
 
+/// Erase a test run's simulator, if this service may
+async fn erase_simulator(state: &SharedState, target: EraseTarget) -> SimulatorErase {
+    let result = if target.allowed {
+        let _lock = state.lock_simulator(&target.udid).await;
+        simctl::erase(&target.udid).await.map_err(|e| e.to_string())
+    } else {
+        Err(
+            "Simulator was booted outside xcbridge when the run started, so it was kept"
+                .to_string(),
+        )
+    };
+
+    if let Err(e) = &result {
+        tracing::warn!("Simulator {} not erased: {}", target.udid, e);
+    }
+    SimulatorErase {
+        udid: target.udid,
+        erased: result.is_ok(),
+        error: result.err(),
+    }
+}
+
 /// GET /test/:id - Get test status
 pub async fn get_test(
     State(state): State<SharedState>,
+    namespace: Namespace,
     Path(test_id): Path<String>,
+    Query(query): Query<StatusQuery>,
 ) -> Result<Json<TestResultResponse>> {
-    let test = state
-        .get_build(&test_id)
+    let filter = super::log_filter(query.grep.as_deref())?;
+    let record = state
+        .get_owned_build(&test_id, &namespace)
         .await
         .ok_or_else(|| XcbridgeError::BuildNotFound(test_id.clone()))?;
+    let queue = state.queue_status(&test_id).await;
 
-    let (status, logs) = match &test {
+    let (status, logs) = match &record.status {
+        BuildStatus::Running { logs } if queue.is_some() => ("queued", logs.clone()),
         BuildStatus::Running { logs } => ("running", logs.clone()),
         BuildStatus::Success { logs, .. } => ("success", logs.clone()),
         BuildStatus::Failed { logs, .. } => ("failed", logs.clone()),
@@ -136,20 +570,483 @@ pub async fn get_test(
     };
 
     // Parse test results from logs (basic parsing)
-    let (passed, failed, skipped) = parse_test_counts(&logs);
+    let (mut passed, mut failed, mut skipped) = parse_test_counts(&logs);
+    let mut failures: Vec<TestFailure> = parse_test_cases(&logs)
+        .into_iter()
+        .flat_map(|case| case.failures)
+        .collect();
+    let mut duration = None;
+    let mut tests = None;
+
+    // Prefer the result bundle's counts when there is one
+    if let Some(summary) = record.test_summary {
+        tests = Some(summary.tests).filter(|tests| !tests.is_empty());
+        (passed, failed, skipped) = (summary.passed, summary.failed, summary.skipped);
+        duration = summary.duration_secs;
+        if failures.is_empty() {
+            failures = summary
+                .failures
+                .into_iter()
+                .map(|failure| TestFailure {
+                    test_name: failure.test_name,
+                    message: failure.message,
+                    file: None,
+                    line: None,
+                    runtime_logs: None,
+                    screenshot_path: None,
+                    screenshot_url: None,
+                })
+                .collect();
+        }
+    }
+
+    for failure in &mut failures {
+        failure.runtime_logs = record.runtime_logs.get(&failure.test_name).cloned();
+        if let Some(path) = record.failure_screenshots.get(&failure.test_name) {
+            let file = path.file_name().unwrap_or_default().to_string_lossy();
+            failure.screenshot_url = Some(format!("/test/{}/screenshots/{}", test_id, file));
+            failure.screenshot_path = Some(path.to_string_lossy().to_string());
+        }
+    }
 
     Ok(Json(TestResultResponse {
         test_id,
+        name: record.name,
         status: status.to_string(),
         passed: Some(passed),
         failed: Some(failed),
         skipped: Some(skipped),
-        duration: None, // TODO: Parse from logs
-        failures: vec![], // TODO: Parse failures from logs
-        logs,
+        duration,
+        cpu_time: record.cpu_time,
+        failures,
+        failure_reason: record.failure_reason,
+        code_signing: record.code_signing,
+        script_phase: record.script_phase,
+        result_bundle_path: record
+            .result_bundle_path
+            .map(|path| path.to_string_lossy().to_string()),
+        tests,
+        simulator_erase: record.simulator_erase,
+        truncated: record.truncated,
+        queue_position: queue.as_ref().map(|queue| queue.position),
+        eta_secs: queue.and_then(|queue| queue.eta_secs),
+        // Counts and failures above come from the full log
+        logs: query
+            .include_logs
+            .then(|| super::filter_logs(logs, filter.as_ref())),
+    }))
+}
+
+/// GET /test/:id/junit - Export test results as JUnit XML
+pub async fn test_junit(
+    State(state): State<SharedState>,
+    namespace: Namespace,
+    Path(test_id): Path<String>,
+) -> Result<impl IntoResponse> {
+    let record = state
+        .get_owned_build(&test_id, &namespace)
+        .await
+        .ok_or_else(|| XcbridgeError::BuildNotFound(test_id.clone()))?;
+
+    let cases = junit_cases(record.test_summary.as_ref(), record.status.logs());
+    let xml = junit::to_junit_xml(&test_id, &cases);
+
+    Ok(([(header::CONTENT_TYPE, "application/xml")], xml))
+}
+
+/// GET /test/:id/attachments - List the attachments in a test run's result bundle
+pub async fn test_attachments(
+    State(state): State<SharedState>,
+    namespace: Namespace,
+    Path(test_id): Path<String>,
+) -> Result<Json<TestAttachmentsResponse>> {
+    let (dir, attachments) = exported_attachments(&state, &namespace, &test_id).await?;
+
+    let attachments = attachments
+        .into_iter()
+        .map(|attachment| TestAttachmentInfo {
+            content_type: attachment_content_type(&attachment.file).to_string(),
+            path: dir.join(&attachment.file).to_string_lossy().to_string(),
+            url: format!("/test/{}/attachments/{}", test_id, attachment.file),
+            test_identifier: attachment.test_identifier,
+            name: attachment.name,
+            associated_with_failure: attachment.associated_with_failure,
+            timestamp: attachment.timestamp,
+            device_name: attachment.device_name,
+        })
+        .collect();
+
+    Ok(Json(TestAttachmentsResponse {
+        test_id,
+        attachments,
     }))
 }
 
+/// GET /test/:id/attachments/:file - Download an attachment
+pub async fn test_attachment(
+    State(state): State<SharedState>,
+    namespace: Namespace,
+    Path((test_id, file)): Path<(String, String)>,
+) -> Result<impl IntoResponse> {
+    let (dir, attachments) = exported_attachments(&state, &namespace, &test_id).await?;
+    // Only serve files listed in the manifest, never a path built from `file`
+    let attachment = attachments
+        .into_iter()
+        .find(|attachment| attachment.file == file)
+        .ok_or_else(|| XcbridgeError::BuildNotFound(format!("{}/attachments/{}", test_id, file)))?;
+    let bytes = tokio::fs::read(dir.join(&attachment.file))
+        .await
+        .map_err(|e| XcbridgeError::Internal(format!("Failed to read attachment: {}", e)))?;
+
+    Ok((
+        [(
+            header::CONTENT_TYPE,
+            attachment_content_type(&attachment.file),
+        )],
+        bytes,
+    ))
+}
+
+/// Export a finished run's attachments, or read the manifest of an earlier
+/// export
+async fn exported_attachments(
+    state: &SharedState,
+    namespace: &Namespace,
+    test_id: &str,
+) -> Result<(PathBuf, Vec<TestAttachment>)> {
+    let record = state
+        .get_owned_build(test_id, namespace)
+        .await
+        .ok_or_else(|| XcbridgeError::BuildNotFound(test_id.to_string()))?;
+    if matches!(record.status, BuildStatus::Running { .. }) {
+        return Err(XcbridgeError::InvalidRequest(format!(
+            "Test run {} is still running",
+            test_id
+        )));
+    }
+    let bundle = record.result_bundle_path.ok_or_else(|| {
+        XcbridgeError::InvalidRequest(format!(
+            "Test run {} has no result bundle. Run it with result_bundle to collect attachments",
+            test_id
+        ))
+    })?;
+
+    if let Some(dir) = record.attachments_dir {
+        let attachments = xcresult::read_attachment_manifest(&dir).await?;
+        return Ok((dir, attachments));
+    }
+
+    // Export into a directory of our own and rename it into place, so
+    // concurrent first requests don't write over each other's export
+    let dir = state.config.attachment_dir().join(test_id);
+    let staging = state
+        .config
+        .attachment_dir()
+        .join(format!(".{}-{}", test_id, Uuid::new_v4()));
+    tokio::fs::create_dir_all(&staging).await.map_err(|e| {
+        XcbridgeError::Internal(format!("Failed to create attachment directory: {}", e))
+    })?;
+    let exported = xcresult::export_attachments(&bundle, &staging).await;
+    let moved = exported.is_ok() && tokio::fs::rename(&staging, &dir).await.is_ok();
+    if !moved {
+        let _ = tokio::fs::remove_dir_all(&staging).await;
+    }
+    let attachments = match exported {
+        Ok(attachments) if moved => attachments,
+        // Another request's export is already in place
+        Ok(_) => xcresult::read_attachment_manifest(&dir).await?,
+        Err(e) => return Err(e),
+    };
+    state.set_attachments_dir(test_id, dir.clone()).await;
+    Ok((dir, attachments))
+}
+
+/// MIME type of an exported attachment, from its extension
+fn attachment_content_type(file: &str) -> &'static str {
+    let extension = std::path::Path::new(file)
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_ascii_lowercase());
+    match extension.as_deref() {
+        Some("png") => "image/png",
+        Some("jpg" | "jpeg") => "image/jpeg",
+        Some("heic") => "image/heic",
+        Some("mp4") => "video/mp4",
+        Some("mov") => "video/quicktime",
+        Some("txt" | "log") => "text/plain; charset=utf-8",
+        Some("json") => "application/json",
+        Some("plist" | "xml") => "application/xml",
+        _ => "application/octet-stream",
+    }
+}
+
+/// GET /test/:id/screenshots/:file - Download a screenshot of a failed test
+pub async fn test_screenshot(
+    State(state): State<SharedState>,
+    namespace: Namespace,
+    Path((test_id, file)): Path<(String, String)>,
+) -> Result<impl IntoResponse> {
+    let record = state
+        .get_owned_build(&test_id, &namespace)
+        .await
+        .ok_or_else(|| XcbridgeError::BuildNotFound(test_id.clone()))?;
+    // Only serve files the run recorded, never a path built from `file`
+    let path = record
+        .failure_screenshots
+        .into_values()
+        .find(|path| path.file_name().is_some_and(|name| name == file.as_str()))
+        .ok_or_else(|| XcbridgeError::BuildNotFound(format!("{}/screenshots/{}", test_id, file)))?;
+    let bytes = tokio::fs::read(&path)
+        .await
+        .map_err(|e| XcbridgeError::Internal(format!("Failed to read screenshot: {}", e)))?;
+
+    Ok(([(header::CONTENT_TYPE, "image/png")], bytes))
+}
+
+/// Per-test results for JUnit export
+///
+/// Uses the result bundle's test tree when there is one, as interleaved
+/// output from parallel test workers can't be attributed reliably. The
+/// assertion failures, with their file and line, still come from the log,
+/// falling back to the bundle's failure messages.
+fn junit_cases(summary: Option<&TestSummary>, logs: &[String]) -> Vec<TestCaseResult> {
+    let from_log = parse_test_cases(logs);
+    let Some(summary) = summary.filter(|summary| !summary.tests.is_empty()) else {
+        return from_log;
+    };
+
+    summary
+        .tests
+        .iter()
+        .map(|run| {
+            // "LoginTests/testLockout()", or "Outer/Inner/test()" for nested suites
+            let (suite, method) = run
+                .identifier
+                .rsplit_once('/')
+                .unwrap_or(("", &run.identifier));
+            let suite = suite.replace('/', ".");
+            let test = method.strip_suffix("()").unwrap_or(method);
+            let status = match run.result.as_str() {
+                "Failed" => "failed",
+                "Skipped" => "skipped",
+                _ => "passed",
+            };
+
+            // Log suites carry the module, e.g. "AppTests.LoginTests"
+            let module_suite = format!(".{}", suite);
+            let mut failures: Vec<TestFailure> = from_log
+                .iter()
+                .filter(|case| {
+                    case.test == test
+                        && (case.suite == suite || case.suite.ends_with(&module_suite))
+                })
+                .flat_map(|case| case.failures.iter().cloned())
+                .collect();
+            if failures.is_empty() && status == "failed" {
+                failures = summary
+                    .failures
+                    .iter()
+                    .filter(|failure| failure.test_name == method)
+                    .map(|failure| TestFailure {
+                        test_name: format!("{}.{}", suite, test),
+                        message: failure.message.clone(),
+                        file: None,
+                        line: None,
+                        runtime_logs: None,
+                        screenshot_path: None,
+                        screenshot_url: None,
+                    })
+                    .collect();
+            }
+
+            TestCaseResult {
+                suite,
+                test: test.to_string(),
+                status: status.to_string(),
+                duration: run.duration_secs,
+                failures,
+            }
+        })
+        .collect()
+}
+
+/// Assemble per-test results from the log's test case and failure lines
+fn parse_test_cases(logs: &[String]) -> Vec<TestCaseResult> {
+    let mut cases = Vec::new();
+    // Failure lines are printed before the test case's "failed" line
+    let mut pending_failures: HashMap<(String, String), Vec<TestFailure>> = HashMap::new();
+
+    for line in logs {
+        if let Some((suite, test, failure)) = parse_test_failure_line(line) {
+            pending_failures
+                .entry((suite, test))
+                .or_default()
+                .push(failure);
+            continue;
+        }
+
+        let Some(event) = parse_test_case_line(line) else {
+            continue;
+        };
+        if event.status == "started" {
+            continue;
+        }
+
+        let failures = pending_failures
+            .remove(&(event.suite.clone(), event.test.clone()))
+            .unwrap_or_default();
+        cases.push(TestCaseResult {
+            suite: event.suite,
+            test: event.test,
+            status: event.status,
+            duration: event.duration,
+            failures,
+        });
+    }
+
+    cases
+}
+
+/// Start and finish times of failed tests, keyed by `Suite.test`
+#[derive(Default)]
+struct FailedTestTimes {
+    started: HashMap<String, DateTime<Utc>>,
+    failed: HashMap<String, (DateTime<Utc>, DateTime<Utc>)>,
+}
+
+impl FailedTestTimes {
+    fn record(&mut self, event: &TestCaseEvent) {
+        let name = format!("{}.{}", event.suite, event.test);
+        let now = Utc::now();
+        match event.status.as_str() {
+            "started" => {
+                self.started.insert(name, now);
+            }
+            "failed" => {
+                let start = self.started.remove(&name).unwrap_or(now);
+                self.failed.insert(name, (start, now));
+            }
+            _ => {
+                self.started.remove(&name);
+            }
+        }
+    }
+}
+
+/// Screenshots of the test simulator, taken as tests fail
+struct FailureScreenshots {
+    udid: String,
+    dir: PathBuf,
+    /// Test name, screenshot path and the task taking it
+    taken: Vec<(String, PathBuf, tokio::task::JoinHandle<Result<()>>)>,
+}
+
+impl FailureScreenshots {
+    fn new(udid: String, dir: PathBuf) -> Self {
+        Self {
+            udid,
+            dir,
+            taken: Vec::new(),
+        }
+    }
+
+    /// Screenshot the simulator if a line shows a test's first failure
+    ///
+    /// XCTest prints assertion failures while the test is still on screen;
+    /// the test case's "failed" line covers failures printed without one.
+    fn observe(&mut self, line: &str) {
+        let name = match parse_test_failure_line(line) {
+            Some((_, _, failure)) => failure.test_name,
+            None => match parse_test_case_line(line) {
+                Some(event) if event.status == "failed" => {
+                    format!("{}.{}", event.suite, event.test)
+                }
+                _ => return,
+            },
+        };
+        if self.taken.iter().any(|(taken, _, _)| *taken == name) {
+            return;
+        }
+
+        let path = self.dir.join(format!("{}.png", self.taken.len() + 1));
+        let udid = self.udid.clone();
+        let output = path.to_string_lossy().to_string();
+        let task = runner::spawn(async move { simctl::screenshot(&udid, &output).await });
+        self.taken.push((name, path, task));
+    }
+
+    /// Wait for the screenshots, returning those taken by test name
+    async fn finish(self) -> HashMap<String, PathBuf> {
+        let mut screenshots = HashMap::new();
+        for (name, path, task) in self.taken {
+            match task.await {
+                Ok(Ok(())) => {
+                    screenshots.insert(name, path);
+                }
+                Ok(Err(e)) => tracing::warn!("Failed to screenshot failed test {}: {}", name, e),
+                Err(e) => tracing::warn!("Failed to screenshot failed test {}: {}", name, e),
+            }
+        }
+        screenshots
+    }
+}
+
+/// Stops a `fail_fast` run once a test fails
+struct FirstFailure {
+    cancel: CancelSignal,
+    /// The first failing test, as `Suite.test`
+    stopped_at: Option<String>,
+}
+
+impl FirstFailure {
+    fn new(cancel: CancelSignal) -> Self {
+        Self {
+            cancel,
+            stopped_at: None,
+        }
+    }
+
+    /// Stop the run if `event` is its first failure, returning the test name
+    ///
+    /// Runs already cancelled by a client are left alone.
+    fn observe(&mut self, event: &TestCaseEvent) -> Option<String> {
+        if event.status != "failed" || self.stopped_at.is_some() || self.cancel.is_cancelled() {
+            return None;
+        }
+        let name = format!("{}.{}", event.suite, event.test);
+        self.stopped_at = Some(name.clone());
+        self.cancel.cancel();
+        Some(name)
+    }
+}
+
+/// Parse an XCTest assertion failure line
+///
+/// `/path/LoginTests.swift:42: error: -[AppTests.LoginTests testLogin] : XCTAssertTrue failed`
+fn parse_test_failure_line(line: &str) -> Option<(String, String, TestFailure)> {
+    let (location, rest) = line.trim().split_once(": error: -[")?;
+    let (name, message) = rest.split_once("] : ")?;
+    let (suite, test) = name.split_once(' ')?;
+
+    let (file, line_number) = match location.rsplit_once(':') {
+        Some((file, line_number)) => (file.to_string(), line_number.parse().ok()),
+        None => (location.to_string(), None),
+    };
+
+    Some((
+        suite.to_string(),
+        test.to_string(),
+        TestFailure {
+            test_name: format!("{}.{}", suite, test),
+            message: message.trim().to_string(),
+            file: Some(file),
+            line: line_number,
+            runtime_logs: None,
+            screenshot_path: None,
+            screenshot_url: None,
+        },
+    ))
+}
+
 /// Parse test counts from xcodebuild output
 fn parse_test_counts(logs: &[String]) -> (u32, u32, u32) {
     let passed = 0u32;
@@ -187,47 +1084,314 @@ fn parse_test_summary(line: &str) -> Option<(u32, u32, u32)> {
     Some((passed, failed, 0))
 }
 
+/// Parse a per-test progress line from xcodebuild output
+///
+/// Handles lines such as:
+/// `Test Case '-[MyAppTests.LoginTests testValidLogin]' started.`
+/// `Test Case '-[MyAppTests.LoginTests testValidLogin]' passed (0.012 seconds).`
+///
+/// and the per-clone lines printed when parallel testing is enabled:
+/// `Test case 'LoginTests.testValidLogin()' passed on 'Clone 1 of iPhone 15 - MyApp (12345)' (0.004 seconds)`
+pub(crate) fn parse_test_case_line(line: &str) -> Option<TestCaseEvent> {
+    let line = line.trim();
+    let (suite, test, rest) = if let Some(rest) = line.strip_prefix("Test Case '-[") {
+        let (name, rest) = rest.split_once("]' ")?;
+        let (suite, test) = name.split_once(' ')?;
+        (suite, test, rest)
+    } else {
+        let rest = line.strip_prefix("Test case '")?;
+        let (name, rest) = rest.split_once("' ")?;
+        let (suite, test) = name.trim_end_matches("()").rsplit_once('.')?;
+        (suite, test, rest)
+    };
+
+    let status = if rest.starts_with("started") {
+        "started"
+    } else if rest.starts_with("passed") {
+        "passed"
+    } else if rest.starts_with("failed") {
+        "failed"
+    } else if rest.starts_with("skipped") {
+        "skipped"
+    } else {
+        return None;
+    };
+
+    // "(0.012 seconds)." - the last parenthesis, as clone names contain one
+    let duration = rest
+        .rsplit_once('(')
+        .and_then(|(_, d)| d.split_whitespace().next())
+        .and_then(|d| d.parse().ok());
+
+    Some(TestCaseEvent {
+        suite: suite.to_string(),
+        test: test.to_string(),
+        status: status.to_string(),
+        duration,
+    })
+}
+
 /// GET /test/:id/logs - Stream test logs via SSE
+///
+/// In addition to raw log lines, emits a `test` event with a JSON
+/// [`TestCaseEvent`] whenever a test case starts or finishes.
 pub async fn test_logs(
     State(state): State<SharedState>,
+    namespace: Namespace,
     Path(test_id): Path<String>,
+    Query(query): Query<LogQuery>,
 ) -> Result<Sse<impl Stream<Item = std::result::Result<Event, Infallible>>>> {
+    let filter = super::log_filter(query.grep.as_deref())?;
+
     // Verify test exists
-    if state.get_build(&test_id).await.is_none() {
+    if state.get_owned_build(&test_id, &namespace).await.is_none() {
         return Err(XcbridgeError::BuildNotFound(test_id));
     }
 
+    let updates = log_stream::follow(state, test_id);
     let stream = async_stream::stream! {
-        let mut last_index = 0;
-        
-        loop {
-            if let Some(test) = state.get_build(&test_id).await {
-                let logs = test.logs();
-                
-                // Send new log lines
-                for line in logs.iter().skip(last_index) {
-                    yield Ok(Event::default().data(line.clone()));
+        for await update in updates {
+            match update {
+                LogUpdate::Line(line) => {
+                    // `test` events are sent whether or not the line matches
+                    let progress = parse_test_case_line(&line);
+                    if filter.as_ref().is_none_or(|filter| filter.is_match(&line)) {
+                        yield Ok(Event::default().data(line));
+                    }
+
+                    if let Some(progress) = progress {
+                        if let Ok(event) = Event::default().event("test").json_data(&progress) {
+                            yield Ok(event);
+                        }
+                    }
                 }
-                last_index = logs.len();
-
-                // Check if test is complete
-                if test.is_complete() {
-                    let status = match &test {
-                        BuildStatus::Success { .. } => "success",
-                        BuildStatus::Failed { .. } => "failed",
-                        BuildStatus::Cancelled => "cancelled",
-                        _ => "unknown",
-                    };
-                    yield Ok(Event::default().event("complete").data(status));
-                    break;
+                LogUpdate::Complete(test) => {
+                    yield Ok(Event::default().event("complete").data(test.name()));
+                }
+                LogUpdate::Queued(queue) => {
+                    if let Ok(event) = Event::default().event("queued").json_data(&queue) {
+                        yield Ok(event);
+                    }
                 }
-            } else {
-                break;
             }
-
-            tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
         }
     };
 
     Ok(Sse::new(stream))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::xcode::runner::MockRunner;
+
+    #[test]
+    fn test_parse_test_case_started() {
+        let event =
+            parse_test_case_line("Test Case '-[MyAppTests.LoginTests testValidLogin]' started.")
+                .unwrap();
+        assert_eq!(event.suite, "MyAppTests.LoginTests");
+        assert_eq!(event.test, "testValidLogin");
+        assert_eq!(event.status, "started");
+        assert_eq!(event.duration, None);
+    }
+
+    #[test]
+    fn test_first_failure_cancels_once() {
+        let cancel = CancelSignal::default();
+        let mut first = FirstFailure::new(cancel.clone());
+        let line = |status: &str| {
+            parse_test_case_line(&format!(
+                "Test Case '-[MyAppTests.LoginTests testLogin]' {} (0.012 seconds).",
+                status
+            ))
+            .unwrap()
+        };
+
+        assert_eq!(first.observe(&line("passed")), None);
+        assert!(!cancel.is_cancelled());
+        assert_eq!(
+            first.observe(&line("failed")).as_deref(),
+            Some("MyAppTests.LoginTests.testLogin")
+        );
+        assert!(cancel.is_cancelled());
+        assert_eq!(first.observe(&line("failed")), None);
+
+        // A run the client cancelled isn't reported as stopped by fail_fast
+        let cancel = CancelSignal::default();
+        cancel.cancel();
+        let mut first = FirstFailure::new(cancel);
+        assert_eq!(first.observe(&line("failed")), None);
+        assert_eq!(first.stopped_at, None);
+    }
+
+    #[test]
+    fn test_attachment_content_type() {
+        assert_eq!(attachment_content_type("0C1A.png"), "image/png");
+        assert_eq!(attachment_content_type("0C1A.JPEG"), "image/jpeg");
+        assert_eq!(
+            attachment_content_type("notes.txt"),
+            "text/plain; charset=utf-8"
+        );
+        assert_eq!(attachment_content_type("trace"), "application/octet-stream");
+    }
+
+    #[tokio::test]
+    async fn test_failure_screenshots_once_per_test() {
+        let dir = std::env::temp_dir().join(format!("xcbridge-{}", Uuid::new_v4()));
+        let mock = Arc::new(MockRunner::new().on(&["xcrun", "simctl", "io"], ""));
+        let mut screenshots = FailureScreenshots::new("SIM-UDID".into(), dir.clone());
+
+        let observed = runner::with_runner(mock.clone(), async move {
+            screenshots.observe("Test Case '-[AppTests.LoginTests testLogin]' started.");
+            screenshots.observe(
+                "/src/LoginTests.swift:42: error: -[AppTests.LoginTests testLogin] : XCTAssertTrue failed",
+            );
+            screenshots.observe("/src/LoginTests.swift:43: error: -[AppTests.LoginTests testLogin] : XCTAssertEqual failed");
+            screenshots.observe("Test Case '-[AppTests.LoginTests testLogin]' failed (0.012 seconds).");
+            screenshots
+                .observe("Test Case '-[AppTests.LoginTests testLogout]' passed (0.004 seconds).");
+            screenshots.observe("Test Case '-[AppTests.LoginTests testCrash]' failed (0.020 seconds).");
+            screenshots
+        })
+        .await;
+
+        let taken: Vec<_> = observed
+            .taken
+            .iter()
+            .map(|(name, path, _)| (name.as_str(), path.clone()))
+            .collect();
+        assert_eq!(
+            taken,
+            vec![
+                ("AppTests.LoginTests.testLogin", dir.join("1.png")),
+                ("AppTests.LoginTests.testCrash", dir.join("2.png")),
+            ]
+        );
+        let finished = observed.finish().await;
+        assert_eq!(finished.len(), 2);
+        assert_eq!(finished["AppTests.LoginTests.testCrash"], dir.join("2.png"));
+
+        // The screenshots ran under the mock, not the real simctl
+        let mut calls = mock.calls();
+        calls.sort();
+        let expected: Vec<Vec<String>> = ["1.png", "2.png"]
+            .iter()
+            .map(|file| {
+                ["xcrun", "simctl", "io", "SIM-UDID", "screenshot"]
+                    .iter()
+                    .map(|arg| arg.to_string())
+                    .chain([dir.join(file).to_string_lossy().to_string()])
+                    .collect()
+            })
+            .collect();
+        assert_eq!(calls, expected);
+    }
+
+    #[test]
+    fn test_parse_test_case_finished() {
+        let event = parse_test_case_line(
+            "Test Case '-[MyAppTests.LoginTests testValidLogin]' failed (0.012 seconds).",
+        )
+        .unwrap();
+        assert_eq!(event.status, "failed");
+        assert_eq!(event.duration, Some(0.012));
+    }
+
+    #[test]
+    fn test_parse_parallel_test_case() {
+        let event = parse_test_case_line(
+            "Test case 'LoginTests.testValidLogin()' passed on 'Clone 1 of iPhone 15 - MyApp (12345)' (0.004 seconds)",
+        )
+        .unwrap();
+        assert_eq!(event.suite, "LoginTests");
+        assert_eq!(event.test, "testValidLogin");
+        assert_eq!(event.status, "passed");
+        assert_eq!(event.duration, Some(0.004));
+    }
+
+    #[test]
+    fn test_parse_test_cases_attaches_failures() {
+        let logs: Vec<String> = [
+            "Test Case '-[AppTests.LoginTests testInvalidLogin]' started.",
+            "/src/LoginTests.swift:42: error: -[AppTests.LoginTests testInvalidLogin] : XCTAssertTrue failed",
+            "Test Case '-[AppTests.LoginTests testInvalidLogin]' failed (0.500 seconds).",
+            "Test Case '-[AppTests.LoginTests testValidLogin]' started.",
+            "Test Case '-[AppTests.LoginTests testValidLogin]' passed (0.012 seconds).",
+        ]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+
+        let cases = parse_test_cases(&logs);
+
+        assert_eq!(cases.len(), 2);
+        assert_eq!(cases[0].status, "failed");
+        assert_eq!(cases[0].failures.len(), 1);
+        assert_eq!(cases[0].failures[0].message, "XCTAssertTrue failed");
+        assert_eq!(
+            cases[0].failures[0].file.as_deref(),
+            Some("/src/LoginTests.swift")
+        );
+        assert_eq!(cases[0].failures[0].line, Some(42));
+        assert_eq!(cases[1].status, "passed");
+        assert!(cases[1].failures.is_empty());
+    }
+
+    #[test]
+    fn test_junit_cases_prefer_result_bundle() {
+        use crate::xcode::xcresult::{TestCaseRun, TestSummaryFailure};
+
+        // Parallel workers interleave their lines, losing testSignup's result
+        let logs: Vec<String> = [
+            "Test Case '-[AppTests.LoginTests testInvalidLogin]' started.",
+            "Test Case '-[AppTests.SignupTests testSignup]' started.",
+            "/src/LoginTests.swift:42: error: -[AppTests.LoginTests testInvalidLogin] : XCTAssertTrue failed",
+            "Test Case '-[AppTests.LoginTests testInvalidLogin]' failed (0.500 seconds).",
+        ]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+        let run = |identifier: &str, result: &str| TestCaseRun {
+            identifier: identifier.to_string(),
+            result: result.to_string(),
+            duration_secs: Some(0.5),
+        };
+        let summary = TestSummary {
+            total: 3,
+            passed: 1,
+            failed: 2,
+            skipped: 0,
+            expected_failures: 0,
+            duration_secs: Some(1.5),
+            failures: vec![TestSummaryFailure {
+                test_name: "testSignup()".to_string(),
+                target_name: Some("AppTests".to_string()),
+                message: "Signup failed".to_string(),
+            }],
+            tests: vec![
+                run("LoginTests/testInvalidLogin()", "Failed"),
+                run("SignupTests/testSignup()", "Failed"),
+                run("SignupTests/testTerms()", "Expected Failure"),
+            ],
+        };
+
+        let cases = junit_cases(Some(&summary), &logs);
+        assert_eq!(cases.len(), 3);
+        assert_eq!(cases[0].suite, "LoginTests");
+        assert_eq!(cases[0].test, "testInvalidLogin");
+        assert_eq!(cases[0].failures[0].line, Some(42));
+        assert_eq!(cases[1].status, "failed");
+        assert_eq!(cases[1].failures[0].message, "Signup failed");
+        assert_eq!(cases[2].status, "passed");
+
+        // Without a result bundle the log is all there is
+        assert_eq!(junit_cases(None, &logs).len(), 1);
+    }
+
+    #[test]
+    fn test_parse_test_case_ignores_other_lines() {
+        assert!(parse_test_case_line("Test Suite 'All tests' started at 2024-01-01").is_none());
+        assert!(parse_test_case_line("** TEST SUCCEEDED **").is_none());
+    }
+}
@@ -0,0 +1,152 @@
+// Copyright 2026 Aptove
+// SPDX-License-Identifier: Apache-2.0
+
+//! JUnit XML export of test results
+
+use crate::models::TestCaseResult;
+use std::fmt::Write;
+
+/// Escape text for use in XML attributes and content
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Render test cases as a JUnit XML document, grouped into one
+/// `<testsuite>` per test class
+pub fn to_junit_xml(name: &str, cases: &[TestCaseResult]) -> String {
+    // Group by suite, keeping the order suites first appear in
+    let mut suites: Vec<(&str, Vec<&TestCaseResult>)> = Vec::new();
+    for case in cases {
+        match suites.iter_mut().find(|(suite, _)| *suite == case.suite) {
+            Some((_, suite_cases)) => suite_cases.push(case),
+            None => suites.push((&case.suite, vec![case])),
+        }
+    }
+
+    let count = |cases: &[&TestCaseResult], status: &str| {
+        cases.iter().filter(|c| c.status == status).count()
+    };
+    let total_time =
+        |cases: &[&TestCaseResult]| -> f64 { cases.iter().filter_map(|c| c.duration).sum() };
+
+    let all: Vec<&TestCaseResult> = cases.iter().collect();
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    let _ = writeln!(
+        xml,
+        "<testsuites name=\"{}\" tests=\"{}\" failures=\"{}\" skipped=\"{}\" time=\"{:.3}\">",
+        escape(name),
+        all.len(),
+        count(&all, "failed"),
+        count(&all, "skipped"),
+        total_time(&all)
+    );
+
+    for (suite, suite_cases) in &suites {
+        let _ = writeln!(
+            xml,
+            "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" skipped=\"{}\" time=\"{:.3}\">",
+            escape(suite),
+            suite_cases.len(),
+            count(suite_cases, "failed"),
+            count(suite_cases, "skipped"),
+            total_time(suite_cases)
+        );
+
+        for case in suite_cases {
+            let _ = write!(
+                xml,
+                "    <testcase classname=\"{}\" name=\"{}\" time=\"{:.3}\"",
+                escape(&case.suite),
+                escape(&case.test),
+                case.duration.unwrap_or_default()
+            );
+
+            match case.status.as_str() {
+                "failed" => {
+                    xml.push_str(">\n");
+                    if case.failures.is_empty() {
+                        xml.push_str("      <failure message=\"Test failed\"/>\n");
+                    }
+                    for failure in &case.failures {
+                        let location = match (&failure.file, failure.line) {
+                            (Some(file), Some(line)) => format!("{}:{}", file, line),
+                            (Some(file), None) => file.clone(),
+                            _ => String::new(),
+                        };
+                        let _ = writeln!(
+                            xml,
+                            "      <failure message=\"{}\">{}</failure>",
+                            escape(&failure.message),
+                            escape(&location)
+                        );
+                    }
+                    xml.push_str("    </testcase>\n");
+                }
+                "skipped" => xml.push_str(">\n      <skipped/>\n    </testcase>\n"),
+                _ => xml.push_str("/>\n"),
+            }
+        }
+
+        xml.push_str("  </testsuite>\n");
+    }
+
+    xml.push_str("</testsuites>\n");
+    xml
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::TestFailure;
+
+    #[test]
+    fn test_to_junit_xml() {
+        let cases = vec![
+            TestCaseResult {
+                suite: "AppTests.LoginTests".to_string(),
+                test: "testValidLogin".to_string(),
+                status: "passed".to_string(),
+                duration: Some(0.012),
+                failures: vec![],
+            },
+            TestCaseResult {
+                suite: "AppTests.LoginTests".to_string(),
+                test: "testInvalidLogin".to_string(),
+                status: "failed".to_string(),
+                duration: Some(0.5),
+                failures: vec![TestFailure {
+                    test_name: "AppTests.LoginTests.testInvalidLogin".to_string(),
+                    message: "XCTAssertEqual failed: (\"1\") is not equal to (\"2\")".to_string(),
+                    file: Some("/src/LoginTests.swift".to_string()),
+                    line: Some(42),
+                    runtime_logs: None,
+                    screenshot_path: None,
+                    screenshot_url: None,
+                }],
+            },
+        ];
+
+        let xml = to_junit_xml("MyApp", &cases);
+
+        assert!(xml.contains(
+            "<testsuite name=\"AppTests.LoginTests\" tests=\"2\" failures=\"1\" skipped=\"0\" time=\"0.512\">"
+        ));
+        assert!(xml.contains(
+            "<testcase classname=\"AppTests.LoginTests\" name=\"testValidLogin\" time=\"0.012\"/>"
+        ));
+        assert!(xml.contains(
+            "<failure message=\"XCTAssertEqual failed: (&quot;1&quot;) is not equal to (&quot;2&quot;)\">/src/LoginTests.swift:42</failure>"
+        ));
+    }
+}
@@ -0,0 +1,171 @@
+// Copyright 2026 Aptove
+// SPDX-License-Identifier: Apache-2.0
+
+//! Following a job's log as it is written
+//!
+//! `append_build_log` publishes each line on the job's broadcast channel. A
+//! follower first replays the lines already logged, then receives new ones
+//! as they are published. A follower that falls behind the channel reads
+//! the lines it missed back from the job's log. The job's final status isn't
+//! published, so completion is checked every `--log-poll-interval`, along
+//! with the queue position of a job waiting for a slot.
+
+use crate::state::{BuildStatus, QueueStatus, SharedState};
+use futures::stream::Stream;
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::time::Instant;
+
+/// How often a queued job's position is repeated when it hasn't changed
+const QUEUE_UPDATE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// What a log follower sees
+#[derive(Debug)]
+pub enum LogUpdate {
+    Line(String),
+    /// The job is waiting for a slot; sent when its position changes and
+    /// every few seconds while it waits
+    Queued(QueueStatus),
+    /// The job finished; always the last update
+    Complete(BuildStatus),
+}
+
+enum Wake {
+    Line(usize, String),
+    Lagged,
+    Poll,
+}
+
+/// Stream a job's log from the start, ending once the job finishes (or is
+/// removed)
+pub fn follow(state: SharedState, build_id: String) -> impl Stream<Item = LogUpdate> {
+    async_stream::stream! {
+        let Some((backlog, mut lines)) = state.subscribe_logs(&build_id).await else {
+            return;
+        };
+        let mut next = backlog.len();
+        for line in backlog {
+            yield LogUpdate::Line(line);
+        }
+
+        let mut poll = tokio::time::interval(state.config.log_poll_interval());
+        let mut last_queued: Option<(usize, Instant)> = None;
+        loop {
+            let wake = tokio::select! {
+                received = lines.recv() => match received {
+                    Ok((index, line)) => Wake::Line(index, line),
+                    Err(RecvError::Lagged(_)) => Wake::Lagged,
+                    // The job was removed
+                    Err(RecvError::Closed) => Wake::Poll,
+                },
+                _ = poll.tick() => Wake::Poll,
+            };
+
+            match wake {
+                // Lines read back after a lag can show up again on the channel
+                Wake::Line(index, line) => {
+                    if index >= next {
+                        next = index + 1;
+                        yield LogUpdate::Line(line);
+                    }
+                }
+                Wake::Lagged => {
+                    for line in state.logs_since(&build_id, next).await.unwrap_or_default() {
+                        next += 1;
+                        yield LogUpdate::Line(line);
+                    }
+                }
+                Wake::Poll => match state.is_build_complete(&build_id).await {
+                    Some(false) => {
+                        if let Some(queue) = state.queue_status(&build_id).await {
+                            let due = last_queued.is_none_or(|(position, sent)| {
+                                position != queue.position
+                                    || sent.elapsed() >= QUEUE_UPDATE_INTERVAL
+                            });
+                            if due {
+                                last_queued = Some((queue.position, Instant::now()));
+                                yield LogUpdate::Queued(queue);
+                            }
+                        }
+                    }
+                    Some(true) => {
+                        let Some(status) = state.get_build(&build_id).await else {
+                            break;
+                        };
+                        for line in status.logs().iter().skip(next) {
+                            yield LogUpdate::Line(line.clone());
+                        }
+                        yield LogUpdate::Complete(status);
+                        break;
+                    }
+                    None => break,
+                },
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::state::AppState;
+    use clap::Parser;
+    use futures::StreamExt;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_follow_replays_backlog_then_live_lines() {
+        let state = Arc::new(AppState::new(
+            Config::parse_from(["xcbridge"]),
+            String::new(),
+        ));
+        state.create_build("job").await.unwrap();
+        state.append_build_log("job", "first".into()).await;
+
+        let stream = follow(Arc::clone(&state), "job".to_string());
+        futures::pin_mut!(stream);
+        assert!(matches!(stream.next().await, Some(LogUpdate::Line(l)) if l == "first"));
+
+        state.append_build_log("job", "second".into()).await;
+        assert!(matches!(stream.next().await, Some(LogUpdate::Line(l)) if l == "second"));
+
+        state.append_build_log("job", "third".into()).await;
+        state.complete_build("job", vec![]).await;
+        assert!(matches!(stream.next().await, Some(LogUpdate::Line(l)) if l == "third"));
+        assert!(matches!(
+            stream.next().await,
+            Some(LogUpdate::Complete(BuildStatus::Success { .. }))
+        ));
+        assert!(stream.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_follow_catches_up_after_lagging() {
+        let state = Arc::new(AppState::new(
+            Config::parse_from(["xcbridge"]),
+            String::new(),
+        ));
+        state.create_build("job").await.unwrap();
+
+        let stream = follow(Arc::clone(&state), "job".to_string());
+        futures::pin_mut!(stream);
+        // Subscribe before flooding the channel
+        state.append_build_log("job", "0".into()).await;
+        assert!(matches!(stream.next().await, Some(LogUpdate::Line(l)) if l == "0"));
+
+        for i in 1..3000 {
+            state.append_build_log("job", i.to_string()).await;
+        }
+        state.complete_build("job", vec![]).await;
+
+        let mut received = vec!["0".to_string()];
+        while let Some(update) = stream.next().await {
+            if let LogUpdate::Line(line) = update {
+                received.push(line);
+            }
+        }
+        let expected: Vec<String> = (0..3000).map(|i| i.to_string()).collect();
+        assert_eq!(received, expected);
+    }
+}
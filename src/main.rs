@@ -7,99 +7,449 @@
 //! allowing AI agents running in Linux containers to access iOS build tooling.
 
 use axum::{
+    extract::DefaultBodyLimit,
     http::{header, Method, StatusCode},
     middleware,
+    response::IntoResponse,
     routing::{delete, get, post},
     Router,
 };
-use clap::Parser;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::net::TcpListener;
 use tower_http::{
-    cors::{Any, CorsLayer},
+    cors::{AllowOrigin, Any, CorsLayer},
     trace::TraceLayer,
 };
 use tracing::{info, Level};
 use tracing_subscriber::{fmt, prelude::*, EnvFilter};
 
+mod audit;
+mod cache;
 mod config;
+mod disk;
 mod error;
+mod grpc;
 mod handlers;
+mod junit;
+mod log_stream;
 mod models;
 mod state;
+mod upload;
 mod xcode;
 
 use config::Config;
+use error::XcbridgeError;
 use state::AppState;
 
 /// API key authentication middleware
+///
+/// Resolves the request's namespace from its API key for handlers to scope
+/// builds by.
 async fn auth_middleware(
     axum::extract::State(state): axum::extract::State<Arc<AppState>>,
-    request: axum::extract::Request,
+    mut request: axum::extract::Request,
     next: middleware::Next,
 ) -> Result<axum::response::Response, StatusCode> {
-    // If no API key is configured, skip authentication
-    let Some(expected_key) = &state.config.api_key else {
-        return Ok(next.run(request).await);
-    };
-
     // Check for API key in header
     let auth_header = request
         .headers()
         .get("X-API-Key")
         .and_then(|v| v.to_str().ok());
 
-    match auth_header {
-        Some(key) if key == expected_key => Ok(next.run(request).await),
-        _ => Err(StatusCode::UNAUTHORIZED),
+    match state.config.namespace_for_key(auth_header) {
+        Some(namespace) => {
+            request.extensions_mut().insert(namespace);
+            Ok(next.run(request).await)
+        }
+        None => Err(StatusCode::UNAUTHORIZED),
+    }
+}
+
+/// Restricts service-wide operations to the admin API key
+async fn admin_middleware(
+    axum::extract::State(state): axum::extract::State<Arc<AppState>>,
+    request: axum::extract::Request,
+    next: middleware::Next,
+) -> Result<axum::response::Response, StatusCode> {
+    let key = request
+        .headers()
+        .get("X-API-Key")
+        .and_then(|v| v.to_str().ok());
+
+    if state.config.is_admin_key(key) {
+        Ok(next.run(request).await)
+    } else {
+        Err(StatusCode::FORBIDDEN)
+    }
+}
+
+/// Fails device requests up front on hosts without devicectl, so
+/// simulator-only hosts report why rather than a failed command
+async fn devicectl_middleware(
+    axum::extract::State(state): axum::extract::State<Arc<AppState>>,
+    request: axum::extract::Request,
+    next: middleware::Next,
+) -> Result<axum::response::Response, XcbridgeError> {
+    if state.capabilities().await.devicectl {
+        Ok(next.run(request).await)
+    } else {
+        Err(XcbridgeError::DevicectlUnavailable)
+    }
+}
+
+/// Fails a request with `timeout` once it has run for `limit` (no limit when
+/// zero), so a wedged simctl or devicectl doesn't hold the connection open
+///
+/// The handler is dropped at the deadline, which kills the command it was
+/// waiting on and then releases any simulator lock it held.
+async fn with_timeout(
+    limit: Duration,
+    request: axum::extract::Request,
+    next: middleware::Next,
+) -> Result<axum::response::Response, XcbridgeError> {
+    if limit.is_zero() {
+        return Ok(next.run(request).await);
+    }
+    let path = request
+        .extensions()
+        .get::<axum::extract::OriginalUri>()
+        .map_or_else(|| request.uri().path(), |uri| uri.path())
+        .to_string();
+    tokio::time::timeout(limit, next.run(request))
+        .await
+        .map_err(|_| {
+            XcbridgeError::Timeout(format!(
+                "{} did not finish within {}s",
+                path,
+                limit.as_secs_f64()
+            ))
+        })
+}
+
+/// Bounds synchronous simulator requests by `--simulator-timeout`
+async fn simulator_timeout_middleware(
+    axum::extract::State(state): axum::extract::State<Arc<AppState>>,
+    request: axum::extract::Request,
+    next: middleware::Next,
+) -> Result<axum::response::Response, XcbridgeError> {
+    with_timeout(
+        Duration::from_secs(state.config.simulator_timeout),
+        request,
+        next,
+    )
+    .await
+}
+
+/// Bounds device requests by `--device-timeout`
+async fn device_timeout_middleware(
+    axum::extract::State(state): axum::extract::State<Arc<AppState>>,
+    request: axum::extract::Request,
+    next: middleware::Next,
+) -> Result<axum::response::Response, XcbridgeError> {
+    with_timeout(
+        Duration::from_secs(state.config.device_timeout),
+        request,
+        next,
+    )
+    .await
+}
+
+/// Returns errors as plain text to clients whose `Accept` header prefers it
+async fn error_format_middleware(
+    request: axum::extract::Request,
+    next: middleware::Next,
+) -> axum::response::Response {
+    let wants_text = request
+        .headers()
+        .get(header::ACCEPT)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(error::prefers_text);
+
+    let response = next.run(request).await;
+    if wants_text {
+        error::into_text_response(response)
+    } else {
+        response
+    }
+}
+
+/// Methods used by the API routes
+const CORS_METHODS: [Method; 3] = [Method::GET, Method::POST, Method::DELETE];
+
+/// CORS configuration from `--cors-origins`
+///
+/// Without the option only localhost origins (on any port) are allowed.
+fn cors_layer(config: &Config) -> CorsLayer {
+    let allow_origin = match config.cors_origins.as_deref() {
+        Some([any]) if any.trim() == "*" => AllowOrigin::from(Any),
+        Some(origins) => AllowOrigin::list(origins.iter().filter_map(|origin| {
+            let origin = origin.trim();
+            // A wildcard can't be listed alongside origins
+            if origin == "*" {
+                tracing::warn!("Ignoring `*` in --cors-origins, which also lists origins");
+                return None;
+            }
+            match origin.parse() {
+                Ok(origin) => Some(origin),
+                Err(_) => {
+                    tracing::warn!("Ignoring invalid CORS origin: {}", origin);
+                    None
+                }
+            }
+        })),
+        None => AllowOrigin::predicate(|origin, _| origin.to_str().is_ok_and(is_localhost_origin)),
+    };
+
+    CorsLayer::new()
+        .allow_origin(allow_origin)
+        .allow_methods(CORS_METHODS)
+        .allow_headers([
+            header::CONTENT_TYPE,
+            header::HeaderName::from_static("x-api-key"),
+        ])
+}
+
+/// Whether an origin is `http(s)://localhost` or a loopback address, with any port
+fn is_localhost_origin(origin: &str) -> bool {
+    let Some((_, host)) = origin.split_once("://") else {
+        return false;
+    };
+    let host = match host.strip_prefix('[') {
+        // IPv6, e.g. [::1]:3000
+        Some(rest) => rest.split(']').next().unwrap_or_default(),
+        None => host.split(':').next().unwrap_or_default(),
+    };
+    matches!(host, "localhost" | "127.0.0.1" | "::1")
+}
+
+/// Largest JSON request body read into an audit entry
+const AUDIT_BODY_LIMIT: usize = 1024 * 1024;
+
+/// Records mutating requests in the audit log, if one is configured
+async fn audit_middleware(
+    axum::extract::State(state): axum::extract::State<Arc<AppState>>,
+    request: axum::extract::Request,
+    next: middleware::Next,
+) -> axum::response::Response {
+    let Some(audit_log) = &state.audit_log else {
+        return next.run(request).await;
+    };
+    if !matches!(
+        *request.method(),
+        Method::POST | Method::PUT | Method::PATCH | Method::DELETE
+    ) {
+        return next.run(request).await;
     }
+
+    let namespace = request
+        .extensions()
+        .get::<state::Namespace>()
+        .cloned()
+        .unwrap_or_default();
+    let endpoint = format!("{} {}", request.method(), request.uri().path());
+    let is_json = request
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("application/json"));
+
+    // Buffer JSON bodies so their parameters can be recorded
+    let (parts, body) = request.into_parts();
+    let (params, body) = if is_json {
+        match axum::body::to_bytes(body, AUDIT_BODY_LIMIT).await {
+            Ok(bytes) => (
+                serde_json::from_slice(&bytes).unwrap_or(serde_json::Value::Null),
+                axum::body::Body::from(bytes),
+            ),
+            Err(_) => return StatusCode::PAYLOAD_TOO_LARGE.into_response(),
+        }
+    } else {
+        (serde_json::Value::Null, body)
+    };
+
+    let mut entry = audit::AuditEntry::new(&namespace, endpoint, params);
+    let response = next
+        .run(axum::extract::Request::from_parts(parts, body))
+        .await;
+    entry.status = Some(response.status().as_u16());
+    audit_log.record(&entry).await;
+
+    response
 }
 
 fn create_router(state: Arc<AppState>) -> Router {
+    // Uploaded project archives may be far larger than axum's default limit
+    let upload_limit = DefaultBodyLimit::max(state.config.max_upload_bytes());
+
     // Build routes
     let build_routes = Router::new()
         .route("/", post(handlers::build::start_build))
-        .route("/{id}", get(handlers::build::get_build))
-        .route("/{id}/logs", get(handlers::build::build_logs))
-        .route("/{id}", delete(handlers::build::cancel_build));
+        .route("/batch", post(handlers::build::start_batch_build))
+        .route(
+            "/upload",
+            post(handlers::build::upload_build).layer(upload_limit),
+        )
+        .route("/:id", get(handlers::build::get_build))
+        .route("/:id/logs", get(handlers::build::build_logs))
+        .route("/:id/logs/download", get(handlers::build::download_logs))
+        .route("/:id", delete(handlers::build::cancel_build));
 
     // Test routes
     let test_routes = Router::new()
         .route("/", post(handlers::test::start_test))
-        .route("/{id}", get(handlers::test::get_test))
-        .route("/{id}/logs", get(handlers::test::test_logs));
+        .route(
+            "/upload",
+            post(handlers::test::upload_test).layer(upload_limit),
+        )
+        .route("/:id", get(handlers::test::get_test))
+        .route("/:id/logs", get(handlers::test::test_logs))
+        .route("/:id/junit", get(handlers::test::test_junit))
+        .route("/:id/attachments", get(handlers::test::test_attachments))
+        .route(
+            "/:id/attachments/:file",
+            get(handlers::test::test_attachment),
+        )
+        .route(
+            "/:id/screenshots/:file",
+            get(handlers::test::test_screenshot),
+        );
 
     // Simulator routes
     let simulator_routes = Router::new()
         .route("/list", get(handlers::simulator::list))
+        .route("/booted", get(handlers::simulator::booted))
         .route("/boot", post(handlers::simulator::boot))
+        .route("/lease", post(handlers::simulator::lease))
+        .route("/release", post(handlers::simulator::release))
         .route("/shutdown", post(handlers::simulator::shutdown))
         .route("/install", post(handlers::simulator::install))
         .route("/launch", post(handlers::simulator::launch))
-        .route("/uninstall", post(handlers::simulator::uninstall));
+        .route("/uninstall", post(handlers::simulator::uninstall))
+        .route("/locale", post(handlers::simulator::locale))
+        .route("/signal", post(handlers::simulator::signal))
+        .route("/:udid", get(handlers::simulator::details))
+        .route("/:udid/documents", get(handlers::simulator::documents))
+        .route("/:udid/logs/tail", get(handlers::simulator::log_tail))
+        .route("/:udid/type", post(handlers::simulator::type_text))
+        .route("/:udid/tap", post(handlers::simulator::tap))
+        .route("/:udid/button", post(handlers::simulator::button));
+
+    #[cfg(feature = "screen-text")]
+    let simulator_routes =
+        simulator_routes.route("/:udid/screen-text", get(handlers::simulator::screen_text));
+
+    // Waits with their own limits, streams and jobs aren't bound by
+    // --simulator-timeout
+    let simulator_routes = simulator_routes
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            simulator_timeout_middleware,
+        ))
+        .route("/wait-ready", post(handlers::simulator::wait_ready))
+        .route("/prewarm", post(handlers::simulator::prewarm))
+        .route("/run", post(handlers::run::run))
+        .route(
+            "/reset-service",
+            post(handlers::simulator::reset_service).layer(middleware::from_fn_with_state(
+                state.clone(),
+                admin_middleware,
+            )),
+        )
+        .route(
+            "/runtimes/install",
+            post(handlers::simulator::install_runtime).layer(middleware::from_fn_with_state(
+                state.clone(),
+                admin_middleware,
+            )),
+        );
 
     // Device routes
     let device_routes = Router::new()
         .route("/list", get(handlers::device::list))
         .route("/install", post(handlers::device::install))
+        .route("/exec", post(handlers::device::exec))
         .route("/launch", post(handlers::device::launch))
-        .route("/uninstall", post(handlers::device::uninstall));
+        .route("/uninstall", post(handlers::device::uninstall))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            device_timeout_middleware,
+        ))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            devicectl_middleware,
+        ));
+
+    // Provisioning profile routes
+    let provisioning_routes = Router::new()
+        .route("/list", get(handlers::provisioning::list))
+        .route("/install", post(handlers::provisioning::install));
+
+    // App routes
+    let app_routes = Router::new()
+        .route("/info", get(handlers::app::info))
+        .route("/compatibility", get(handlers::app::compatibility))
+        .route("/validate", post(handlers::app::validate));
+
+    // Code-signing routes
+    let signing_routes = Router::new().route("/status", get(handlers::signing::status));
 
-    // CORS configuration
-    let cors = CorsLayer::new()
-        .allow_origin(Any)
-        .allow_methods([Method::GET, Method::POST, Method::DELETE])
-        .allow_headers([header::CONTENT_TYPE, header::HeaderName::from_static("x-api-key")]);
+    // Localization routes
+    let localization_routes = Router::new()
+        .route("/export", post(handlers::localization::export))
+        .route("/import", post(handlers::localization::import));
+
+    // Project routes
+    let project_routes = Router::new()
+        .route("/destinations", get(handlers::project::destinations))
+        .route("/test-plans", get(handlers::project::test_plans))
+        .route("/test-plans/:name", get(handlers::project::test_plan));
 
     // Combine all routes
     Router::new()
         .route("/status", get(handlers::status::status))
+        .route("/capabilities", get(handlers::status::capabilities))
+        .route("/system", get(handlers::status::system))
+        .route("/metrics", get(handlers::status::metrics))
+        .route(
+            "/debug/last-commands",
+            get(handlers::status::last_commands).layer(middleware::from_fn_with_state(
+                state.clone(),
+                admin_middleware,
+            )),
+        )
+        .route("/refresh", post(handlers::status::refresh))
+        .route("/builds/recent", get(handlers::build::recent_builds))
+        .route("/builds/cleanup", post(handlers::build::cleanup_builds))
+        .route("/builds/cancel", post(handlers::build::cancel_builds))
+        .route(
+            "/builds/cancel-all",
+            post(handlers::build::cancel_all_builds).layer(middleware::from_fn_with_state(
+                state.clone(),
+                admin_middleware,
+            )),
+        )
         .nest("/build", build_routes)
         .nest("/test", test_routes)
         .nest("/simulator", simulator_routes)
         .nest("/device", device_routes)
-        .layer(middleware::from_fn_with_state(state.clone(), auth_middleware))
-        .layer(cors)
+        .nest("/provisioning", provisioning_routes)
+        .nest("/signing", signing_routes)
+        .nest("/project", project_routes)
+        .nest("/localizations", localization_routes)
+        .nest("/app", app_routes)
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            audit_middleware,
+        ))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            auth_middleware,
+        ))
+        // Routes added after the auth layer don't need an API key
+        .route("/version", get(handlers::status::version))
+        .layer(middleware::from_fn(error_format_middleware))
+        .layer(cors_layer(&state.config))
         .layer(TraceLayer::new_for_http())
         .with_state(state)
 }
@@ -107,7 +457,7 @@ fn create_router(state: Arc<AppState>) -> Router {
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     // Parse configuration
-    let config = Config::parse();
+    let config = Config::parse_args();
 
     // Initialize logging
     let log_level = match config.log_level.to_lowercase().as_str() {
@@ -127,8 +477,25 @@ async fn main() -> anyhow::Result<()> {
         )
         .init();
 
+    // Refuse to serve builds to the network without authentication
+    if config.exposed_without_auth() {
+        if !config.insecure {
+            tracing::error!(
+                "Refusing to bind to {} without an API key, which would let anyone on the network run builds",
+                config.host
+            );
+            tracing::error!("Set --api-key, bind to 127.0.0.1, or pass --insecure to allow it");
+            std::process::exit(1);
+        }
+        tracing::warn!(
+            "Binding to {} without an API key (--insecure); anyone who can reach it can run builds",
+            config.host
+        );
+    }
+
     // Verify Xcode is available and get version
-    let xcode_version = match xcode::xcodebuild::get_xcode_version().await {
+    let xcode_version = match xcode::xcodebuild::discover_xcode(config.xcode_path.as_deref()).await
+    {
         Ok(version) => {
             info!("Xcode version: {}", version);
             version
@@ -136,31 +503,125 @@ async fn main() -> anyhow::Result<()> {
         Err(e) => {
             tracing::error!("Xcode not found or not working: {}", e);
             tracing::error!("xcbridge requires Xcode to be installed and configured");
+            tracing::error!(
+                "Set --xcode-path or DEVELOPER_DIR if Xcode is in a nonstandard location"
+            );
             std::process::exit(1);
         }
     };
 
+    xcode::simctl::set_max_retries(config.simctl_retries);
+    xcode::xcodebuild::set_build_nice(config.build_nice);
+    xcode::devicectl::set_command_timeout(config.device_timeout);
+
     // Create application state
-    let state = Arc::new(AppState::new(config.clone(), xcode_version));
+    let mut app_state = AppState::new(config.clone(), xcode_version);
+    if let Some(path) = &config.audit_log {
+        app_state.audit_log =
+            Some(audit::AuditLog::open(path).await.map_err(|e| {
+                anyhow::anyhow!("Failed to open audit log {}: {}", path.display(), e)
+            })?);
+        info!("Auditing mutating requests to {}", path.display());
+    }
+    let state = Arc::new(app_state);
+
+    // Cache the SDK and runtime lists, then keep them current
+    let host_state = Arc::clone(&state);
+    tokio::spawn(async move {
+        let period = host_state.config.host_refresh_interval;
+        if period == 0 {
+            host_state.refresh_host_info().await;
+            return;
+        }
+        let mut interval = tokio::time::interval(Duration::from_secs(period));
+        loop {
+            interval.tick().await;
+            host_state.refresh_host_info().await;
+        }
+    });
+
+    // Watch for a wedged CoreSimulator service
+    if config.simulator_health_interval > 0 {
+        let health_state = Arc::clone(&state);
+        tokio::spawn(async move {
+            let period = Duration::from_secs(health_state.config.simulator_health_interval);
+            let mut interval = tokio::time::interval(period);
+            loop {
+                interval.tick().await;
+                health_state.check_simulator_service().await;
+            }
+        });
+    }
+
+    // Periodically remove old completed builds
+    let reaper_state = Arc::clone(&state);
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(60));
+        loop {
+            interval.tick().await;
+            reaper_state
+                .cleanup_old_builds(reaper_state.config.max_completed_builds)
+                .await;
+        }
+    });
+
+    // Boot the prewarm simulators in the background so startup isn't delayed
+    if config.prewarm_on_start {
+        let simulators = config.prewarm_simulators.clone().unwrap_or_default();
+        let prewarm_state = Arc::clone(&state);
+        tokio::spawn(async move {
+            for result in handlers::simulator::prewarm_simulators(&prewarm_state, simulators).await
+            {
+                match result.error {
+                    None => info!("Prewarmed simulator {}", result.simulator),
+                    Some(e) => tracing::warn!("Failed to prewarm {}: {}", result.simulator, e),
+                }
+            }
+        });
+    }
+
+    // Start the optional gRPC interface. Binding (host, port) resolves
+    // hostnames and IPv6 addresses that "host:port" parsing would reject.
+    let grpc_server = match config.grpc_port {
+        Some(grpc_port) => {
+            let grpc_listener = TcpListener::bind((config.host.as_str(), grpc_port)).await?;
+            info!(
+                "gRPC interface listening on {}",
+                grpc_listener.local_addr()?
+            );
+            Some(tokio::spawn(grpc::serve(Arc::clone(&state), grpc_listener)))
+        }
+        None => None,
+    };
 
     // Create router
     let app = create_router(state);
 
     // Bind to address
-    let addr = format!("{}:{}", config.host, config.port);
+    let addr = config.socket_addr();
     let listener = TcpListener::bind(&addr).await?;
 
     info!("xcbridge listening on {}", addr);
     info!("API documentation available at http://{}/", addr);
 
-    if config.api_key.is_some() {
+    if config.auth_required() {
         info!("API key authentication enabled");
     } else {
         tracing::warn!("No API key configured - authentication disabled");
     }
 
-    // Start server
-    axum::serve(listener, app).await?;
+    // Start server, stopping if the gRPC interface fails
+    let http_server = axum::serve(listener, app);
+    match grpc_server {
+        Some(grpc_server) => tokio::select! {
+            result = http_server => result?,
+            result = grpc_server => match result? {
+                Ok(()) => anyhow::bail!("gRPC server stopped"),
+                Err(e) => anyhow::bail!("gRPC server failed: {}", e),
+            },
+        },
+        None => http_server.await?,
+    }
 
     Ok(())
 }
@@ -168,6 +629,7 @@ async fn main() -> anyhow::Result<()> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::xcode::capabilities::Capabilities;
     use axum::{
         body::Body,
         http::{Request, StatusCode},
@@ -178,9 +640,36 @@ mod tests {
         Config {
             port: 9090,
             host: "127.0.0.1".to_string(),
+            insecure: false,
+            grpc_port: None,
             api_key: None,
+            api_keys: None,
+            admin_key: None,
             log_level: "info".to_string(),
-            allowed_paths: vec![],
+            allowed_paths: None,
+            derived_data_base: None,
+            xcode_path: None,
+            simctl_retries: 2,
+            prewarm_simulators: None,
+            prewarm_on_start: false,
+            cors_origins: None,
+            audit_log: None,
+            max_completed_builds: 100,
+            max_builds: 1000,
+            max_concurrent_jobs: 0,
+            destination_timeout: 30,
+            host_refresh_interval: 600,
+            build_nice: 0,
+            build_jobs: None,
+            devicectl_exec_allow: None,
+            simulator_health_interval: 60,
+            upload_dir: None,
+            max_upload_size: 512,
+            max_extracted_size: 4096,
+            max_extracted_file_size: 1024,
+            log_poll_interval: 100,
+            simulator_timeout: 300,
+            device_timeout: 600,
         }
     }
 
@@ -197,6 +686,33 @@ mod tests {
         assert_eq!(response.status(), StatusCode::OK);
     }
 
+    #[tokio::test]
+    async fn test_metrics_count_finished_jobs() {
+        let state = Arc::new(AppState::new(test_config(), "15.0".to_string()));
+        state.create_build("build").await.unwrap();
+        state.complete_build("build", vec![]).await;
+        let app = create_router(state);
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/metrics")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        assert!(body.contains("xcbridge_jobs_finished_total{kind=\"build\"} 1\n"));
+        assert!(body.contains("xcbridge_jobs_finished_total{kind=\"test\"} 0\n"));
+        assert!(body.contains("xcbridge_job_cpu_seconds_total{kind=\"build\",mode=\"user\"} 0\n"));
+    }
+
     #[tokio::test]
     async fn test_auth_required_when_api_key_set() {
         let mut config = test_config();
@@ -227,4 +743,483 @@ mod tests {
 
         assert_eq!(response.status(), StatusCode::OK);
     }
+
+    #[tokio::test]
+    async fn test_version_does_not_require_api_key() {
+        let mut config = test_config();
+        config.api_key = Some("secret-key".to_string());
+        let state = Arc::new(AppState::new(config, "Xcode 15.2".to_string()));
+        let app = create_router(state);
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/version")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let version: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(version["version"], env!("CARGO_PKG_VERSION"));
+        assert_eq!(version["xcode_version"], "Xcode 15.2");
+    }
+
+    #[tokio::test]
+    async fn test_admin_routes_require_admin_key() {
+        let mut config = test_config();
+        config.api_key = Some("user-key".to_string());
+        config.admin_key = Some("admin-key".to_string());
+        let state = Arc::new(AppState::new(config, "15.0".to_string()));
+        let app = create_router(state);
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/simulator/reset-service")
+                    .header("X-API-Key", "user-key")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        let cancel_all = |key: &str| {
+            Request::builder()
+                .method("POST")
+                .uri("/builds/cancel-all")
+                .header("X-API-Key", key)
+                .body(Body::empty())
+                .unwrap()
+        };
+        let response = app.clone().oneshot(cancel_all("user-key")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let response = app.clone().oneshot(cancel_all("admin-key")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let install_runtime = |key: &str| {
+            Request::builder()
+                .method("POST")
+                .uri("/simulator/runtimes/install")
+                .header("X-API-Key", key)
+                .header("Content-Type", "application/json")
+                .body(Body::from(r#"{"platform": "Android"}"#))
+                .unwrap()
+        };
+        let response = app
+            .clone()
+            .oneshot(install_runtime("user-key"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let response = app
+            .clone()
+            .oneshot(install_runtime("admin-key"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let last_commands = |key: &str| {
+            Request::builder()
+                .uri("/debug/last-commands")
+                .header("X-API-Key", key)
+                .body(Body::empty())
+                .unwrap()
+        };
+        let response = app
+            .clone()
+            .oneshot(last_commands("user-key"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let response = app.oneshot(last_commands("admin-key")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_device_routes_without_devicectl() {
+        let state = Arc::new(AppState::new(test_config(), "15.0".to_string()));
+        state.host.write().await.capabilities = Some(Capabilities::default());
+        let app = create_router(state);
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/device/list")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_IMPLEMENTED);
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/status")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let status: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(status["devicectl_available"], false);
+        assert_eq!(status["connected_devices"], serde_json::json!([]));
+    }
+
+    #[tokio::test]
+    async fn test_device_install_needs_a_device() {
+        let state = Arc::new(AppState::new(test_config(), "15.0".to_string()));
+        state.host.write().await.capabilities = Some(Capabilities {
+            devicectl: true,
+            ..Capabilities::default()
+        });
+        let app = create_router(state);
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/device/install")
+                    .header("Content-Type", "application/json")
+                    .body(Body::from(
+                        r#"{"app_path": "/tmp/MyApp.app", "device_id": []}"#,
+                    ))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_malformed_body_is_unprocessable() {
+        let state = Arc::new(AppState::new(test_config(), "15.0".to_string()));
+        let app = create_router(state);
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/build")
+                    .header("Content-Type", "application/json")
+                    .body(Body::from(r#"{"scheme": "App", "use_cache": "yes"}"#))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["error"], "invalid_body");
+        assert_eq!(json["field"], "use_cache");
+        assert_eq!(json["expected"], "a boolean");
+    }
+
+    #[tokio::test]
+    async fn test_errors_as_plain_text_when_accepted() {
+        let state = Arc::new(AppState::new(test_config(), "15.0".to_string()));
+        let app = create_router(state);
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/build/missing")
+                    .header("Accept", "text/plain")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(
+            response.headers()[header::CONTENT_TYPE],
+            "text/plain; charset=utf-8"
+        );
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(body, "build_not_found: Build not found: missing\n");
+    }
+
+    #[tokio::test]
+    async fn test_slow_requests_time_out() {
+        let app = Router::new()
+            .route(
+                "/slow",
+                post(|| async {
+                    tokio::time::sleep(Duration::from_secs(5)).await;
+                    "done"
+                }),
+            )
+            .route_layer(middleware::from_fn(|request, next| {
+                with_timeout(Duration::from_millis(10), request, next)
+            }));
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/slow")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::GATEWAY_TIMEOUT);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["error"], "timeout");
+        assert!(json["message"].as_str().unwrap().contains("/slow"));
+    }
+
+    #[tokio::test]
+    async fn test_timed_out_request_kills_command_and_releases_lock() {
+        let state = Arc::new(AppState::new(test_config(), "15.0".to_string()));
+        let marker = std::env::temp_dir().join(format!("xcbridge-{}", uuid::Uuid::new_v4()));
+        let script = format!("sleep 0.5; touch {}", marker.display());
+        let app = Router::new()
+            .route(
+                "/wedged",
+                post(
+                    |axum::extract::State(state): axum::extract::State<Arc<AppState>>| async move {
+                        let _lock = state.lock_simulator("AAAA").await;
+                        let _ = xcode::runner::output(
+                            tokio::process::Command::new("sh").args(["-c", &script]),
+                        )
+                        .await;
+                    },
+                ),
+            )
+            .route_layer(middleware::from_fn(|request, next| {
+                with_timeout(Duration::from_millis(50), request, next)
+            }))
+            .with_state(Arc::clone(&state));
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/wedged")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::GATEWAY_TIMEOUT);
+
+        // The lock is free right away, and the command never finishes
+        tokio::time::timeout(Duration::from_millis(10), state.lock_simulator("AAAA"))
+            .await
+            .expect("simulator lock still held");
+        tokio::time::sleep(Duration::from_millis(800)).await;
+        assert!(!marker.exists(), "timed-out command kept running");
+    }
+
+    #[test]
+    fn test_is_localhost_origin() {
+        assert!(is_localhost_origin("http://localhost:3000"));
+        assert!(is_localhost_origin("https://127.0.0.1"));
+        assert!(is_localhost_origin("http://[::1]:8080"));
+        assert!(!is_localhost_origin("http://localhost.example.com"));
+        assert!(!is_localhost_origin("https://example.com"));
+    }
+
+    #[tokio::test]
+    async fn test_cors_origins() {
+        let preflight = |origin: &str| {
+            Request::builder()
+                .method("OPTIONS")
+                .uri("/status")
+                .header("Origin", origin)
+                .header("Access-Control-Request-Method", "GET")
+                .body(Body::empty())
+                .unwrap()
+        };
+        let allowed_origin = |response: axum::response::Response| {
+            response
+                .headers()
+                .get("access-control-allow-origin")
+                .map(|v| v.to_str().unwrap().to_string())
+        };
+
+        // Localhost only by default
+        let app = create_router(Arc::new(AppState::new(test_config(), "15.0".to_string())));
+        let response = app
+            .clone()
+            .oneshot(preflight("http://localhost:3000"))
+            .await
+            .unwrap();
+        assert_eq!(
+            allowed_origin(response).as_deref(),
+            Some("http://localhost:3000")
+        );
+        let response = app
+            .oneshot(preflight("https://evil.example"))
+            .await
+            .unwrap();
+        assert_eq!(allowed_origin(response), None);
+
+        let mut config = test_config();
+        config.cors_origins = Some(vec!["https://dashboard.example".to_string()]);
+        let app = create_router(Arc::new(AppState::new(config, "15.0".to_string())));
+        let response = app
+            .clone()
+            .oneshot(preflight("https://dashboard.example"))
+            .await
+            .unwrap();
+        assert_eq!(
+            allowed_origin(response).as_deref(),
+            Some("https://dashboard.example")
+        );
+        let response = app
+            .oneshot(preflight("http://localhost:3000"))
+            .await
+            .unwrap();
+        assert_eq!(allowed_origin(response), None);
+
+        // A wildcard mixed with origins is dropped rather than panicking
+        let mut config = test_config();
+        config.cors_origins = Some(vec!["*".to_string(), "https://a.example".to_string()]);
+        let app = create_router(Arc::new(AppState::new(config, "15.0".to_string())));
+        let response = app.oneshot(preflight("https://a.example")).await.unwrap();
+        assert_eq!(
+            allowed_origin(response).as_deref(),
+            Some("https://a.example")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_mutating_requests_are_audited() {
+        let path =
+            std::env::temp_dir().join(format!("xcbridge-audit-{}.jsonl", uuid::Uuid::new_v4()));
+        let mut config = test_config();
+        config.api_keys = Some(vec!["ci:ci-key".to_string()]);
+        let mut app_state = AppState::new(config, "15.0".to_string());
+        app_state.audit_log = Some(audit::AuditLog::open(&path).await.unwrap());
+        let app = create_router(Arc::new(app_state));
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/builds/cleanup")
+                    .header("X-API-Key", "ci-key")
+                    .header("Content-Type", "application/json")
+                    .body(Body::from(r#"{"environment": {"TOKEN": "abc"}}"#))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        // Reads aren't audited
+        app.oneshot(
+            Request::builder()
+                .uri("/status")
+                .header("X-API-Key", "ci-key")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<_> = contents.lines().collect();
+        assert_eq!(lines.len(), 1);
+        let entry: serde_json::Value = serde_json::from_str(lines[0]).unwrap();
+        assert_eq!(entry["key_label"], "ci");
+        assert_eq!(entry["endpoint"], "POST /builds/cleanup");
+        assert_eq!(entry["params"]["environment"], "[redacted]");
+        assert_eq!(entry["status"], 200);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_builds_are_scoped_to_api_key_namespace() {
+        let mut config = test_config();
+        config.api_keys = Some(vec!["team-a:key-a".to_string(), "team-b:key-b".to_string()]);
+        let state = Arc::new(AppState::new(config, "15.0".to_string()));
+        state
+            .insert_build(
+                "build-a",
+                state::BuildRecord {
+                    namespace: state::Namespace::new("team-a"),
+                    ..state::BuildRecord::default()
+                },
+            )
+            .await
+            .unwrap();
+        let app = create_router(state);
+
+        let get_build = |key: &str| {
+            Request::builder()
+                .uri("/build/build-a")
+                .header("X-API-Key", key)
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        let response = app.clone().oneshot(get_build("key-b")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let response = app.oneshot(get_build("key-a")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_build_status_without_logs() {
+        let state = Arc::new(AppState::new(test_config(), "15.0".to_string()));
+        state.create_build("build").await.unwrap();
+        state
+            .append_build_log("build", "Compiling".to_string())
+            .await;
+        let app = create_router(state);
+
+        let get_build = |uri: &str| Request::builder().uri(uri).body(Body::empty()).unwrap();
+        let status = |response: axum::response::Response| async {
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            serde_json::from_slice::<serde_json::Value>(&body).unwrap()
+        };
+
+        let response = app
+            .clone()
+            .oneshot(get_build("/build/build"))
+            .await
+            .unwrap();
+        assert_eq!(
+            status(response).await["logs"],
+            serde_json::json!(["Compiling"])
+        );
+
+        let response = app
+            .oneshot(get_build("/build/build?include_logs=false"))
+            .await
+            .unwrap();
+        let outcome = status(response).await;
+        assert_eq!(outcome["status"], "running");
+        assert!(outcome.get("logs").is_none());
+    }
 }
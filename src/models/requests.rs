@@ -3,7 +3,10 @@
 
 //! Request models for xcbridge API
 
+use crate::xcode::simctl::{Appearance, StatusBarOverrides};
+use crate::xcode::ui_input::Button;
 use serde::Deserialize;
+use std::collections::HashMap;
 
 fn default_configuration() -> String {
     "Debug".to_string()
@@ -12,43 +15,299 @@ fn default_configuration() -> String {
 /// Request to start a build
 #[derive(Debug, Deserialize)]
 pub struct BuildRequest {
+    /// Name for the job in status, listings and logs (generated if omitted)
+    pub name: Option<String>,
     /// Path to .xcodeproj file
     pub project: Option<String>,
     /// Path to .xcworkspace file
     pub workspace: Option<String>,
     /// Build scheme
-    pub scheme: String,
+    pub scheme: Option<String>,
+    /// Target to build instead of a scheme (projects only)
+    pub target: Option<String>,
     /// Build configuration (Debug, Release)
     #[serde(default = "default_configuration")]
     pub configuration: String,
     /// Build destination (e.g., "platform=iOS Simulator,name=iPhone 15 Pro")
     pub destination: Option<String>,
+    /// Build for a leased simulator, by lease id or tag, instead of a
+    /// destination (renews the lease)
+    pub lease: Option<String>,
     /// Custom derived data path
     pub derived_data_path: Option<String>,
+    /// Remove the DerivedData directory along with the build (defaults to
+    /// true for server-assigned directories, false for `derived_data_path`)
+    pub cleanup_derived_data: Option<bool>,
+    /// Swift toolchain name or identifier (e.g., "org.swift.59202401011a")
+    pub toolchain: Option<String>,
     /// Additional xcodebuild arguments
     #[serde(default)]
     pub extra_args: Vec<String>,
+    /// Reuse a previous successful build with the same fingerprint
+    #[serde(default)]
+    pub use_cache: bool,
+    /// Caller-supplied source fingerprint (e.g., a git commit); defaults to
+    /// a hash of the project directory
+    pub fingerprint: Option<String>,
+    /// Write a result bundle and report structured diagnostics from it
+    #[serde(default)]
+    pub result_bundle: bool,
+    /// Keep the result bundle when the build is removed, e.g. to download it
+    #[serde(default)]
+    pub keep_result_bundle: bool,
+    /// Environment variables for the xcodebuild process (e.g., `CI=1`)
+    #[serde(default)]
+    pub environment: HashMap<String, String>,
+    /// Directory to run xcodebuild in (defaults to the project's directory)
+    pub working_directory: Option<String>,
+    /// Seconds to search for the destination before failing (defaults to
+    /// `--destination-timeout`)
+    pub destination_timeout: Option<u32>,
+    /// Resolve Swift package dependencies before building
+    #[serde(default)]
+    pub resolve_packages: bool,
+    /// Trust package plugins and macros without Xcode's validation prompt
+    #[serde(default)]
+    pub skip_plugin_validation: bool,
+    /// Concurrent build tasks (`-jobs`; defaults to `--build-jobs`)
+    pub jobs: Option<u32>,
+    /// If the build fails on stale DerivedData (e.g. a module or
+    /// precompiled header out of date), rerun it once from clean
+    #[serde(default)]
+    pub retry_clean_on_failure: bool,
+    /// Pass `-showBuildTimingSummary` and report time spent per kind of
+    /// build task
+    #[serde(default)]
+    pub timing_summary: bool,
+    /// Copy the products into this directory once the build succeeds, e.g.
+    /// a volume shared with the client
+    pub output_dir: Option<String>,
+}
+
+/// Request to build several schemes one after another as one job
+#[derive(Debug, Deserialize)]
+pub struct BatchBuildRequest {
+    /// Name for the job in status, listings and logs (generated if omitted)
+    pub name: Option<String>,
+    /// Builds to run, in order
+    pub builds: Vec<BuildRequest>,
+    /// Keep building the remaining schemes after one fails
+    #[serde(default)]
+    pub continue_on_error: bool,
+    /// DerivedData directory shared by the builds (defaults to one for the job)
+    pub derived_data_path: Option<String>,
+    /// Remove the DerivedData directory along with the job (defaults to
+    /// true for server-assigned directories, false for `derived_data_path`)
+    pub cleanup_derived_data: Option<bool>,
 }
 
 /// Request to start tests
 #[derive(Debug, Deserialize)]
 pub struct TestRequest {
+    /// Name for the job in status, listings and logs (generated if omitted)
+    pub name: Option<String>,
     /// Path to .xcodeproj file
     pub project: Option<String>,
     /// Path to .xcworkspace file
     pub workspace: Option<String>,
-    /// Test scheme
-    pub scheme: String,
+    /// Test scheme (required unless `xctestrun_path` is set)
+    pub scheme: Option<String>,
+    /// Run the tests described by an `.xctestrun` file from an earlier
+    /// `build-for-testing`, with `test-without-building`, instead of a scheme
+    pub xctestrun_path: Option<String>,
     /// Test destination
     pub destination: Option<String>,
+    /// Test on a leased simulator, by lease id or tag, instead of a
+    /// destination (renews the lease)
+    pub lease: Option<String>,
     /// Test plan to use
     pub test_plan: Option<String>,
+    /// Only run these configurations of the test plan
+    #[serde(default)]
+    pub only_test_configuration: Vec<String>,
+    /// Skip these configurations of the test plan
+    #[serde(default)]
+    pub skip_test_configuration: Vec<String>,
+    /// Language to run the tests in (e.g. "de" or "zh-Hans")
+    pub test_language: Option<String>,
+    /// Region to run the tests in (e.g. "AT")
+    pub test_region: Option<String>,
+    /// Swift toolchain name or identifier
+    pub toolchain: Option<String>,
     /// Only run these tests
     #[serde(default)]
     pub only_testing: Vec<String>,
     /// Skip these tests
     #[serde(default)]
     pub skip_testing: Vec<String>,
+    /// Run test classes in parallel on clones of the destination simulator
+    #[serde(default)]
+    pub parallel_testing: bool,
+    /// Number of parallel test workers (requires `parallel_testing`)
+    pub parallel_workers: Option<u32>,
+    /// Write a result bundle, e.g. to export attachments from it
+    #[serde(default)]
+    pub result_bundle: bool,
+    /// Keep the result bundle when the test run is removed
+    #[serde(default)]
+    pub keep_result_bundle: bool,
+    /// Stream the simulator's log during the run and attach the lines
+    /// logged while each failed test ran to its failures
+    #[serde(default)]
+    pub capture_runtime_logs: bool,
+    /// Erase the destination simulator once the run finishes, if this
+    /// service (or the run) booted it
+    #[serde(default)]
+    pub erase_after: bool,
+    /// Seconds to search for the destination before failing (defaults to
+    /// `--destination-timeout`)
+    pub destination_timeout: Option<u32>,
+    /// Concurrent build tasks (`-jobs`; defaults to `--build-jobs`)
+    pub jobs: Option<u32>,
+    /// Stop the run at the first failing test, reporting results so far
+    #[serde(default)]
+    pub fail_fast: bool,
+    /// Screenshot the test simulator when a test fails
+    #[serde(default)]
+    pub screenshot_on_failure: bool,
+}
+
+/// Query identifying a scheme in a project or workspace
+#[derive(Debug, Deserialize)]
+pub struct SchemeQuery {
+    /// Path to .xcodeproj file
+    pub project: Option<String>,
+    /// Path to .xcworkspace file
+    pub workspace: Option<String>,
+    /// Scheme name
+    pub scheme: String,
+}
+
+/// Query selecting running jobs to cancel
+#[derive(Debug, Deserialize)]
+pub struct CancelBuildsQuery {
+    /// Scheme (or target) being built or tested
+    pub scheme: Option<String>,
+    /// Job name
+    pub name: Option<String>,
+}
+
+/// Query for the most recent jobs of a scheme
+#[derive(Debug, Deserialize)]
+pub struct RecentBuildsQuery {
+    /// Scheme name
+    pub scheme: String,
+    /// Maximum number of jobs to return
+    #[serde(default = "default_recent_limit")]
+    pub limit: usize,
+}
+
+fn default_recent_limit() -> usize {
+    10
+}
+
+/// Query filtering a job's log lines
+#[derive(Debug, Default, Deserialize)]
+pub struct LogQuery {
+    /// Only return lines matching this regular expression
+    pub grep: Option<String>,
+}
+
+/// Query for a job's status
+#[derive(Debug, Deserialize)]
+pub struct StatusQuery {
+    /// Only return lines matching this regular expression
+    pub grep: Option<String>,
+    /// Include the log in the response; clients following the log stream
+    /// can turn this off to fetch just the outcome
+    #[serde(default = "default_include_logs")]
+    pub include_logs: bool,
+}
+
+fn default_include_logs() -> bool {
+    true
+}
+
+impl Default for StatusQuery {
+    fn default() -> Self {
+        Self {
+            grep: None,
+            include_logs: default_include_logs(),
+        }
+    }
+}
+
+/// Query for downloading a job's log as a file
+#[derive(Debug, Default, Deserialize)]
+pub struct LogDownloadQuery {
+    /// Only include lines matching this regular expression
+    pub grep: Option<String>,
+    /// File format: "text" (the default) or "json"
+    #[serde(default)]
+    pub format: LogFormat,
+}
+
+/// Format of a downloaded log
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// Plain text, one line per log line
+    #[default]
+    Text,
+    /// A JSON document with the job's id, name and status alongside its lines
+    Json,
+}
+
+/// Query selecting an app on a simulator
+#[derive(Debug, Deserialize)]
+pub struct SimulatorAppQuery {
+    /// Bundle identifier of the installed app
+    pub bundle_id: String,
+}
+
+/// Query for the tail of a simulator's log
+#[derive(Debug, Deserialize)]
+pub struct SimulatorLogTailQuery {
+    /// Number of lines to return, at most 5000
+    #[serde(default = "default_tail_lines")]
+    pub lines: usize,
+    /// How many minutes back to search
+    #[serde(default = "default_tail_minutes")]
+    pub minutes: u32,
+    /// Only lines from this process, e.g. "MyApp" or "SpringBoard"
+    pub process: Option<String>,
+}
+
+fn default_tail_lines() -> usize {
+    500
+}
+
+fn default_tail_minutes() -> u32 {
+    5
+}
+
+/// Query identifying a built app bundle
+#[derive(Debug, Deserialize)]
+pub struct AppInfoQuery {
+    /// Path to the .app bundle
+    pub path: String,
+}
+
+/// Request to validate an app bundle before installing it
+#[derive(Debug, Deserialize)]
+pub struct AppValidateRequest {
+    /// Path to the .app bundle
+    pub path: String,
+    /// Platform the app will be installed on, as named in
+    /// CFBundleSupportedPlatforms (e.g. "iPhoneOS" for devices)
+    pub platform: Option<String>,
+}
+
+/// Request to install a provisioning profile
+#[derive(Debug, Deserialize)]
+pub struct ProvisioningInstallRequest {
+    /// Path to the .mobileprovision file
+    pub path: String,
 }
 
 /// Request to boot a simulator
@@ -60,6 +319,141 @@ pub struct SimulatorBootRequest {
     pub udid: Option<String>,
     /// Runtime (e.g., "iOS 17.0")
     pub runtime: Option<String>,
+    /// Switch to light or dark mode once booted
+    pub appearance: Option<Appearance>,
+    /// Preferred language to set once booted (e.g., "fr"); needs `locale`
+    pub language: Option<String>,
+    /// Region locale identifier to set once booted (e.g., "fr_FR"); needs
+    /// `language`
+    pub locale: Option<String>,
+    /// Status bar values to override once booted
+    pub status_bar: Option<StatusBarOverrides>,
+}
+
+/// Request to lease a booted simulator for a session
+#[derive(Debug, Deserialize)]
+pub struct SimulatorLeaseRequest {
+    /// Session tag; leasing the same tag again renews and returns its lease
+    pub tag: String,
+    /// Specific simulator UDID
+    pub udid: Option<String>,
+    /// Device type name (e.g., "iPhone 15 Pro")
+    pub device_type: Option<String>,
+    /// Runtime (e.g., "iOS 17.0")
+    pub runtime: Option<String>,
+    /// Seconds until the lease expires unless renewed
+    #[serde(default = "default_lease_ttl")]
+    pub ttl_secs: u64,
+}
+
+fn default_lease_ttl() -> u64 {
+    1800
+}
+
+/// Request to release a simulator lease
+#[derive(Debug, Deserialize)]
+pub struct SimulatorReleaseRequest {
+    /// Lease id or tag
+    pub lease: String,
+    /// Shut the simulator down rather than leaving it booted
+    #[serde(default)]
+    pub shutdown: bool,
+}
+
+/// Request to wait until a simulator is booted and ready
+#[derive(Debug, Deserialize)]
+pub struct SimulatorWaitReadyRequest {
+    /// Simulator UDID
+    pub udid: String,
+    /// Boot the simulator first if it isn't already booting
+    #[serde(default)]
+    pub boot: bool,
+    /// Maximum time to wait in seconds
+    #[serde(default = "default_wait_timeout")]
+    pub timeout_secs: u64,
+}
+
+fn default_wait_timeout() -> u64 {
+    120
+}
+
+/// Request to boot simulators ahead of use
+#[derive(Debug, Default, Deserialize)]
+pub struct SimulatorPrewarmRequest {
+    /// Simulator UDIDs or device names (defaults to `--prewarm-simulators`)
+    #[serde(default)]
+    pub simulators: Vec<String>,
+}
+
+/// Request to install a simulator runtime
+#[derive(Debug, Deserialize)]
+pub struct RuntimeInstallRequest {
+    /// Platform to download: "iOS", "watchOS", "tvOS" or "visionOS"
+    #[serde(default = "default_runtime_platform")]
+    pub platform: String,
+    /// OS or build version to download (defaults to the one matching Xcode)
+    pub version: Option<String>,
+    /// Path to a downloaded runtime disk image to add instead of downloading
+    pub image: Option<String>,
+}
+
+fn default_runtime_platform() -> String {
+    "iOS".to_string()
+}
+
+/// Request to export a project's localizations as .xcloc bundles
+#[derive(Debug, Deserialize)]
+pub struct LocalizationExportRequest {
+    /// Path to .xcodeproj
+    pub project: String,
+    /// Directory to write the .xcloc bundles to
+    pub localization_path: String,
+    /// Languages to export (e.g., ["fr", "de"]); defaults to the project's
+    /// development language
+    #[serde(default)]
+    pub languages: Vec<String>,
+    /// Include screenshots captured by UI tests for context
+    #[serde(default)]
+    pub include_screenshots: bool,
+    /// Name for the job in status, listings and logs (generated if omitted)
+    pub name: Option<String>,
+}
+
+/// Request to import a translated .xcloc bundle into a project
+#[derive(Debug, Deserialize)]
+pub struct LocalizationImportRequest {
+    /// Path to .xcodeproj
+    pub project: String,
+    /// .xcloc bundle or .xliff file to import
+    pub localization_path: String,
+    /// Merge with the project's existing translations (`-mergeImport`)
+    #[serde(default)]
+    pub merge: bool,
+    /// Name for the job in status, listings and logs (generated if omitted)
+    pub name: Option<String>,
+}
+
+/// Request to build an app and run it on a simulator in one session
+#[derive(Debug, Deserialize)]
+pub struct SimulatorRunRequest {
+    /// Path to .xcodeproj file
+    pub project: Option<String>,
+    /// Path to .xcworkspace file
+    pub workspace: Option<String>,
+    /// Build scheme
+    pub scheme: String,
+    /// Build configuration (Debug, Release)
+    #[serde(default = "default_configuration")]
+    pub configuration: String,
+    /// App bundle identifier to launch (read from the built app if omitted)
+    pub bundle_id: Option<String>,
+    /// Simulator UDID
+    pub udid: Option<String>,
+    /// Device type name, used when no UDID is given (e.g., "iPhone 15 Pro")
+    pub device_type: Option<String>,
+    /// Launch arguments
+    #[serde(default)]
+    pub arguments: Vec<String>,
 }
 
 /// Request to shut down a simulator
@@ -79,6 +473,9 @@ pub struct SimulatorInstallRequest {
     pub app_path: String,
     /// Simulator UDID (uses booted if not specified)
     pub udid: Option<String>,
+    /// Also return the installed app's data container path
+    #[serde(default)]
+    pub data_container: bool,
 }
 
 /// Request to launch an app on a simulator
@@ -91,6 +488,25 @@ pub struct SimulatorLaunchRequest {
     /// Launch arguments
     #[serde(default)]
     pub arguments: Vec<String>,
+    /// Terminate an already-running instance of the app first
+    #[serde(default)]
+    pub terminate_running: bool,
+}
+
+/// Request to send a signal to a process in a simulator
+#[derive(Debug, Deserialize)]
+pub struct SimulatorSignalRequest {
+    /// Process id (e.g., from a launch response)
+    pub pid: u32,
+    /// Signal name or number (e.g., "TERM", "SIGSTOP", "9")
+    #[serde(default = "default_signal")]
+    pub signal: String,
+    /// Simulator UDID (uses booted if not specified)
+    pub udid: Option<String>,
+}
+
+fn default_signal() -> String {
+    "TERM".to_string()
 }
 
 /// Request to uninstall an app from a simulator
@@ -102,13 +518,87 @@ pub struct SimulatorUninstallRequest {
     pub udid: Option<String>,
 }
 
-/// Request to install an app on a physical device
+/// Request to type text on a simulator
+#[derive(Debug, Deserialize)]
+pub struct SimulatorTypeRequest {
+    /// Text to type into the focused field
+    pub text: String,
+}
+
+/// Request to tap a point on a simulator's screen
+#[derive(Debug, Deserialize)]
+pub struct SimulatorTapRequest {
+    /// Horizontal position in points from the left edge
+    pub x: f64,
+    /// Vertical position in points from the top edge
+    pub y: f64,
+    /// Hold the touch this long, for long presses
+    pub duration_secs: Option<f64>,
+}
+
+/// Request to press a hardware button on a simulator
+#[derive(Debug, Deserialize)]
+pub struct SimulatorButtonRequest {
+    /// "home", "lock", "siri" or "shake"
+    pub button: Button,
+}
+
+/// Request to set a simulator's language and region
+#[derive(Debug, Deserialize)]
+pub struct SimulatorLocaleRequest {
+    /// Simulator UDID (uses booted if not specified)
+    pub udid: Option<String>,
+    /// Preferred language (e.g., "fr")
+    pub language: String,
+    /// Region locale identifier (e.g., "fr_FR")
+    pub locale: String,
+    /// Connect the host hardware keyboard (applies to all simulators)
+    pub hardware_keyboard: Option<bool>,
+}
+
+/// One or more device UDIDs
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum DeviceIds {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl DeviceIds {
+    pub fn into_vec(self) -> Vec<String> {
+        match self {
+            DeviceIds::One(id) => vec![id],
+            DeviceIds::Many(ids) => ids,
+        }
+    }
+}
+
+/// Request to install an app on one or more physical devices
 #[derive(Debug, Deserialize)]
 pub struct DeviceInstallRequest {
     /// Path to .app or .ipa bundle
     pub app_path: String,
+    /// Device UDID, or a list of UDIDs to install to concurrently
+    pub device_id: DeviceIds,
+    /// Install even if the app's minimum OS or device family rules the
+    /// device out (the mismatch is only logged)
+    #[serde(default)]
+    pub skip_compatibility_check: bool,
+}
+
+/// Request to run a `devicectl device` subcommand
+#[derive(Debug, Deserialize)]
+pub struct DeviceExecRequest {
     /// Device UDID
     pub device_id: String,
+    /// Subcommand words after `devicectl device`, e.g. `["info", "lockState"]`
+    pub command: Vec<String>,
+    /// Further arguments, placed after `--device`
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// Ask devicectl for JSON output and return it parsed
+    #[serde(default)]
+    pub json: bool,
 }
 
 /// Request to launch an app on a physical device
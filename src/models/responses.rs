@@ -3,8 +3,20 @@
 
 //! Response models for xcbridge API
 
+use crate::state::{BatchStep, FailureReason, JobKind, SimulatorServiceHealth};
+use crate::xcode::bundle::AppInfo;
 use crate::xcode::devicectl::Device;
-use crate::xcode::simctl::Simulator;
+use crate::xcode::keychain::{KeychainStatus, SigningIdentity};
+use crate::xcode::provisioning::ProvisioningProfile;
+use crate::xcode::runner::CommandRecord;
+use crate::xcode::script_phase::ScriptPhaseFailure;
+use crate::xcode::signing::CodeSigningIssue;
+use crate::xcode::simctl::{Simulator, SimulatorErase};
+use crate::xcode::test_plans::TestPlanInfo;
+use crate::xcode::ui_input::TextInputMethod;
+use crate::xcode::xcodebuild::{BuildPhaseTiming, CpuTime, Destination, Sdk};
+use crate::xcode::xcresult::{BuildDiagnostics, TestCaseRun};
+use chrono::{DateTime, Utc};
 use serde::Serialize;
 
 /// Health check and status response
@@ -14,10 +26,41 @@ pub struct StatusResponse {
     pub healthy: bool,
     /// Xcode version
     pub xcode_version: String,
+    /// Installed SDKs
+    pub sdks: Vec<Sdk>,
     /// Available simulators
     pub simulators: Vec<SimulatorInfo>,
     /// Connected physical devices
     pub connected_devices: Vec<DeviceInfo>,
+    /// devicectl is installed (Xcode 15+), so physical devices can be used
+    pub devicectl_available: bool,
+    /// Whether CoreSimulator answered its last periodic probe
+    pub simulator_service: SimulatorServiceHealth,
+}
+
+/// Versions of xcbridge and the host's developer tools
+#[derive(Debug, Serialize)]
+pub struct VersionResponse {
+    /// xcbridge version
+    pub version: String,
+    /// Xcode version
+    pub xcode_version: String,
+    /// Command Line Tools version (None if they aren't installed)
+    pub command_line_tools_version: Option<String>,
+}
+
+/// Health of the host's subsystems
+#[derive(Debug, Serialize)]
+pub struct SystemResponse {
+    /// Whether CoreSimulator answered its last periodic probe
+    pub simulator_service: SimulatorServiceHealth,
+}
+
+/// Commands the bridge ran recently, for debugging the host
+#[derive(Debug, Serialize)]
+pub struct LastCommandsResponse {
+    /// Newest first
+    pub commands: Vec<CommandRecord>,
 }
 
 /// Simplified simulator info for status response
@@ -38,6 +81,40 @@ impl From<Simulator> for SimulatorInfo {
     }
 }
 
+/// Everything simctl reports about one simulator
+#[derive(Debug, Serialize)]
+pub struct SimulatorDetails {
+    pub udid: String,
+    pub name: String,
+    pub state: String,
+    pub is_available: bool,
+    /// Why the simulator is unavailable, e.g. its runtime isn't installed
+    pub availability_error: Option<String>,
+    /// e.g. "com.apple.CoreSimulator.SimDeviceType.iPhone-15"
+    pub device_type_identifier: Option<String>,
+    /// e.g. "com.apple.CoreSimulator.SimRuntime.iOS-17-0"
+    pub runtime: Option<String>,
+    /// The simulator's data directory, which holds app containers
+    pub data_path: Option<String>,
+    pub log_path: Option<String>,
+}
+
+impl From<Simulator> for SimulatorDetails {
+    fn from(sim: Simulator) -> Self {
+        Self {
+            udid: sim.udid,
+            name: sim.name,
+            state: sim.state,
+            is_available: sim.is_available,
+            availability_error: sim.availability_error,
+            device_type_identifier: sim.device_type_identifier,
+            runtime: sim.runtime,
+            data_path: sim.data_path,
+            log_path: sim.log_path,
+        }
+    }
+}
+
 /// Simplified device info for status response
 #[derive(Debug, Serialize)]
 pub struct DeviceInfo {
@@ -63,10 +140,34 @@ impl From<Device> for DeviceInfo {
 pub struct BuildStartedResponse {
     /// Unique build identifier
     pub build_id: String,
+    /// Job name
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
     /// Build status
     pub status: String,
     /// URL to stream logs
     pub logs_url: String,
+    /// Whether a cached build was reused (only set when caching was requested)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cache_hit: Option<bool>,
+    /// Artifacts of the reused build (on a cache hit)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub artifacts: Option<Vec<String>>,
+    /// Copies of the reused build's artifacts in `output_dir` (on a cache hit)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exported_artifacts: Option<Vec<String>>,
+    /// DerivedData directory used by the build
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub derived_data_path: Option<String>,
+}
+
+/// A job's log, downloaded with `?format=json`
+#[derive(Debug, Serialize)]
+pub struct LogExport {
+    pub build_id: String,
+    pub name: Option<String>,
+    pub status: String,
+    pub logs: Vec<String>,
 }
 
 /// Response for build status query
@@ -74,16 +175,115 @@ pub struct BuildStartedResponse {
 pub struct BuildStatusResponse {
     /// Build identifier
     pub build_id: String,
+    /// Job name
+    pub name: Option<String>,
     /// Current status: "running", "success", "failed", "cancelled"
     pub status: String,
     /// Exit code (if completed)
     pub exit_code: Option<i32>,
     /// Build artifacts (if successful)
     pub artifacts: Option<Vec<String>>,
+    /// Copies of the artifacts in the request's `output_dir`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exported_artifacts: Option<Vec<String>>,
     /// Error message (if failed)
     pub error: Option<String>,
-    /// Build logs
-    pub logs: Vec<String>,
+    /// Build logs (unless `include_logs=false`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub logs: Option<Vec<String>>,
+    /// DerivedData directory used by the build
+    pub derived_data_path: Option<String>,
+    /// App bundles produced by the build (if successful)
+    pub apps: Option<Vec<AppInfo>>,
+    /// Errors and warnings from the result bundle (if requested)
+    pub diagnostics: Option<BuildDiagnostics>,
+    /// Result bundle written by the build (if requested)
+    pub result_bundle_path: Option<String>,
+    /// Known cause of the failure, e.g. "disk_full" (if failed)
+    pub failure_reason: Option<FailureReason>,
+    /// Details of a code-signing failure
+    pub code_signing: Option<CodeSigningIssue>,
+    /// The run-script phase that failed and its output
+    pub script_phase: Option<ScriptPhaseFailure>,
+    /// The build failed on stale DerivedData and was rerun from clean
+    pub retried_clean: bool,
+    /// Time per kind of build task, slowest first (with `timing_summary`)
+    pub timing_summary: Option<Vec<BuildPhaseTiming>>,
+    /// Status and artifacts of each scheme (batch builds only)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub batch: Option<Vec<BatchStep>>,
+    /// Wall-clock time in seconds, or so far if still running
+    pub duration_secs: f64,
+    /// CPU time of xcodebuild and the tools it ran, once they have exited
+    pub cpu_time: Option<CpuTime>,
+    /// Place in the queue for a job slot, 1 being next (while queued)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub queue_position: Option<usize>,
+    /// Rough seconds until the queued job finishes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub eta_secs: Option<f64>,
+}
+
+/// Response for a cleanup of completed jobs
+#[derive(Debug, Serialize)]
+pub struct BuildCleanupResponse {
+    /// Number of completed jobs removed
+    pub removed: usize,
+    /// Free space in bytes on the DerivedData volume afterwards
+    pub free_bytes: Option<u64>,
+}
+
+/// Response for cancelling running jobs by scheme or name
+#[derive(Debug, Serialize)]
+pub struct BuildCancelResponse {
+    /// Ids of the builds and tests cancelled
+    pub cancelled: Vec<String>,
+}
+
+/// Response for cancelling all running jobs
+#[derive(Debug, Serialize)]
+pub struct BuildCancelAllResponse {
+    /// Number of running builds and tests cancelled
+    pub cancelled: usize,
+}
+
+/// Summary of a build or test job
+#[derive(Debug, Serialize)]
+pub struct RecentBuild {
+    pub build_id: String,
+    pub name: Option<String>,
+    /// "build" or "test"
+    pub kind: JobKind,
+    /// "running", "success", "failed", "cancelled"
+    pub status: String,
+    /// Error message (if failed)
+    pub error: Option<String>,
+    /// Known cause of the failure (if failed)
+    pub failure_reason: Option<FailureReason>,
+    pub started_at: DateTime<Utc>,
+    /// Duration in seconds (elapsed so far if still running)
+    pub duration_secs: f64,
+}
+
+/// Response for recent jobs of a scheme
+#[derive(Debug, Serialize)]
+pub struct RecentBuildsResponse {
+    pub scheme: String,
+    pub builds: Vec<RecentBuild>,
+}
+
+/// Response for scheme destination listing
+#[derive(Debug, Serialize)]
+pub struct DestinationListResponse {
+    pub scheme: String,
+    pub destinations: Vec<Destination>,
+}
+
+/// Response for scheme test plan listing
+#[derive(Debug, Serialize)]
+pub struct TestPlanListResponse {
+    pub scheme: String,
+    pub test_plans: Vec<TestPlanInfo>,
 }
 
 /// Response when a simulator is booted
@@ -95,6 +295,36 @@ pub struct SimulatorBootResponse {
     pub name: String,
     /// Current state
     pub status: String,
+    /// The simulator was booted before the request
+    pub already_booted: bool,
+    /// Requested settings that could not be applied
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub failed_settings: Vec<BootSettingFailure>,
+}
+
+/// A setting requested with a boot that could not be applied
+#[derive(Debug, Serialize)]
+pub struct BootSettingFailure {
+    /// "appearance", "locale" or "status_bar"
+    pub setting: String,
+    /// Why it failed
+    pub error: String,
+}
+
+/// Response when a simulator is leased
+#[derive(Debug, Serialize)]
+pub struct SimulatorLeaseResponse {
+    pub lease_id: String,
+    pub tag: String,
+    /// Simulator UDID
+    pub udid: String,
+    /// Simulator name
+    pub name: String,
+    /// Current state
+    pub status: String,
+    /// When the lease lapses unless renewed by another lease request or a
+    /// build or test that uses it
+    pub expires_at: DateTime<Utc>,
 }
 
 /// Response for simulator list
@@ -119,6 +349,137 @@ impl SuccessResponse {
     }
 }
 
+/// Response when an app is installed on a simulator
+#[derive(Debug, Serialize)]
+pub struct InstallResponse {
+    pub success: bool,
+    pub message: String,
+    /// Bundle id of the installed app (with `data_container`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bundle_id: Option<String>,
+    /// The app's data container, resolved after the install (with
+    /// `data_container`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data_container: Option<String>,
+}
+
+/// Response when an app is launched on a simulator
+#[derive(Debug, Serialize)]
+pub struct LaunchResponse {
+    pub success: bool,
+    pub message: String,
+    /// Launched process id (if reported by simctl)
+    pub pid: Option<u32>,
+}
+
+/// Response for an uninstall, which succeeds even if the app was absent
+#[derive(Debug, Serialize)]
+pub struct UninstallResponse {
+    pub success: bool,
+    pub message: String,
+    /// False if the app was not installed
+    pub was_installed: bool,
+}
+
+/// Response when text is sent to a simulator
+#[derive(Debug, Serialize)]
+pub struct SimulatorTypeResponse {
+    pub udid: String,
+    /// "typed", or "pasteboard" if the text could only be put on the
+    /// pasteboard
+    pub method: TextInputMethod,
+    pub message: String,
+}
+
+/// The latest lines of a simulator's log
+#[derive(Debug, Serialize)]
+pub struct SimulatorLogTailResponse {
+    pub udid: String,
+    /// Log lines, oldest first
+    pub lines: Vec<String>,
+    /// Older lines in the time window were left out
+    pub truncated: bool,
+}
+
+/// Text recognized on a simulator's screen
+#[cfg(feature = "screen-text")]
+#[derive(Debug, Serialize)]
+pub struct ScreenTextResponse {
+    pub udid: String,
+    /// Recognized lines of text, top to bottom as reported by Vision
+    pub texts: Vec<crate::xcode::screen_text::RecognizedText>,
+}
+
+/// Response for a CoreSimulatorService reset
+#[derive(Debug, Serialize)]
+pub struct ResetServiceResponse {
+    pub success: bool,
+    /// False if the service wasn't running
+    pub was_running: bool,
+}
+
+/// Per-simulator outcome of a prewarm
+#[derive(Debug, Serialize)]
+pub struct SimulatorPrewarmResult {
+    /// The UDID or device name as requested
+    pub simulator: String,
+    /// Resolved simulator UDID
+    pub udid: Option<String>,
+    pub success: bool,
+    /// Error message (if the simulator couldn't be booted)
+    pub error: Option<String>,
+}
+
+/// Response for a simulator prewarm
+#[derive(Debug, Serialize)]
+pub struct SimulatorPrewarmResponse {
+    /// True only if every simulator is booted and ready
+    pub success: bool,
+    pub results: Vec<SimulatorPrewarmResult>,
+}
+
+/// Per-device outcome of an install
+#[derive(Debug, Serialize)]
+pub struct DeviceInstallResult {
+    pub device_id: String,
+    pub success: bool,
+    /// Error message (if the install failed)
+    pub error: Option<String>,
+}
+
+/// Response for a (possibly multi-device) install
+#[derive(Debug, Serialize)]
+pub struct DeviceInstallResponse {
+    /// True only if every device succeeded
+    pub success: bool,
+    pub message: String,
+    pub results: Vec<DeviceInstallResult>,
+}
+
+/// Response for provisioning profile list
+#[derive(Debug, Serialize)]
+pub struct ProvisioningListResponse {
+    pub profiles: Vec<ProvisioningProfile>,
+}
+
+/// Whether the host is set up to sign for devices
+#[derive(Debug, Serialize)]
+pub struct SigningStatusResponse {
+    /// A build can likely be signed: the keychain is unlocked and there is
+    /// a valid identity and an unexpired profile
+    pub ready: bool,
+    /// The login keychain
+    pub keychain: KeychainStatus,
+    /// Code-signing identities, including expired or revoked ones
+    pub identities: Vec<SigningIdentity>,
+    /// Installed profiles that haven't expired
+    pub profiles: Vec<ProvisioningProfile>,
+    /// Installed profiles that have expired
+    pub expired_profiles: usize,
+    /// What stops signing from working, if anything
+    pub problems: Vec<String>,
+}
+
 /// Response for device list
 #[derive(Debug, Serialize)]
 pub struct DeviceListResponse {
@@ -130,6 +491,8 @@ pub struct DeviceListResponse {
 pub struct TestResultResponse {
     /// Test run identifier
     pub test_id: String,
+    /// Job name
+    pub name: Option<String>,
     /// Current status
     pub status: String,
     /// Number of passed tests
@@ -140,14 +503,120 @@ pub struct TestResultResponse {
     pub skipped: Option<u32>,
     /// Total duration in seconds
     pub duration: Option<f64>,
+    /// CPU time of xcodebuild and the tools it ran, once they have exited
+    pub cpu_time: Option<CpuTime>,
     /// Test failures
     pub failures: Vec<TestFailure>,
-    /// Test logs
-    pub logs: Vec<String>,
+    /// Known cause of the run failing, e.g. "disk_full" (if failed)
+    pub failure_reason: Option<FailureReason>,
+    /// Details of a code-signing failure
+    pub code_signing: Option<CodeSigningIssue>,
+    /// The run-script phase that failed and its output
+    pub script_phase: Option<ScriptPhaseFailure>,
+    /// Result bundle written by the run (with `result_bundle`, parallel
+    /// testing or xctestrun)
+    pub result_bundle_path: Option<String>,
+    /// Test cases that ran, read from the result bundle
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tests: Option<Vec<TestCaseRun>>,
+    /// Outcome of erasing the simulator after the run (with `erase_after`)
+    pub simulator_erase: Option<SimulatorErase>,
+    /// The run was stopped at its first failing test (`fail_fast`), so
+    /// later tests didn't run
+    pub truncated: bool,
+    /// Place in the queue for a job slot, 1 being next (while queued)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub queue_position: Option<usize>,
+    /// Rough seconds until the queued run finishes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub eta_secs: Option<f64>,
+    /// Test logs (unless `include_logs=false`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub logs: Option<Vec<String>>,
 }
 
-/// Individual test failure
+/// Attachments exported from a test run's result bundle
+#[derive(Debug, Serialize)]
+pub struct TestAttachmentsResponse {
+    pub test_id: String,
+    pub attachments: Vec<TestAttachmentInfo>,
+}
+
+/// A file a test attached with `XCTAttachment`
 #[derive(Debug, Serialize)]
+pub struct TestAttachmentInfo {
+    /// Test that added it, e.g. "LoginTests/testValidLogin()"
+    pub test_identifier: String,
+    /// Name given to the attachment in the test
+    pub name: String,
+    /// MIME type, from the file extension
+    pub content_type: String,
+    /// Added as part of a test failure
+    pub associated_with_failure: bool,
+    /// When it was added (seconds since the Unix epoch)
+    pub timestamp: Option<f64>,
+    /// Device the test ran on
+    pub device_name: Option<String>,
+    /// Exported file on the server
+    pub path: String,
+    /// URL to download the file
+    pub url: String,
+}
+
+/// Live progress for an individual test case, sent as an SSE `test` event
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TestCaseEvent {
+    /// Test suite (class) name
+    pub suite: String,
+    /// Test method name
+    pub test: String,
+    /// Outcome: "started", "passed", "failed"
+    pub status: String,
+    /// Duration in seconds (once finished)
+    pub duration: Option<f64>,
+}
+
+/// A product bundle finished during a build, sent as an SSE `artifact` event
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ArtifactEvent {
+    /// Path to the bundle
+    pub path: String,
+    /// Target that produced it, when the log names one
+    pub target: Option<String>,
+}
+
+/// Phase marker for a build-and-run session, sent as an SSE `phase` event
+#[derive(Debug, Clone, Serialize)]
+pub struct RunPhaseEvent {
+    /// Phase: "build", "boot", "install", "launch"
+    pub phase: String,
+    /// "started", "success", "failed"
+    pub status: String,
+    /// Detail or error message
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    /// Build id (on the build phase's start marker)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub build_id: Option<String>,
+}
+
+/// Outcome of a single finished test case
+#[derive(Debug, Clone, Serialize)]
+pub struct TestCaseResult {
+    /// Test suite (class) name
+    pub suite: String,
+    /// Test method name
+    pub test: String,
+    /// "passed", "failed", "skipped"
+    pub status: String,
+    /// Duration in seconds
+    pub duration: Option<f64>,
+    /// Assertion failures reported by the test
+    pub failures: Vec<TestFailure>,
+}
+
+/// Individual test failure
+#[derive(Debug, Clone, Serialize)]
 pub struct TestFailure {
     /// Test name
     pub test_name: String,
@@ -157,4 +626,13 @@ pub struct TestFailure {
     pub file: Option<String>,
     /// Line number
     pub line: Option<u32>,
+    /// Simulator log lines from while the test ran (with `capture_runtime_logs`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub runtime_logs: Option<Vec<String>>,
+    /// Screenshot of the simulator when the test failed (with `screenshot_on_failure`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub screenshot_path: Option<String>,
+    /// URL to download the screenshot
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub screenshot_url: Option<String>,
 }
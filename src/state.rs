@@ -3,10 +3,34 @@
 
 //! Application state for xcbridge
 
+use crate::audit::AuditLog;
 use crate::config::Config;
-use std::collections::HashMap;
+use crate::disk;
+use crate::error::XcbridgeError;
+use crate::xcode::capabilities::{self, Capabilities};
+use crate::xcode::script_phase::{self, ScriptPhaseFailure};
+use crate::xcode::signing::{self, CodeSigningIssue};
+use crate::xcode::simctl::{self, Runtime, Simulator, SimulatorErase};
+use crate::xcode::xcodebuild::{self, BuildPhaseTiming, CancelSignal, CpuTime, Sdk};
+use crate::xcode::xcresult::{BuildDiagnostics, TestSummary};
+use axum::extract::FromRequestParts;
+use axum::http::request::Parts;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::convert::Infallible;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use tokio::sync::{
+    broadcast, Mutex, OwnedMutexGuard, OwnedSemaphorePermit, RwLock, RwLockWriteGuard, Semaphore,
+};
+
+/// Log lines buffered per job for subscribers that fall behind; a subscriber
+/// further behind than this reads the missed lines back from the job's log
+const LOG_CHANNEL_CAPACITY: usize = 1024;
+
+/// Recent successful runs of a scheme averaged to estimate a queued job's ETA
+const ETA_SAMPLE_SIZE: usize = 10;
 
 /// Status of a build
 #[derive(Debug, Clone)]
@@ -42,102 +66,1746 @@ impl BuildStatus {
             BuildStatus::Cancelled => &[],
         }
     }
+
+    /// Status name as reported by the API
+    pub fn name(&self) -> &'static str {
+        match self {
+            BuildStatus::Running { .. } => "running",
+            BuildStatus::Success { .. } => "success",
+            BuildStatus::Failed { .. } => "failed",
+            BuildStatus::Cancelled => "cancelled",
+        }
+    }
+}
+
+/// Tenant owning a build, derived from the API key that created it
+///
+/// The default (empty) namespace is used when authentication is disabled,
+/// in which case all builds are visible to every client.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Namespace(pub String);
+
+impl Namespace {
+    pub fn new(label: &str) -> Self {
+        Self(label.to_string())
+    }
+}
+
+/// Extracts the namespace the auth middleware resolved for the request
+#[axum::async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for Namespace {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(parts
+            .extensions
+            .get::<Namespace>()
+            .cloned()
+            .unwrap_or_default())
+    }
+}
+
+/// Known cause of a failed job, for callers that can act on it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FailureReason {
+    /// The disk filled up; free space (e.g. `POST /builds/cleanup`) rather
+    /// than retrying
+    DiskFull,
+    /// Signing failed; see the job's `code_signing` details
+    CodeSigning,
+    /// Swift package dependencies couldn't be resolved, so the build never ran
+    PackageResolution,
+    /// A run-script build phase exited unsuccessfully; see the job's
+    /// `script_phase` details
+    ScriptPhase,
+}
+
+impl FailureReason {
+    /// Reason name as reported by the API
+    pub fn name(&self) -> &'static str {
+        match self {
+            FailureReason::DiskFull => "disk_full",
+            FailureReason::CodeSigning => "code_signing",
+            FailureReason::PackageResolution => "package_resolution",
+            FailureReason::ScriptPhase => "script_phase",
+        }
+    }
+}
+
+/// Progress of one build in a batch
+#[derive(Debug, Clone, Serialize)]
+pub struct BatchStep {
+    /// Scheme or target built
+    pub scheme: String,
+    pub status: BatchStepStatus,
+    /// Products of the build (if successful)
+    pub artifacts: Vec<String>,
+    /// Error message (if failed)
+    pub error: Option<String>,
+    /// Time the build took in seconds (once finished)
+    pub duration_secs: Option<f64>,
+}
+
+impl BatchStep {
+    pub fn pending(scheme: &str) -> Self {
+        Self {
+            scheme: scheme.to_string(),
+            status: BatchStepStatus::Pending,
+            artifacts: Vec::new(),
+            error: None,
+            duration_secs: None,
+        }
+    }
+}
+
+/// Status of one build in a batch
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BatchStepStatus {
+    Pending,
+    Running,
+    Success,
+    Failed,
+    /// Not run, after an earlier build failed or the job was cancelled
+    Skipped,
+}
+
+/// Kind of job tracked in the build state
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobKind {
+    #[default]
+    Build,
+    Test,
+    /// Simulator runtime download or install
+    Runtime,
+    /// Localization export or import
+    Localization,
+}
+
+impl JobKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JobKind::Build => "build",
+            JobKind::Test => "test",
+            JobKind::Runtime => "runtime",
+            JobKind::Localization => "localization",
+        }
+    }
+}
+
+/// Time spent on finished jobs of one kind, since the server started
+#[derive(Debug, Clone, Copy, Default)]
+pub struct JobUsage {
+    pub finished: u64,
+    pub wall_secs: f64,
+    pub cpu_time: CpuTime,
+}
+
+/// A tracked build or test run
+#[derive(Debug, Clone)]
+pub struct BuildRecord {
+    pub status: BuildStatus,
+    /// DerivedData directory used by the build
+    pub derived_data_path: Option<PathBuf>,
+    /// Whether the DerivedData directory should be removed with the build
+    /// (server-assigned ones are, unless the request opted out)
+    pub owns_derived_data: bool,
+    /// Namespace of the client that started the job
+    pub namespace: Namespace,
+    pub kind: JobKind,
+    /// Client-chosen or generated name, for logs and dashboards
+    pub name: Option<String>,
+    /// Scheme being built or tested
+    pub scheme: Option<String>,
+    pub started_at: DateTime<Utc>,
+    /// When the job completed, failed or was cancelled
+    pub finished_at: Option<DateTime<Utc>>,
+    /// Errors and warnings read from the build's result bundle
+    pub diagnostics: Option<BuildDiagnostics>,
+    /// Test counts read from a test run's result bundle
+    pub test_summary: Option<TestSummary>,
+    /// Simulator log lines captured around each failed test, by test name
+    pub runtime_logs: HashMap<String, Vec<String>>,
+    /// Directory for screenshots of failed tests (with `screenshot_on_failure`)
+    pub screenshot_dir: Option<PathBuf>,
+    /// Screenshots taken as tests failed, by test name
+    pub failure_screenshots: HashMap<String, PathBuf>,
+    /// Where the result bundle's attachments were exported (once requested)
+    pub attachments_dir: Option<PathBuf>,
+    /// Result bundle written by the job (if any)
+    pub result_bundle_path: Option<PathBuf>,
+    /// Leave the result bundle on disk when the job is removed
+    pub keep_result_bundle: bool,
+    /// Known cause of the failure (if failed)
+    pub failure_reason: Option<FailureReason>,
+    /// Details of a code-signing failure
+    pub code_signing: Option<CodeSigningIssue>,
+    /// The run-script phase that failed, with what it printed
+    pub script_phase: Option<ScriptPhaseFailure>,
+    /// Stops the job's xcodebuild process when the job is cancelled
+    pub cancel: CancelSignal,
+    /// Outcome of erasing the test simulator after the run
+    pub simulator_erase: Option<SimulatorErase>,
+    /// The build failed on stale DerivedData and was rerun from clean
+    pub retried_clean: bool,
+    /// The test run was stopped at its first failing test
+    pub truncated: bool,
+    /// Time per kind of build task, from `-showBuildTimingSummary`
+    pub timing_summary: Option<Vec<BuildPhaseTiming>>,
+    /// Per-scheme progress of a batch build
+    pub batch: Option<Vec<BatchStep>>,
+    /// CPU time of the job's xcodebuild runs, once they have exited
+    pub cpu_time: Option<CpuTime>,
+    /// Publishes each log line, with its index in the log, as it is appended
+    pub log_channel: broadcast::Sender<(usize, String)>,
+    /// Waiting for a `--max-concurrent-jobs` slot
+    pub queued: bool,
+    /// When the job got a slot and started running
+    pub run_started_at: Option<DateTime<Utc>>,
+    /// Copies of the build's products in the request's `output_dir`
+    pub exported_artifacts: Option<Vec<String>>,
+}
+
+impl BuildRecord {
+    /// Time taken by the job, or so far if it is still running
+    pub fn duration_secs(&self) -> f64 {
+        let end = self.finished_at.unwrap_or_else(Utc::now);
+        (end - self.started_at).num_milliseconds() as f64 / 1000.0
+    }
+
+    /// Time spent running after leaving the queue, or so far if still running
+    fn run_secs(&self) -> Option<f64> {
+        let end = self.finished_at.unwrap_or_else(Utc::now);
+        Some((end - self.run_started_at?).num_milliseconds() as f64 / 1000.0)
+    }
+}
+
+/// Where a job waiting for a slot stands
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct QueueStatus {
+    /// 1 for the next job to start
+    pub position: usize,
+    /// Rough seconds until the job finishes, from recent runs of its scheme
+    /// and of the jobs ahead of it (None without successful runs to go by)
+    pub eta_secs: Option<f64>,
+}
+
+/// A `--max-concurrent-jobs` slot, released when dropped
+pub struct JobSlot {
+    _permit: Option<OwnedSemaphorePermit>,
+}
+
+impl Default for BuildRecord {
+    fn default() -> Self {
+        Self {
+            status: BuildStatus::Running { logs: Vec::new() },
+            derived_data_path: None,
+            owns_derived_data: false,
+            namespace: Namespace::default(),
+            kind: JobKind::Build,
+            name: None,
+            scheme: None,
+            started_at: Utc::now(),
+            finished_at: None,
+            diagnostics: None,
+            test_summary: None,
+            runtime_logs: HashMap::new(),
+            screenshot_dir: None,
+            failure_screenshots: HashMap::new(),
+            attachments_dir: None,
+            result_bundle_path: None,
+            keep_result_bundle: false,
+            failure_reason: None,
+            code_signing: None,
+            script_phase: None,
+            cancel: CancelSignal::default(),
+            simulator_erase: None,
+            retried_clean: false,
+            truncated: false,
+            timing_summary: None,
+            batch: None,
+            cpu_time: None,
+            log_channel: broadcast::channel(LOG_CHANNEL_CAPACITY).0,
+            queued: false,
+            run_started_at: None,
+            exported_artifacts: None,
+        }
+    }
+}
+
+/// Average run time of the last [`ETA_SAMPLE_SIZE`] successful jobs of the
+/// same kind and scheme as `job`
+fn average_run_secs(builds: &HashMap<String, BuildRecord>, job: &BuildRecord) -> Option<f64> {
+    let mut runs: Vec<(DateTime<Utc>, f64)> = builds
+        .values()
+        .filter(|record| record.kind == job.kind && record.scheme == job.scheme)
+        .filter(|record| matches!(record.status, BuildStatus::Success { .. }))
+        .filter_map(|record| Some((record.finished_at?, record.run_secs()?)))
+        .collect();
+    runs.sort_by_key(|(finished_at, _)| std::cmp::Reverse(*finished_at));
+    runs.truncate(ETA_SAMPLE_SIZE);
+    if runs.is_empty() {
+        return None;
+    }
+    Some(runs.iter().map(|(_, secs)| secs).sum::<f64>() / runs.len() as f64)
+}
+
+/// Toolchain facts about the host, cached so requests don't re-query them
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct HostInfo {
+    pub xcode_version: String,
+    /// Version of the standalone Command Line Tools (`None` if not installed)
+    pub command_line_tools_version: Option<String>,
+    pub sdks: Vec<Sdk>,
+    pub runtimes: Vec<Runtime>,
+    #[serde(skip)]
+    pub capabilities: Option<Capabilities>,
+    /// When the lists were last queried (`None` until the first refresh)
+    pub refreshed_at: Option<DateTime<Utc>>,
+}
+
+/// Result of the periodic CoreSimulator probe
+#[derive(Debug, Clone, Serialize)]
+pub struct SimulatorServiceHealth {
+    /// The last probe succeeded (assumed until the first probe runs)
+    pub healthy: bool,
+    /// When the service was last probed
+    pub checked_at: Option<DateTime<Utc>>,
+    /// Error from the most recent failed probe, kept after recovery
+    pub last_error: Option<String>,
+    /// When the most recent failed probe ran
+    pub last_error_at: Option<DateTime<Utc>>,
+}
+
+impl Default for SimulatorServiceHealth {
+    fn default() -> Self {
+        Self {
+            healthy: true,
+            checked_at: None,
+            last_error: None,
+            last_error_at: None,
+        }
+    }
+}
+
+/// A simulator reserved for one client's session
+///
+/// Leases are advisory: they keep other leases off the simulator, but
+/// requests that name it directly, by `destination` or UDID, are not
+/// checked against them.
+#[derive(Debug, Clone)]
+pub struct SimulatorLease {
+    pub lease_id: String,
+    /// Client-chosen tag; leasing the same tag again renews this lease
+    pub tag: String,
+    pub udid: String,
+    pub namespace: Namespace,
+    /// How long each renewal extends the lease by
+    pub ttl: chrono::Duration,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl SimulatorLease {
+    fn matches(&self, key: &str, namespace: &Namespace) -> bool {
+        &self.namespace == namespace && (self.lease_id == key || self.tag == key)
+    }
 }
 
 /// Shared application state
 pub struct AppState {
     pub config: Config,
-    pub builds: RwLock<HashMap<String, BuildStatus>>,
-    pub xcode_version: String,
+    pub builds: RwLock<HashMap<String, BuildRecord>>,
+    /// Fingerprints of successful builds, mapped to their build ids
+    pub build_cache: RwLock<HashMap<String, String>>,
+    pub host: RwLock<HostInfo>,
+    /// One lock per simulator UDID, held while it is booted, installed to,
+    /// launched in or erased
+    simulator_locks: Mutex<HashMap<String, Arc<Mutex<()>>>>,
+    /// Simulator leases by lease id; expired leases are dropped on access
+    leases: RwLock<HashMap<String, SimulatorLease>>,
+    /// Audit log of mutating requests (if `--audit-log` is set)
+    pub audit_log: Option<AuditLog>,
+    simulator_health: RwLock<SimulatorServiceHealth>,
+    /// Totals for finished jobs, kept after the jobs themselves are removed
+    job_usage: RwLock<HashMap<JobKind, JobUsage>>,
+    /// Slots for running builds and tests (None with no `--max-concurrent-jobs`)
+    job_slots: Option<Arc<Semaphore>>,
 }
 
 impl AppState {
     pub fn new(config: Config, xcode_version: String) -> Self {
         Self {
-            config,
             builds: RwLock::new(HashMap::new()),
-            xcode_version,
+            build_cache: RwLock::new(HashMap::new()),
+            host: RwLock::new(HostInfo {
+                xcode_version,
+                ..Default::default()
+            }),
+            simulator_locks: Mutex::new(HashMap::new()),
+            leases: RwLock::new(HashMap::new()),
+            audit_log: None,
+            simulator_health: RwLock::new(SimulatorServiceHealth::default()),
+            job_usage: RwLock::new(HashMap::new()),
+            job_slots: (config.max_concurrent_jobs > 0)
+                .then(|| Arc::new(Semaphore::new(config.max_concurrent_jobs))),
+            config,
+        }
+    }
+
+    /// Wait for exclusive use of a simulator
+    ///
+    /// Operations on the same simulator run one at a time, so concurrent
+    /// requests don't trip over each other's boots and installs; different
+    /// simulators still proceed in parallel.
+    pub async fn lock_simulator(&self, udid: &str) -> OwnedMutexGuard<()> {
+        let lock = {
+            let mut locks = self.simulator_locks.lock().await;
+            // Forget simulators nobody holds or waits for, so the map doesn't
+            // grow with every UDID ever seen
+            locks.retain(|_, lock| Arc::strong_count(lock) > 1);
+            Arc::clone(locks.entry(udid.to_ascii_uppercase()).or_default())
+        };
+        lock.lock_owned().await
+    }
+
+    /// Lease a simulator for `tag`, or renew the tag's existing lease
+    ///
+    /// `choose` picks the simulator for a new lease, given the (uppercased)
+    /// UDIDs other leases hold. Returns the lease and whether it is new.
+    pub async fn acquire_lease(
+        &self,
+        tag: &str,
+        namespace: &Namespace,
+        ttl: chrono::Duration,
+        choose: impl FnOnce(&HashSet<String>) -> crate::error::Result<Simulator>,
+    ) -> crate::error::Result<(SimulatorLease, bool)> {
+        let now = Utc::now();
+        let mut leases = self.leases.write().await;
+        leases.retain(|_, lease| lease.expires_at > now);
+
+        if let Some(lease) = leases
+            .values_mut()
+            .find(|lease| lease.tag == tag && &lease.namespace == namespace)
+        {
+            lease.ttl = ttl;
+            lease.expires_at = now + ttl;
+            return Ok((lease.clone(), false));
+        }
+
+        let leased = leases
+            .values()
+            .map(|lease| lease.udid.to_ascii_uppercase())
+            .collect();
+        let simulator = choose(&leased)?;
+        let lease = SimulatorLease {
+            lease_id: uuid::Uuid::new_v4().to_string(),
+            tag: tag.to_string(),
+            udid: simulator.udid,
+            namespace: namespace.clone(),
+            ttl,
+            expires_at: now + ttl,
+        };
+        leases.insert(lease.lease_id.clone(), lease.clone());
+        Ok((lease, true))
+    }
+
+    /// Extend a lease, found by its id or tag, by its TTL
+    pub async fn renew_lease(&self, key: &str, namespace: &Namespace) -> Option<SimulatorLease> {
+        let now = Utc::now();
+        let mut leases = self.leases.write().await;
+        leases.retain(|_, lease| lease.expires_at > now);
+
+        let lease = leases
+            .values_mut()
+            .find(|lease| lease.matches(key, namespace))?;
+        lease.expires_at = now + lease.ttl;
+        Some(lease.clone())
+    }
+
+    /// End a lease, found by its id or tag
+    pub async fn release_lease(&self, key: &str, namespace: &Namespace) -> Option<SimulatorLease> {
+        let mut leases = self.leases.write().await;
+        let lease_id = leases
+            .values()
+            .find(|lease| lease.matches(key, namespace))?
+            .lease_id
+            .clone();
+        leases
+            .remove(&lease_id)
+            .filter(|lease| lease.expires_at > Utc::now())
+    }
+
+    /// Cached host info
+    pub async fn host_info(&self) -> HostInfo {
+        self.host.read().await.clone()
+    }
+
+    /// Cached host capabilities, probing them if they haven't been yet
+    pub async fn capabilities(&self) -> Capabilities {
+        if let Some(capabilities) = self.host.read().await.capabilities.clone() {
+            return capabilities;
         }
+        self.refresh_host_info()
+            .await
+            .capabilities
+            .expect("refresh always probes capabilities")
+    }
+
+    /// Latest CoreSimulator probe result
+    pub async fn simulator_health(&self) -> SimulatorServiceHealth {
+        self.simulator_health.read().await.clone()
+    }
+
+    /// Probe CoreSimulator and record the result
+    pub async fn check_simulator_service(&self) -> SimulatorServiceHealth {
+        let result = simctl::probe_service().await;
+        let now = Utc::now();
+
+        let mut health = self.simulator_health.write().await;
+        match result {
+            Ok(()) => {
+                if !health.healthy {
+                    tracing::info!("CoreSimulator service is responding again");
+                }
+                health.healthy = true;
+            }
+            Err(e) => {
+                if health.healthy {
+                    tracing::warn!("CoreSimulator service is unhealthy: {}", e);
+                }
+                health.healthy = false;
+                health.last_error = Some(e.to_string());
+                health.last_error_at = Some(now);
+            }
+        }
+        health.checked_at = Some(now);
+        health.clone()
+    }
+
+    /// Re-query the Xcode and Command Line Tools versions, SDKs, runtimes
+    /// and capabilities
+    ///
+    /// A query that fails keeps its previous value.
+    pub async fn refresh_host_info(&self) -> HostInfo {
+        let (version, clt_version, sdks, runtimes) = tokio::join!(
+            xcodebuild::get_xcode_version(),
+            xcodebuild::command_line_tools_version(),
+            xcodebuild::list_sdks(),
+            simctl::list_runtimes(),
+        );
+
+        let mut host = self.host_info().await;
+        match version {
+            Ok(version) => host.xcode_version = version,
+            Err(e) => tracing::warn!("Failed to refresh Xcode version: {}", e),
+        }
+        host.command_line_tools_version = clt_version;
+        match sdks {
+            Ok(sdks) => host.sdks = sdks,
+            Err(e) => tracing::warn!("Failed to refresh SDK list: {}", e),
+        }
+        match runtimes {
+            Ok(runtimes) => host.runtimes = runtimes,
+            Err(e) => tracing::warn!("Failed to refresh simulator runtimes: {}", e),
+        }
+        host.capabilities = Some(capabilities::probe(&host.xcode_version, &host.runtimes).await);
+        host.refreshed_at = Some(Utc::now());
+
+        *self.host.write().await = host.clone();
+        host
     }
 
     /// Create a new build entry
-    pub async fn create_build(&self, build_id: &str) {
+    #[cfg(test)]
+    pub async fn create_build(&self, build_id: &str) -> crate::error::Result<()> {
+        self.insert_build(build_id, BuildRecord::default()).await
+    }
+
+    /// Track a new build with pre-populated metadata
+    ///
+    /// Fails with [`XcbridgeError::TooBusy`] once `--max-builds` jobs are
+    /// tracked, running or completed, so a burst of submissions is pushed
+    /// back to clients rather than held in memory.
+    pub async fn insert_build(
+        &self,
+        build_id: &str,
+        record: BuildRecord,
+    ) -> crate::error::Result<()> {
         let mut builds = self.builds.write().await;
-        builds.insert(
-            build_id.to_string(),
-            BuildStatus::Running { logs: Vec::new() },
-        );
+        let max_builds = self.config.max_builds;
+        if max_builds > 0 && builds.len() >= max_builds {
+            return Err(XcbridgeError::TooBusy(max_builds));
+        }
+        builds.insert(build_id.to_string(), record);
+        Ok(())
     }
 
-    /// Append a log line to a build
+    /// Append a log line to a build and publish it to log subscribers
     pub async fn append_build_log(&self, build_id: &str, line: String) {
         let mut builds = self.builds.write().await;
-        if let Some(BuildStatus::Running { logs }) = builds.get_mut(build_id) {
+        if let Some(BuildRecord {
+            status: BuildStatus::Running { logs },
+            log_channel,
+            ..
+        }) = builds.get_mut(build_id)
+        {
+            if log_channel.receiver_count() > 0 {
+                let _ = log_channel.send((logs.len(), line.clone()));
+            }
             logs.push(line);
         }
     }
 
+    /// Subscribe to a job's new log lines, along with the lines logged so far
+    ///
+    /// Both are read under one lock, so the first line received is the one
+    /// right after the backlog.
+    pub async fn subscribe_logs(
+        &self,
+        build_id: &str,
+    ) -> Option<(Vec<String>, broadcast::Receiver<(usize, String)>)> {
+        let builds = self.builds.read().await;
+        let record = builds.get(build_id)?;
+        Some((
+            record.status.logs().to_vec(),
+            record.log_channel.subscribe(),
+        ))
+    }
+
+    /// A job's log lines from index `from` on
+    pub async fn logs_since(&self, build_id: &str, from: usize) -> Option<Vec<String>> {
+        let builds = self.builds.read().await;
+        let logs = builds.get(build_id)?.status.logs();
+        Some(logs.get(from..).unwrap_or_default().to_vec())
+    }
+
+    /// Whether a job has finished, without copying its log
+    pub async fn is_build_complete(&self, build_id: &str) -> Option<bool> {
+        let builds = self.builds.read().await;
+        builds
+            .get(build_id)
+            .map(|record| record.status.is_complete())
+    }
+
+    /// Record that a build is being rerun from clean
+    pub async fn mark_retried_clean(&self, build_id: &str) {
+        let mut builds = self.builds.write().await;
+        if let Some(record) = builds.get_mut(build_id) {
+            record.retried_clean = true;
+        }
+    }
+
+    /// Record that a test run was stopped at its first failing test
+    pub async fn mark_truncated(&self, build_id: &str) {
+        let mut builds = self.builds.write().await;
+        if let Some(record) = builds.get_mut(build_id) {
+            record.truncated = true;
+        }
+    }
+
+    /// Record where a build's products were copied
+    pub async fn set_exported_artifacts(&self, build_id: &str, exported: Vec<String>) {
+        let mut builds = self.builds.write().await;
+        if let Some(record) = builds.get_mut(build_id) {
+            record.exported_artifacts = Some(exported);
+        }
+    }
+
+    /// Attach a build's timing summary
+    pub async fn set_timing_summary(&self, build_id: &str, timings: Vec<BuildPhaseTiming>) {
+        let mut builds = self.builds.write().await;
+        if let Some(record) = builds.get_mut(build_id) {
+            record.timing_summary = Some(timings);
+        }
+    }
+
+    /// Attach result bundle diagnostics to a build
+    pub async fn set_build_diagnostics(&self, build_id: &str, diagnostics: BuildDiagnostics) {
+        let mut builds = self.builds.write().await;
+        if let Some(record) = builds.get_mut(build_id) {
+            record.diagnostics = Some(diagnostics);
+        }
+    }
+
+    /// Attach a result bundle test summary to a test run
+    pub async fn set_test_summary(&self, test_id: &str, summary: TestSummary) {
+        let mut builds = self.builds.write().await;
+        if let Some(record) = builds.get_mut(test_id) {
+            record.test_summary = Some(summary);
+        }
+    }
+
+    /// Attach captured simulator logs to a test run's failures
+    pub async fn set_runtime_logs(
+        &self,
+        test_id: &str,
+        runtime_logs: HashMap<String, Vec<String>>,
+    ) {
+        let mut builds = self.builds.write().await;
+        if let Some(record) = builds.get_mut(test_id) {
+            record.runtime_logs = runtime_logs;
+        }
+    }
+
+    /// Attach screenshots of the simulator to a test run's failures
+    pub async fn set_failure_screenshots(
+        &self,
+        test_id: &str,
+        screenshots: HashMap<String, PathBuf>,
+    ) {
+        let mut builds = self.builds.write().await;
+        if let Some(record) = builds.get_mut(test_id) {
+            record.failure_screenshots = screenshots;
+        }
+    }
+
+    /// Record where a test run's attachments were exported
+    pub async fn set_attachments_dir(&self, test_id: &str, dir: PathBuf) {
+        let mut builds = self.builds.write().await;
+        if let Some(record) = builds.get_mut(test_id) {
+            record.attachments_dir = Some(dir);
+        }
+    }
+
+    /// Update one build of a batch
+    pub async fn set_batch_step(&self, build_id: &str, index: usize, step: BatchStep) {
+        let mut builds = self.builds.write().await;
+        if let Some(slot) = builds
+            .get_mut(build_id)
+            .and_then(|record| record.batch.as_mut())
+            .and_then(|batch| batch.get_mut(index))
+        {
+            *slot = step;
+        }
+    }
+
+    /// Add the CPU time of one of a job's xcodebuild runs
+    ///
+    /// The usage totals get a job's CPU time when it finishes, or straight
+    /// away for a run that exits after its job was cancelled.
+    pub async fn add_cpu_time(&self, build_id: &str, cpu_time: Option<CpuTime>) {
+        let Some(cpu_time) = cpu_time else { return };
+        let mut builds = self.builds.write().await;
+        if let Some(record) = builds.get_mut(build_id) {
+            *record.cpu_time.get_or_insert_with(CpuTime::default) += cpu_time;
+            if record.finished_at.is_some() {
+                let mut usage = self.job_usage.write().await;
+                usage.entry(record.kind).or_default().cpu_time += cpu_time;
+            }
+        }
+    }
+
+    /// Totals for finished jobs by kind
+    pub async fn job_usage(&self) -> HashMap<JobKind, JobUsage> {
+        self.job_usage.read().await.clone()
+    }
+
+    /// Wait for a slot to run a job in, with `--max-concurrent-jobs`
+    ///
+    /// Jobs get slots in the order they asked for them. The job is marked
+    /// as queued while it waits. Returns `None` if it is cancelled first.
+    pub async fn acquire_job_slot(&self, build_id: &str, cancel: &CancelSignal) -> Option<JobSlot> {
+        let permit = match &self.job_slots {
+            None => None,
+            Some(slots) => match Arc::clone(slots).try_acquire_owned() {
+                Ok(permit) => Some(permit),
+                Err(_) => {
+                    self.set_queued(build_id, true).await;
+                    self.append_build_log(build_id, "Waiting for a free job slot".to_string())
+                        .await;
+                    let acquired = tokio::select! {
+                        permit = Arc::clone(slots).acquire_owned() => permit.ok(),
+                        _ = cancel.cancelled() => None,
+                    };
+                    self.set_queued(build_id, false).await;
+                    Some(acquired?)
+                }
+            },
+        };
+
+        if let Some(record) = self.builds.write().await.get_mut(build_id) {
+            record.run_started_at = Some(Utc::now());
+        }
+        Some(JobSlot { _permit: permit })
+    }
+
+    async fn set_queued(&self, build_id: &str, queued: bool) {
+        if let Some(record) = self.builds.write().await.get_mut(build_id) {
+            record.queued = queued;
+        }
+    }
+
+    /// Position and estimated completion of a job waiting for a slot
+    ///
+    /// The ETA assumes each job takes as long as the average of its scheme's
+    /// recent successful runs, with the work ahead shared between the slots.
+    pub async fn queue_status(&self, build_id: &str) -> Option<QueueStatus> {
+        let builds = self.builds.read().await;
+        let job = builds.get(build_id).filter(|record| record.queued)?;
+
+        let mut ahead: Vec<&BuildRecord> = builds
+            .values()
+            .filter(|record| record.queued && record.started_at < job.started_at)
+            .collect();
+        let position = ahead.len() + 1;
+
+        let Some(own) = average_run_secs(&builds, job) else {
+            return Some(QueueStatus {
+                position,
+                eta_secs: None,
+            });
+        };
+        let estimate = |record: &BuildRecord| average_run_secs(&builds, record).unwrap_or(own);
+
+        // Running jobs are counted by how long they have left
+        ahead.extend(
+            builds
+                .values()
+                .filter(|record| !record.status.is_complete() && record.run_started_at.is_some()),
+        );
+        let work_ahead: f64 = ahead
+            .into_iter()
+            .map(|record| {
+                let elapsed = record.run_secs().unwrap_or(0.0);
+                (estimate(record) - elapsed).max(0.0)
+            })
+            .sum();
+        let slots = self.config.max_concurrent_jobs.max(1) as f64;
+
+        Some(QueueStatus {
+            position,
+            eta_secs: Some(work_ahead / slots + own),
+        })
+    }
+
+    /// Stamp a job's finish time and count it towards the usage totals
+    async fn finish(&self, record: &mut BuildRecord) {
+        record.finished_at = Some(Utc::now());
+        let mut usage = self.job_usage.write().await;
+        let usage = usage.entry(record.kind).or_default();
+        usage.finished += 1;
+        usage.wall_secs += record.duration_secs();
+        usage.cpu_time += record.cpu_time.unwrap_or_default();
+    }
+
+    /// Record the outcome of erasing a test run's simulator
+    pub async fn set_simulator_erase(&self, test_id: &str, erase: SimulatorErase) {
+        let mut builds = self.builds.write().await;
+        if let Some(record) = builds.get_mut(test_id) {
+            record.simulator_erase = Some(erase);
+        }
+    }
+
     /// Mark a build as successful
     pub async fn complete_build(&self, build_id: &str, artifacts: Vec<String>) {
         let mut builds = self.builds.write().await;
-        if let Some(status) = builds.get_mut(build_id) {
+        if let Some(record) = builds.get_mut(build_id) {
+            let status = &mut record.status;
             if let BuildStatus::Running { logs } = status {
                 *status = BuildStatus::Success {
                     logs: std::mem::take(logs),
                     artifacts,
                 };
+                self.finish(record).await;
             }
         }
     }
 
     /// Mark a build as failed
+    ///
+    /// Failures caused by a full disk are flagged with
+    /// [`FailureReason::DiskFull`] and report the space left on the build's
+    /// volume. Code-signing failures are flagged with
+    /// [`FailureReason::CodeSigning`] along with the parsed details.
     pub async fn fail_build(&self, build_id: &str, error: String, exit_code: Option<i32>) {
+        self.fail_build_with_reason(build_id, error, exit_code, None)
+            .await
+    }
+
+    /// Mark a job failed for a known `reason`, unless its output shows a
+    /// more specific one (a full disk, a signing failure or a failed
+    /// run-script phase)
+    pub async fn fail_build_with_reason(
+        &self,
+        build_id: &str,
+        error: String,
+        exit_code: Option<i32>,
+        reason: Option<FailureReason>,
+    ) {
+        let code_signing = self.signing_error(build_id, &error).await;
+        let script_phase = self.script_phase_failure(build_id).await;
+        let (error, failure_reason) = if let Some(issue) = &code_signing {
+            let error = XcbridgeError::CodeSigningError(issue.clone()).to_string();
+            (error, Some(FailureReason::CodeSigning))
+        } else if let Some(volume) = self.disk_full_volume(build_id, &error).await {
+            let free = disk::free_space(&volume)
+                .await
+                .map(disk::format_bytes)
+                .unwrap_or_else(|| "unknown".to_string());
+            let error = format!("Disk full: {} ({} free)", error, free);
+            (error, Some(FailureReason::DiskFull))
+        } else if let Some(failure) = &script_phase {
+            let error = match failure.output.last() {
+                Some(line) => format!("Script phase \"{}\" failed: {}", failure.script, line),
+                None => format!("Script phase \"{}\" failed: {}", failure.script, error),
+            };
+            (error, Some(FailureReason::ScriptPhase))
+        } else {
+            (error, reason)
+        };
+
         let mut builds = self.builds.write().await;
-        if let Some(status) = builds.get_mut(build_id) {
+        if let Some(record) = builds.get_mut(build_id) {
+            let status = &mut record.status;
             if let BuildStatus::Running { logs } = status {
                 *status = BuildStatus::Failed {
                     logs: std::mem::take(logs),
                     error,
                     exit_code,
                 };
+                record.failure_reason = failure_reason;
+                record.code_signing = code_signing;
+                record.script_phase = script_phase;
+                self.finish(record).await;
             }
         }
     }
 
+    /// The first run-script phase that failed in a job's logs
+    async fn script_phase_failure(&self, build_id: &str) -> Option<ScriptPhaseFailure> {
+        let builds = self.builds.read().await;
+        let record = builds.get(build_id)?;
+        script_phase::find_script_phase_failure(record.status.logs().iter().map(String::as_str))
+    }
+
+    /// The first code-signing failure in a job's error or logs
+    async fn signing_error(&self, build_id: &str, error: &str) -> Option<CodeSigningIssue> {
+        let builds = self.builds.read().await;
+        let record = builds.get(build_id)?;
+        let lines = std::iter::once(error).chain(record.status.logs().iter().map(String::as_str));
+        signing::find_signing_error(lines)
+    }
+
+    /// Path on the volume a job was writing to, if it ran out of disk space
+    async fn disk_full_volume(&self, build_id: &str, error: &str) -> Option<PathBuf> {
+        let builds = self.builds.read().await;
+        let record = builds.get(build_id)?;
+        let disk_full = disk::is_disk_full_error(error)
+            || record
+                .status
+                .logs()
+                .iter()
+                .any(|l| disk::is_disk_full_error(l));
+
+        disk_full.then(|| {
+            record
+                .derived_data_path
+                .clone()
+                .unwrap_or_else(|| self.config.derived_data_base())
+        })
+    }
+
+    /// Whether a built product may be read by `namespace`: within
+    /// `--allowed-paths`, or under the DerivedData of one of the namespace's
+    /// tracked jobs, where products land even outside the allowed paths
+    pub async fn is_product_path_allowed(&self, path: &Path, namespace: &Namespace) -> bool {
+        if self.config.is_path_allowed(path) {
+            return true;
+        }
+        let Ok(canonical) = path.canonicalize() else {
+            return false;
+        };
+        self.builds
+            .read()
+            .await
+            .values()
+            .filter(|record| &record.namespace == namespace)
+            .filter_map(|record| record.derived_data_path.as_deref())
+            .any(|dir| {
+                dir.canonicalize()
+                    .is_ok_and(|dir| canonical.starts_with(dir))
+            })
+    }
+
     /// Get build status
     pub async fn get_build(&self, build_id: &str) -> Option<BuildStatus> {
+        let builds = self.builds.read().await;
+        builds.get(build_id).map(|record| record.status.clone())
+    }
+
+    /// Get a build's status along with its metadata
+    pub async fn get_build_record(&self, build_id: &str) -> Option<BuildRecord> {
         let builds = self.builds.read().await;
         builds.get(build_id).cloned()
     }
 
-    /// Cancel a build
-    pub async fn cancel_build(&self, build_id: &str) -> bool {
+    /// Get a build's record if it belongs to `namespace`
+    ///
+    /// Builds of other namespaces are reported as missing, so clients can't
+    /// probe for each other's build ids.
+    pub async fn get_owned_build(
+        &self,
+        build_id: &str,
+        namespace: &Namespace,
+    ) -> Option<BuildRecord> {
+        self.get_build_record(build_id)
+            .await
+            .filter(|record| &record.namespace == namespace)
+    }
+
+    /// Most recent jobs for a scheme in a namespace, newest first
+    pub async fn recent_builds(
+        &self,
+        namespace: &Namespace,
+        scheme: &str,
+        limit: usize,
+    ) -> Vec<(String, BuildRecord)> {
+        let builds = self.builds.read().await;
+        let mut recent: Vec<_> = builds
+            .iter()
+            .filter(|(_, record)| &record.namespace == namespace)
+            .filter(|(_, record)| record.scheme.as_deref() == Some(scheme))
+            .map(|(id, record)| (id.clone(), record.clone()))
+            .collect();
+        recent.sort_by_key(|(_, record)| std::cmp::Reverse(record.started_at));
+        recent.truncate(limit);
+        recent
+    }
+
+    /// Look up a successful build of the namespace by fingerprint, returning
+    /// its id and artifacts
+    pub async fn get_cached_build(
+        &self,
+        fingerprint: &str,
+        namespace: &Namespace,
+    ) -> Option<(String, Vec<String>)> {
+        let build_id = self.build_cache.read().await.get(fingerprint).cloned()?;
+        match self.get_owned_build(&build_id, namespace).await {
+            Some(BuildRecord {
+                status: BuildStatus::Success { artifacts, .. },
+                ..
+            }) => Some((build_id, artifacts)),
+            _ => None,
+        }
+    }
+
+    /// Record the fingerprint of a successful build
+    pub async fn cache_build(&self, fingerprint: String, build_id: &str) {
+        let mut cache = self.build_cache.write().await;
+        cache.insert(fingerprint, build_id.to_string());
+    }
+
+    /// Cancel a build of the namespace
+    pub async fn cancel_build(&self, build_id: &str, namespace: &Namespace) -> bool {
         let mut builds = self.builds.write().await;
-        if let Some(status) = builds.get_mut(build_id) {
-            if matches!(status, BuildStatus::Running { .. }) {
-                *status = BuildStatus::Cancelled;
+        if let Some(record) = builds.get_mut(build_id) {
+            if &record.namespace == namespace
+                && matches!(record.status, BuildStatus::Running { .. })
+            {
+                record.status = BuildStatus::Cancelled;
+                self.finish(record).await;
+                record.cancel.cancel();
                 return true;
             }
         }
         false
     }
 
+    /// Cancel the namespace's running jobs with a matching scheme and name
+    ///
+    /// Filters left as `None` match any job. Returns the ids cancelled.
+    pub async fn cancel_matching_builds(
+        &self,
+        namespace: &Namespace,
+        scheme: Option<&str>,
+        name: Option<&str>,
+    ) -> Vec<String> {
+        let mut builds = self.builds.write().await;
+        let mut cancelled = Vec::new();
+        for (id, record) in builds.iter_mut() {
+            if &record.namespace == namespace
+                && matches!(record.status, BuildStatus::Running { .. })
+                && scheme.is_none_or(|scheme| record.scheme.as_deref() == Some(scheme))
+                && name.is_none_or(|name| record.name.as_deref() == Some(name))
+            {
+                record.status = BuildStatus::Cancelled;
+                self.finish(record).await;
+                record.cancel.cancel();
+                cancelled.push(id.clone());
+            }
+        }
+        cancelled.sort();
+        cancelled
+    }
+
+    /// Cancel every running build and test, in all namespaces
+    ///
+    /// Returns the number of jobs cancelled.
+    pub async fn cancel_all_builds(&self) -> usize {
+        let mut builds = self.builds.write().await;
+        let mut cancelled = 0;
+        for record in builds.values_mut() {
+            if matches!(record.status, BuildStatus::Running { .. }) {
+                record.status = BuildStatus::Cancelled;
+                self.finish(record).await;
+                record.cancel.cancel();
+                cancelled += 1;
+            }
+        }
+        cancelled
+    }
+
     /// Clean up old completed builds (call periodically)
+    ///
+    /// Server-assigned DerivedData directories of removed builds are deleted.
     pub async fn cleanup_old_builds(&self, max_completed: usize) {
-        let mut builds = self.builds.write().await;
-        let completed: Vec<_> = builds
+        let builds = self.builds.write().await;
+        let mut completed: Vec<_> = builds
             .iter()
-            .filter(|(_, status)| status.is_complete())
-            .map(|(id, _)| id.clone())
+            .filter(|(_, record)| record.status.is_complete())
+            .map(|(id, record)| (record.finished_at, id.clone()))
             .collect();
+        // Oldest first
+        completed.sort();
 
         let remove_count = completed.len().saturating_sub(max_completed);
-        if remove_count > 0 {
-            for id in completed.into_iter().take(remove_count) {
-                builds.remove(&id);
+        let ids: Vec<_> = completed
+            .into_iter()
+            .take(remove_count)
+            .map(|(_, id)| id)
+            .collect();
+        self.remove_builds(builds, &ids).await;
+    }
+
+    /// Remove all of a namespace's completed jobs and their DerivedData
+    ///
+    /// Returns the number of jobs removed.
+    pub async fn cleanup_completed_builds(&self, namespace: &Namespace) -> usize {
+        let builds = self.builds.write().await;
+        let ids: Vec<_> = builds
+            .iter()
+            .filter(|(_, record)| record.status.is_complete() && &record.namespace == namespace)
+            .map(|(id, _)| id.clone())
+            .collect();
+        self.remove_builds(builds, &ids).await;
+        ids.len()
+    }
+
+    /// Remove jobs along with their cache entries, server-assigned DerivedData,
+    /// failure screenshots, exported attachments and result bundles (unless
+    /// kept)
+    async fn remove_builds(
+        &self,
+        mut builds: RwLockWriteGuard<'_, HashMap<String, BuildRecord>>,
+        ids: &[String],
+    ) {
+        if ids.is_empty() {
+            return;
+        }
+
+        let mut derived_data = Vec::new();
+        let mut result_bundles = Vec::new();
+        let mut screenshots = Vec::new();
+        let mut attachments = Vec::new();
+        for id in ids {
+            if let Some(record) = builds.remove(id) {
+                if record.owns_derived_data {
+                    derived_data.extend(record.derived_data_path);
+                }
+                if !record.keep_result_bundle {
+                    result_bundles.extend(record.result_bundle_path);
+                }
+                screenshots.extend(record.screenshot_dir);
+                attachments.extend(record.attachments_dir);
             }
         }
+
+        let mut cache = self.build_cache.write().await;
+        cache.retain(|_, build_id| builds.contains_key(build_id));
+        drop(cache);
+        drop(builds);
+
+        for path in derived_data {
+            remove_derived_data(&path).await;
+        }
+        for path in result_bundles {
+            remove_result_bundle(&path).await;
+        }
+        for path in screenshots {
+            remove_dir(&path, "screenshots").await;
+        }
+        for path in attachments {
+            remove_dir(&path, "attachments").await;
+        }
+    }
+}
+
+/// Delete a DerivedData directory owned by a build
+pub async fn remove_derived_data(path: &std::path::Path) {
+    remove_dir(path, "DerivedData").await;
+}
+
+/// Delete the workspace an uploaded project was extracted into
+pub async fn remove_upload(path: &std::path::Path) {
+    remove_dir(path, "upload").await;
+}
+
+/// Delete a job's .xcresult bundle
+pub async fn remove_result_bundle(path: &std::path::Path) {
+    remove_dir(path, "result bundle").await;
+}
+
+async fn remove_dir(path: &std::path::Path, what: &str) {
+    tracing::debug!("Removing {} {}", what, path.display());
+    if let Err(e) = tokio::fs::remove_dir_all(path).await {
+        if e.kind() != std::io::ErrorKind::NotFound {
+            tracing::warn!("Failed to remove {} {}: {}", what, path.display(), e);
+        }
     }
 }
 
 pub type SharedState = Arc<AppState>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    fn record(scheme: &str, started_at: DateTime<Utc>) -> BuildRecord {
+        BuildRecord {
+            scheme: Some(scheme.to_string()),
+            started_at,
+            ..BuildRecord::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_check_simulator_service_keeps_last_error() {
+        use crate::xcode::runner::{self, MockRunner};
+
+        let state = AppState::new(Config::parse_from(["xcbridge"]), String::new());
+        assert!(state.simulator_health().await.healthy);
+
+        let wedged = Arc::new(MockRunner::new().fail(
+            &["xcrun", "simctl", "list"],
+            "CoreSimulatorService connection became invalid",
+        ));
+        let health = runner::with_runner(wedged, state.check_simulator_service()).await;
+        assert!(!health.healthy);
+        assert!(health
+            .last_error
+            .as_deref()
+            .is_some_and(|e| e.contains("connection became invalid")));
+
+        let recovered =
+            Arc::new(MockRunner::new().on(&["xcrun", "simctl", "list"], r#"{"runtimes": []}"#));
+        let health = runner::with_runner(recovered, state.check_simulator_service()).await;
+        assert!(health.healthy);
+        assert!(health.last_error.is_some());
+        assert!(health.checked_at > health.last_error_at);
+    }
+
+    #[tokio::test]
+    async fn test_lock_simulator_serializes_per_udid() {
+        let state = AppState::new(Config::parse_from(["xcbridge"]), String::new());
+
+        let guard = state.lock_simulator("AAAA-1111").await;
+        // A different simulator isn't blocked
+        let other = state.lock_simulator("BBBB-2222").await;
+        drop(other);
+
+        // The same simulator (in any case) waits for the first holder
+        let waiting = tokio::time::timeout(
+            std::time::Duration::from_millis(50),
+            state.lock_simulator("aaaa-1111"),
+        )
+        .await;
+        assert!(waiting.is_err());
+
+        drop(guard);
+        let _guard = state.lock_simulator("AAAA-1111").await;
+        // BBBB-2222 was released, so its lock is gone
+        assert_eq!(state.simulator_locks.lock().await.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_simulator_leases() {
+        let state = AppState::new(Config::parse_from(["xcbridge"]), String::new());
+        let ttl = chrono::Duration::minutes(5);
+        let simulator = |udid: &str| Simulator {
+            udid: udid.to_string(),
+            name: "iPhone 15".to_string(),
+            state: "Booted".to_string(),
+            is_available: true,
+            device_type_identifier: None,
+            data_path: None,
+            log_path: None,
+            availability_error: None,
+            runtime: None,
+        };
+        let ci = Namespace::new("ci");
+
+        let (lease, new) = state
+            .acquire_lease("agent-1", &ci, ttl, |_| Ok(simulator("aaaa")))
+            .await
+            .unwrap();
+        assert!(new);
+
+        // The same tag renews; another tag sees the simulator as taken
+        let (again, new) = state
+            .acquire_lease("agent-1", &ci, ttl, |_| unreachable!())
+            .await
+            .unwrap();
+        assert!(!new);
+        assert_eq!(again.lease_id, lease.lease_id);
+        state
+            .acquire_lease("agent-2", &ci, ttl, |leased| {
+                assert!(leased.contains("AAAA"));
+                Ok(simulator("BBBB"))
+            })
+            .await
+            .unwrap();
+
+        assert!(state
+            .renew_lease("agent-1", &Namespace::default())
+            .await
+            .is_none());
+        assert_eq!(
+            state.renew_lease(&lease.lease_id, &ci).await.unwrap().udid,
+            "aaaa"
+        );
+        assert!(state.release_lease("agent-1", &ci).await.is_some());
+        assert!(state.renew_lease("agent-1", &ci).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_recent_builds_newest_first() {
+        let state = AppState::new(Config::parse_from(["xcbridge"]), String::new());
+        let now = Utc::now();
+        for (id, scheme, age) in [
+            ("a", "App", 3),
+            ("b", "App", 1),
+            ("c", "Other", 0),
+            ("d", "App", 2),
+        ] {
+            state
+                .insert_build(id, record(scheme, now - chrono::Duration::seconds(age)))
+                .await
+                .unwrap();
+        }
+
+        let recent = state.recent_builds(&Namespace::default(), "App", 2).await;
+        let ids: Vec<_> = recent.iter().map(|(id, _)| id.as_str()).collect();
+
+        assert_eq!(ids, ["b", "d"]);
+    }
+
+    #[tokio::test]
+    async fn test_cancel_all_builds_signals_running_jobs() {
+        let state = AppState::new(Config::parse_from(["xcbridge"]), String::new());
+        let mut signals = Vec::new();
+        for (id, namespace) in [("a", "ci"), ("b", "dev")] {
+            let cancel = CancelSignal::default();
+            state
+                .insert_build(
+                    id,
+                    BuildRecord {
+                        namespace: Namespace::new(namespace),
+                        cancel: cancel.clone(),
+                        ..BuildRecord::default()
+                    },
+                )
+                .await
+                .unwrap();
+            signals.push(cancel);
+        }
+        state.create_build("done").await.unwrap();
+        state.complete_build("done", vec![]).await;
+
+        assert_eq!(state.cancel_all_builds().await, 2);
+
+        for signal in &signals {
+            tokio::time::timeout(std::time::Duration::from_secs(1), signal.cancelled())
+                .await
+                .unwrap();
+        }
+        assert!(matches!(
+            state.get_build("a").await,
+            Some(BuildStatus::Cancelled)
+        ));
+        assert!(matches!(
+            state.get_build("done").await,
+            Some(BuildStatus::Success { .. })
+        ));
+        assert_eq!(state.cancel_all_builds().await, 0);
+    }
+
+    #[tokio::test]
+    async fn test_insert_build_rejects_past_max_builds() {
+        let state = AppState::new(
+            Config::parse_from(["xcbridge", "--max-builds", "2"]),
+            String::new(),
+        );
+        state.create_build("first").await.unwrap();
+        state.create_build("second").await.unwrap();
+        state.complete_build("first", vec![]).await;
+
+        assert!(matches!(
+            state.create_build("third").await,
+            Err(XcbridgeError::TooBusy(2))
+        ));
+        state.cleanup_old_builds(0).await;
+        state.create_build("third").await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_queued_job_position_and_eta() {
+        let state = Arc::new(AppState::new(
+            Config::parse_from(["xcbridge", "--max-concurrent-jobs", "1"]),
+            String::new(),
+        ));
+        let now = Utc::now();
+        let job = |started_at| BuildRecord {
+            scheme: Some("App".to_string()),
+            started_at,
+            ..BuildRecord::default()
+        };
+        // A previous run of the scheme took 100s
+        state
+            .insert_build(
+                "previous",
+                BuildRecord {
+                    status: BuildStatus::Success {
+                        logs: vec![],
+                        artifacts: vec![],
+                    },
+                    run_started_at: Some(now - chrono::Duration::seconds(100)),
+                    finished_at: Some(now),
+                    ..job(now - chrono::Duration::seconds(100))
+                },
+            )
+            .await
+            .unwrap();
+        state.insert_build("running", job(now)).await.unwrap();
+        state
+            .insert_build("waiting", job(now + chrono::Duration::seconds(1)))
+            .await
+            .unwrap();
+
+        let slot = state
+            .acquire_job_slot("running", &CancelSignal::default())
+            .await;
+        assert!(slot.is_some());
+        assert_eq!(state.queue_status("running").await, None);
+
+        let cancel = CancelSignal::default();
+        let waiter = tokio::spawn({
+            let state = Arc::clone(&state);
+            let cancel = cancel.clone();
+            async move { state.acquire_job_slot("waiting", &cancel).await.is_some() }
+        });
+        while state.queue_status("waiting").await.is_none() {
+            tokio::task::yield_now().await;
+        }
+        let queue = state.queue_status("waiting").await.unwrap();
+        assert_eq!(queue.position, 1);
+        // What's left of the running job's 100s, then its own 100s
+        let eta = queue.eta_secs.unwrap();
+        assert!(eta > 190.0 && eta <= 200.0, "eta {}", eta);
+
+        // Cancelling a queued job stops it waiting
+        cancel.cancel();
+        assert!(!waiter.await.unwrap());
+        assert_eq!(state.queue_status("waiting").await, None);
+    }
+
+    #[tokio::test]
+    async fn test_job_usage_outlives_removed_jobs() {
+        let state = AppState::new(Config::parse_from(["xcbridge"]), String::new());
+        let cpu_time = CpuTime {
+            user_secs: 1.5,
+            system_secs: 0.5,
+        };
+        for id in ["first", "second"] {
+            state.create_build(id).await.unwrap();
+            state.add_cpu_time(id, Some(cpu_time)).await;
+        }
+        state.add_cpu_time("second", None).await;
+        // Running jobs aren't counted yet
+        assert!(state.job_usage().await.is_empty());
+        state.complete_build("first", vec![]).await;
+        state.fail_build("second", "failed".into(), Some(65)).await;
+        state.cleanup_old_builds(0).await;
+
+        assert!(state.get_build("first").await.is_none());
+        let usage = state.job_usage().await[&JobKind::Build];
+        assert_eq!(usage.finished, 2);
+        assert_eq!(usage.cpu_time.user_secs, 3.0);
+        assert_eq!(usage.cpu_time.system_secs, 1.0);
+    }
+
+    #[tokio::test]
+    async fn test_cancel_matching_builds() {
+        let state = AppState::new(Config::parse_from(["xcbridge"]), String::new());
+        let jobs = [
+            ("a", "ci", "App", "nightly"),
+            ("b", "ci", "App", "pr-12"),
+            ("c", "ci", "Core", "nightly"),
+            ("d", "dev", "App", "nightly"),
+        ];
+        for (id, namespace, scheme, name) in jobs {
+            state
+                .insert_build(
+                    id,
+                    BuildRecord {
+                        namespace: Namespace::new(namespace),
+                        scheme: Some(scheme.to_string()),
+                        name: Some(name.to_string()),
+                        ..BuildRecord::default()
+                    },
+                )
+                .await
+                .unwrap();
+        }
+        let ci = Namespace::new("ci");
+
+        let cancelled = state
+            .cancel_matching_builds(&ci, Some("App"), Some("nightly"))
+            .await;
+        assert_eq!(cancelled, ["a"]);
+        let cancelled = state.cancel_matching_builds(&ci, Some("App"), None).await;
+        assert_eq!(cancelled, ["b"]);
+        let cancelled = state
+            .cancel_matching_builds(&ci, None, Some("nightly"))
+            .await;
+        assert_eq!(cancelled, ["c"]);
+        assert!(matches!(
+            state.get_build("d").await,
+            Some(BuildStatus::Running { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_cleanup_removes_oldest_completed() {
+        let state = AppState::new(Config::parse_from(["xcbridge"]), String::new());
+        for id in ["first", "second", "third"] {
+            state.create_build(id).await.unwrap();
+            state.complete_build(id, vec![]).await;
+            tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+        }
+
+        state.cleanup_old_builds(2).await;
+
+        assert!(state.get_build("first").await.is_none());
+        assert!(state.get_build("second").await.is_some());
+        assert!(state.get_build("third").await.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_cleanup_removes_unkept_result_bundles() {
+        let state = AppState::new(Config::parse_from(["xcbridge"]), String::new());
+        let dir = std::env::temp_dir().join(format!("xcbridge-{}", uuid::Uuid::new_v4()));
+        for (id, keep) in [("removed", false), ("kept", true)] {
+            let bundle = dir.join(format!("{}.xcresult", id));
+            std::fs::create_dir_all(&bundle).unwrap();
+            state
+                .insert_build(
+                    id,
+                    BuildRecord {
+                        result_bundle_path: Some(bundle),
+                        keep_result_bundle: keep,
+                        ..BuildRecord::default()
+                    },
+                )
+                .await
+                .unwrap();
+            state.complete_build(id, vec![]).await;
+        }
+
+        state.cleanup_old_builds(0).await;
+
+        assert!(!dir.join("removed.xcresult").exists());
+        assert!(dir.join("kept.xcresult").exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_product_paths_allowed_in_derived_data() {
+        let dir = std::env::temp_dir().join(format!("xcbridge-{}", uuid::Uuid::new_v4()));
+        let (allowed, base, job, other) = (
+            dir.join("allowed"),
+            dir.join("base"),
+            dir.join("job"),
+            dir.join("other"),
+        );
+        for path in [&allowed, &base, &job, &other] {
+            std::fs::create_dir_all(path.join("MyApp.app")).unwrap();
+        }
+        let state = AppState::new(
+            Config::parse_from([
+                "xcbridge",
+                "--allowed-paths",
+                &allowed.to_string_lossy(),
+                "--derived-data-base",
+                &base.to_string_lossy(),
+            ]),
+            String::new(),
+        );
+        let (a, b) = (Namespace::new("a"), Namespace::new("b"));
+        state
+            .insert_build(
+                "job",
+                BuildRecord {
+                    namespace: a.clone(),
+                    derived_data_path: Some(job.clone()),
+                    ..BuildRecord::default()
+                },
+            )
+            .await
+            .unwrap();
+
+        for path in [&allowed, &job] {
+            assert!(
+                state
+                    .is_product_path_allowed(&path.join("MyApp.app"), &a)
+                    .await
+            );
+        }
+        // Another namespace can't read the job's products
+        assert!(
+            !state
+                .is_product_path_allowed(&job.join("MyApp.app"), &b)
+                .await
+        );
+        assert!(
+            state
+                .is_product_path_allowed(&allowed.join("MyApp.app"), &b)
+                .await
+        );
+        // Nor can anyone read DerivedData no tracked job uses
+        for path in [&base, &other] {
+            assert!(
+                !state
+                    .is_product_path_allowed(&path.join("MyApp.app"), &a)
+                    .await
+            );
+        }
+        assert!(
+            !state
+                .is_product_path_allowed(&job.join("../other/MyApp.app"), &a)
+                .await
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_cleanup_removes_only_owned_derived_data() {
+        let state = AppState::new(Config::parse_from(["xcbridge"]), String::new());
+        let dir = std::env::temp_dir().join(format!("xcbridge-{}", uuid::Uuid::new_v4()));
+        for (id, owned) in [("owned", true), ("shared", false)] {
+            let derived_data = dir.join(id);
+            std::fs::create_dir_all(&derived_data).unwrap();
+            state
+                .insert_build(
+                    id,
+                    BuildRecord {
+                        derived_data_path: Some(derived_data),
+                        owns_derived_data: owned,
+                        ..BuildRecord::default()
+                    },
+                )
+                .await
+                .unwrap();
+            state.complete_build(id, vec![]).await;
+        }
+
+        state.cleanup_old_builds(0).await;
+
+        assert!(!dir.join("owned").exists());
+        assert!(dir.join("shared").exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_fail_build_detects_code_signing() {
+        let state = AppState::new(Config::parse_from(["xcbridge"]), String::new());
+        state.create_build("unsigned").await.unwrap();
+        state
+            .append_build_log(
+                "unsigned",
+                "error: No Account for Team \"ABCDE12345\". Add a new account.".to_string(),
+            )
+            .await;
+        state
+            .fail_build("unsigned", "Build failed".to_string(), Some(65))
+            .await;
+
+        let record = state.get_build_record("unsigned").await.unwrap();
+        assert_eq!(record.failure_reason, Some(FailureReason::CodeSigning));
+        assert_eq!(
+            record.code_signing.unwrap().team_id.as_deref(),
+            Some("ABCDE12345")
+        );
+        assert!(matches!(
+            record.status,
+            BuildStatus::Failed { error, .. } if error.contains("/provisioning/install")
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_fail_build_detects_script_phase() {
+        let state = AppState::new(Config::parse_from(["xcbridge"]), String::new());
+        state.create_build("lint").await.unwrap();
+        for line in [
+            "PhaseScriptExecution SwiftLint /dd/Script-A.sh (in target 'App' from project 'App')",
+            "    /bin/sh -c /dd/Script-A.sh",
+            "Found 3 violations",
+            "Command PhaseScriptExecution failed with a nonzero exit code",
+        ] {
+            state.append_build_log("lint", line.to_string()).await;
+        }
+        state
+            .fail_build("lint", "Build failed".to_string(), Some(65))
+            .await;
+
+        let record = state.get_build_record("lint").await.unwrap();
+        assert_eq!(record.failure_reason, Some(FailureReason::ScriptPhase));
+        assert_eq!(record.script_phase.unwrap().script, "SwiftLint");
+        assert!(matches!(
+            record.status,
+            BuildStatus::Failed { error, .. }
+                if error == "Script phase \"SwiftLint\" failed: Found 3 violations"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_fail_build_detects_disk_full() {
+        let state = AppState::new(Config::parse_from(["xcbridge"]), String::new());
+        state.create_build("full").await.unwrap();
+        state
+            .append_build_log(
+                "full",
+                "error: unable to write 'Foo.o': No space left on device".to_string(),
+            )
+            .await;
+        state
+            .fail_build("full", "Build failed".to_string(), Some(65))
+            .await;
+        state.create_build("compile").await.unwrap();
+        state
+            .fail_build("compile", "error: cannot find 'Foo'".to_string(), Some(65))
+            .await;
+
+        let full = state.get_build_record("full").await.unwrap();
+        assert_eq!(full.failure_reason, Some(FailureReason::DiskFull));
+        assert!(matches!(
+            full.status,
+            BuildStatus::Failed { error, .. } if error.starts_with("Disk full: Build failed (")
+        ));
+
+        let compile = state.get_build_record("compile").await.unwrap();
+        assert_eq!(compile.failure_reason, None);
+    }
+}
@@ -0,0 +1,366 @@
+// Copyright 2026 Aptove
+// SPDX-License-Identifier: Apache-2.0
+
+//! Projects uploaded as archives with a build or test request
+//!
+//! Agents without a shared volume send the project as a .zip or .tar
+//! archive alongside the JSON request. Each upload is extracted into its own
+//! workspace under `--upload-dir`, the request's paths are resolved inside
+//! that workspace, and the workspace is removed when the job finishes.
+
+use crate::config::Config;
+use crate::error::{Result, XcbridgeError};
+use crate::state;
+use crate::xcode::runner;
+use axum::body::Bytes;
+use axum::extract::multipart::{Multipart, MultipartError};
+use axum::http::StatusCode;
+use std::os::unix::process::ExitStatusExt;
+use std::path::{Component, Path, PathBuf};
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use uuid::Uuid;
+
+/// An extracted project and the request sent with it
+pub struct Upload {
+    /// Workspace the archive was extracted into
+    pub dir: PathBuf,
+    /// The `request` field, a JSON build or test request
+    pub request: Bytes,
+}
+
+/// Read a multipart upload with `request` and `archive` fields, and extract
+/// the archive into a new workspace
+pub async fn receive(config: &Config, mut multipart: Multipart) -> Result<Upload> {
+    let root = config.upload_dir();
+    if !config.is_output_path_allowed(&root) {
+        return Err(XcbridgeError::PathNotAllowed(root.display().to_string()));
+    }
+    tokio::fs::create_dir_all(&root).await.map_err(|e| {
+        XcbridgeError::Internal(format!("Failed to create upload directory: {}", e))
+    })?;
+
+    let id = Uuid::new_v4().to_string();
+    let archive = root.join(format!("{}.archive", id));
+    let dir = root.join(id);
+
+    let received = read_fields(&mut multipart, &archive, config.max_upload_size).await;
+    let result = match received {
+        Ok(request) => extract(&archive, &dir, ExtractLimits::from(config))
+            .await
+            .map(|_| Upload {
+                dir: dir.clone(),
+                request,
+            }),
+        Err(e) => Err(e),
+    };
+    let _ = tokio::fs::remove_file(&archive).await;
+    if result.is_err() {
+        state::remove_upload(&dir).await;
+    }
+    result
+}
+
+/// Save the `archive` field to `archive` and return the `request` field
+async fn read_fields(multipart: &mut Multipart, archive: &Path, max_mb: u64) -> Result<Bytes> {
+    let mut request = None;
+    let mut has_archive = false;
+
+    while let Some(mut field) = multipart.next_field().await.map_err(upload_error(max_mb))? {
+        match field.name() {
+            Some("request") => {
+                request = Some(field.bytes().await.map_err(upload_error(max_mb))?);
+            }
+            Some("archive") => {
+                let mut file = tokio::fs::File::create(archive).await.map_err(|e| {
+                    XcbridgeError::Internal(format!("Failed to save upload: {}", e))
+                })?;
+                let mut size = 0u64;
+                while let Some(chunk) = field.chunk().await.map_err(upload_error(max_mb))? {
+                    size += chunk.len() as u64;
+                    if size > mb_to_bytes(max_mb) {
+                        return Err(XcbridgeError::UploadTooLarge(max_mb));
+                    }
+                    file.write_all(&chunk).await.map_err(|e| {
+                        XcbridgeError::Internal(format!("Failed to save upload: {}", e))
+                    })?;
+                }
+                file.flush().await.map_err(|e| {
+                    XcbridgeError::Internal(format!("Failed to save upload: {}", e))
+                })?;
+                has_archive = true;
+            }
+            name => {
+                return Err(XcbridgeError::InvalidRequest(format!(
+                    "Unexpected upload field: {}",
+                    name.unwrap_or("(unnamed)")
+                )))
+            }
+        }
+    }
+
+    if !has_archive {
+        return Err(XcbridgeError::InvalidRequest(
+            "Missing `archive` field with the project archive".into(),
+        ));
+    }
+    request.ok_or_else(|| {
+        XcbridgeError::InvalidRequest("Missing `request` field with the JSON request".into())
+    })
+}
+
+fn upload_error(max_mb: u64) -> impl Fn(MultipartError) -> XcbridgeError {
+    move |e| {
+        if e.status() == StatusCode::PAYLOAD_TOO_LARGE {
+            XcbridgeError::UploadTooLarge(max_mb)
+        } else {
+            XcbridgeError::InvalidRequest(format!("Invalid upload: {}", e.body_text()))
+        }
+    }
+}
+
+fn mb_to_bytes(mb: u64) -> u64 {
+    mb.saturating_mul(1024 * 1024)
+}
+
+/// Limits on what an uploaded archive may extract to, in MB
+#[derive(Debug, Clone, Copy)]
+struct ExtractLimits {
+    total_mb: u64,
+    file_mb: u64,
+}
+
+impl From<&Config> for ExtractLimits {
+    fn from(config: &Config) -> Self {
+        Self {
+            total_mb: config.max_extracted_size,
+            file_mb: config.max_extracted_file_size,
+        }
+    }
+}
+
+/// Extract a .zip, .tar or compressed tar archive into `dir`
+///
+/// bsdtar detects the format itself, and by default refuses entries with
+/// absolute paths, `..` components or paths through symlinks, so nothing
+/// is written outside `dir`. The archive's listing is checked against
+/// `limits` first, and tar can't write a file past the per-file limit even
+/// if the listing understates its size.
+async fn extract(archive: &Path, dir: &Path, limits: ExtractLimits) -> Result<()> {
+    check_listing(archive, limits).await?;
+
+    tokio::fs::create_dir_all(dir).await.map_err(|e| {
+        XcbridgeError::Internal(format!("Failed to create upload directory: {}", e))
+    })?;
+
+    let mut cmd = Command::new("tar");
+    cmd.arg("-xf").arg(archive).arg("-C").arg(dir);
+    let max_file = mb_to_bytes(limits.file_mb) as libc::rlim_t;
+    // SAFETY: setrlimit is async-signal-safe and nothing is allocated
+    unsafe {
+        cmd.pre_exec(move || {
+            let limit = libc::rlimit {
+                rlim_cur: max_file,
+                rlim_max: max_file,
+            };
+            if libc::setrlimit(libc::RLIMIT_FSIZE, &limit) == 0 {
+                Ok(())
+            } else {
+                Err(std::io::Error::last_os_error())
+            }
+        });
+    }
+    let output = runner::output(&mut cmd)
+        .await
+        .map_err(|e| XcbridgeError::CommandFailed(format!("Failed to run tar: {}", e)))?;
+    if output.status.signal() == Some(libc::SIGXFSZ) {
+        return Err(XcbridgeError::ExtractedTooLarge(format!(
+            "a file is larger than the {} MB limit",
+            limits.file_mb
+        )));
+    }
+    if !output.status.success() {
+        return Err(XcbridgeError::InvalidRequest(format!(
+            "Failed to extract the uploaded archive: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(())
+}
+
+/// Refuse an archive whose listing has a file over the per-file limit, or
+/// files adding up to more than the total limit
+async fn check_listing(archive: &Path, limits: ExtractLimits) -> Result<()> {
+    let mut cmd = Command::new("tar");
+    cmd.arg("-tvf").arg(archive);
+    let output = runner::output(&mut cmd)
+        .await
+        .map_err(|e| XcbridgeError::CommandFailed(format!("Failed to run tar: {}", e)))?;
+    if !output.status.success() {
+        return Err(XcbridgeError::InvalidRequest(format!(
+            "Failed to read the uploaded archive: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    let mut total = 0u64;
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        let Some(size) = listed_size(line) else {
+            continue;
+        };
+        if size > mb_to_bytes(limits.file_mb) {
+            return Err(XcbridgeError::ExtractedTooLarge(format!(
+                "a file is larger than the {} MB limit",
+                limits.file_mb
+            )));
+        }
+        total = total.saturating_add(size);
+        if total > mb_to_bytes(limits.total_mb) {
+            return Err(XcbridgeError::ExtractedTooLarge(format!(
+                "its files add up to more than the {} MB limit",
+                limits.total_mb
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Size of an entry in a `tar -tv` listing, e.g.
+/// `-rw-r--r--  0 user  staff  1234 Oct 16 12:00 App/main.swift`
+fn listed_size(line: &str) -> Option<u64> {
+    line.split_whitespace().nth(4)?.parse().ok()
+}
+
+/// Resolve a path from an uploaded request against its workspace
+///
+/// Paths must be relative to the archive root, exist, and stay within the
+/// workspace once symlinks are followed.
+pub fn resolve(dir: &Path, path: &str) -> Result<String> {
+    let relative = Path::new(path);
+    if relative
+        .components()
+        .any(|c| !matches!(c, Component::Normal(_) | Component::CurDir))
+    {
+        return Err(XcbridgeError::InvalidRequest(format!(
+            "Paths in an upload must be relative to the archive root: {}",
+            path
+        )));
+    }
+
+    let resolved = dir.join(relative).canonicalize().map_err(|_| {
+        XcbridgeError::InvalidRequest(format!("Not found in the uploaded archive: {}", path))
+    })?;
+    let root = dir
+        .canonicalize()
+        .map_err(|e| XcbridgeError::Internal(format!("Upload directory missing: {}", e)))?;
+    if !resolved.starts_with(&root) {
+        return Err(XcbridgeError::PathNotAllowed(path.to_string()));
+    }
+    Ok(resolved.to_string_lossy().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::xcode::runner::MockRunner;
+    use std::sync::Arc;
+
+    const LIMITS: ExtractLimits = ExtractLimits {
+        total_mb: 3,
+        file_mb: 2,
+    };
+
+    #[test]
+    fn test_resolve_stays_within_upload() {
+        let dir = std::env::temp_dir().join(format!("xcbridge-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(dir.join("App/App.xcodeproj")).unwrap();
+        std::os::unix::fs::symlink("/tmp", dir.join("escape")).unwrap();
+
+        let resolved = resolve(&dir, "App/App.xcodeproj").unwrap();
+        assert!(resolved.ends_with("App/App.xcodeproj"));
+        assert!(matches!(
+            resolve(&dir, "../App.xcodeproj"),
+            Err(XcbridgeError::InvalidRequest(_))
+        ));
+        assert!(matches!(
+            resolve(&dir, "/tmp"),
+            Err(XcbridgeError::InvalidRequest(_))
+        ));
+        assert!(matches!(
+            resolve(&dir, "Missing.xcodeproj"),
+            Err(XcbridgeError::InvalidRequest(_))
+        ));
+        assert!(matches!(
+            resolve(&dir, "escape"),
+            Err(XcbridgeError::PathNotAllowed(_))
+        ));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_extract_reports_tar_errors() {
+        let dir = std::env::temp_dir().join(format!("xcbridge-{}", Uuid::new_v4()));
+        let archive = dir.with_extension("archive");
+        let mock = Arc::new(
+            MockRunner::new()
+                .on(&["tar", "-tvf"], "")
+                .fail(&["tar", "-xf"], "Unrecognized archive format"),
+        );
+
+        let result = runner::with_runner(mock.clone(), extract(&archive, &dir, LIMITS)).await;
+        match result {
+            Err(XcbridgeError::InvalidRequest(message)) => {
+                assert!(message.contains("Unrecognized archive format"))
+            }
+            other => panic!("unexpected result: {:?}", other.err()),
+        }
+        let call = &mock.calls()[1];
+        assert_eq!(call[3], "-C");
+        assert_eq!(call[4], dir.to_string_lossy());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_extract_refuses_archives_over_limits() {
+        let dir = std::env::temp_dir().join(format!("xcbridge-{}", Uuid::new_v4()));
+        let archive = dir.with_extension("archive");
+        let listing = |sizes: &[u64]| {
+            sizes
+                .iter()
+                .enumerate()
+                .map(|(i, size)| {
+                    format!(
+                        "-rw-r--r--  0 dev    staff  {} Oct 16 12:00 App/{}.bin\n",
+                        size, i
+                    )
+                })
+                .collect::<String>()
+        };
+        let mb = 1024 * 1024;
+
+        for (sizes, expected) in [
+            (vec![2 * mb + 1], "2 MB"),
+            (vec![mb + mb / 2, mb + mb / 2, 1], "3 MB"),
+        ] {
+            let mock = Arc::new(MockRunner::new().on(&["tar", "-tvf"], &listing(&sizes)));
+            let result = runner::with_runner(mock.clone(), extract(&archive, &dir, LIMITS)).await;
+            match result {
+                Err(XcbridgeError::ExtractedTooLarge(message)) => {
+                    assert!(message.contains(expected), "{}", message)
+                }
+                other => panic!("unexpected result: {:?}", other.err()),
+            }
+            // Nothing is extracted
+            assert_eq!(mock.calls().len(), 1);
+            assert!(!dir.exists());
+        }
+
+        assert_eq!(
+            listed_size("drwxr-xr-x  0 dev    staff  0 Oct 16 12:00 App/"),
+            Some(0)
+        );
+        assert_eq!(listed_size("garbage"), None);
+    }
+}
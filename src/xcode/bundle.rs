@@ -0,0 +1,277 @@
+// Copyright 2026 Aptove
+// SPDX-License-Identifier: Apache-2.0
+
+//! App bundle inspection
+
+use crate::error::{Result, XcbridgeError};
+use crate::xcode::runner;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use tokio::process::Command;
+
+/// Metadata read from an app bundle's Info.plist
+#[derive(Debug, Clone, Serialize)]
+pub struct AppInfo {
+    /// Path to the .app bundle
+    pub path: String,
+    /// CFBundleIdentifier
+    pub bundle_id: String,
+    /// CFBundleDisplayName, falling back to CFBundleName
+    pub name: Option<String>,
+    /// CFBundleShortVersionString
+    pub version: Option<String>,
+    /// CFBundleVersion
+    pub build: Option<String>,
+    /// CFBundleExecutable
+    pub executable: Option<String>,
+    /// MinimumOSVersion (iOS) or LSMinimumSystemVersion (macOS)
+    pub minimum_os_version: Option<String>,
+}
+
+/// Read an app bundle's Info.plist
+///
+/// iOS bundles keep Info.plist at the top level, macOS bundles under `Contents/`.
+pub fn read_app_info(app: &Path) -> Result<AppInfo> {
+    let plist_path = [app.join("Info.plist"), app.join("Contents/Info.plist")]
+        .into_iter()
+        .find(|path| path.is_file())
+        .ok_or_else(|| {
+            XcbridgeError::InvalidRequest(format!("Not an app bundle: {}", app.display()))
+        })?;
+
+    let plist = plist::Value::from_file(&plist_path).map_err(|e| {
+        XcbridgeError::InvalidRequest(format!("Failed to read {}: {}", plist_path.display(), e))
+    })?;
+    let dict = plist.as_dictionary().ok_or_else(|| {
+        XcbridgeError::InvalidRequest(format!("Malformed {}", plist_path.display()))
+    })?;
+    let string = |key: &str| {
+        dict.get(key)
+            .and_then(plist::Value::as_string)
+            .map(str::to_string)
+    };
+
+    let bundle_id = string("CFBundleIdentifier").ok_or_else(|| {
+        XcbridgeError::InvalidRequest(format!(
+            "{} has no CFBundleIdentifier",
+            plist_path.display()
+        ))
+    })?;
+
+    Ok(AppInfo {
+        path: app.to_string_lossy().to_string(),
+        bundle_id,
+        name: string("CFBundleDisplayName").or_else(|| string("CFBundleName")),
+        version: string("CFBundleShortVersionString"),
+        build: string("CFBundleVersion"),
+        executable: string("CFBundleExecutable"),
+        minimum_os_version: string("MinimumOSVersion").or_else(|| string("LSMinimumSystemVersion")),
+    })
+}
+
+/// Bundle extensions reported as build products
+const PRODUCT_EXTENSIONS: &[&str] = &["app", "appex", "framework", "xctest"];
+
+/// Whether `path` is a product bundle directly in a target build directory
+/// (`Build/Products/<configuration>-<platform>/`), as [`find_products`] lists
+pub fn is_product(path: &Path) -> bool {
+    let in_products = path
+        .parent()
+        .and_then(Path::parent)
+        .is_some_and(|products| products.ends_with("Build/Products"));
+    in_products
+        && path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| PRODUCT_EXTENSIONS.contains(&ext))
+}
+
+/// Find the app bundles built into a DerivedData directory
+pub fn find_apps(derived_data: &Path) -> Vec<PathBuf> {
+    find_bundles(derived_data, &["app"])
+}
+
+/// Find every product bundle built into a DerivedData directory
+///
+/// Covers apps, app extensions and widgets, frameworks and test bundles in
+/// each target build directory (`Build/Products/<configuration>-<platform>`).
+pub fn find_products(derived_data: &Path) -> Vec<PathBuf> {
+    find_bundles(derived_data, PRODUCT_EXTENSIONS)
+}
+
+fn find_bundles(derived_data: &Path, extensions: &[&str]) -> Vec<PathBuf> {
+    let Ok(products) = std::fs::read_dir(derived_data.join("Build/Products")) else {
+        return Vec::new();
+    };
+
+    let mut bundles: Vec<PathBuf> = products
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| std::fs::read_dir(entry.path()).ok())
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.extension()
+                .and_then(|ext| ext.to_str())
+                .is_some_and(|ext| extensions.contains(&ext))
+        })
+        .collect();
+    bundles.sort();
+    bundles
+}
+
+/// Held while products are exported, so two exports to the same directory
+/// don't remove each other's copies halfway through
+static EXPORT: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+/// Copy build products into `output_dir`, returning the copies' paths
+///
+/// Products keep their `<configuration>-<platform>` directory, so a bundle
+/// built for two platforms isn't overwritten. `ditto` keeps the bundles'
+/// symlinks, extended attributes and signatures intact. A copy left by an
+/// earlier export is replaced rather than merged into.
+pub async fn export_products(products: &[String], output_dir: &Path) -> Result<Vec<String>> {
+    let _export = EXPORT.lock().await;
+    let mut exported = Vec::new();
+    for product in products {
+        let source = Path::new(product);
+        let relative = if is_product(source) {
+            source.strip_prefix(source.parent().and_then(Path::parent).unwrap_or(source))
+        } else {
+            source.strip_prefix(source.parent().unwrap_or(source))
+        }
+        .unwrap_or(source);
+        let dest = output_dir.join(relative);
+
+        if let Ok(metadata) = tokio::fs::symlink_metadata(&dest).await {
+            let removed = if metadata.is_dir() {
+                tokio::fs::remove_dir_all(&dest).await
+            } else {
+                tokio::fs::remove_file(&dest).await
+            };
+            removed.map_err(|e| {
+                XcbridgeError::Internal(format!("Failed to replace {}: {}", dest.display(), e))
+            })?;
+        }
+
+        let output = runner::output(Command::new("ditto").arg(source).arg(&dest))
+            .await
+            .map_err(|e| XcbridgeError::CommandFailed(format!("Failed to run ditto: {}", e)))?;
+        if !output.status.success() {
+            return Err(XcbridgeError::CommandFailed(format!(
+                "Failed to copy {}: {}",
+                product,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        exported.push(dest.to_string_lossy().to_string());
+    }
+    Ok(exported)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::xcode::runner::MockRunner;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_read_app_info() {
+        let dir = std::env::temp_dir().join(format!("xcbridge-{}", uuid::Uuid::new_v4()));
+        let app = dir.join("Build/Products/Debug-iphonesimulator/MyApp.app");
+        std::fs::create_dir_all(&app).unwrap();
+        std::fs::write(
+            app.join("Info.plist"),
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+    <key>CFBundleIdentifier</key>
+    <string>com.example.MyApp</string>
+    <key>CFBundleName</key>
+    <string>MyApp</string>
+    <key>CFBundleShortVersionString</key>
+    <string>1.2</string>
+    <key>MinimumOSVersion</key>
+    <string>17.0</string>
+</dict>
+</plist>"#,
+        )
+        .unwrap();
+
+        let apps = find_apps(&dir);
+        assert_eq!(apps, vec![app.clone()]);
+
+        let info = read_app_info(&app).unwrap();
+        assert_eq!(info.bundle_id, "com.example.MyApp");
+        assert_eq!(info.name.as_deref(), Some("MyApp"));
+        assert_eq!(info.version.as_deref(), Some("1.2"));
+        assert_eq!(info.build, None);
+        assert_eq!(info.minimum_os_version.as_deref(), Some("17.0"));
+
+        assert!(read_app_info(&dir).is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_find_products_includes_extensions() {
+        let dir = std::env::temp_dir().join(format!("xcbridge-{}", uuid::Uuid::new_v4()));
+        let products = dir.join("Build/Products/Debug-iphonesimulator");
+        for bundle in [
+            "MyApp.app",
+            "MyApp.app/PlugIns/MyWidget.appex",
+            "MyWidget.appex",
+            "MyKit.framework",
+            "MyAppTests.xctest",
+            "MyApp.swiftmodule",
+        ] {
+            std::fs::create_dir_all(products.join(bundle)).unwrap();
+        }
+        std::fs::write(products.join("libMyLib.a"), "").unwrap();
+
+        assert_eq!(
+            find_products(&dir),
+            vec![
+                products.join("MyApp.app"),
+                products.join("MyAppTests.xctest"),
+                products.join("MyKit.framework"),
+                products.join("MyWidget.appex"),
+            ]
+        );
+        assert_eq!(find_apps(&dir), vec![products.join("MyApp.app")]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_export_products_keeps_platform_directory() {
+        let output_dir = std::env::temp_dir().join(format!("xcbridge-{}", uuid::Uuid::new_v4()));
+        let stale = output_dir.join("Release-iphoneos/MyApp.app");
+        std::fs::create_dir_all(&stale).unwrap();
+        let mock = Arc::new(MockRunner::new().on(&["ditto"], ""));
+
+        let products = [
+            "/dd/Build/Products/Release-iphoneos/MyApp.app".to_string(),
+            "/dd/Build/Intermediates.noindex/MyTool".to_string(),
+        ];
+        let exported = runner::with_runner(mock.clone(), export_products(&products, &output_dir))
+            .await
+            .unwrap();
+        assert_eq!(
+            exported,
+            [
+                stale.to_string_lossy().to_string(),
+                output_dir.join("MyTool").to_string_lossy().to_string(),
+            ]
+        );
+        // The earlier copy is removed so ditto doesn't merge into it
+        assert!(!stale.exists());
+        assert_eq!(
+            mock.calls()[0],
+            ["ditto", &products[0], &exported[0]].map(str::to_string)
+        );
+
+        std::fs::remove_dir_all(&output_dir).unwrap();
+    }
+}
@@ -0,0 +1,149 @@
+// Copyright 2026 Aptove
+// SPDX-License-Identifier: Apache-2.0
+
+//! Detection of optional host features
+
+use crate::xcode::simctl::Runtime;
+use crate::xcode::{runner, xcodebuild};
+use serde::Serialize;
+use tokio::process::Command;
+
+/// Optional features available on this host
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Capabilities {
+    /// Xcode version, e.g. "Xcode 15.2"
+    pub xcode_version: String,
+    /// `devicectl` is available for physical devices (Xcode 15+)
+    pub devicectl: bool,
+    /// Version of `xcbeautify`, if installed
+    pub xcbeautify: Option<String>,
+    /// Swift compiler version, e.g. "5.10"
+    pub swift_version: Option<String>,
+    /// Swift Testing (`import Testing`) tests run (Xcode 16+)
+    pub swift_testing: bool,
+    /// Installed simulator runtimes
+    pub runtimes: Vec<RuntimeCapability>,
+    /// On-screen text recognition was compiled in
+    pub screen_text: bool,
+}
+
+/// An installed simulator runtime
+#[derive(Debug, Clone, Serialize)]
+pub struct RuntimeCapability {
+    pub platform: String,
+    pub version: String,
+    pub identifier: String,
+}
+
+/// Probe the host's optional tools, given its Xcode version and runtimes
+pub async fn probe(xcode_version: &str, runtimes: &[Runtime]) -> Capabilities {
+    let mut find_devicectl = xcodebuild::xcrun();
+    find_devicectl.args(["--find", "devicectl"]);
+    let mut xcbeautify = Command::new("xcbeautify");
+    xcbeautify.arg("--version");
+    let mut swift = xcodebuild::xcrun();
+    swift.args(["swift", "--version"]);
+
+    let (devicectl, xcbeautify, swift_version) =
+        tokio::join!(succeeds(find_devicectl), stdout(xcbeautify), stdout(swift),);
+
+    let runtimes = runtimes
+        .iter()
+        .map(|r| RuntimeCapability {
+            platform: r.platform.clone(),
+            version: r.version.clone(),
+            identifier: r.identifier.clone(),
+        })
+        .collect();
+
+    Capabilities {
+        xcode_version: xcode_version.to_string(),
+        swift_testing: xcode_major_version(xcode_version).is_some_and(|major| major >= 16),
+        devicectl,
+        xcbeautify: xcbeautify.and_then(|out| Some(out.lines().next()?.trim().to_string())),
+        swift_version: swift_version.as_deref().and_then(parse_swift_version),
+        runtimes,
+        screen_text: cfg!(feature = "screen-text"),
+    }
+}
+
+async fn succeeds(mut command: Command) -> bool {
+    runner::output(&mut command)
+        .await
+        .is_ok_and(|output| output.status.success())
+}
+
+/// Stdout of a command, if it succeeded
+async fn stdout(mut command: Command) -> Option<String> {
+    let output = runner::output(&mut command).await.ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// Major version of an `xcodebuild -version` line such as "Xcode 16.1"
+fn xcode_major_version(xcode_version: &str) -> Option<u32> {
+    xcode_version
+        .strip_prefix("Xcode ")?
+        .split('.')
+        .next()?
+        .parse()
+        .ok()
+}
+
+/// Version from `swift --version` output, whose version line looks like
+/// "Apple Swift version 5.10 (swiftlang-5.10.0.13 clang-1500.3.9.4)"
+fn parse_swift_version(output: &str) -> Option<String> {
+    let (_, rest) = output.split_once("Swift version ")?;
+    rest.split_whitespace().next().map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::xcode::runner::MockRunner;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_probe_with_mock_runner() {
+        let mock = Arc::new(
+            MockRunner::new()
+                .on(&["xcrun", "--find", "devicectl"], "/usr/bin/devicectl\n")
+                .fail(&["xcbeautify"], "command not found")
+                .on(
+                    &["xcrun", "swift", "--version"],
+                    "swift-driver version: 1.115\nApple Swift version 6.0 (swiftlang-6.0.0.9.10 clang-1600.0.26.2)\n",
+                ),
+        );
+        let runtimes: Vec<Runtime> = serde_json::from_str(
+            r#"[{
+                "bundlePath": "/Library/Developer/CoreSimulator/iOS.simruntime",
+                "buildversion": "22A3351",
+                "platform": "iOS",
+                "runtimeRoot": "/",
+                "identifier": "com.apple.CoreSimulator.SimRuntime.iOS-18-0",
+                "version": "18.0",
+                "isInternal": false,
+                "isAvailable": true,
+                "name": "iOS 18.0"
+            }]"#,
+        )
+        .unwrap();
+
+        let capabilities = runner::with_runner(mock, probe("Xcode 16.0", &runtimes)).await;
+        assert!(capabilities.devicectl);
+        assert_eq!(capabilities.xcbeautify, None);
+        assert_eq!(capabilities.swift_version.as_deref(), Some("6.0"));
+        assert!(capabilities.swift_testing);
+        assert_eq!(capabilities.runtimes.len(), 1);
+        assert_eq!(capabilities.runtimes[0].version, "18.0");
+    }
+
+    #[test]
+    fn test_xcode_major_version() {
+        assert_eq!(xcode_major_version("Xcode 15.2"), Some(15));
+        assert_eq!(xcode_major_version("Xcode 16"), Some(16));
+        assert_eq!(xcode_major_version("Unknown"), None);
+    }
+}
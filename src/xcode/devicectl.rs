@@ -3,8 +3,12 @@
 
 //! devicectl command wrapper for physical iOS device management
 
-use crate::error::{Result, XcbridgeError};
+use crate::error::{CommandFailure, Result, XcbridgeError};
+use crate::xcode::{runner, signing, xcodebuild};
 use serde::{Deserialize, Serialize};
+use std::process::Output;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
 use tokio::process::Command;
 
 /// Physical device information
@@ -66,14 +70,32 @@ struct ConnectionProperties {
     transport_type: Option<String>,
 }
 
+/// Seconds a devicectl command may run (0 for no limit)
+static COMMAND_TIMEOUT: AtomicU64 = AtomicU64::new(0);
+
+/// Set how long a devicectl command may run before it is killed, so callers
+/// outside the HTTP device routes are bounded by `--device-timeout` too
+pub fn set_command_timeout(secs: u64) {
+    COMMAND_TIMEOUT.store(secs, Ordering::Relaxed);
+}
+
+/// Run a devicectl command line, killing it after the command timeout
+async fn run(cmd: &mut Command) -> Result<Output> {
+    let output = runner::output(cmd);
+    let result = match COMMAND_TIMEOUT.load(Ordering::Relaxed) {
+        0 => output.await,
+        secs => tokio::time::timeout(Duration::from_secs(secs), output)
+            .await
+            .map_err(|_| {
+                XcbridgeError::Timeout(format!("devicectl did not finish within {}s", secs))
+            })?,
+    };
+    result.map_err(|e| XcbridgeError::CommandFailed(format!("devicectl failed: {}", e)))
+}
+
 /// Run devicectl command
 async fn devicectl(args: &[&str]) -> Result<String> {
-    let output = Command::new("xcrun")
-        .arg("devicectl")
-        .args(args)
-        .output()
-        .await
-        .map_err(|e| XcbridgeError::CommandFailed(format!("devicectl failed: {}", e)))?;
+    let output = run(xcodebuild::xcrun().arg("devicectl").args(args)).await?;
 
     if output.status.success() {
         Ok(String::from_utf8_lossy(&output.stdout).to_string())
@@ -85,8 +107,100 @@ async fn devicectl(args: &[&str]) -> Result<String> {
                 "devicectl not available. Requires Xcode 15+".to_string(),
             ));
         }
-        Err(XcbridgeError::DeviceError(stderr.to_string()))
+        Err(XcbridgeError::DeviceCommandFailed(CommandFailure::new(
+            "xcrun devicectl",
+            args,
+            &output,
+        )))
+    }
+}
+
+/// `devicectl device` subcommands `POST /device/exec` runs by default: the
+/// read-only `info` queries
+pub const DEFAULT_EXEC_COMMANDS: &[&str] = &[
+    "info apps",
+    "info details",
+    "info displays",
+    "info files",
+    "info lockState",
+    "info processes",
+];
+
+/// Whether `POST /device/exec` may run a `devicectl device` subcommand,
+/// given as its words, e.g. `["info", "lockState"]`
+///
+/// `allowed` is `--devicectl-exec-allow`; without it, only
+/// [`DEFAULT_EXEC_COMMANDS`] may run.
+pub fn is_exec_command_allowed(allowed: Option<&[String]>, command: &[String]) -> bool {
+    let command = command.join(" ");
+    match allowed {
+        Some(allowed) => allowed.iter().any(|entry| entry.trim() == command),
+        None => DEFAULT_EXEC_COMMANDS.contains(&command.as_str()),
+    }
+}
+
+/// Options that write to files on the host, which exec arguments may not set
+const EXEC_DENIED_OPTIONS: &[&str] = &["--json-output", "-j", "--log-output"];
+
+/// Output of a passthrough devicectl command
+#[derive(Debug, Serialize)]
+pub struct ExecOutput {
+    /// Command line that ran
+    pub command: String,
+    pub exit_code: Option<i32>,
+    pub stdout: String,
+    pub stderr: String,
+    /// stdout parsed as JSON, when JSON output was requested
+    pub json: Option<serde_json::Value>,
+}
+
+/// Run `devicectl device <command> --device <id> <args>` and return its
+/// output, whether or not it succeeded
+///
+/// With `json`, devicectl writes its JSON result to stdout, which is also
+/// returned parsed.
+pub async fn exec(
+    device_id: &str,
+    command: &[String],
+    args: &[String],
+    json: bool,
+) -> Result<ExecOutput> {
+    if let Some(arg) = args.iter().find(|arg| is_denied_exec_arg(arg)) {
+        return Err(XcbridgeError::InvalidRequest(format!(
+            "devicectl argument {:?} is not allowed",
+            arg
+        )));
     }
+
+    let mut cmd = xcodebuild::xcrun();
+    cmd.arg("devicectl")
+        .arg("device")
+        .args(command)
+        .args(["--device", device_id])
+        .args(args);
+    if json {
+        cmd.args(["--json-output", "-"]);
+    }
+    let command_line = runner::command_line(&cmd).join(" ");
+    tracing::info!("Running: {}", command_line);
+
+    let output = run(&mut cmd).await?;
+    let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+
+    Ok(ExecOutput {
+        command: command_line,
+        exit_code: output.status.code(),
+        json: json.then(|| serde_json::from_str(&stdout).ok()).flatten(),
+        stdout,
+        stderr: String::from_utf8_lossy(&output.stderr).to_string(),
+    })
+}
+
+fn is_denied_exec_arg(arg: &str) -> bool {
+    arg.contains('\0')
+        || EXEC_DENIED_OPTIONS
+            .iter()
+            .any(|option| arg == *option || arg.starts_with(&format!("{}=", option)))
 }
 
 /// List all connected physical devices
@@ -129,6 +243,7 @@ pub async fn list_devices() -> Result<Vec<Device>> {
 }
 
 /// Get a specific device by UDID
+#[allow(dead_code)] // Not routed yet
 pub async fn get_device(udid: &str) -> Result<Device> {
     let devices = list_devices().await?;
     devices
@@ -140,8 +255,17 @@ pub async fn get_device(udid: &str) -> Result<Device> {
 /// Install an app on a physical device
 pub async fn install(device_id: &str, app_path: &str) -> Result<()> {
     tracing::info!("Installing {} to device {}", app_path, device_id);
-    devicectl(&["device", "install", "app", "--device", device_id, app_path]).await?;
-    Ok(())
+    match devicectl(&["device", "install", "app", "--device", device_id, app_path]).await {
+        Ok(_) => Ok(()),
+        // Report signing problems rather than a generic install failure
+        Err(XcbridgeError::DeviceCommandFailed(failure)) => {
+            match signing::find_signing_error(failure.stderr.lines()) {
+                Some(issue) => Err(XcbridgeError::CodeSigningError(issue)),
+                None => Err(XcbridgeError::DeviceCommandFailed(failure)),
+            }
+        }
+        Err(e) => Err(e),
+    }
 }
 
 /// Launch an app on a physical device
@@ -151,14 +275,45 @@ pub async fn launch(device_id: &str, bundle_id: &str) -> Result<()> {
     Ok(())
 }
 
+/// stderr fragments devicectl emits when uninstalling an app that isn't installed
+const NOT_INSTALLED_ERRORS: &[&str] = &[
+    "is not installed",
+    "No app with bundle identifier",
+    "ApplicationNotFound",
+];
+
+fn is_not_installed_error(stderr: &str) -> bool {
+    NOT_INSTALLED_ERRORS.iter().any(|e| stderr.contains(e))
+}
+
 /// Uninstall an app from a physical device
-pub async fn uninstall(device_id: &str, bundle_id: &str) -> Result<()> {
+///
+/// Returns `false` if the app was not installed to begin with.
+pub async fn uninstall(device_id: &str, bundle_id: &str) -> Result<bool> {
     tracing::info!("Uninstalling {} from device {}", bundle_id, device_id);
-    devicectl(&["device", "uninstall", "app", "--device", device_id, bundle_id]).await?;
-    Ok(())
+    match devicectl(&[
+        "device",
+        "uninstall",
+        "app",
+        "--device",
+        device_id,
+        bundle_id,
+    ])
+    .await
+    {
+        Ok(_) => Ok(true),
+        Err(XcbridgeError::DeviceCommandFailed(failure))
+            if is_not_installed_error(&failure.stderr) =>
+        {
+            tracing::info!("{} was not installed on device {}", bundle_id, device_id);
+            Ok(false)
+        }
+        Err(e) => Err(e),
+    }
 }
 
 /// Copy files from device
+#[allow(dead_code)] // Not routed yet
 pub async fn copy_from_device(device_id: &str, source: &str, destination: &str) -> Result<()> {
     devicectl(&[
         "device",
@@ -174,6 +329,7 @@ pub async fn copy_from_device(device_id: &str, source: &str, destination: &str)
 }
 
 /// Copy files to device
+#[allow(dead_code)] // Not routed yet
 pub async fn copy_to_device(device_id: &str, source: &str, destination: &str) -> Result<()> {
     devicectl(&[
         "device",
@@ -187,3 +343,111 @@ pub async fn copy_to_device(device_id: &str, source: &str, destination: &str) ->
     .await?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::xcode::runner::MockRunner;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_is_exec_command_allowed() {
+        let words = |words: &[&str]| words.iter().map(|w| w.to_string()).collect::<Vec<_>>();
+
+        assert!(is_exec_command_allowed(
+            None,
+            &words(&["info", "lockState"])
+        ));
+        assert!(!is_exec_command_allowed(None, &words(&["info"])));
+        assert!(!is_exec_command_allowed(None, &words(&["reboot"])));
+
+        let allowed = words(&["reboot", " info details"]);
+        assert!(is_exec_command_allowed(Some(&allowed), &words(&["reboot"])));
+        assert!(is_exec_command_allowed(
+            Some(&allowed),
+            &words(&["info", "details"])
+        ));
+        assert!(!is_exec_command_allowed(
+            Some(&allowed),
+            &words(&["info", "lockState"])
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_exec_with_mock_runner() {
+        let mock = Arc::new(MockRunner::new().on(
+            &["xcrun", "devicectl", "device", "info", "lockState"],
+            r#"{"result": {"passcodeRequired": true, "unlockedSinceBoot": true}}"#,
+        ));
+        let command = vec!["info".to_string(), "lockState".to_string()];
+
+        let output = runner::with_runner(mock.clone(), exec("00008110-000A", &command, &[], true))
+            .await
+            .unwrap();
+        assert_eq!(output.exit_code, Some(0));
+        assert_eq!(output.json.unwrap()["result"]["passcodeRequired"], true);
+        assert_eq!(
+            output.command,
+            "xcrun devicectl device info lockState --device 00008110-000A --json-output -"
+        );
+
+        let err = runner::with_runner(
+            mock.clone(),
+            exec(
+                "00008110-000A",
+                &command,
+                &["--json-output=/tmp/out.json".to_string()],
+                false,
+            ),
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(err, XcbridgeError::InvalidRequest(_)));
+        assert_eq!(mock.calls().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_list_devices_with_mock_runner() {
+        let output = r#"{
+            "result": {
+                "devices": [
+                    {
+                        "identifier": "E1F2",
+                        "hardwareProperties": {
+                            "udid": "00008110-000A",
+                            "platform": "iOS",
+                            "deviceType": "iPhone"
+                        },
+                        "deviceProperties": {"name": "Test iPhone", "osVersionNumber": "17.1"},
+                        "connectionProperties": {"transportType": "wired"}
+                    },
+                    {"identifier": "A3B4"}
+                ]
+            }
+        }"#;
+        let mock = Arc::new(MockRunner::new().on(&["xcrun", "devicectl", "list"], output));
+
+        let devices = runner::with_runner(mock.clone(), list_devices())
+            .await
+            .unwrap();
+        assert_eq!(devices.len(), 2);
+        assert_eq!(devices[0].udid, "00008110-000A");
+        assert_eq!(devices[0].name, "Test iPhone");
+        assert_eq!(devices[0].os_version, "17.1");
+        assert_eq!(devices[0].connection_type, "wired");
+        // Devices without hardware properties fall back to the identifier
+        assert_eq!(devices[1].udid, "A3B4");
+        assert_eq!(devices[1].name, "Unknown");
+        assert_eq!(
+            mock.calls(),
+            vec![vec![
+                "xcrun",
+                "devicectl",
+                "list",
+                "devices",
+                "--json-output",
+                "-"
+            ]]
+        );
+    }
+}
@@ -0,0 +1,167 @@
+// Copyright 2026 Aptove
+// SPDX-License-Identifier: Apache-2.0
+
+//! Keychain and code-signing identity inspection with `security`
+
+use crate::error::{Result, XcbridgeError};
+use crate::xcode::runner;
+use serde::Serialize;
+use tokio::process::Command;
+
+/// State of the login keychain, which holds the signing certificates
+#[derive(Debug, Clone, Serialize)]
+pub struct KeychainStatus {
+    /// Path to the login keychain (None if there isn't one)
+    pub path: Option<String>,
+    /// Whether codesign can use its keys without a password prompt
+    pub unlocked: bool,
+}
+
+/// A certificate and private key usable for code signing
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SigningIdentity {
+    /// SHA-1 hash of the certificate
+    pub hash: String,
+    /// Common name, e.g. "Apple Development: Jane Doe (ABCDE12345)"
+    pub name: String,
+    /// Whether the certificate can be used, i.e. isn't expired or revoked
+    pub valid: bool,
+    /// Why the certificate can't be used, e.g. "CSSMERR_TP_CERT_EXPIRED"
+    pub reason: Option<String>,
+}
+
+async fn security(args: &[&str]) -> Result<std::process::Output> {
+    runner::output(Command::new("security").args(args))
+        .await
+        .map_err(|e| XcbridgeError::CommandFailed(format!("Failed to run security: {}", e)))
+}
+
+/// Find the login keychain and whether it is unlocked
+///
+/// `show-keychain-info` fails without prompting when the keychain is locked,
+/// as it is over SSH until unlocked with `security unlock-keychain`.
+pub async fn login_keychain_status() -> Result<KeychainStatus> {
+    let output = security(&["login-keychain"]).await?;
+    let path = String::from_utf8_lossy(&output.stdout)
+        .trim()
+        .trim_matches('"')
+        .to_string();
+    if !output.status.success() || path.is_empty() {
+        return Ok(KeychainStatus {
+            path: None,
+            unlocked: false,
+        });
+    }
+
+    let unlocked = security(&["show-keychain-info", &path])
+        .await?
+        .status
+        .success();
+    Ok(KeychainStatus {
+        path: Some(path),
+        unlocked,
+    })
+}
+
+/// List code-signing identities in the keychain search list, including
+/// ones that can't be used
+pub async fn signing_identities() -> Result<Vec<SigningIdentity>> {
+    let output = security(&["find-identity", "-p", "codesigning"]).await?;
+    if !output.status.success() {
+        return Err(XcbridgeError::CommandFailed(format!(
+            "security find-identity failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(parse_identities(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Parse the "Matching identities" section of `security find-identity`
+///
+/// Lines look like `1) 0123ABCD... "Apple Development: Jane Doe (ABCDE12345)"`,
+/// followed by the reason in parentheses for identities that aren't valid.
+fn parse_identities(output: &str) -> Vec<SigningIdentity> {
+    output
+        .lines()
+        .map(str::trim)
+        // The "Valid identities only" section repeats the valid ones
+        .take_while(|line| !line.starts_with("Valid identities only"))
+        .filter_map(|line| {
+            let (number, rest) = line.split_once(") ")?;
+            number.parse::<u32>().ok()?;
+            let (hash, rest) = rest.split_once(' ')?;
+            let rest = rest.strip_prefix('"')?;
+            let (name, rest) = rest.rsplit_once('"')?;
+            let reason = rest
+                .trim()
+                .strip_prefix('(')
+                .and_then(|r| r.strip_suffix(')'))
+                .map(str::to_string);
+            Some(SigningIdentity {
+                hash: hash.to_string(),
+                name: name.to_string(),
+                valid: reason.is_none(),
+                reason,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::xcode::runner::MockRunner;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_parse_identities() {
+        let output = r#"
+Policy: Code Signing
+  Matching identities
+  1) 0123456789ABCDEF0123456789ABCDEF01234567 "Apple Development: Jane Doe (ABCDE12345)"
+  2) 89ABCDEF0123456789ABCDEF0123456789ABCDEF "iPhone Distribution: Example Inc (XYZ9876543)" (CSSMERR_TP_CERT_EXPIRED)
+     2 identities found
+
+  Valid identities only
+  1) 0123456789ABCDEF0123456789ABCDEF01234567 "Apple Development: Jane Doe (ABCDE12345)"
+     1 valid identities found
+"#;
+        let identities = parse_identities(output);
+        assert_eq!(identities.len(), 2);
+        assert_eq!(
+            identities[0].name,
+            "Apple Development: Jane Doe (ABCDE12345)"
+        );
+        assert!(identities[0].valid);
+        assert_eq!(
+            identities[1].reason.as_deref(),
+            Some("CSSMERR_TP_CERT_EXPIRED")
+        );
+        assert!(!identities[1].valid);
+        assert!(parse_identities("\n     0 valid identities found\n").is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_locked_login_keychain() {
+        let mock = Arc::new(
+            MockRunner::new()
+                .on(
+                    &["security", "login-keychain"],
+                    "    \"/Users/ci/Library/Keychains/login.keychain-db\"\n",
+                )
+                .fail(
+                    &["security", "show-keychain-info"],
+                    "security: SecKeychainCopySettings: User interaction is not allowed.",
+                ),
+        );
+
+        let status = runner::with_runner(mock, login_keychain_status())
+            .await
+            .unwrap();
+        assert_eq!(
+            status.path.as_deref(),
+            Some("/Users/ci/Library/Keychains/login.keychain-db")
+        );
+        assert!(!status.unlocked);
+    }
+}
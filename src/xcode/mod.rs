@@ -3,6 +3,20 @@
 
 //! Xcode command wrappers
 
+pub mod bundle;
+pub mod capabilities;
 pub mod devicectl;
+pub mod keychain;
+pub mod provisioning;
+pub mod runner;
+pub mod runtime_logs;
+#[cfg(feature = "screen-text")]
+pub mod screen_text;
+pub mod script_phase;
+pub mod signing;
 pub mod simctl;
+pub mod test_plans;
+pub mod ui_input;
+pub mod validation;
 pub mod xcodebuild;
+pub mod xcresult;
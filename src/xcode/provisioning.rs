@@ -0,0 +1,234 @@
+// Copyright 2026 Aptove
+// SPDX-License-Identifier: Apache-2.0
+
+//! Provisioning profile inspection and installation
+
+use crate::error::{Result, XcbridgeError};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Metadata read from a provisioning profile's embedded plist
+#[derive(Debug, Clone, Serialize)]
+pub struct ProvisioningProfile {
+    /// Path to the .mobileprovision file
+    pub path: String,
+    /// UUID
+    pub uuid: String,
+    /// Name
+    pub name: Option<String>,
+    /// First TeamIdentifier
+    pub team_id: Option<String>,
+    /// TeamName
+    pub team_name: Option<String>,
+    /// AppIDName
+    pub app_id_name: Option<String>,
+    /// application-identifier entitlement, e.g. "ABCDE12345.com.example.*"
+    pub application_identifier: Option<String>,
+    /// CreationDate
+    pub created_at: Option<DateTime<Utc>>,
+    /// ExpirationDate
+    pub expires_at: Option<DateTime<Utc>>,
+    /// Whether ExpirationDate has passed
+    pub expired: bool,
+    /// Number of ProvisionedDevices (absent for App Store and enterprise profiles)
+    pub device_count: Option<usize>,
+}
+
+/// Directory Xcode and devicectl read installed profiles from
+pub fn profiles_dir() -> Result<PathBuf> {
+    let home = std::env::var_os("HOME")
+        .ok_or_else(|| XcbridgeError::Internal("HOME is not set".into()))?;
+    Ok(PathBuf::from(home).join("Library/MobileDevice/Provisioning Profiles"))
+}
+
+/// Read a .mobileprovision file
+///
+/// Profiles are CMS-signed; the plist is stored unencrypted inside the
+/// signed data, so it is read directly rather than through `security cms`.
+pub fn read_profile(path: &Path) -> Result<ProvisioningProfile> {
+    let data = std::fs::read(path).map_err(|e| {
+        XcbridgeError::InvalidRequest(format!("Failed to read {}: {}", path.display(), e))
+    })?;
+    parse_profile(path, &data)
+}
+
+fn parse_profile(path: &Path, data: &[u8]) -> Result<ProvisioningProfile> {
+    let malformed =
+        || XcbridgeError::InvalidRequest(format!("Not a provisioning profile: {}", path.display()));
+
+    let start = find(data, b"<?xml").ok_or_else(malformed)?;
+    let end = find(&data[start..], b"</plist>").ok_or_else(malformed)? + start + b"</plist>".len();
+
+    let plist = plist::Value::from_reader_xml(&data[start..end]).map_err(|_| malformed())?;
+    let dict = plist.as_dictionary().ok_or_else(malformed)?;
+    let string = |key: &str| {
+        dict.get(key)
+            .and_then(plist::Value::as_string)
+            .map(str::to_string)
+    };
+    let date = |key: &str| {
+        dict.get(key)
+            .and_then(plist::Value::as_date)
+            .map(|d| DateTime::<Utc>::from(SystemTime::from(d)))
+    };
+
+    let uuid = string("UUID").ok_or_else(malformed)?;
+    let expires_at = date("ExpirationDate");
+
+    Ok(ProvisioningProfile {
+        path: path.to_string_lossy().to_string(),
+        uuid,
+        name: string("Name"),
+        team_id: dict
+            .get("TeamIdentifier")
+            .and_then(plist::Value::as_array)
+            .and_then(|ids| ids.first())
+            .and_then(plist::Value::as_string)
+            .map(str::to_string),
+        team_name: string("TeamName"),
+        app_id_name: string("AppIDName"),
+        application_identifier: dict
+            .get("Entitlements")
+            .and_then(plist::Value::as_dictionary)
+            .and_then(|e| e.get("application-identifier"))
+            .and_then(plist::Value::as_string)
+            .map(str::to_string),
+        created_at: date("CreationDate"),
+        expired: expires_at.is_some_and(|expiry| expiry < Utc::now()),
+        expires_at,
+        device_count: dict
+            .get("ProvisionedDevices")
+            .and_then(plist::Value::as_array)
+            .map(Vec::len),
+    })
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+/// List the installed provisioning profiles, skipping unreadable files
+pub fn list_profiles() -> Result<Vec<ProvisioningProfile>> {
+    let Ok(entries) = std::fs::read_dir(profiles_dir()?) else {
+        return Ok(Vec::new());
+    };
+
+    let mut profiles: Vec<_> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "mobileprovision"))
+        .filter_map(|path| read_profile(&path).ok())
+        .collect();
+    profiles.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(profiles)
+}
+
+/// Install a provisioning profile as `<UUID>.mobileprovision`
+///
+/// Replaces any installed copy of the same profile. Returns the installed
+/// profile.
+pub fn install_profile(path: &Path) -> Result<ProvisioningProfile> {
+    let profile = read_profile(path)?;
+    // The UUID names the installed file, so it must not be a path
+    let file_name = profile_file_name(&profile.uuid)?;
+
+    let dir = profiles_dir()?;
+    std::fs::create_dir_all(&dir).map_err(|e| {
+        XcbridgeError::Internal(format!("Failed to create {}: {}", dir.display(), e))
+    })?;
+
+    let dest = dir.join(file_name);
+    std::fs::copy(path, &dest).map_err(|e| {
+        XcbridgeError::Internal(format!("Failed to install {}: {}", dest.display(), e))
+    })?;
+
+    Ok(ProvisioningProfile {
+        path: dest.to_string_lossy().to_string(),
+        ..profile
+    })
+}
+
+/// `<UUID>.mobileprovision`, for a profile UUID that is a well-formed UUID
+fn profile_file_name(uuid: &str) -> Result<String> {
+    let uuid = uuid::Uuid::parse_str(uuid).map_err(|_| {
+        XcbridgeError::InvalidRequest(format!("Invalid provisioning profile UUID: {}", uuid))
+    })?;
+    Ok(format!(
+        "{}.mobileprovision",
+        uuid.hyphenated().to_string().to_uppercase()
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_profile() {
+        // The plist is wrapped in CMS signature data in real profiles
+        let mut data = vec![0x30, 0x80, 0x06, 0x09];
+        data.extend_from_slice(
+            br#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+    <key>AppIDName</key>
+    <string>MyApp</string>
+    <key>Entitlements</key>
+    <dict>
+        <key>application-identifier</key>
+        <string>ABCDE12345.com.example.MyApp</string>
+    </dict>
+    <key>ExpirationDate</key>
+    <date>2020-01-01T00:00:00Z</date>
+    <key>Name</key>
+    <string>MyApp Development</string>
+    <key>ProvisionedDevices</key>
+    <array>
+        <string>00008110-000A1B2C3D4E5F6A</string>
+    </array>
+    <key>TeamIdentifier</key>
+    <array>
+        <string>ABCDE12345</string>
+    </array>
+    <key>TeamName</key>
+    <string>Example Inc.</string>
+    <key>UUID</key>
+    <string>11111111-2222-3333-4444-555555555555</string>
+</dict>
+</plist>"#,
+        );
+        data.extend_from_slice(&[0xa0, 0x82, 0x0b, 0x00]);
+
+        let profile = parse_profile(Path::new("MyApp.mobileprovision"), &data).unwrap();
+
+        assert_eq!(profile.uuid, "11111111-2222-3333-4444-555555555555");
+        assert_eq!(profile.name.as_deref(), Some("MyApp Development"));
+        assert_eq!(profile.team_id.as_deref(), Some("ABCDE12345"));
+        assert_eq!(profile.team_name.as_deref(), Some("Example Inc."));
+        assert_eq!(
+            profile.application_identifier.as_deref(),
+            Some("ABCDE12345.com.example.MyApp")
+        );
+        assert_eq!(profile.device_count, Some(1));
+        assert!(profile.expired);
+
+        assert!(parse_profile(Path::new("x"), b"not a profile").is_err());
+    }
+
+    #[test]
+    fn test_profile_file_name() {
+        assert_eq!(
+            profile_file_name("11111111-2222-3333-4444-55555555555a").unwrap(),
+            "11111111-2222-3333-4444-55555555555A.mobileprovision"
+        );
+        assert!(matches!(
+            profile_file_name("../../Library/LaunchAgents/x"),
+            Err(XcbridgeError::InvalidRequest(_))
+        ));
+    }
+}
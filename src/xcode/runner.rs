@@ -0,0 +1,266 @@
+// Copyright 2026 Aptove
+// SPDX-License-Identifier: Apache-2.0
+
+//! Command execution behind a swappable runner
+//!
+//! The simctl, devicectl and xcodebuild wrappers run their one-shot commands
+//! through [`output`], which uses the system by default. Tests can run code
+//! under `with_runner` to answer those commands from a `MockRunner`
+//! instead, so parsing and argument construction can be checked without a
+//! Mac. Builds are answered by that runner in one go; outside it they and
+//! other long-running streamed commands (log streams) spawn processes
+//! directly.
+//!
+//! The last [`HISTORY_SIZE`] commands run through [`output`] are kept in
+//! memory for `GET /debug/last-commands`.
+
+use chrono::{DateTime, Utc};
+use futures::future::BoxFuture;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::io;
+use std::process::Output;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tokio::process::Command;
+
+/// Number of recent commands kept for debugging
+pub const HISTORY_SIZE: usize = 100;
+
+/// Bytes of stderr kept per recorded command (the end is kept)
+const STDERR_LIMIT: usize = 4096;
+
+static HISTORY: Mutex<VecDeque<CommandRecord>> = Mutex::new(VecDeque::new());
+
+/// A command run through [`output`]
+#[derive(Debug, Clone, Serialize)]
+pub struct CommandRecord {
+    pub command: String,
+    pub args: Vec<String>,
+    pub started_at: DateTime<Utc>,
+    pub duration_secs: f64,
+    /// Exit code (None if it was killed by a signal or failed to start)
+    pub exit_code: Option<i32>,
+    /// End of the command's stderr
+    pub stderr: String,
+    pub stderr_truncated: bool,
+    /// Why the command couldn't be run at all
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Runs a command to completion and captures its output
+pub trait CommandRunner: Send + Sync {
+    fn output<'a>(&'a self, command: &'a mut Command) -> BoxFuture<'a, io::Result<Output>>;
+}
+
+/// Runs commands as real processes
+///
+/// A process is killed if its output is no longer awaited, e.g. when a
+/// request times out, so it can't outlive the simulator lock held for it.
+pub struct SystemRunner;
+
+impl CommandRunner for SystemRunner {
+    fn output<'a>(&'a self, command: &'a mut Command) -> BoxFuture<'a, io::Result<Output>> {
+        Box::pin(command.kill_on_drop(true).output())
+    }
+}
+
+tokio::task_local! {
+    static RUNNER: Arc<dyn CommandRunner>;
+}
+
+/// Run `future` with every command it runs through [`output`] going to `runner`
+#[cfg(test)]
+pub async fn with_runner<F: std::future::Future>(
+    runner: Arc<dyn CommandRunner>,
+    future: F,
+) -> F::Output {
+    RUNNER.scope(runner, future).await
+}
+
+/// Whether the current task has its own runner, rather than the system
+pub fn has_task_runner() -> bool {
+    RUNNER.try_with(|_| ()).is_ok()
+}
+
+/// Spawn a task whose commands go to the current task's runner, so work
+/// started in the background stays with the runner of the task that
+/// started it
+pub fn spawn<F>(future: F) -> tokio::task::JoinHandle<F::Output>
+where
+    F: std::future::Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    match RUNNER.try_with(Arc::clone) {
+        Ok(runner) => tokio::spawn(RUNNER.scope(runner, future)),
+        Err(_) => tokio::spawn(future),
+    }
+}
+
+/// Run a command with the current task's runner, or the system if none is set
+pub async fn output(command: &mut Command) -> io::Result<Output> {
+    let started_at = Utc::now();
+    let start = Instant::now();
+    let result = match RUNNER.try_with(Arc::clone) {
+        Ok(runner) => runner.output(command).await,
+        Err(_) => SystemRunner.output(command).await,
+    };
+    record(command, started_at, start.elapsed().as_secs_f64(), &result);
+    result
+}
+
+fn record(
+    command: &Command,
+    started_at: DateTime<Utc>,
+    duration_secs: f64,
+    result: &io::Result<Output>,
+) {
+    let mut line = command_line(command).into_iter();
+    let program = line.next().unwrap_or_default();
+    let (exit_code, stderr, error) = match result {
+        Ok(output) => (output.status.code(), output.stderr.as_slice(), None),
+        Err(e) => (None, &[][..], Some(e.to_string())),
+    };
+    let start = stderr.len().saturating_sub(STDERR_LIMIT);
+    let entry = CommandRecord {
+        command: program,
+        args: line.collect(),
+        started_at,
+        duration_secs,
+        exit_code,
+        stderr: String::from_utf8_lossy(&stderr[start..]).into_owned(),
+        stderr_truncated: start > 0,
+        error,
+    };
+
+    let mut history = HISTORY.lock().unwrap();
+    if history.len() == HISTORY_SIZE {
+        history.pop_front();
+    }
+    history.push_back(entry);
+}
+
+/// Commands recently run through [`output`], newest first
+pub fn recent_commands() -> Vec<CommandRecord> {
+    HISTORY.lock().unwrap().iter().rev().cloned().collect()
+}
+
+/// Program and arguments of a command, e.g. `["xcrun", "simctl", "list"]`
+pub fn command_line(command: &Command) -> Vec<String> {
+    let command = command.as_std();
+    std::iter::once(command.get_program())
+        .chain(command.get_args())
+        .map(|arg| arg.to_string_lossy().to_string())
+        .collect()
+}
+
+/// Runner that answers commands with canned output and records what ran
+#[cfg(test)]
+#[derive(Default)]
+pub struct MockRunner {
+    responses: Vec<(Vec<String>, Output)>,
+    calls: std::sync::Mutex<Vec<Vec<String>>>,
+}
+
+#[cfg(test)]
+impl MockRunner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Answer commands starting with `prefix` with `stdout` and exit code 0
+    pub fn on(self, prefix: &[&str], stdout: &str) -> Self {
+        self.respond(prefix, 0, stdout, "")
+    }
+
+    /// Answer commands starting with `prefix` with `stderr` and exit code 1
+    pub fn fail(self, prefix: &[&str], stderr: &str) -> Self {
+        self.respond(prefix, 1, "", stderr)
+    }
+
+    fn respond(mut self, prefix: &[&str], code: i32, stdout: &str, stderr: &str) -> Self {
+        use std::os::unix::process::ExitStatusExt;
+
+        let output = Output {
+            status: std::process::ExitStatus::from_raw(code << 8),
+            stdout: stdout.as_bytes().to_vec(),
+            stderr: stderr.as_bytes().to_vec(),
+        };
+        let prefix = prefix.iter().map(|s| s.to_string()).collect();
+        self.responses.push((prefix, output));
+        self
+    }
+
+    /// Command lines run so far
+    pub fn calls(&self) -> Vec<Vec<String>> {
+        self.calls.lock().unwrap().clone()
+    }
+}
+
+#[cfg(test)]
+impl CommandRunner for MockRunner {
+    fn output<'a>(&'a self, command: &'a mut Command) -> BoxFuture<'a, io::Result<Output>> {
+        let line = command_line(command);
+        self.calls.lock().unwrap().push(line.clone());
+        let response = self
+            .responses
+            .iter()
+            .find(|(prefix, _)| line.starts_with(prefix))
+            .map(|(_, output)| output.clone())
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("no mock response for {:?}", line),
+                )
+            });
+        Box::pin(async move { response })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_mock_runner_matches_prefix() {
+        let mock = Arc::new(MockRunner::new().on(&["echo", "hi"], "hi\n"));
+
+        let echoed = with_runner(mock.clone(), async {
+            output(Command::new("echo").args(["hi", "there"])).await
+        })
+        .await
+        .unwrap();
+        assert!(echoed.status.success());
+        assert_eq!(echoed.stdout, b"hi\n");
+
+        let missing = with_runner(mock.clone(), async {
+            output(&mut Command::new("false")).await
+        })
+        .await;
+        assert!(missing.is_err());
+        assert_eq!(mock.calls().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_output_records_recent_commands() {
+        let stderr = "x".repeat(STDERR_LIMIT + 10);
+        let marker = uuid::Uuid::new_v4().to_string();
+        let mock = Arc::new(MockRunner::new().fail(&["history-test"], &stderr));
+
+        let _ = with_runner(mock, async {
+            output(Command::new("history-test").arg(&marker)).await
+        })
+        .await;
+
+        let recorded = recent_commands()
+            .into_iter()
+            .find(|record| record.args == [marker.clone()])
+            .unwrap();
+        assert_eq!(recorded.command, "history-test");
+        assert_eq!(recorded.exit_code, Some(1));
+        assert_eq!(recorded.stderr.len(), STDERR_LIMIT);
+        assert!(recorded.stderr_truncated);
+        assert!(recorded.error.is_none());
+    }
+}
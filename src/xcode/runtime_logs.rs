@@ -0,0 +1,129 @@
+// Copyright 2026 Aptove
+// SPDX-License-Identifier: Apache-2.0
+
+//! Capture of a simulator's unified log during a test run
+//!
+//! `simctl spawn <udid> log stream` runs alongside xcodebuild. Lines are
+//! timestamped as they arrive and kept in a bounded buffer, from which the
+//! window covering each failed test is taken once the run finishes.
+
+use super::xcodebuild;
+use crate::error::{Result, XcbridgeError};
+use chrono::{DateTime, Duration, Utc};
+use std::collections::VecDeque;
+use std::process::Stdio;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::process::Child;
+
+/// Maximum number of log lines buffered for a run; older lines are dropped
+pub const MAX_BUFFERED_LINES: usize = 5000;
+
+/// Maximum number of log lines attached to a single failure (the latest are kept)
+pub const MAX_LINES_PER_FAILURE: usize = 200;
+
+/// How long to keep reading after the run ends, as log delivery lags slightly
+const DRAIN_GRACE: std::time::Duration = std::time::Duration::from_secs(1);
+
+/// Bounded buffer of timestamped log lines
+#[derive(Debug, Default)]
+pub struct RuntimeLogBuffer {
+    lines: VecDeque<(DateTime<Utc>, String)>,
+}
+
+impl RuntimeLogBuffer {
+    /// Add a line, dropping the oldest once the buffer is full
+    pub fn push(&mut self, at: DateTime<Utc>, line: String) {
+        if self.lines.len() == MAX_BUFFERED_LINES {
+            self.lines.pop_front();
+        }
+        self.lines.push_back((at, line));
+    }
+
+    /// Lines received between `start` and `end`, padded by a second on each
+    /// side for delivery lag, capped at [`MAX_LINES_PER_FAILURE`]
+    pub fn window(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> Vec<String> {
+        let start = start - Duration::seconds(1);
+        let end = end + Duration::seconds(1);
+        let lines: Vec<&String> = self
+            .lines
+            .iter()
+            .filter(|(at, _)| *at >= start && *at <= end)
+            .map(|(_, line)| line)
+            .collect();
+        let skip = lines.len().saturating_sub(MAX_LINES_PER_FAILURE);
+        lines.into_iter().skip(skip).cloned().collect()
+    }
+}
+
+/// A running `log stream` for one simulator
+pub struct RuntimeLogCapture {
+    child: Child,
+    buffer: Arc<Mutex<RuntimeLogBuffer>>,
+}
+
+impl RuntimeLogCapture {
+    /// Start streaming the simulator's log
+    pub fn start(udid: &str) -> Result<Self> {
+        let mut child = xcodebuild::xcrun()
+            .args([
+                "simctl", "spawn", udid, "log", "stream", "--style", "compact", "--level", "debug",
+            ])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| {
+                XcbridgeError::CommandFailed(format!("Failed to start log stream: {}", e))
+            })?;
+
+        let buffer = Arc::new(Mutex::new(RuntimeLogBuffer::default()));
+        if let Some(stdout) = child.stdout.take() {
+            let buffer = Arc::clone(&buffer);
+            tokio::spawn(async move {
+                let mut lines = BufReader::new(stdout).lines();
+                while let Ok(Some(line)) = lines.next_line().await {
+                    buffer.lock().unwrap().push(Utc::now(), line);
+                }
+            });
+        }
+
+        Ok(Self { child, buffer })
+    }
+
+    /// Stop the stream and return what was captured
+    pub async fn finish(mut self) -> RuntimeLogBuffer {
+        tokio::time::sleep(DRAIN_GRACE).await;
+        let _ = self.child.kill().await;
+        std::mem::take(&mut *self.buffer.lock().unwrap())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_runtime_log_window() {
+        let start = Utc::now();
+        let mut buffer = RuntimeLogBuffer::default();
+        for i in 0..MAX_BUFFERED_LINES + 10 {
+            buffer.push(start + Duration::seconds(i as i64), format!("line {}", i));
+        }
+
+        // The oldest lines were dropped
+        assert_eq!(buffer.lines.len(), MAX_BUFFERED_LINES);
+        assert_eq!(buffer.lines[0].1, "line 10");
+
+        let window = buffer.window(start + Duration::seconds(20), start + Duration::seconds(22));
+        assert_eq!(
+            window,
+            ["line 19", "line 20", "line 21", "line 22", "line 23"]
+        );
+
+        // Long windows keep the lines closest to the failure
+        let window = buffer.window(start, start + Duration::seconds(1000));
+        assert_eq!(window.len(), MAX_LINES_PER_FAILURE);
+        assert_eq!(window.last().unwrap(), "line 1001");
+    }
+}
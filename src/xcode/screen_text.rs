@@ -0,0 +1,92 @@
+// Copyright 2026 Aptove
+// SPDX-License-Identifier: Apache-2.0
+
+//! On-screen text recognition with the Vision framework
+//!
+//! Vision has no command line interface, so a small Swift helper is compiled
+//! with the installed toolchain on first use and cached in the temp directory.
+
+use crate::error::{Result, XcbridgeError};
+use crate::xcode::xcodebuild;
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use tokio::process::Command;
+use tokio::sync::OnceCell;
+
+const HELPER_SOURCE: &str = include_str!("screen_text.swift");
+
+static HELPER: OnceCell<PathBuf> = OnceCell::const_new();
+
+/// A line of text recognized on screen
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecognizedText {
+    pub text: String,
+    /// Recognition confidence from 0 to 1
+    pub confidence: f64,
+    /// Bounding box in screenshot pixels, from the top-left corner
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+/// Compile the helper, reusing a binary built from the same source
+async fn compile_helper() -> Result<PathBuf> {
+    let mut hasher = DefaultHasher::new();
+    HELPER_SOURCE.hash(&mut hasher);
+    let dir = std::env::temp_dir().join("xcbridge");
+    let binary = dir.join(format!("screen-text-{:016x}", hasher.finish()));
+    if binary.is_file() {
+        return Ok(binary);
+    }
+
+    tokio::fs::create_dir_all(&dir)
+        .await
+        .map_err(|e| XcbridgeError::Internal(e.to_string()))?;
+    let source = dir.join("screen_text.swift");
+    tokio::fs::write(&source, HELPER_SOURCE)
+        .await
+        .map_err(|e| XcbridgeError::Internal(e.to_string()))?;
+
+    tracing::info!("Compiling text recognition helper");
+    let output = xcodebuild::xcrun()
+        .args(["swiftc", "-O"])
+        .arg(&source)
+        .arg("-o")
+        .arg(&binary)
+        .output()
+        .await
+        .map_err(|e| XcbridgeError::CommandFailed(format!("swiftc failed: {}", e)))?;
+
+    if !output.status.success() {
+        return Err(XcbridgeError::CommandFailed(format!(
+            "Failed to compile text recognition helper: {}",
+            String::from_utf8_lossy(&output.stderr)
+        )));
+    }
+
+    Ok(binary)
+}
+
+/// Recognize the text in an image
+pub async fn recognize_text(image: &Path) -> Result<Vec<RecognizedText>> {
+    let helper = HELPER.get_or_try_init(compile_helper).await?;
+
+    let output = Command::new(helper)
+        .arg(image)
+        .output()
+        .await
+        .map_err(|e| XcbridgeError::CommandFailed(format!("Text recognition failed: {}", e)))?;
+
+    if !output.status.success() {
+        return Err(XcbridgeError::CommandFailed(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+
+    serde_json::from_slice(&output.stdout).map_err(|e| {
+        XcbridgeError::Internal(format!("Failed to parse text recognition output: {}", e))
+    })
+}
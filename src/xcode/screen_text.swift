@@ -0,0 +1,60 @@
+// Copyright 2026 Aptove
+// SPDX-License-Identifier: Apache-2.0
+//
+// Recognizes text in an image with the Vision framework and prints it as a
+// JSON array, with bounding boxes in pixels from the top-left corner.
+//
+// Usage: screen-text <image>
+
+import Foundation
+import ImageIO
+import Vision
+
+func fail(_ message: String) -> Never {
+    FileHandle.standardError.write(Data((message + "\n").utf8))
+    exit(1)
+}
+
+guard CommandLine.arguments.count == 2 else {
+    fail("usage: screen-text <image>")
+}
+
+let url = URL(fileURLWithPath: CommandLine.arguments[1])
+guard let source = CGImageSourceCreateWithURL(url as CFURL, nil),
+      let image = CGImageSourceCreateImageAtIndex(source, 0, nil)
+else {
+    fail("cannot read image: \(url.path)")
+}
+
+let request = VNRecognizeTextRequest()
+request.recognitionLevel = .accurate
+request.usesLanguageCorrection = true
+
+do {
+    try VNImageRequestHandler(cgImage: image).perform([request])
+} catch {
+    fail("text recognition failed: \(error)")
+}
+
+let width = Double(image.width)
+let height = Double(image.height)
+
+// Vision boxes are normalized with the origin at the bottom left
+let results: [[String: Any]] = (request.results ?? []).compactMap { observation in
+    guard let candidate = observation.topCandidates(1).first else { return nil }
+    let box = observation.boundingBox
+    return [
+        "text": candidate.string,
+        "confidence": Double(candidate.confidence),
+        "x": Double(box.minX) * width,
+        "y": (1 - Double(box.maxY)) * height,
+        "width": Double(box.width) * width,
+        "height": Double(box.height) * height,
+    ]
+}
+
+do {
+    FileHandle.standardOutput.write(try JSONSerialization.data(withJSONObject: results))
+} catch {
+    fail("cannot encode results: \(error)")
+}
@@ -0,0 +1,111 @@
+// Copyright 2026 Aptove
+// SPDX-License-Identifier: Apache-2.0
+
+//! Recognition of failed run-script build phases in xcodebuild output
+//!
+//! xcodebuild prints each script phase as a block: a `PhaseScriptExecution`
+//! header, the indented commands it ran, the script's own output, and for a
+//! failure, `Command PhaseScriptExecution failed with a nonzero exit code`.
+
+use serde::Serialize;
+
+const FAILED_MARKER: &str = "Command PhaseScriptExecution failed with a nonzero exit code";
+
+/// Lines of script output kept, from the end of the block
+const MAX_OUTPUT_LINES: usize = 50;
+
+/// A run-script phase that exited unsuccessfully
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ScriptPhaseFailure {
+    /// Name of the build phase, e.g. "SwiftLint"
+    pub script: String,
+    /// Target the phase belongs to
+    pub target: Option<String>,
+    /// What the script printed before failing (the last 50 lines)
+    pub output: Vec<String>,
+}
+
+/// Find the first run-script phase that failed
+pub fn find_script_phase_failure<'a>(
+    lines: impl IntoIterator<Item = &'a str>,
+) -> Option<ScriptPhaseFailure> {
+    let mut current: Option<ScriptPhaseFailure> = None;
+    for line in lines {
+        if let Some(header) = line.strip_prefix("PhaseScriptExecution ") {
+            current = Some(parse_header(header));
+        } else if line.contains(FAILED_MARKER) {
+            if let Some(mut failure) = current.take() {
+                let skip = failure.output.len().saturating_sub(MAX_OUTPUT_LINES);
+                failure.output.drain(..skip);
+                return Some(failure);
+            }
+        } else if let Some(failure) = current.as_mut() {
+            // The indented lines are the commands xcodebuild ran the script with
+            if !line.starts_with("    ") && !line.trim().is_empty() {
+                failure.output.push(line.to_string());
+            }
+        }
+    }
+    None
+}
+
+/// Parse `Run\ Script /path/Script-1A2B.sh (in target 'MyApp' from project 'MyApp')`
+fn parse_header(header: &str) -> ScriptPhaseFailure {
+    let (phase, target) = match header.split_once(" (in target '") {
+        Some((phase, rest)) => (phase, rest.split_once('\'').map(|(t, _)| t.to_string())),
+        None => (header.trim_end(), None),
+    };
+
+    // The name comes first, with its spaces escaped, then the script's path
+    let mut script = String::new();
+    let mut chars = phase.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => script.extend(chars.next()),
+            ' ' => break,
+            c => script.push(c),
+        }
+    }
+
+    ScriptPhaseFailure {
+        script,
+        target,
+        output: Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_script_phase_failure() {
+        let log = "\
+PhaseScriptExecution Generate\\ Assets /dd/Script-AAA.sh (in target 'MyApp' from project 'MyApp')
+    cd /src/MyApp
+    /bin/sh -c /dd/Script-AAA.sh
+Generated 12 assets
+PhaseScriptExecution Run\\ SwiftLint /dd/Script-BBB.sh (in target 'MyApp' from project 'MyApp')
+    cd /src/MyApp
+    export PATH\\=/usr/bin
+    /bin/sh -c /dd/Script-BBB.sh
+
+/src/MyApp/View.swift:3:1: error: Line Length Violation (line_length)
+Done linting! Found 1 violation, 1 serious in 4 files.
+Command PhaseScriptExecution failed with a nonzero exit code
+** BUILD FAILED **";
+
+        let failure = find_script_phase_failure(log.lines()).unwrap();
+        assert_eq!(failure.script, "Run SwiftLint");
+        assert_eq!(failure.target.as_deref(), Some("MyApp"));
+        assert_eq!(
+            failure.output,
+            [
+                "/src/MyApp/View.swift:3:1: error: Line Length Violation (line_length)",
+                "Done linting! Found 1 violation, 1 serious in 4 files.",
+            ]
+        );
+
+        assert!(find_script_phase_failure(log.lines().take(4)).is_none());
+    }
+}
@@ -0,0 +1,126 @@
+// Copyright 2026 Aptove
+// SPDX-License-Identifier: Apache-2.0
+
+//! Recognition of code-signing failures in xcodebuild and devicectl output
+
+use serde::Serialize;
+
+/// Where to look when signing fails, appended to signing error messages
+pub const SIGNING_HINT: &str = "Install a matching profile with POST /provisioning/install \
+     (see GET /provisioning/list for installed profiles) and make sure the signing \
+     certificate is in the login keychain";
+
+/// A code-signing failure with the details parsed from the message
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CodeSigningIssue {
+    /// "no_profiles", "team_required", "no_certificate", "no_account",
+    /// "profile_mismatch" or "verification_failed"
+    pub kind: String,
+    /// The line the failure was recognized from
+    pub message: String,
+    pub bundle_id: Option<String>,
+    pub team_id: Option<String>,
+    /// Target that couldn't be signed
+    pub target: Option<String>,
+}
+
+impl CodeSigningIssue {
+    fn new(kind: &str, line: &str) -> Self {
+        Self {
+            kind: kind.to_string(),
+            message: line.trim().to_string(),
+            bundle_id: None,
+            team_id: None,
+            target: None,
+        }
+    }
+}
+
+/// Recognize a code-signing failure in a line of output
+///
+/// Handles messages such as:
+/// `error: No profiles for 'com.example.MyApp' were found: ...`
+/// `error: Signing for "MyApp" requires a development team. ...`
+/// `error: No signing certificate "iOS Development" found: ... matching team ID "ABCDE12345" ...`
+/// `error: No Account for Team "ABCDE12345". ...`
+pub fn parse_signing_error(line: &str) -> Option<CodeSigningIssue> {
+    if line.contains("No profiles for") {
+        let mut issue = CodeSigningIssue::new("no_profiles", line);
+        issue.bundle_id = quoted_after(line, "No profiles for ", '\'');
+        return Some(issue);
+    }
+    if line.contains("requires a development team") {
+        let mut issue = CodeSigningIssue::new("team_required", line);
+        issue.target = quoted_after(line, "Signing for ", '"');
+        return Some(issue);
+    }
+    if line.contains("No signing certificate") {
+        let mut issue = CodeSigningIssue::new("no_certificate", line);
+        issue.team_id = quoted_after(line, "team ID ", '"');
+        return Some(issue);
+    }
+    if line.contains("No Account for Team") {
+        let mut issue = CodeSigningIssue::new("no_account", line);
+        issue.team_id = quoted_after(line, "No Account for Team ", '"');
+        return Some(issue);
+    }
+    if line.contains("doesn't include signing certificate")
+        || line.contains("doesn't match the entitlements")
+        || line.contains("provisioning profile for this executable was not found")
+    {
+        return Some(CodeSigningIssue::new("profile_mismatch", line));
+    }
+    if line.contains("ApplicationVerificationFailed") || line.contains("could not be verified") {
+        return Some(CodeSigningIssue::new("verification_failed", line));
+    }
+    None
+}
+
+/// Find the first code-signing failure in a job's output
+pub fn find_signing_error<'a>(
+    lines: impl IntoIterator<Item = &'a str>,
+) -> Option<CodeSigningIssue> {
+    lines.into_iter().find_map(parse_signing_error)
+}
+
+/// Text inside the quotes immediately following `prefix`
+fn quoted_after(line: &str, prefix: &str, quote: char) -> Option<String> {
+    let rest = &line[line.find(prefix)? + prefix.len()..];
+    let rest = rest.strip_prefix(quote)?;
+    Some(rest[..rest.find(quote)?].to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_signing_error() {
+        let issue = parse_signing_error(
+            "/src/MyApp.xcodeproj: error: No profiles for 'com.example.MyApp' were found: \
+             Xcode couldn't find any iOS App Development provisioning profiles matching \
+             'com.example.MyApp'. (in target 'MyApp' from project 'MyApp')",
+        )
+        .unwrap();
+        assert_eq!(issue.kind, "no_profiles");
+        assert_eq!(issue.bundle_id.as_deref(), Some("com.example.MyApp"));
+
+        let issue = parse_signing_error(
+            "error: Signing for \"MyApp\" requires a development team. Select a development \
+             team in the Signing & Capabilities editor.",
+        )
+        .unwrap();
+        assert_eq!(issue.kind, "team_required");
+        assert_eq!(issue.target.as_deref(), Some("MyApp"));
+
+        let issue = parse_signing_error(
+            "error: No signing certificate \"iOS Development\" found: No \"iOS Development\" \
+             signing certificate matching team ID \"ABCDE12345\" with a private key was found.",
+        )
+        .unwrap();
+        assert_eq!(issue.kind, "no_certificate");
+        assert_eq!(issue.team_id.as_deref(), Some("ABCDE12345"));
+
+        assert!(parse_signing_error("error: cannot find 'Foo' in scope").is_none());
+    }
+}
@@ -3,9 +3,16 @@
 
 //! simctl command wrapper for iOS Simulator management
 
-use crate::error::{Result, XcbridgeError};
+use crate::error::{CommandFailure, Result, XcbridgeError};
+use crate::xcode::{runner, xcodebuild};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
 use tokio::process::Command;
 
 /// Simulator device information
@@ -22,6 +29,12 @@ pub struct Simulator {
     pub data_path: Option<String>,
     #[serde(default)]
     pub log_path: Option<String>,
+    /// Why the simulator is unavailable, e.g. its runtime isn't installed
+    #[serde(rename = "availabilityError", default)]
+    pub availability_error: Option<String>,
+    /// Runtime identifier (e.g. "com.apple.CoreSimulator.SimRuntime.iOS-17-0")
+    #[serde(default)]
+    pub runtime: Option<String>,
 }
 
 /// Runtime information
@@ -43,6 +56,32 @@ pub struct Runtime {
     pub name: String,
 }
 
+/// Outcome of erasing a simulator after a test run
+#[derive(Debug, Clone, Serialize)]
+pub struct SimulatorErase {
+    pub udid: String,
+    pub erased: bool,
+    /// Why the simulator wasn't erased
+    pub error: Option<String>,
+}
+
+/// Simulators this service booted, the only ones it erases on its own
+static SERVICE_BOOTED: Mutex<BTreeSet<String>> = Mutex::new(BTreeSet::new());
+
+/// Record that this service booted a simulator
+pub fn mark_service_booted(udid: &str) {
+    SERVICE_BOOTED.lock().unwrap().insert(udid.to_string());
+}
+
+/// Whether this service booted a simulator (and hasn't shut it down since)
+pub fn is_service_booted(udid: &str) -> bool {
+    SERVICE_BOOTED.lock().unwrap().contains(udid)
+}
+
+fn forget_service_booted(udid: &str) {
+    SERVICE_BOOTED.lock().unwrap().remove(udid);
+}
+
 /// Output from simctl list -j
 #[derive(Debug, Deserialize)]
 struct SimctlListOutput {
@@ -53,32 +92,111 @@ struct SimctlListOutput {
 
 /// Run simctl command
 async fn simctl(args: &[&str]) -> Result<String> {
-    let output = Command::new("xcrun")
-        .arg("simctl")
-        .args(args)
-        .output()
+    let output = runner::output(xcodebuild::xcrun().arg("simctl").args(args))
         .await
         .map_err(|e| XcbridgeError::CommandFailed(format!("simctl failed: {}", e)))?;
 
     if output.status.success() {
         Ok(String::from_utf8_lossy(&output.stdout).to_string())
     } else {
-        Err(XcbridgeError::SimulatorError(
-            String::from_utf8_lossy(&output.stderr).to_string(),
-        ))
+        Err(XcbridgeError::SimulatorCommandFailed(CommandFailure::new(
+            "xcrun simctl",
+            args,
+            &output,
+        )))
     }
 }
 
-/// List all simulators
+/// stderr fragments of simctl failures that usually succeed when retried
+const TRANSIENT_ERRORS: &[&str] = &[
+    "Unable to boot device in current state",
+    "Unable to lookup in current state",
+    "CoreSimulatorService connection",
+    "Connection invalid",
+    "Failed to start launchd_sim",
+    "(ipc/mig) server died",
+    "Mach error -308",
+];
+
+/// Retries of transient failures for boot, install and launch
+static MAX_RETRIES: AtomicU32 = AtomicU32::new(2);
+
+/// Set how often boot, install and launch retry transient failures
+pub fn set_max_retries(retries: u32) {
+    MAX_RETRIES.store(retries, Ordering::Relaxed);
+}
+
+fn is_transient_error(stderr: &str) -> bool {
+    // Booting an already booted or booting device won't succeed on retry either
+    !stderr.contains("current state: Booted")
+        && !stderr.contains("current state: Booting")
+        && TRANSIENT_ERRORS.iter().any(|e| stderr.contains(e))
+}
+
+/// Longest wait between retries, however many `--simctl-retries` allows
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(10);
+
+/// Backoff before retry `attempt` (from 1): 500ms, doubling up to
+/// [`MAX_RETRY_DELAY`]
+fn retry_delay(attempt: u32) -> Duration {
+    1u64.checked_shl(attempt.saturating_sub(1))
+        .and_then(|factor| factor.checked_mul(500))
+        .map(Duration::from_millis)
+        .map_or(MAX_RETRY_DELAY, |delay| delay.min(MAX_RETRY_DELAY))
+}
+
+/// Run simctl command, retrying transient CoreSimulator failures with backoff
+async fn simctl_with_retry(args: &[&str]) -> Result<String> {
+    let retries = MAX_RETRIES.load(Ordering::Relaxed);
+    let mut attempt = 0;
+    loop {
+        match simctl(args).await {
+            Err(XcbridgeError::SimulatorCommandFailed(failure))
+                if attempt < retries && is_transient_error(&failure.stderr) =>
+            {
+                attempt += 1;
+                let delay = retry_delay(attempt);
+                tracing::warn!(
+                    "{} failed (attempt {} of {}), retrying in {:?}: {}",
+                    failure.command,
+                    attempt,
+                    retries + 1,
+                    delay,
+                    failure.stderr
+                );
+                tokio::time::sleep(delay).await;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// List all available simulators
 pub async fn list_devices() -> Result<Vec<Simulator>> {
+    let simulators = list_all_devices().await?;
+    Ok(simulators.into_iter().filter(|s| s.is_available).collect())
+}
+
+/// List all simulators, including those whose runtime is unavailable
+pub async fn list_all_devices() -> Result<Vec<Simulator>> {
     let output = simctl(&["list", "devices", "-j"]).await?;
-    let list: SimctlListOutput = serde_json::from_str(&output)
+    parse_device_list(&output)
+}
+
+/// Parse `simctl list devices -j` output, recording each device's runtime
+fn parse_device_list(output: &str) -> Result<Vec<Simulator>> {
+    let list: SimctlListOutput = serde_json::from_str(output)
         .map_err(|e| XcbridgeError::Internal(format!("Failed to parse simctl output: {}", e)))?;
 
     let mut simulators = Vec::new();
-    for (_runtime, devices) in list.devices {
-        simulators.extend(devices.into_iter().filter(|d| d.is_available));
+    for (runtime, devices) in list.devices {
+        simulators.extend(devices.into_iter().map(|mut device| {
+            device.runtime = Some(runtime.clone());
+            device
+        }));
     }
+    // Map order is arbitrary; keep listings stable
+    simulators.sort_by(|a, b| a.runtime.cmp(&b.runtime).then_with(|| a.name.cmp(&b.name)));
 
     Ok(simulators)
 }
@@ -92,10 +210,54 @@ pub async fn list_runtimes() -> Result<Vec<Runtime>> {
     Ok(list.runtimes.into_iter().filter(|r| r.is_available).collect())
 }
 
-/// Find a simulator by device type and runtime
+/// How long the CoreSimulator probe waits before calling the service wedged
+const SERVICE_PROBE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Held while a probe's `simctl list` runs, so overlapping checks never
+/// stack up simctl processes against a wedged service
+static SERVICE_PROBE: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+/// Check that CoreSimulator answers a cheap `simctl list`
+///
+/// A wedged service tends to hang rather than fail, so the probe times out,
+/// killing its simctl. Probes run one at a time, and time spent waiting for
+/// an earlier one counts towards the timeout.
+pub async fn probe_service() -> Result<()> {
+    let probe = async {
+        let _probing = SERVICE_PROBE.lock().await;
+        simctl(&["list", "runtimes", "-j"]).await
+    };
+    match tokio::time::timeout(SERVICE_PROBE_TIMEOUT, probe).await {
+        Ok(result) => result.map(|_| ()),
+        Err(_) => Err(XcbridgeError::Timeout(format!(
+            "simctl list did not answer within {}s",
+            SERVICE_PROBE_TIMEOUT.as_secs()
+        ))),
+    }
+}
+
+/// Add a simulator runtime from a downloaded disk image
+pub async fn add_runtime(image: &str) -> Result<String> {
+    tracing::info!("Adding simulator runtime from {}", image);
+    simctl(&["runtime", "add", image]).await
+}
+
+/// Find a simulator by device type and runtime, preferring one that is
+/// already booted
+///
+/// Fails with [`XcbridgeError::SimulatorUnavailable`] when only unavailable
+/// simulators match, and [`XcbridgeError::SimulatorNotFound`] when none do.
 pub async fn find_simulator(device_type: &str, runtime: Option<&str>) -> Result<Simulator> {
-    let simulators = list_devices().await?;
-    
+    let simulators = list_all_devices().await?;
+    select_simulator(simulators, device_type, runtime)
+}
+
+fn select_simulator(
+    mut simulators: Vec<Simulator>,
+    device_type: &str,
+    runtime: Option<&str>,
+) -> Result<Simulator> {
+    simulators.sort_by_key(|s| s.state != "Booted");
     let matches: Vec<_> = simulators
         .into_iter()
         .filter(|s| s.name.to_lowercase().contains(&device_type.to_lowercase()))
@@ -111,58 +273,284 @@ pub async fn find_simulator(device_type: &str, runtime: Option<&str>) -> Result<
         })
         .collect();
 
-    matches
+    available_or_unavailable_error(matches, device_type)
+}
+
+/// Get a simulator by UDID (in any case), including one that is unavailable
+pub async fn simulator_details(udid: &str) -> Result<Simulator> {
+    list_all_devices()
+        .await?
         .into_iter()
-        .next()
-        .ok_or_else(|| XcbridgeError::SimulatorNotFound(device_type.to_string()))
+        .find(|s| s.udid.eq_ignore_ascii_case(udid))
+        .ok_or_else(|| XcbridgeError::SimulatorNotFound(udid.to_string()))
+}
+
+/// Pick a simulator to lease, by UDID or device type, skipping the
+/// (uppercased) UDIDs in `leased`
+///
+/// Booted simulators are preferred so a lease starts warm. Fails with
+/// [`XcbridgeError::SimulatorUnavailable`] when every match is leased.
+pub fn select_for_lease(
+    simulators: Vec<Simulator>,
+    udid: Option<&str>,
+    device_type: Option<&str>,
+    runtime: Option<&str>,
+    leased: &HashSet<String>,
+) -> Result<Simulator> {
+    let is_leased = |s: &Simulator| leased.contains(&s.udid.to_ascii_uppercase());
+    let all_leased = |simulator: String| XcbridgeError::SimulatorUnavailable {
+        simulator,
+        reason: "it is leased to another session".to_string(),
+    };
+
+    if let Some(udid) = udid {
+        let simulator = select_by_udid(simulators, udid)?;
+        if is_leased(&simulator) {
+            return Err(all_leased(format!(
+                "{} ({})",
+                simulator.name, simulator.udid
+            )));
+        }
+        return Ok(simulator);
+    }
+
+    let Some(device_type) = device_type else {
+        return Err(XcbridgeError::InvalidRequest(
+            "Either udid or device_type must be specified".into(),
+        ));
+    };
+    let (taken, free): (Vec<_>, Vec<_>) = simulators.into_iter().partition(is_leased);
+    match select_simulator(free, device_type, runtime) {
+        Err(XcbridgeError::SimulatorNotFound(_))
+            if select_simulator(taken, device_type, runtime).is_ok() =>
+        {
+            Err(all_leased(device_type.to_string()))
+        }
+        result => result,
+    }
 }
 
 /// Get simulator by UDID
+///
+/// Fails with [`XcbridgeError::SimulatorUnavailable`] if the simulator exists
+/// but can't be used.
 pub async fn get_simulator(udid: &str) -> Result<Simulator> {
-    let simulators = list_devices().await?;
-    simulators
-        .into_iter()
-        .find(|s| s.udid == udid)
-        .ok_or_else(|| XcbridgeError::SimulatorNotFound(udid.to_string()))
+    let simulators = list_all_devices().await?;
+    select_by_udid(simulators, udid)
+}
+
+fn select_by_udid(simulators: Vec<Simulator>, udid: &str) -> Result<Simulator> {
+    let matches = simulators.into_iter().filter(|s| s.udid == udid).collect();
+    available_or_unavailable_error(matches, udid)
+}
+
+/// The first available simulator, or an error saying why there is none
+fn available_or_unavailable_error(matches: Vec<Simulator>, query: &str) -> Result<Simulator> {
+    let (mut available, unavailable): (Vec<_>, Vec<_>) =
+        matches.into_iter().partition(|s| s.is_available);
+    if !available.is_empty() {
+        return Ok(available.remove(0));
+    }
+
+    match unavailable.into_iter().next() {
+        Some(simulator) => Err(XcbridgeError::SimulatorUnavailable {
+            simulator: format!("{} ({})", simulator.name, simulator.udid),
+            reason: simulator.availability_error.unwrap_or_else(|| {
+                format!(
+                    "runtime {} is unavailable",
+                    simulator.runtime.as_deref().unwrap_or("unknown")
+                )
+            }),
+        }),
+        None => Err(XcbridgeError::SimulatorNotFound(query.to_string())),
+    }
 }
 
 /// Get the currently booted simulator (if any)
 pub async fn get_booted_simulator() -> Result<Option<Simulator>> {
+    Ok(booted_simulators().await?.into_iter().next())
+}
+
+/// List all currently booted simulators
+pub async fn booted_simulators() -> Result<Vec<Simulator>> {
     let simulators = list_devices().await?;
-    Ok(simulators.into_iter().find(|s| s.state == "Booted"))
+    Ok(simulators
+        .into_iter()
+        .filter(|s| s.state == "Booted")
+        .collect())
 }
 
-/// Boot a simulator
-pub async fn boot(udid: &str) -> Result<()> {
-    // Check if already booted
-    let sim = get_simulator(udid).await?;
+/// States a simulator passes through on its way to "Shutdown" or "Booted"
+const TRANSITIONAL_STATES: &[&str] = &["Creating", "Booting", "Shutting Down"];
+
+/// How long to wait for a simulator to boot or shut down
+const STATE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Whether a simulator state is on its way to another
+pub fn is_transitional_state(state: &str) -> bool {
+    TRANSITIONAL_STATES.contains(&state)
+}
+
+/// Wait until a simulator is in `state`, e.g. "Booted" or "Shutdown"
+pub async fn wait_for_state(udid: &str, state: &str, timeout: Duration) -> Result<Simulator> {
+    poll_state(udid, timeout, state, |current| current == state).await
+}
+
+/// Wait until a simulator has finished creating, booting or shutting down
+pub async fn wait_until_settled(udid: &str, timeout: Duration) -> Result<Simulator> {
+    poll_state(udid, timeout, "a settled state", |current| {
+        !is_transitional_state(current)
+    })
+    .await
+}
+
+async fn poll_state(
+    udid: &str,
+    timeout: Duration,
+    waiting_for: &str,
+    done: impl Fn(&str) -> bool,
+) -> Result<Simulator> {
+    let deadline = tokio::time::Instant::now() + timeout;
+    loop {
+        let sim = get_simulator(udid).await?;
+        if done(&sim.state) {
+            return Ok(sim);
+        }
+        if tokio::time::Instant::now() >= deadline {
+            return Err(XcbridgeError::Timeout(format!(
+                "Simulator {} did not reach {} within {}s (state: {})",
+                udid,
+                waiting_for,
+                timeout.as_secs(),
+                sim.state
+            )));
+        }
+        tokio::time::sleep(Duration::from_millis(500)).await;
+    }
+}
+
+/// Boot a simulator, returning whether it was already booted
+pub async fn boot(udid: &str) -> Result<bool> {
+    // Let a create, boot or shutdown in progress finish first
+    let sim = wait_until_settled(udid, STATE_TIMEOUT).await?;
     if sim.state == "Booted" {
         tracing::info!("Simulator {} is already booted", udid);
-        return Ok(());
+        return Ok(true);
     }
 
     tracing::info!("Booting simulator {}", udid);
-    simctl(&["boot", udid]).await?;
+    match simctl_with_retry(&["boot", udid]).await {
+        Ok(_) => {}
+        // Booted in the meantime, e.g. by a retried attempt
+        Err(XcbridgeError::SimulatorCommandFailed(failure))
+            if failure.stderr.contains("current state: Booted") => {}
+        // Booted by something outside the simulator lock, such as
+        // xcodebuild starting its destination, so wait for that boot instead
+        Err(XcbridgeError::SimulatorCommandFailed(failure))
+            if failure.stderr.contains("current state: Booting") =>
+        {
+            tracing::info!("Simulator {} is already booting, waiting for it", udid);
+            wait_for_state(udid, "Booted", STATE_TIMEOUT).await?;
+            return Ok(true);
+        }
+        Err(e) => return Err(e),
+    }
+    mark_service_booted(udid);
 
-    // Wait for boot to complete
-    for _ in 0..30 {
-        tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
-        let sim = get_simulator(udid).await?;
-        if sim.state == "Booted" {
-            tracing::info!("Simulator {} is now booted", udid);
+    wait_for_state(udid, "Booted", STATE_TIMEOUT).await?;
+    tracing::info!("Simulator {} is now booted", udid);
+    Ok(false)
+}
+
+/// Wait until a simulator has finished booting and SpringBoard is running
+///
+/// Uses `simctl bootstatus`, booting the simulator first if `boot` is set.
+/// Callers should bound this with a timeout.
+pub async fn wait_until_ready(udid: &str, boot: bool) -> Result<()> {
+    let mut args = vec!["bootstatus", udid];
+    let booting = boot && wait_until_settled(udid, STATE_TIMEOUT).await?.state == "Shutdown";
+    if boot {
+        args.push("-b");
+    }
+    simctl(&args).await?;
+    if booting {
+        mark_service_booted(udid);
+    }
+
+    // bootstatus can return before the home screen is responsive
+    loop {
+        let output = simctl(&["spawn", udid, "launchctl", "list"]).await?;
+        if is_springboard_running(&output) {
+            tracing::info!("Simulator {} is ready", udid);
             return Ok(());
         }
+        tokio::time::sleep(Duration::from_millis(500)).await;
     }
+}
 
-    Err(XcbridgeError::SimulatorError(
-        "Simulator boot timeout".to_string(),
-    ))
+/// Check `launchctl list` output for a running SpringBoard process
+fn is_springboard_running(launchctl_list: &str) -> bool {
+    launchctl_list.lines().any(|line| {
+        let mut columns = line.split_whitespace();
+        let pid = columns.next();
+        let label = columns.nth(1);
+        label == Some("com.apple.SpringBoard") && pid.is_some_and(|p| p.parse::<u32>().is_ok())
+    })
 }
 
-/// Shutdown a simulator
+const CORE_SIMULATOR_SERVICE: &str = "com.apple.CoreSimulator.CoreSimulatorService";
+
+/// Kill CoreSimulatorService so launchd respawns it
+///
+/// The service normally runs as the current user, so an unprivileged
+/// `killall` is tried first, then `sudo -n` (which needs passwordless sudo
+/// for `killall`). Returns `false` if the service wasn't running.
+pub async fn kill_core_simulator_service() -> Result<bool> {
+    tracing::warn!("Killing {}", CORE_SIMULATOR_SERVICE);
+    let args = ["-9", CORE_SIMULATOR_SERVICE];
+    let output = runner::output(Command::new("killall").args(args))
+        .await
+        .map_err(|e| XcbridgeError::CommandFailed(format!("killall failed: {}", e)))?;
+    if output.status.success() {
+        return Ok(true);
+    }
+    let not_ours = String::from_utf8_lossy(&output.stderr).contains("No matching processes");
+
+    let sudo_args = ["-n", "killall", "-9", CORE_SIMULATOR_SERVICE];
+    let output = runner::output(Command::new("sudo").args(sudo_args))
+        .await
+        .map_err(|e| XcbridgeError::CommandFailed(format!("sudo killall failed: {}", e)))?;
+    if output.status.success() {
+        return Ok(true);
+    }
+
+    // Not running as this user, and either not running at all or sudo isn't
+    // available to reach another user's instance
+    if not_ours || String::from_utf8_lossy(&output.stderr).contains("No matching processes") {
+        return Ok(false);
+    }
+    Err(XcbridgeError::SimulatorCommandFailed(CommandFailure::new(
+        "sudo", &sudo_args, &output,
+    )))
+}
+
+/// Wait until CoreSimulatorService answers simctl requests again
+pub async fn wait_for_service() {
+    while simctl(&["list", "devices", "-j"]).await.is_err() {
+        tokio::time::sleep(Duration::from_millis(500)).await;
+    }
+}
+
+/// Shutdown a simulator, waiting until it has stopped
 pub async fn shutdown(udid: &str) -> Result<()> {
+    if wait_until_settled(udid, STATE_TIMEOUT).await?.state == "Shutdown" {
+        forget_service_booted(udid);
+        return Ok(());
+    }
     tracing::info!("Shutting down simulator {}", udid);
     simctl(&["shutdown", udid]).await?;
+    forget_service_booted(udid);
+    wait_for_state(udid, "Shutdown", STATE_TIMEOUT).await?;
     Ok(())
 }
 
@@ -170,34 +558,132 @@ pub async fn shutdown(udid: &str) -> Result<()> {
 pub async fn shutdown_all() -> Result<()> {
     tracing::info!("Shutting down all simulators");
     simctl(&["shutdown", "all"]).await?;
+    SERVICE_BOOTED.lock().unwrap().clear();
+    Ok(())
+}
+
+/// Shut down a simulator if needed and erase its contents and settings
+pub async fn erase(udid: &str) -> Result<()> {
+    shutdown(udid).await?;
+    tracing::info!("Erasing simulator {}", udid);
+    simctl_with_retry(&["erase", udid]).await?;
+    forget_service_booted(udid);
     Ok(())
 }
 
 /// Install an app on a simulator
 pub async fn install(udid: &str, app_path: &str) -> Result<()> {
     tracing::info!("Installing {} to simulator {}", app_path, udid);
-    simctl(&["install", udid, app_path]).await?;
+    simctl_with_retry(&["install", udid, app_path]).await?;
     Ok(())
 }
 
+/// stderr fragments simctl emits when uninstalling an app that isn't installed
+const NOT_INSTALLED_ERRORS: &[&str] = &[
+    "is not installed",
+    "No such file or directory",
+    "Failed to look up app",
+];
+
+fn is_not_installed_error(stderr: &str) -> bool {
+    NOT_INSTALLED_ERRORS.iter().any(|e| stderr.contains(e))
+}
+
 /// Uninstall an app from a simulator
-pub async fn uninstall(udid: &str, bundle_id: &str) -> Result<()> {
+///
+/// Returns `false` if the app was not installed to begin with.
+pub async fn uninstall(udid: &str, bundle_id: &str) -> Result<bool> {
     tracing::info!("Uninstalling {} from simulator {}", bundle_id, udid);
-    simctl(&["uninstall", udid, bundle_id]).await?;
-    Ok(())
+    match simctl(&["uninstall", udid, bundle_id]).await {
+        Ok(_) => Ok(true),
+        Err(XcbridgeError::SimulatorCommandFailed(failure))
+            if is_not_installed_error(&failure.stderr) =>
+        {
+            tracing::info!("{} was not installed on simulator {}", bundle_id, udid);
+            Ok(false)
+        }
+        Err(e) => Err(e),
+    }
 }
 
-/// Launch an app on a simulator
-pub async fn launch(udid: &str, bundle_id: &str, args: &[String]) -> Result<()> {
+/// Launch an app on a simulator, returning the launched process id
+///
+/// With `terminate_running`, an already-running instance is terminated first.
+pub async fn launch(
+    udid: &str,
+    bundle_id: &str,
+    args: &[String],
+    terminate_running: bool,
+) -> Result<Option<u32>> {
     tracing::info!("Launching {} on simulator {}", bundle_id, udid);
-    let mut cmd_args = vec!["launch", udid, bundle_id];
+    let mut cmd_args = vec!["launch"];
+    if terminate_running {
+        cmd_args.push("--terminate-running-process");
+    }
+    cmd_args.extend([udid, bundle_id]);
     let args_refs: Vec<&str> = args.iter().map(|s| s.as_str()).collect();
     cmd_args.extend(args_refs);
-    simctl(&cmd_args).await?;
+    let output = simctl_with_retry(&cmd_args).await?;
+    Ok(parse_launch_pid(&output))
+}
+
+/// Parse the pid from `simctl launch` output ("com.example.MyApp: 12345")
+fn parse_launch_pid(output: &str) -> Option<u32> {
+    output
+        .lines()
+        .find_map(|line| line.rsplit_once(": "))
+        .and_then(|(_, pid)| pid.trim().parse().ok())
+}
+
+/// Signals that may be sent to simulator processes, with their numbers on
+/// Darwin
+const ALLOWED_SIGNALS: &[(&str, u8)] = &[
+    ("HUP", 1),
+    ("INT", 2),
+    ("QUIT", 3),
+    ("KILL", 9),
+    ("USR1", 30),
+    ("USR2", 31),
+    ("TERM", 15),
+    ("STOP", 17),
+    ("CONT", 19),
+];
+
+/// Normalize a signal name or number ("SIGTERM", "term", "15") to the name
+/// `kill` expects, if it is one of [`ALLOWED_SIGNALS`]
+fn normalize_signal(signal: &str) -> Option<String> {
+    let name = match signal.parse::<u8>() {
+        Ok(number) => ALLOWED_SIGNALS.iter().find(|(_, n)| *n == number)?.0,
+        Err(_) => {
+            let name = signal.to_uppercase();
+            let name = name.strip_prefix("SIG").unwrap_or(&name).to_string();
+            ALLOWED_SIGNALS.iter().find(|(n, _)| *n == name)?.0
+        }
+    };
+    Some(name.to_string())
+}
+
+/// Send a signal to a process running in a booted simulator
+///
+/// pid 1 is the simulator's launchd, so only pids above it are accepted.
+pub async fn signal(udid: &str, pid: u32, signal: &str) -> Result<()> {
+    if pid <= 1 {
+        return Err(XcbridgeError::InvalidRequest(format!(
+            "Can't signal pid {}",
+            pid
+        )));
+    }
+    let signal = normalize_signal(signal)
+        .ok_or_else(|| XcbridgeError::InvalidRequest(format!("Unsupported signal: {}", signal)))?;
+    let pid = pid.to_string();
+
+    tracing::info!("Sending SIG{} to pid {} on simulator {}", signal, pid, udid);
+    simctl(&["spawn", udid, "kill", &format!("-{}", signal), &pid]).await?;
     Ok(())
 }
 
 /// Terminate an app on a simulator
+#[allow(dead_code)] // Not routed yet
 pub async fn terminate(udid: &str, bundle_id: &str) -> Result<()> {
     tracing::info!("Terminating {} on simulator {}", bundle_id, udid);
     // Ignore errors - app might not be running
@@ -205,13 +691,308 @@ pub async fn terminate(udid: &str, bundle_id: &str) -> Result<()> {
     Ok(())
 }
 
+/// Set the simulator's preferred language and region
+///
+/// Writes `AppleLanguages`/`AppleLocale` to the simulator's global
+/// preferences and restarts SpringBoard so running apps pick them up.
+/// The simulator must be booted.
+pub async fn set_locale(udid: &str, language: &str, locale: &str) -> Result<()> {
+    tracing::info!(
+        "Setting locale {} ({}) on simulator {}",
+        locale,
+        language,
+        udid
+    );
+    simctl(&[
+        "spawn",
+        udid,
+        "defaults",
+        "write",
+        "-g",
+        "AppleLanguages",
+        "-array",
+        language,
+    ])
+    .await?;
+    simctl(&[
+        "spawn",
+        udid,
+        "defaults",
+        "write",
+        "-g",
+        "AppleLocale",
+        "-string",
+        locale,
+    ])
+    .await?;
+    respring(udid).await
+}
+
+/// Restart SpringBoard on a booted simulator
+pub async fn respring(udid: &str) -> Result<()> {
+    simctl(&["spawn", udid, "launchctl", "stop", "com.apple.SpringBoard"]).await?;
+    Ok(())
+}
+
+/// Light or dark mode
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Appearance {
+    Light,
+    Dark,
+}
+
+impl Appearance {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Appearance::Light => "light",
+            Appearance::Dark => "dark",
+        }
+    }
+}
+
+/// Switch a booted simulator between light and dark mode
+pub async fn set_appearance(udid: &str, appearance: Appearance) -> Result<()> {
+    simctl(&["ui", udid, "appearance", appearance.as_str()]).await?;
+    Ok(())
+}
+
+/// Values to show in the status bar, e.g. 9:41 and a full battery for
+/// screenshots; unset values keep their real state
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct StatusBarOverrides {
+    /// Time text, e.g. "9:41"
+    pub time: Option<String>,
+    /// "hide", "wifi", "3g", "4g", "lte", "lte-a", "lte+", "5g", "5g+", "5g-uwb" or "5g-uc"
+    pub data_network: Option<String>,
+    /// "searching", "failed" or "active"
+    pub wifi_mode: Option<String>,
+    /// 0 to 3
+    pub wifi_bars: Option<u8>,
+    /// "notSupported", "searching", "failed" or "active"
+    pub cellular_mode: Option<String>,
+    /// 0 to 4
+    pub cellular_bars: Option<u8>,
+    /// Carrier name
+    pub operator_name: Option<String>,
+    /// "charging", "charged" or "discharging"
+    pub battery_state: Option<String>,
+    /// 0 to 100
+    pub battery_level: Option<u8>,
+}
+
+impl StatusBarOverrides {
+    /// `simctl status_bar override` flags for the values that are set
+    fn args(&self) -> Vec<String> {
+        let flags = [
+            ("--time", self.time.clone()),
+            ("--dataNetwork", self.data_network.clone()),
+            ("--wifiMode", self.wifi_mode.clone()),
+            ("--wifiBars", self.wifi_bars.map(|n| n.to_string())),
+            ("--cellularMode", self.cellular_mode.clone()),
+            ("--cellularBars", self.cellular_bars.map(|n| n.to_string())),
+            ("--operatorName", self.operator_name.clone()),
+            ("--batteryState", self.battery_state.clone()),
+            ("--batteryLevel", self.battery_level.map(|n| n.to_string())),
+        ];
+        flags
+            .into_iter()
+            .filter_map(|(flag, value)| value.map(|v| [flag.to_string(), v]))
+            .flatten()
+            .collect()
+    }
+}
+
+/// Override status bar values on a booted simulator
+pub async fn override_status_bar(udid: &str, overrides: &StatusBarOverrides) -> Result<()> {
+    let flags = overrides.args();
+    if flags.is_empty() {
+        return Ok(());
+    }
+    let mut args = vec!["status_bar", udid, "override"];
+    args.extend(flags.iter().map(String::as_str));
+    simctl(&args).await?;
+    Ok(())
+}
+
+/// Connect or disconnect the host hardware keyboard for all simulators
+///
+/// This is a Simulator.app preference on the host, so it applies to every
+/// simulator and takes effect the next time a simulator window is focused.
+pub async fn set_hardware_keyboard(enabled: bool) -> Result<()> {
+    let value = if enabled { "true" } else { "false" };
+    let output = runner::output(Command::new("defaults").args([
+        "write",
+        "com.apple.iphonesimulator",
+        "ConnectHardwareKeyboard",
+        "-bool",
+        value,
+    ]))
+    .await
+        .map_err(|e| XcbridgeError::CommandFailed(format!("defaults failed: {}", e)))?;
+
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(XcbridgeError::SimulatorError(
+            String::from_utf8_lossy(&output.stderr).to_string(),
+        ))
+    }
+}
+
 /// Get the app container path
 pub async fn get_app_container(udid: &str, bundle_id: &str, container: &str) -> Result<String> {
     let output = simctl(&["get_app_container", udid, bundle_id, container]).await?;
     Ok(output.trim().to_string())
 }
 
+/// Tarball of an app's Documents directory
+///
+/// `udid` may be "booted". Only the Documents directory of the app's own data
+/// container is archived; symlinks are stored as links, not followed.
+pub async fn archive_documents(udid: &str, bundle_id: &str) -> Result<Vec<u8>> {
+    if !is_valid_bundle_id(bundle_id) {
+        return Err(XcbridgeError::InvalidRequest(format!(
+            "Invalid bundle_id: {}",
+            bundle_id
+        )));
+    }
+
+    let simulator = if udid == "booted" {
+        get_booted_simulator().await?.ok_or_else(|| {
+            XcbridgeError::SimulatorError("No simulator is currently booted".into())
+        })?
+    } else {
+        get_simulator(udid).await?
+    };
+
+    let container = PathBuf::from(get_app_container(&simulator.udid, bundle_id, "data").await?);
+    if !is_app_data_container(&container, &simulator.udid) {
+        return Err(XcbridgeError::PathNotAllowed(
+            container.display().to_string(),
+        ));
+    }
+
+    // Documents must not lead out of the container, e.g. through a symlink
+    let documents = tokio::fs::canonicalize(container.join("Documents"))
+        .await
+        .map_err(|_| {
+            XcbridgeError::SimulatorError(format!("App {} has no Documents directory", bundle_id))
+        })?;
+    let container = tokio::fs::canonicalize(&container)
+        .await
+        .map_err(|e| XcbridgeError::Internal(e.to_string()))?;
+    if documents != container.join("Documents") || !documents.is_dir() {
+        return Err(XcbridgeError::PathNotAllowed(
+            documents.display().to_string(),
+        ));
+    }
+
+    let output = runner::output(
+        Command::new("tar")
+            .args(["-cf", "-", "-C"])
+            .arg(&container)
+            .arg("Documents"),
+    )
+    .await
+    .map_err(|e| XcbridgeError::CommandFailed(format!("tar failed: {}", e)))?;
+
+    if output.status.success() {
+        Ok(output.stdout)
+    } else {
+        Err(XcbridgeError::CommandFailed(format!(
+            "tar failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )))
+    }
+}
+
+/// Most lines [`log_tail`] returns
+pub const MAX_TAIL_LINES: usize = 5000;
+
+/// Most minutes [`log_tail`] searches back
+pub const MAX_TAIL_MINUTES: u32 = 60;
+
+/// The last `lines` lines a simulator logged in the past `minutes`
+///
+/// Returns the lines, oldest first, and whether older ones were left out.
+pub async fn log_tail(
+    udid: &str,
+    lines: usize,
+    minutes: u32,
+    process: Option<&str>,
+) -> Result<(Vec<String>, bool)> {
+    let last = format!("{}m", minutes.clamp(1, MAX_TAIL_MINUTES));
+    let mut args = vec![
+        "spawn", udid, "log", "show", "--last", &last, "--style", "compact",
+    ];
+    let predicate;
+    if let Some(process) = process {
+        if process.is_empty() || process.contains(['"', '\\']) {
+            return Err(XcbridgeError::InvalidRequest(format!(
+                "Invalid process name: {}",
+                process
+            )));
+        }
+        predicate = format!("process == \"{}\"", process);
+        args.extend(["--predicate", &predicate]);
+    }
+
+    let output = simctl(&args).await?;
+    Ok(tail_log_lines(&output, lines.min(MAX_TAIL_LINES)))
+}
+
+/// The last `count` entries of `log show --style compact` output
+fn tail_log_lines(output: &str, count: usize) -> (Vec<String>, bool) {
+    let lines: Vec<&str> = output
+        .lines()
+        .filter(|line| {
+            !line.is_empty()
+                && !line.starts_with("Timestamp ")
+                && !line.starts_with("Filtering the log data")
+        })
+        .collect();
+    let skip = lines.len().saturating_sub(count);
+    let tail = lines[skip..].iter().map(|line| line.to_string()).collect();
+    (tail, skip > 0)
+}
+
+/// Reverse-DNS bundle identifier, e.g. "com.example.MyApp"
+fn is_valid_bundle_id(bundle_id: &str) -> bool {
+    !bundle_id.is_empty()
+        && !bundle_id.starts_with(['-', '.'])
+        && bundle_id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-')
+}
+
+/// Whether `path` is an app data container of the simulator `udid`:
+/// `.../CoreSimulator/Devices/<udid>/data/Containers/Data/Application/<uuid>`
+fn is_app_data_container(path: &Path, udid: &str) -> bool {
+    let Some(applications) = path.parent() else {
+        return false;
+    };
+    let components: Vec<_> = applications
+        .components()
+        .rev()
+        .take(7)
+        .map(|c| c.as_os_str().to_string_lossy().to_string())
+        .collect();
+    components
+        == [
+            "Application",
+            "Data",
+            "Containers",
+            "data",
+            udid,
+            "Devices",
+            "CoreSimulator",
+        ]
+        && path.file_name().is_some()
+}
+
 /// Open a URL in the simulator
+#[allow(dead_code)] // Not routed yet
 pub async fn open_url(udid: &str, url: &str) -> Result<()> {
     simctl(&["openurl", udid, url]).await?;
     Ok(())
@@ -223,11 +1004,423 @@ pub async fn screenshot(udid: &str, output_path: &str) -> Result<()> {
     Ok(())
 }
 
+/// Shake the device, as Device > Shake in Simulator.app does
+pub async fn shake(udid: &str) -> Result<()> {
+    simctl(&[
+        "spawn",
+        udid,
+        "notifyutil",
+        "-p",
+        "com.apple.UIKit.SimulatorShake",
+    ])
+    .await?;
+    Ok(())
+}
+
+/// Put text on a simulator's pasteboard
+///
+/// `simctl pbcopy` reads the text from stdin, so it is spawned directly
+/// rather than through the runner.
+pub async fn set_pasteboard(udid: &str, text: &str) -> Result<()> {
+    let mut child = xcodebuild::xcrun()
+        .args(["simctl", "pbcopy", udid])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| XcbridgeError::CommandFailed(format!("simctl failed: {}", e)))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(text.as_bytes())
+            .await
+            .map_err(|e| XcbridgeError::CommandFailed(format!("simctl pbcopy failed: {}", e)))?;
+    }
+    let output = child
+        .wait_with_output()
+        .await
+        .map_err(|e| XcbridgeError::CommandFailed(format!("simctl pbcopy failed: {}", e)))?;
+
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(XcbridgeError::SimulatorCommandFailed(CommandFailure::new(
+            "xcrun simctl",
+            &["pbcopy", udid],
+            &output,
+        )))
+    }
+}
+
 /// Record video
+#[allow(dead_code)] // Not routed yet
 pub async fn record_video(udid: &str, output_path: &str) -> Result<tokio::process::Child> {
-    let child = Command::new("xcrun")
+    let child = xcodebuild::xcrun()
         .args(["simctl", "io", udid, "recordVideo", output_path])
         .spawn()
         .map_err(|e| XcbridgeError::CommandFailed(format!("Failed to start recording: {}", e)))?;
     Ok(child)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::xcode::runner::MockRunner;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_is_app_data_container() {
+        let udid = "8A3B2C1D-0000-4000-8000-000000000001";
+        let container = format!(
+            "/Users/me/Library/Developer/CoreSimulator/Devices/{}/data/Containers/Data/Application/5E6F",
+            udid
+        );
+        assert!(is_app_data_container(Path::new(&container), udid));
+        assert!(!is_app_data_container(Path::new(&container), "OTHER-UDID"));
+        assert!(!is_app_data_container(
+            Path::new(&format!(
+                "/Users/me/Library/Developer/CoreSimulator/Devices/{}/data/Containers/Bundle/Application/5E6F",
+                udid
+            )),
+            udid
+        ));
+        assert!(!is_app_data_container(Path::new("/etc"), udid));
+    }
+
+    #[test]
+    fn test_tail_log_lines() {
+        let output = "Filtering the log data using \"process == \\\"MyApp\\\"\"\n\
+                      Timestamp               Ty Process[PID:TID]\n\
+                      2024-01-15 10:30:00.100 Df MyApp[123:456] one\n\
+                      2024-01-15 10:30:00.200 E  MyApp[123:456] two\n\
+                      2024-01-15 10:30:00.300 Df MyApp[123:456] three\n";
+
+        let (lines, truncated) = tail_log_lines(output, 2);
+        assert_eq!(
+            lines,
+            [
+                "2024-01-15 10:30:00.200 E  MyApp[123:456] two",
+                "2024-01-15 10:30:00.300 Df MyApp[123:456] three"
+            ]
+        );
+        assert!(truncated);
+
+        let (lines, truncated) = tail_log_lines(output, 10);
+        assert_eq!(lines.len(), 3);
+        assert!(!truncated);
+    }
+
+    #[test]
+    fn test_is_valid_bundle_id() {
+        assert!(is_valid_bundle_id("com.example.My-App2"));
+        assert!(!is_valid_bundle_id(""));
+        assert!(!is_valid_bundle_id("--help"));
+        assert!(!is_valid_bundle_id("../../etc"));
+        assert!(!is_valid_bundle_id("com.example/app"));
+    }
+
+    #[test]
+    fn test_is_transient_error() {
+        assert!(is_transient_error(
+            "An error was encountered processing the command (domain=com.apple.CoreSimulator.SimError, code=405):\nUnable to boot device in current state: Shutting Down"
+        ));
+        assert!(is_transient_error(
+            "CoreSimulatorService connection interrupted. Resubscribing to notifications."
+        ));
+        assert!(!is_transient_error(
+            "Unable to boot device in current state: Booted"
+        ));
+        assert!(!is_transient_error(
+            "Unable to boot device in current state: Booting"
+        ));
+        assert!(!is_transient_error(
+            "Invalid device: 00000000-0000-0000-0000-000000000000"
+        ));
+    }
+
+    #[test]
+    fn test_service_booted_tracking() {
+        let udid = "5D2C2F6A-0000-4000-8000-000000000001";
+        assert!(!is_service_booted(udid));
+        mark_service_booted(udid);
+        assert!(is_service_booted(udid));
+        forget_service_booted(udid);
+        assert!(!is_service_booted(udid));
+    }
+
+    #[test]
+    fn test_is_springboard_running() {
+        let running = "PID\tStatus\tLabel\n412\t0\tcom.apple.SpringBoard\n-\t0\tcom.apple.Siri\n";
+        let stopped = "PID\tStatus\tLabel\n-\t0\tcom.apple.SpringBoard\n";
+        assert!(is_springboard_running(running));
+        assert!(!is_springboard_running(stopped));
+    }
+
+    #[test]
+    fn test_parse_launch_pid() {
+        assert_eq!(parse_launch_pid("com.example.MyApp: 41235\n"), Some(41235));
+        assert_eq!(parse_launch_pid(""), None);
+    }
+
+    #[test]
+    fn test_normalize_signal() {
+        assert_eq!(normalize_signal("SIGTERM").as_deref(), Some("TERM"));
+        assert_eq!(normalize_signal("stop").as_deref(), Some("STOP"));
+        assert_eq!(normalize_signal("9").as_deref(), Some("KILL"));
+        assert_eq!(normalize_signal("17").as_deref(), Some("STOP"));
+        // Numbers outside the allowlist are refused like names
+        assert_eq!(normalize_signal("6"), None);
+        assert_eq!(normalize_signal("0"), None);
+        assert_eq!(normalize_signal("TERM; rm -rf /"), None);
+    }
+
+    #[test]
+    fn test_retry_delay_is_capped() {
+        assert_eq!(retry_delay(1), Duration::from_millis(500));
+        assert_eq!(retry_delay(2), Duration::from_secs(1));
+        assert_eq!(retry_delay(5), Duration::from_secs(8));
+        assert_eq!(retry_delay(6), MAX_RETRY_DELAY);
+        assert_eq!(retry_delay(64), MAX_RETRY_DELAY);
+        assert_eq!(retry_delay(u32::MAX), MAX_RETRY_DELAY);
+    }
+
+    #[tokio::test]
+    async fn test_signal_refuses_launchd() {
+        let mock = Arc::new(MockRunner::new().on(&["xcrun", "simctl", "spawn"], ""));
+        for pid in [0, 1] {
+            let result = runner::with_runner(mock.clone(), signal("booted", pid, "TERM")).await;
+            assert!(matches!(result, Err(XcbridgeError::InvalidRequest(_))));
+        }
+        assert!(mock.calls().is_empty());
+    }
+
+    #[test]
+    fn test_is_not_installed_error() {
+        assert!(is_not_installed_error(
+            "An error was encountered processing the command (domain=NSPOSIXErrorDomain, code=2):\n\
+             The operation couldn't be completed. No such file or directory"
+        ));
+        assert!(!is_not_installed_error(
+            "Unable to boot device in current state: Booted"
+        ));
+    }
+
+    const DEVICE_LIST: &str = r#"{
+        "devices": {
+            "com.apple.CoreSimulator.SimRuntime.iOS-17-0": [
+                {
+                    "udid": "AAAA",
+                    "name": "iPhone 15",
+                    "state": "Shutdown",
+                    "isAvailable": true,
+                    "deviceTypeIdentifier": "com.apple.CoreSimulator.SimDeviceType.iPhone-15"
+                }
+            ],
+            "com.apple.CoreSimulator.SimRuntime.iOS-16-4": [
+                {
+                    "udid": "BBBB",
+                    "name": "iPhone 14",
+                    "state": "Shutdown",
+                    "isAvailable": false,
+                    "availabilityError": "runtime profile not found using \"System\" match policy",
+                    "deviceTypeIdentifier": "com.apple.CoreSimulator.SimDeviceType.iPhone-14"
+                }
+            ]
+        }
+    }"#;
+
+    #[tokio::test]
+    async fn test_wait_for_state() {
+        let list = DEVICE_LIST.replace("\"Shutdown\"", "\"Shutting Down\"");
+        let mock = Arc::new(MockRunner::new().on(&["xcrun", "simctl", "list"], &list));
+
+        let err = runner::with_runner(
+            mock.clone(),
+            wait_for_state("AAAA", "Shutdown", Duration::from_millis(10)),
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(err, XcbridgeError::Timeout(_)));
+        assert!(err.to_string().contains("Shutting Down"));
+
+        let sim = runner::with_runner(
+            mock,
+            wait_for_state("AAAA", "Shutting Down", Duration::from_millis(10)),
+        )
+        .await
+        .unwrap();
+        assert!(is_transitional_state(&sim.state));
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_of_shutdown_simulator_is_noop() {
+        let mock = Arc::new(MockRunner::new().on(&["xcrun", "simctl", "list"], DEVICE_LIST));
+
+        runner::with_runner(mock.clone(), shutdown("AAAA"))
+            .await
+            .unwrap();
+        assert!(mock
+            .calls()
+            .iter()
+            .all(|call| !call.contains(&"shutdown".to_string())));
+    }
+
+    #[test]
+    fn test_select_simulator_not_found() {
+        let devices = parse_device_list(DEVICE_LIST).unwrap();
+        assert_eq!(devices.len(), 2);
+
+        let err = select_simulator(devices.clone(), "iPad", None).unwrap_err();
+        assert!(matches!(err, XcbridgeError::SimulatorNotFound(_)));
+        let err = select_by_udid(devices, "CCCC").unwrap_err();
+        assert!(matches!(err, XcbridgeError::SimulatorNotFound(_)));
+    }
+
+    #[test]
+    fn test_select_simulator_unavailable() {
+        let devices = parse_device_list(DEVICE_LIST).unwrap();
+
+        let sim = select_simulator(devices.clone(), "iPhone 15", None).unwrap();
+        assert_eq!(sim.udid, "AAAA");
+        assert_eq!(
+            sim.runtime.as_deref(),
+            Some("com.apple.CoreSimulator.SimRuntime.iOS-17-0")
+        );
+
+        let err = select_simulator(devices.clone(), "iPhone 14", None).unwrap_err();
+        match err {
+            XcbridgeError::SimulatorUnavailable { simulator, reason } => {
+                assert_eq!(simulator, "iPhone 14 (BBBB)");
+                assert!(reason.contains("runtime profile not found"));
+            }
+            other => panic!("unexpected error: {other:?}"),
+        }
+        let err = select_by_udid(devices, "BBBB").unwrap_err();
+        assert!(matches!(err, XcbridgeError::SimulatorUnavailable { .. }));
+    }
+
+    #[test]
+    fn test_select_simulator_prefers_booted() {
+        let mut devices = parse_device_list(DEVICE_LIST).unwrap();
+        let mut booted = devices.iter().find(|s| s.udid == "AAAA").unwrap().clone();
+        booted.udid = "DDDD".to_string();
+        booted.state = "Booted".to_string();
+        devices.push(booted);
+
+        let sim = select_simulator(devices, "iPhone 15", None).unwrap();
+        assert_eq!(sim.udid, "DDDD");
+    }
+
+    #[test]
+    fn test_select_for_lease() {
+        let mut devices = parse_device_list(DEVICE_LIST).unwrap();
+        let mut booted = devices.iter().find(|s| s.udid == "AAAA").unwrap().clone();
+        booted.udid = "DDDD".to_string();
+        booted.state = "Booted".to_string();
+        devices.push(booted);
+
+        let none = HashSet::new();
+        let sim = select_for_lease(devices.clone(), None, Some("iPhone 15"), None, &none).unwrap();
+        assert_eq!(sim.udid, "DDDD");
+
+        let leased = HashSet::from(["DDDD".to_string()]);
+        let sim =
+            select_for_lease(devices.clone(), None, Some("iPhone 15"), None, &leased).unwrap();
+        assert_eq!(sim.udid, "AAAA");
+
+        let leased = HashSet::from(["AAAA".to_string(), "DDDD".to_string()]);
+        let err =
+            select_for_lease(devices.clone(), None, Some("iPhone 15"), None, &leased).unwrap_err();
+        assert!(matches!(err, XcbridgeError::SimulatorUnavailable { .. }));
+        let err = select_for_lease(devices, Some("AAAA"), None, None, &leased).unwrap_err();
+        assert!(matches!(err, XcbridgeError::SimulatorUnavailable { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_simulator_details() {
+        let mock = Arc::new(MockRunner::new().on(&["xcrun", "simctl", "list"], DEVICE_LIST));
+
+        let sim = runner::with_runner(mock.clone(), simulator_details("bbbb"))
+            .await
+            .unwrap();
+        assert_eq!(sim.udid, "BBBB");
+        assert!(!sim.is_available);
+        assert_eq!(
+            sim.runtime.as_deref(),
+            Some("com.apple.CoreSimulator.SimRuntime.iOS-16-4")
+        );
+
+        let err = runner::with_runner(mock, simulator_details("CCCC"))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, XcbridgeError::SimulatorNotFound(_)));
+    }
+
+    #[tokio::test]
+    async fn test_list_devices_with_mock_runner() {
+        let mock = Arc::new(MockRunner::new().on(&["xcrun", "simctl", "list"], DEVICE_LIST));
+
+        let devices = runner::with_runner(mock.clone(), list_devices())
+            .await
+            .unwrap();
+        assert_eq!(devices.len(), 1);
+        assert_eq!(devices[0].udid, "AAAA");
+        assert_eq!(
+            mock.calls(),
+            vec![vec!["xcrun", "simctl", "list", "devices", "-j"]]
+        );
+
+        let err = runner::with_runner(mock, get_simulator("BBBB"))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, XcbridgeError::SimulatorUnavailable { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_override_status_bar() {
+        let mock = Arc::new(MockRunner::new().on(&["xcrun", "simctl", "status_bar"], ""));
+        let overrides = StatusBarOverrides {
+            time: Some("9:41".into()),
+            battery_level: Some(100),
+            ..Default::default()
+        };
+
+        runner::with_runner(mock.clone(), async {
+            override_status_bar("AAAA", &overrides).await.unwrap();
+            override_status_bar("AAAA", &StatusBarOverrides::default())
+                .await
+                .unwrap();
+        })
+        .await;
+        assert_eq!(
+            mock.calls(),
+            vec![vec![
+                "xcrun",
+                "simctl",
+                "status_bar",
+                "AAAA",
+                "override",
+                "--time",
+                "9:41",
+                "--batteryLevel",
+                "100"
+            ]]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_list_devices_reports_simctl_failure() {
+        let mock = Arc::new(MockRunner::new().fail(
+            &["xcrun", "simctl"],
+            "CoreSimulatorService connection became invalid",
+        ));
+
+        let err = runner::with_runner(mock, list_devices()).await.unwrap_err();
+        match err {
+            XcbridgeError::SimulatorCommandFailed(failure) => {
+                assert_eq!(failure.command, "xcrun simctl list devices -j");
+                assert_eq!(failure.exit_code, Some(1));
+            }
+            other => panic!("unexpected error: {other:?}"),
+        }
+    }
+}
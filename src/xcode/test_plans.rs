@@ -0,0 +1,448 @@
+// Copyright 2026 Aptove
+// SPDX-License-Identifier: Apache-2.0
+
+//! Test plan discovery from scheme files, and reading the plans themselves
+
+use crate::error::{Result, XcbridgeError};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// A test plan referenced by a scheme
+#[derive(Debug, Clone, Serialize)]
+pub struct TestPlanInfo {
+    /// Plan name, as passed to `test_plan`
+    pub name: String,
+    /// Path to the .xctestplan file
+    pub path: String,
+    /// Whether the scheme runs this plan by default
+    pub default: bool,
+    /// Names of the plan's configurations
+    pub configurations: Vec<String>,
+    /// Names of the test targets the plan runs
+    pub test_targets: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct TestPlanFile {
+    #[serde(default)]
+    configurations: Vec<TestPlanConfiguration>,
+    #[serde(default, rename = "testTargets")]
+    test_targets: Vec<TestPlanTarget>,
+}
+
+#[derive(Deserialize)]
+struct TestPlanConfiguration {
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct TestPlanTarget {
+    target: TargetReference,
+}
+
+#[derive(Deserialize)]
+struct TargetReference {
+    name: String,
+}
+
+/// A test plan's contents
+#[derive(Debug, Clone, Serialize)]
+pub struct TestPlan {
+    /// Plan name, as passed to `test_plan`
+    pub name: String,
+    /// Path to the .xctestplan file
+    pub path: String,
+    /// Options shared by every configuration, as written in the plan
+    pub default_options: serde_json::Value,
+    /// Configurations, each run of the plan running every one of them
+    pub configurations: Vec<TestPlanConfigurationInfo>,
+    /// Test targets, including disabled ones
+    pub test_targets: Vec<TestPlanTargetInfo>,
+}
+
+/// A configuration of a test plan
+#[derive(Debug, Clone, Serialize)]
+pub struct TestPlanConfigurationInfo {
+    /// Name, as passed to `only_test_configuration`
+    pub name: String,
+    /// Language the tests run in, e.g. "fr"
+    pub language: Option<String>,
+    /// Region the tests run in, e.g. "FR"
+    pub region: Option<String>,
+    /// Options overriding the plan's defaults, as written in the plan
+    pub options: serde_json::Value,
+}
+
+/// A test target of a test plan
+#[derive(Debug, Clone, Serialize)]
+pub struct TestPlanTargetInfo {
+    pub name: String,
+    /// Whether the plan runs the target
+    pub enabled: bool,
+    /// Whether its test classes may run in parallel
+    pub parallelizable: bool,
+    /// The only tests the plan runs, if it lists them, as `only_testing`
+    /// identifiers (`Target/Class/test`)
+    pub selected_tests: Vec<String>,
+    /// Tests the plan skips, as `skip_testing` identifiers
+    pub skipped_tests: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct TestPlanContents {
+    #[serde(default, rename = "defaultOptions")]
+    default_options: serde_json::Value,
+    #[serde(default)]
+    configurations: Vec<ConfigurationContents>,
+    #[serde(default, rename = "testTargets")]
+    test_targets: Vec<TargetContents>,
+}
+
+#[derive(Deserialize)]
+struct ConfigurationContents {
+    name: String,
+    #[serde(default)]
+    options: serde_json::Value,
+}
+
+#[derive(Deserialize)]
+struct TargetContents {
+    target: TargetReference,
+    #[serde(default = "enabled_by_default")]
+    enabled: bool,
+    #[serde(default)]
+    parallelizable: bool,
+    #[serde(default, rename = "selectedTests")]
+    selected_tests: Vec<String>,
+    #[serde(default, rename = "skippedTests")]
+    skipped_tests: Vec<String>,
+}
+
+fn enabled_by_default() -> bool {
+    true
+}
+
+/// Read a test plan's configurations and test targets
+pub fn read_test_plan_contents(path: &Path) -> Result<TestPlan> {
+    let json = std::fs::read_to_string(path).map_err(|e| {
+        XcbridgeError::InvalidRequest(format!("Failed to read {}: {}", path.display(), e))
+    })?;
+    let plan: TestPlanContents = serde_json::from_str(&json).map_err(|e| {
+        XcbridgeError::InvalidRequest(format!("Malformed {}: {}", path.display(), e))
+    })?;
+
+    let option = |options: &serde_json::Value, key: &str| {
+        options
+            .get(key)
+            .and_then(serde_json::Value::as_str)
+            .map(str::to_string)
+    };
+    Ok(TestPlan {
+        name: path
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default(),
+        path: path.to_string_lossy().to_string(),
+        configurations: plan
+            .configurations
+            .into_iter()
+            .map(|configuration| TestPlanConfigurationInfo {
+                language: option(&configuration.options, "language")
+                    .or_else(|| option(&plan.default_options, "language")),
+                region: option(&configuration.options, "region")
+                    .or_else(|| option(&plan.default_options, "region")),
+                name: configuration.name,
+                options: configuration.options,
+            })
+            .collect(),
+        default_options: plan.default_options,
+        test_targets: plan
+            .test_targets
+            .into_iter()
+            .map(|target| {
+                let identifiers = |tests: Vec<String>| {
+                    tests
+                        .iter()
+                        .map(|test| test_identifier(&target.target.name, test))
+                        .collect()
+                };
+                TestPlanTargetInfo {
+                    selected_tests: identifiers(target.selected_tests),
+                    skipped_tests: identifiers(target.skipped_tests),
+                    name: target.target.name,
+                    enabled: target.enabled,
+                    parallelizable: target.parallelizable,
+                }
+            })
+            .collect(),
+    })
+}
+
+/// `-only-testing` identifier for a plan's `Class/test()` entry in a target
+fn test_identifier(target: &str, test: &str) -> String {
+    format!("{}/{}", target, test.trim_end_matches("()"))
+}
+
+/// List the test plans of a scheme in a project or workspace
+///
+/// Plans that are referenced but can't be read are still listed, without
+/// configurations or targets.
+pub fn scheme_test_plans(container: &Path, scheme: &str) -> Result<Vec<TestPlanInfo>> {
+    let scheme_path = find_scheme(container, scheme)
+        .ok_or_else(|| XcbridgeError::InvalidRequest(format!("Scheme not found: {}", scheme)))?;
+
+    let xml = std::fs::read_to_string(&scheme_path).map_err(|e| {
+        XcbridgeError::Internal(format!("Failed to read {}: {}", scheme_path.display(), e))
+    })?;
+
+    // "container:" references are relative to the directory holding the
+    // project or workspace that owns the scheme
+    let owner = scheme_path
+        .ancestors()
+        .find(|p| is_container(p))
+        .unwrap_or(container);
+    let base = owner.parent().unwrap_or(owner);
+
+    Ok(parse_test_plan_references(&xml)
+        .into_iter()
+        .map(|(reference, default)| {
+            let path = base.join(reference.strip_prefix("container:").unwrap_or(&reference));
+            read_test_plan(&path, default)
+        })
+        .collect())
+}
+
+fn read_test_plan(path: &Path, default: bool) -> TestPlanInfo {
+    let plan: Option<TestPlanFile> = std::fs::read_to_string(path)
+        .ok()
+        .and_then(|json| serde_json::from_str(&json).ok());
+
+    let (configurations, test_targets) = match plan {
+        Some(plan) => (
+            plan.configurations.into_iter().map(|c| c.name).collect(),
+            plan.test_targets
+                .into_iter()
+                .map(|t| t.target.name)
+                .collect(),
+        ),
+        None => (Vec::new(), Vec::new()),
+    };
+
+    TestPlanInfo {
+        name: path
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default(),
+        path: path.to_string_lossy().to_string(),
+        default,
+        configurations,
+        test_targets,
+    }
+}
+
+fn is_container(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext == "xcodeproj" || ext == "xcworkspace")
+}
+
+/// Find a scheme's .xcscheme file, shared schemes first
+///
+/// Workspace schemes may also live in any project the workspace references.
+fn find_scheme(container: &Path, scheme: &str) -> Option<PathBuf> {
+    let file_name = format!("{}.xcscheme", scheme);
+    let mut containers = vec![container.to_path_buf()];
+    if container
+        .extension()
+        .is_some_and(|ext| ext == "xcworkspace")
+    {
+        containers.extend(workspace_projects(container));
+    }
+
+    containers.iter().find_map(|container| {
+        let shared = container.join("xcshareddata/xcschemes").join(&file_name);
+        if shared.is_file() {
+            return Some(shared);
+        }
+
+        std::fs::read_dir(container.join("xcuserdata"))
+            .ok()?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path().join("xcschemes").join(&file_name))
+            .find(|path| path.is_file())
+    })
+}
+
+/// Projects referenced from a workspace's contents.xcworkspacedata
+fn workspace_projects(workspace: &Path) -> Vec<PathBuf> {
+    let Ok(contents) = std::fs::read_to_string(workspace.join("contents.xcworkspacedata")) else {
+        return Vec::new();
+    };
+    let base = workspace.parent().unwrap_or(workspace);
+
+    attribute_values(&contents, "location")
+        .into_iter()
+        .filter_map(|location| {
+            let (kind, path) = location.split_once(':')?;
+            match kind {
+                "group" | "container" => Some(base.join(path)),
+                "absolute" => Some(PathBuf::from(path)),
+                _ => None,
+            }
+        })
+        .filter(|path| path.extension().is_some_and(|ext| ext == "xcodeproj"))
+        .collect()
+}
+
+/// Test plan references in a scheme, with whether each is the default
+fn parse_test_plan_references(xml: &str) -> Vec<(String, bool)> {
+    xml.split("<TestPlanReference")
+        .skip(1)
+        .filter_map(|element| {
+            let element = &element[..element.find('>').unwrap_or(element.len())];
+            let reference = attribute_values(element, "reference").into_iter().next()?;
+            let default = attribute_values(element, "default")
+                .first()
+                .map(String::as_str)
+                == Some("YES");
+            Some((reference, default))
+        })
+        .collect()
+}
+
+/// Values of an XML attribute, written as `name = "value"` in Xcode's files
+fn attribute_values(xml: &str, name: &str) -> Vec<String> {
+    let mut values = Vec::new();
+    let mut rest = xml;
+    while let Some(index) = rest.find(name) {
+        rest = &rest[index + name.len()..];
+        let Some(value) = rest.trim_start().strip_prefix('=') else {
+            continue;
+        };
+        let Some(value) = value.trim_start().strip_prefix('"') else {
+            continue;
+        };
+        if let Some(end) = value.find('"') {
+            values.push(value[..end].to_string());
+        }
+    }
+    values
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scheme_test_plans() {
+        let dir = std::env::temp_dir().join(format!("xcbridge-{}", uuid::Uuid::new_v4()));
+        let project = dir.join("MyApp.xcodeproj");
+        let schemes = project.join("xcshareddata/xcschemes");
+        std::fs::create_dir_all(&schemes).unwrap();
+        std::fs::write(
+            schemes.join("MyApp.xcscheme"),
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<Scheme LastUpgradeVersion = "1500" version = "1.7">
+   <TestAction buildConfiguration = "Debug">
+      <TestPlans>
+         <TestPlanReference
+            reference = "container:MyApp.xctestplan"
+            default = "YES">
+         </TestPlanReference>
+         <TestPlanReference
+            reference = "container:Plans/Smoke.xctestplan">
+         </TestPlanReference>
+      </TestPlans>
+   </TestAction>
+</Scheme>"#,
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("MyApp.xctestplan"),
+            r#"{
+  "configurations": [
+    {"id": "A", "name": "English", "options": {}},
+    {"id": "B", "name": "French", "options": {"language": "fr"}}
+  ],
+  "defaultOptions": {},
+  "testTargets": [
+    {"target": {"containerPath": "container:MyApp.xcodeproj", "identifier": "1", "name": "MyAppTests"}}
+  ],
+  "version": 1
+}"#,
+        )
+        .unwrap();
+
+        let plans = scheme_test_plans(&project, "MyApp").unwrap();
+
+        assert_eq!(plans.len(), 2);
+        assert_eq!(plans[0].name, "MyApp");
+        assert!(plans[0].default);
+        assert_eq!(plans[0].configurations, ["English", "French"]);
+        assert_eq!(plans[0].test_targets, ["MyAppTests"]);
+        assert_eq!(plans[1].name, "Smoke");
+        assert!(!plans[1].default);
+        assert!(plans[1].configurations.is_empty());
+
+        assert!(scheme_test_plans(&project, "Missing").is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_read_test_plan_contents() {
+        let path =
+            std::env::temp_dir().join(format!("xcbridge-{}.xctestplan", uuid::Uuid::new_v4()));
+        std::fs::write(
+            &path,
+            r#"{
+  "configurations": [
+    {"id": "A", "name": "English", "options": {}},
+    {"id": "B", "name": "French", "options": {"language": "fr", "region": "FR"}}
+  ],
+  "defaultOptions": {"language": "en", "testTimeoutsEnabled": true},
+  "testTargets": [
+    {
+      "parallelizable": true,
+      "skippedTests": ["LoginTests/testSlowLogin()", "FlakyTests"],
+      "target": {"containerPath": "container:MyApp.xcodeproj", "identifier": "1", "name": "MyAppTests"}
+    },
+    {
+      "enabled": false,
+      "selectedTests": ["LaunchTests/testLaunch()"],
+      "target": {"containerPath": "container:MyApp.xcodeproj", "identifier": "2", "name": "MyAppUITests"}
+    }
+  ],
+  "version": 1
+}"#,
+        )
+        .unwrap();
+
+        let plan = read_test_plan_contents(&path).unwrap();
+        assert_eq!(plan.default_options["testTimeoutsEnabled"], true);
+        assert_eq!(plan.configurations[0].language.as_deref(), Some("en"));
+        assert_eq!(plan.configurations[1].language.as_deref(), Some("fr"));
+        assert_eq!(plan.configurations[1].region.as_deref(), Some("FR"));
+
+        let tests = &plan.test_targets[0];
+        assert!(tests.enabled && tests.parallelizable);
+        assert_eq!(
+            tests.skipped_tests,
+            [
+                "MyAppTests/LoginTests/testSlowLogin",
+                "MyAppTests/FlakyTests"
+            ]
+        );
+        let ui_tests = &plan.test_targets[1];
+        assert!(!ui_tests.enabled && !ui_tests.parallelizable);
+        assert_eq!(
+            ui_tests.selected_tests,
+            ["MyAppUITests/LaunchTests/testLaunch"]
+        );
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(
+            read_test_plan_contents(&path),
+            Err(XcbridgeError::InvalidRequest(_))
+        ));
+    }
+}
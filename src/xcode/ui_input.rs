@@ -0,0 +1,205 @@
+// Copyright 2026 Aptove
+// SPDX-License-Identifier: Apache-2.0
+
+//! Taps, text input and hardware buttons on simulators
+//!
+//! simctl can't send touches, key presses or button presses. When Meta's
+//! `idb` client and `idb_companion` are installed, they go through `idb ui`.
+//! Without them, text can only be put on the simulator's pasteboard for the
+//! app to paste, shakes are posted with `notifyutil`, and taps and the other
+//! buttons are unavailable.
+
+use crate::error::{CommandFailure, Result, XcbridgeError};
+use crate::xcode::{runner, simctl};
+use serde::{Deserialize, Serialize};
+use std::io::ErrorKind;
+use tokio::process::Command;
+
+/// How text reached a simulator
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TextInputMethod {
+    /// Typed into the focused field with `idb ui text`
+    Typed,
+    /// Put on the pasteboard, since idb isn't installed
+    Pasteboard,
+}
+
+/// A hardware button or gesture
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Button {
+    Home,
+    Lock,
+    Siri,
+    Shake,
+}
+
+impl Button {
+    pub fn name(self) -> &'static str {
+        match self {
+            Button::Home => "home",
+            Button::Lock => "lock",
+            Button::Siri => "siri",
+            Button::Shake => "shake",
+        }
+    }
+
+    /// Name of the button in `idb ui button`
+    fn idb_name(self) -> Option<&'static str> {
+        match self {
+            Button::Home => Some("HOME"),
+            Button::Lock => Some("LOCK"),
+            Button::Siri => Some("SIRI"),
+            Button::Shake => None,
+        }
+    }
+}
+
+const IDB_MISSING: &str = "This needs idb, which isn't installed. Install it with \
+    `brew install facebook/fb/idb-companion` and `pip3 install fb-idb`";
+
+/// Tap a point on the screen, in points from the top left
+pub async fn tap(udid: &str, x: f64, y: f64, duration_secs: Option<f64>) -> Result<()> {
+    let mut args = vec![
+        "ui".to_string(),
+        "tap".to_string(),
+        "--udid".to_string(),
+        udid.to_string(),
+    ];
+    if let Some(duration) = duration_secs {
+        args.extend(["--duration".to_string(), duration.to_string()]);
+    }
+    // idb takes whole points
+    args.extend([
+        (x.round() as i64).to_string(),
+        (y.round() as i64).to_string(),
+    ]);
+
+    match idb(&args).await? {
+        Some(()) => Ok(()),
+        None => Err(XcbridgeError::SimulatorError(IDB_MISSING.into())),
+    }
+}
+
+/// Type text into the focused field, or put it on the pasteboard without idb
+pub async fn type_text(udid: &str, text: &str) -> Result<TextInputMethod> {
+    let args = ["ui", "text", "--udid", udid, "--", text].map(str::to_string);
+    match idb(&args).await? {
+        Some(()) => Ok(TextInputMethod::Typed),
+        None => {
+            simctl::set_pasteboard(udid, text).await?;
+            Ok(TextInputMethod::Pasteboard)
+        }
+    }
+}
+
+/// Press a hardware button, or shake the device
+pub async fn press_button(udid: &str, button: Button) -> Result<()> {
+    let Some(name) = button.idb_name() else {
+        return simctl::shake(udid).await;
+    };
+    let args = ["ui", "button", "--udid", udid, name].map(str::to_string);
+    match idb(&args).await? {
+        Some(()) => Ok(()),
+        None => Err(XcbridgeError::SimulatorError(IDB_MISSING.into())),
+    }
+}
+
+/// Run an idb command, or return `None` if idb isn't installed
+async fn idb(args: &[String]) -> Result<Option<()>> {
+    let mut cmd = Command::new("idb");
+    cmd.args(args);
+    let output = match runner::output(&mut cmd).await {
+        Ok(output) => output,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(XcbridgeError::CommandFailed(format!("idb failed: {}", e))),
+    };
+
+    if output.status.success() {
+        Ok(Some(()))
+    } else {
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        Err(XcbridgeError::SimulatorCommandFailed(CommandFailure::new(
+            "idb", &args, &output,
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::xcode::runner::MockRunner;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_tap_and_type_with_idb() {
+        let mock = Arc::new(MockRunner::new().on(&["idb", "ui"], ""));
+
+        runner::with_runner(mock.clone(), async {
+            tap("AAAA", 120.4, 300.6, Some(0.5)).await.unwrap();
+            let method = type_text("AAAA", "-hello").await.unwrap();
+            assert_eq!(method, TextInputMethod::Typed);
+        })
+        .await;
+
+        let calls = mock.calls();
+        assert_eq!(
+            calls[0],
+            [
+                "idb",
+                "ui",
+                "tap",
+                "--udid",
+                "AAAA",
+                "--duration",
+                "0.5",
+                "120",
+                "301"
+            ]
+        );
+        assert_eq!(
+            calls[1],
+            ["idb", "ui", "text", "--udid", "AAAA", "--", "-hello"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_press_button() {
+        let mock = Arc::new(
+            MockRunner::new()
+                .on(&["idb", "ui", "button"], "")
+                .on(&["xcrun", "simctl", "spawn"], ""),
+        );
+
+        runner::with_runner(mock.clone(), async {
+            press_button("AAAA", Button::Lock).await.unwrap();
+            press_button("AAAA", Button::Shake).await.unwrap();
+        })
+        .await;
+
+        let calls = mock.calls();
+        assert_eq!(calls[0], ["idb", "ui", "button", "--udid", "AAAA", "LOCK"]);
+        assert_eq!(
+            calls[1],
+            [
+                "xcrun",
+                "simctl",
+                "spawn",
+                "AAAA",
+                "notifyutil",
+                "-p",
+                "com.apple.UIKit.SimulatorShake"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_tap_without_idb() {
+        // The mock answers unknown commands with NotFound, as a missing binary does
+        let mock = Arc::new(MockRunner::new());
+
+        let result = runner::with_runner(mock, tap("AAAA", 1.0, 2.0, None)).await;
+        assert!(matches!(result, Err(XcbridgeError::SimulatorError(_))));
+    }
+}
@@ -0,0 +1,621 @@
+// Copyright 2026 Aptove
+// SPDX-License-Identifier: Apache-2.0
+
+//! Pre-install validation of app bundles
+
+use super::devicectl::Device;
+use super::{runner, xcodebuild};
+use crate::error::{Result, XcbridgeError};
+use serde::Serialize;
+use std::cmp::Ordering;
+use std::path::Path;
+use tokio::process::Command;
+
+/// Outcome of validating an app bundle
+#[derive(Debug, Serialize)]
+pub struct AppValidation {
+    /// Path to the .app bundle
+    pub path: String,
+    /// Whether every check passed
+    pub valid: bool,
+    /// CFBundleIdentifier (if readable)
+    pub bundle_id: Option<String>,
+    /// CFBundleSupportedPlatforms (e.g. "iPhoneSimulator", "iPhoneOS")
+    pub platforms: Vec<String>,
+    /// Architectures of the main executable (e.g. "arm64", "x86_64")
+    pub architectures: Vec<String>,
+    /// MinimumOSVersion (iOS) or LSMinimumSystemVersion (macOS)
+    pub minimum_os_version: Option<String>,
+    /// Individual checks, in the order they ran
+    pub checks: Vec<ValidationCheck>,
+}
+
+/// What an app bundle needs from the device it runs on
+#[derive(Debug, Serialize)]
+pub struct AppCompatibility {
+    /// Path to the .app bundle
+    pub path: String,
+    /// CFBundleIdentifier (if readable)
+    pub bundle_id: Option<String>,
+    /// MinimumOSVersion (iOS) or LSMinimumSystemVersion (macOS)
+    pub minimum_os_version: Option<String>,
+    /// UIDeviceFamily as names: "iphone", "ipad", "tv", "watch", "mac" or
+    /// "vision" (unknown families keep their number)
+    pub device_families: Vec<String>,
+    /// CFBundleSupportedPlatforms (e.g. "iPhoneSimulator", "iPhoneOS")
+    pub platforms: Vec<String>,
+    /// Architectures of the main executable (e.g. "arm64", "x86_64")
+    pub architectures: Vec<String>,
+}
+
+/// A single validation check
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ValidationCheck {
+    /// "info_plist", "bundle_id", "executable", "version", "platform",
+    /// "architecture", "embedded_profile" or "code_signature"
+    pub name: String,
+    pub passed: bool,
+    /// Why the check failed
+    pub message: Option<String>,
+}
+
+impl ValidationCheck {
+    fn pass(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            passed: true,
+            message: None,
+        }
+    }
+
+    fn fail(name: &str, message: impl Into<String>) -> Self {
+        Self {
+            name: name.to_string(),
+            passed: false,
+            message: Some(message.into()),
+        }
+    }
+}
+
+/// Info.plist fields used by validation
+#[derive(Debug, Default)]
+struct BundleInfo {
+    bundle_id: Option<String>,
+    executable: Option<String>,
+    platforms: Vec<String>,
+    minimum_os_version: Option<String>,
+    device_families: Vec<String>,
+}
+
+/// Validate an app bundle, optionally for a target platform
+///
+/// `platform` is a CFBundleSupportedPlatforms name such as "iPhoneOS" (devices)
+/// or "iPhoneSimulator", compared case-insensitively.
+pub async fn validate_app(app: &Path, platform: Option<&str>) -> AppValidation {
+    let (info, mut checks) = check_info_plist(app);
+
+    let executable = info
+        .executable
+        .as_ref()
+        .map(|name| app.join(name))
+        .filter(|path| path.is_file());
+    let architectures = match &executable {
+        Some(executable) => executable_architectures(executable).await,
+        None => Vec::new(),
+    };
+
+    if let Some(platform) = platform {
+        checks.extend(check_platform(&info, &architectures, platform));
+        if is_device_platform(platform) {
+            if app.join("embedded.mobileprovision").is_file() {
+                checks.push(ValidationCheck::pass("embedded_profile"));
+            } else {
+                checks.push(ValidationCheck::fail(
+                    "embedded_profile",
+                    "No embedded.mobileprovision; device installs require a provisioned build",
+                ));
+            }
+        }
+    }
+
+    checks.push(verify_code_signature(app).await);
+
+    AppValidation {
+        path: app.to_string_lossy().to_string(),
+        valid: checks.iter().all(|check| check.passed),
+        bundle_id: info.bundle_id,
+        platforms: info.platforms,
+        architectures,
+        minimum_os_version: info.minimum_os_version,
+        checks,
+    }
+}
+
+/// Check the Info.plist basics: readable, with a bundle id, an executable
+/// that exists and version strings
+fn check_info_plist(app: &Path) -> (BundleInfo, Vec<ValidationCheck>) {
+    let mut checks = Vec::new();
+
+    let Some(plist_path) = [app.join("Info.plist"), app.join("Contents/Info.plist")]
+        .into_iter()
+        .find(|path| path.is_file())
+    else {
+        checks.push(ValidationCheck::fail(
+            "info_plist",
+            "No Info.plist in bundle",
+        ));
+        return (BundleInfo::default(), checks);
+    };
+
+    let dict = match plist::Value::from_file(&plist_path) {
+        Ok(plist::Value::Dictionary(dict)) => dict,
+        Ok(_) => {
+            checks.push(ValidationCheck::fail(
+                "info_plist",
+                "Info.plist is not a dictionary",
+            ));
+            return (BundleInfo::default(), checks);
+        }
+        Err(e) => {
+            checks.push(ValidationCheck::fail(
+                "info_plist",
+                format!("Failed to read Info.plist: {}", e),
+            ));
+            return (BundleInfo::default(), checks);
+        }
+    };
+    checks.push(ValidationCheck::pass("info_plist"));
+
+    let string = |key: &str| {
+        dict.get(key)
+            .and_then(plist::Value::as_string)
+            .filter(|value| !value.is_empty())
+            .map(str::to_string)
+    };
+    let info = BundleInfo {
+        bundle_id: string("CFBundleIdentifier"),
+        executable: string("CFBundleExecutable"),
+        platforms: dict
+            .get("CFBundleSupportedPlatforms")
+            .and_then(plist::Value::as_array)
+            .map(|platforms| {
+                platforms
+                    .iter()
+                    .filter_map(plist::Value::as_string)
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default(),
+        minimum_os_version: string("MinimumOSVersion").or_else(|| string("LSMinimumSystemVersion")),
+        device_families: dict
+            .get("UIDeviceFamily")
+            .and_then(plist::Value::as_array)
+            .map(|families| families.iter().filter_map(device_family_name).collect())
+            .unwrap_or_default(),
+    };
+
+    checks.push(match &info.bundle_id {
+        Some(_) => ValidationCheck::pass("bundle_id"),
+        None => ValidationCheck::fail("bundle_id", "CFBundleIdentifier is missing"),
+    });
+
+    checks.push(match &info.executable {
+        Some(name) if app.join(name).is_file() => ValidationCheck::pass("executable"),
+        Some(name) => ValidationCheck::fail(
+            "executable",
+            format!("Executable {} named by CFBundleExecutable is missing", name),
+        ),
+        None => ValidationCheck::fail("executable", "CFBundleExecutable is missing"),
+    });
+
+    let missing: Vec<&str> = ["CFBundleVersion", "CFBundleShortVersionString"]
+        .into_iter()
+        .filter(|key| string(key).is_none())
+        .collect();
+    checks.push(if missing.is_empty() {
+        ValidationCheck::pass("version")
+    } else {
+        ValidationCheck::fail("version", format!("{} missing", missing.join(" and ")))
+    });
+
+    (info, checks)
+}
+
+/// Name of a UIDeviceFamily entry, which is an integer (occasionally a string)
+fn device_family_name(family: &plist::Value) -> Option<String> {
+    let number = match family {
+        plist::Value::Integer(n) => n.as_unsigned()?,
+        plist::Value::String(s) => s.trim().parse().ok()?,
+        _ => return None,
+    };
+    Some(
+        match number {
+            1 => "iphone",
+            2 => "ipad",
+            3 => "tv",
+            4 => "watch",
+            6 => "mac",
+            7 => "vision",
+            other => return Some(other.to_string()),
+        }
+        .to_string(),
+    )
+}
+
+/// Read an app's minimum OS, device families and architectures
+pub async fn app_compatibility(app: &Path) -> Result<AppCompatibility> {
+    let (info, checks) = check_info_plist(app);
+    if let Some(message) = checks
+        .iter()
+        .find(|check| check.name == "info_plist" && !check.passed)
+        .and_then(|check| check.message.clone())
+    {
+        return Err(XcbridgeError::InvalidRequest(format!(
+            "{}: {}",
+            message,
+            app.display()
+        )));
+    }
+
+    let architectures = match &info.executable {
+        Some(name) if app.join(name).is_file() => executable_architectures(&app.join(name)).await,
+        _ => Vec::new(),
+    };
+
+    Ok(AppCompatibility {
+        path: app.to_string_lossy().to_string(),
+        bundle_id: info.bundle_id,
+        minimum_os_version: info.minimum_os_version,
+        device_families: info.device_families,
+        platforms: info.platforms,
+        architectures,
+    })
+}
+
+/// Why an app can't run on a device, or `None` if nothing rules it out
+///
+/// Checks the device's OS version against the app's minimum, its model
+/// against UIDeviceFamily (iPads also run iPhone apps) and that the app has
+/// an arm64 slice. Anything the device doesn't report is not held against it.
+pub fn device_incompatibility(app: &AppCompatibility, device: &Device) -> Option<String> {
+    if let Some(minimum) = &app.minimum_os_version {
+        if !device.os_version.is_empty()
+            && compare_versions(&device.os_version, minimum) == Ordering::Less
+        {
+            return Some(format!(
+                "App requires OS {} or later, but {} runs {}",
+                minimum, device.name, device.os_version
+            ));
+        }
+    }
+
+    if let Some(family) = model_family(&device.model_name) {
+        let supported = app.device_families.is_empty()
+            || app.device_families.iter().any(|f| f == family)
+            || (family == "ipad" && app.device_families.iter().any(|f| f == "iphone"));
+        if !supported {
+            return Some(format!(
+                "App supports {} devices, but {} is a {}",
+                app.device_families.join(", "),
+                device.name,
+                device.model_name
+            ));
+        }
+    }
+
+    if !app.architectures.is_empty()
+        && !app
+            .architectures
+            .iter()
+            .any(|arch| arch.starts_with("arm64"))
+    {
+        return Some(format!(
+            "Executable has no arm64 slice ({})",
+            app.architectures.join(", ")
+        ));
+    }
+
+    None
+}
+
+/// UIDeviceFamily name for a devicectl model name such as "iPhone 15 Pro"
+fn model_family(model_name: &str) -> Option<&'static str> {
+    [
+        ("iPhone", "iphone"),
+        ("iPad", "ipad"),
+        ("Apple TV", "tv"),
+        ("Apple Watch", "watch"),
+        ("Apple Vision", "vision"),
+    ]
+    .into_iter()
+    .find(|(prefix, _)| model_name.starts_with(prefix))
+    .map(|(_, family)| family)
+}
+
+/// Compare dotted versions numerically, treating missing components as 0
+fn compare_versions(a: &str, b: &str) -> Ordering {
+    let parse = |v: &str| -> Vec<u32> {
+        v.split('.')
+            .map(|part| part.trim().parse().unwrap_or(0))
+            .collect()
+    };
+    let (a, b) = (parse(a), parse(b));
+    for i in 0..a.len().max(b.len()) {
+        let ordering = a.get(i).unwrap_or(&0).cmp(b.get(i).unwrap_or(&0));
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+    Ordering::Equal
+}
+
+/// Check the bundle supports the target platform and has an executable slice for it
+fn check_platform(
+    info: &BundleInfo,
+    architectures: &[String],
+    platform: &str,
+) -> Vec<ValidationCheck> {
+    let mut checks = Vec::new();
+
+    if info
+        .platforms
+        .iter()
+        .any(|supported| supported.eq_ignore_ascii_case(platform))
+    {
+        checks.push(ValidationCheck::pass("platform"));
+    } else {
+        checks.push(ValidationCheck::fail(
+            "platform",
+            format!(
+                "Built for {} rather than {}",
+                if info.platforms.is_empty() {
+                    "an unknown platform".to_string()
+                } else {
+                    info.platforms.join(", ")
+                },
+                platform
+            ),
+        ));
+    }
+
+    // Devices only run arm64 slices; simulators run whatever the host does
+    if is_device_platform(platform) && !architectures.is_empty() {
+        if architectures.iter().any(|arch| arch.starts_with("arm64")) {
+            checks.push(ValidationCheck::pass("architecture"));
+        } else {
+            checks.push(ValidationCheck::fail(
+                "architecture",
+                format!(
+                    "Executable has no arm64 slice ({})",
+                    architectures.join(", ")
+                ),
+            ));
+        }
+    }
+
+    checks
+}
+
+fn is_device_platform(platform: &str) -> bool {
+    ["iPhoneOS", "AppleTVOS", "WatchOS", "XROS"]
+        .iter()
+        .any(|device| device.eq_ignore_ascii_case(platform))
+}
+
+/// Architectures of a Mach-O executable, via `lipo -archs`
+async fn executable_architectures(executable: &Path) -> Vec<String> {
+    let output = runner::output(
+        xcodebuild::xcrun()
+            .arg("lipo")
+            .arg("-archs")
+            .arg(executable),
+    )
+    .await;
+
+    match output {
+        Ok(output) if output.status.success() => String::from_utf8_lossy(&output.stdout)
+            .split_whitespace()
+            .map(str::to_string)
+            .collect(),
+        Ok(output) => {
+            tracing::warn!(
+                "lipo -archs {} failed: {}",
+                executable.display(),
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+            Vec::new()
+        }
+        Err(e) => {
+            tracing::warn!("Failed to run lipo: {}", e);
+            Vec::new()
+        }
+    }
+}
+
+/// Verify the bundle's signature and everything nested in it
+async fn verify_code_signature(app: &Path) -> ValidationCheck {
+    let output = runner::output(
+        Command::new("codesign")
+            .args(["--verify", "--deep", "--strict"])
+            .arg(app),
+    )
+    .await;
+
+    match output {
+        Ok(output) if output.status.success() => ValidationCheck::pass("code_signature"),
+        Ok(output) => ValidationCheck::fail(
+            "code_signature",
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ),
+        Err(e) => ValidationCheck::fail("code_signature", format!("Failed to run codesign: {}", e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::xcode::runner::MockRunner;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_executable_architectures_with_mock_runner() {
+        let mock = Arc::new(
+            MockRunner::new()
+                .on(
+                    &["xcrun", "lipo", "-archs", "/dd/MyApp.app/MyApp"],
+                    "x86_64 arm64\n",
+                )
+                .fail(&["xcrun", "lipo"], "fatal error: can't open input file"),
+        );
+
+        let archs = runner::with_runner(
+            mock.clone(),
+            executable_architectures(Path::new("/dd/MyApp.app/MyApp")),
+        )
+        .await;
+        assert_eq!(archs, ["x86_64", "arm64"]);
+
+        // A failed lipo reports no architectures
+        let archs = runner::with_runner(
+            mock.clone(),
+            executable_architectures(Path::new("/dd/Missing.app/Missing")),
+        )
+        .await;
+        assert!(archs.is_empty());
+        assert_eq!(mock.calls().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_verify_code_signature_with_mock_runner() {
+        let mock = Arc::new(
+            MockRunner::new()
+                .on(
+                    &[
+                        "codesign",
+                        "--verify",
+                        "--deep",
+                        "--strict",
+                        "/dd/Signed.app",
+                    ],
+                    "",
+                )
+                .fail(
+                    &["codesign", "--verify"],
+                    "/dd/Unsigned.app: code object is not signed at all\n",
+                ),
+        );
+
+        let check = runner::with_runner(
+            mock.clone(),
+            verify_code_signature(Path::new("/dd/Signed.app")),
+        )
+        .await;
+        assert!(check.passed);
+
+        let check = runner::with_runner(
+            mock.clone(),
+            verify_code_signature(Path::new("/dd/Unsigned.app")),
+        )
+        .await;
+        assert!(!check.passed);
+        assert_eq!(
+            check.message.as_deref(),
+            Some("/dd/Unsigned.app: code object is not signed at all")
+        );
+    }
+
+    #[test]
+    fn test_check_info_plist() {
+        let dir = std::env::temp_dir().join(format!("xcbridge-{}", uuid::Uuid::new_v4()));
+        let app = dir.join("MyApp.app");
+        std::fs::create_dir_all(&app).unwrap();
+        std::fs::write(
+            app.join("Info.plist"),
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+    <key>CFBundleIdentifier</key>
+    <string>com.example.MyApp</string>
+    <key>CFBundleExecutable</key>
+    <string>MyApp</string>
+    <key>CFBundleVersion</key>
+    <string>1</string>
+    <key>CFBundleSupportedPlatforms</key>
+    <array>
+        <string>iPhoneSimulator</string>
+    </array>
+    <key>UIDeviceFamily</key>
+    <array>
+        <integer>1</integer>
+        <integer>2</integer>
+    </array>
+</dict>
+</plist>"#,
+        )
+        .unwrap();
+
+        let (info, checks) = check_info_plist(&app);
+        assert_eq!(info.bundle_id.as_deref(), Some("com.example.MyApp"));
+        assert_eq!(info.platforms, ["iPhoneSimulator"]);
+        assert_eq!(info.device_families, ["iphone", "ipad"]);
+        let failed: Vec<&str> = checks
+            .iter()
+            .filter(|check| !check.passed)
+            .map(|check| check.name.as_str())
+            .collect();
+        assert_eq!(failed, ["executable", "version"]);
+
+        let checks = check_platform(&info, &["x86_64".to_string()], "iPhoneOS");
+        assert!(checks.iter().all(|check| !check.passed));
+        assert!(check_platform(&info, &[], "iphonesimulator")[0].passed);
+
+        let (_, checks) = check_info_plist(&dir);
+        assert_eq!(
+            checks,
+            [ValidationCheck::fail(
+                "info_plist",
+                "No Info.plist in bundle"
+            )]
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_device_incompatibility() {
+        let app = AppCompatibility {
+            path: "/tmp/MyApp.app".to_string(),
+            bundle_id: Some("com.example.MyApp".to_string()),
+            minimum_os_version: Some("17.0".to_string()),
+            device_families: vec!["iphone".to_string()],
+            platforms: vec!["iPhoneOS".to_string()],
+            architectures: vec!["arm64".to_string()],
+        };
+        let device = |model: &str, os: &str| Device {
+            udid: "00008110-000A".to_string(),
+            name: "Test Device".to_string(),
+            os_version: os.to_string(),
+            connection_type: "wired".to_string(),
+            platform: "iOS".to_string(),
+            model_name: model.to_string(),
+        };
+
+        assert_eq!(
+            device_incompatibility(&app, &device("iPhone 15", "17.0.1")),
+            None
+        );
+        assert_eq!(
+            device_incompatibility(&app, &device("iPad Pro", "18")),
+            None
+        );
+        assert!(device_incompatibility(&app, &device("iPhone 12", "16.7.2"))
+            .unwrap()
+            .contains("requires OS 17.0"));
+        assert!(device_incompatibility(&app, &device("Apple TV 4K", "17.2")).is_some());
+        assert_eq!(device_incompatibility(&app, &device("", "")), None);
+
+        assert_eq!(compare_versions("17.10", "17.2"), Ordering::Greater);
+        assert_eq!(compare_versions("17", "17.0.0"), Ordering::Equal);
+    }
+}
@@ -4,15 +4,150 @@
 //! xcodebuild command wrapper
 
 use crate::error::{Result, XcbridgeError};
-use std::process::Stdio;
+use crate::xcode::runner;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::os::unix::process::{CommandExt, ExitStatusExt};
+use std::path::{Path, PathBuf};
+use std::process::{ExitStatus, Stdio};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
 use tokio::io::{AsyncBufReadExt, BufReader};
-use tokio::process::Command;
+use tokio::process::{ChildStderr, ChildStdout, Command};
+use tokio::sync::watch;
+use tokio::time::Instant;
+
+/// Location of the xcodebuild binary chosen at startup
+#[derive(Debug, Clone)]
+struct XcodeLocation {
+    xcodebuild: PathBuf,
+    /// Developer directory passed to Xcode tools as `DEVELOPER_DIR`
+    developer_dir: Option<PathBuf>,
+}
+
+static XCODE: OnceLock<XcodeLocation> = OnceLock::new();
+
+/// Niceness xcodebuild jobs are lowered to (0 leaves them at normal priority)
+static BUILD_NICE: AtomicU32 = AtomicU32::new(0);
+
+/// Run xcodebuild jobs at a lower priority so the host stays responsive
+pub fn set_build_nice(nice: u32) {
+    BUILD_NICE.store(nice, Ordering::Relaxed);
+}
+
+/// Normalize an Xcode path to its developer directory, accepting either
+/// `Xcode.app` or `Xcode.app/Contents/Developer`
+fn developer_dir_for(xcode_path: &Path) -> PathBuf {
+    if xcode_path.extension().is_some_and(|ext| ext == "app") {
+        xcode_path.join("Contents/Developer")
+    } else {
+        xcode_path.to_path_buf()
+    }
+}
+
+/// Developer directory reported by `xcode-select -p`
+async fn xcode_select_path() -> Option<PathBuf> {
+    let output = runner::output(tool_command("xcode-select", XCODE.get()).arg("-p"))
+        .await
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let path = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (!path.is_empty()).then(|| PathBuf::from(path))
+}
+
+/// Find a working xcodebuild and return the installed Xcode version
+///
+/// Tries, in order: the configured Xcode path, `xcodebuild` on PATH, the
+/// developer directory from `xcode-select -p`, and `/usr/bin/xcodebuild`.
+/// The first candidate that reports a version is used for all later Xcode
+/// commands.
+pub async fn discover_xcode(xcode_path: Option<&Path>) -> Result<String> {
+    let mut candidates = Vec::new();
+    if let Some(xcode_path) = xcode_path {
+        let developer_dir = developer_dir_for(xcode_path);
+        candidates.push(XcodeLocation {
+            xcodebuild: developer_dir.join("usr/bin/xcodebuild"),
+            developer_dir: Some(developer_dir),
+        });
+    }
+    candidates.push(XcodeLocation {
+        xcodebuild: PathBuf::from("xcodebuild"),
+        developer_dir: None,
+    });
+    if let Some(developer_dir) = xcode_select_path().await {
+        candidates.push(XcodeLocation {
+            xcodebuild: developer_dir.join("usr/bin/xcodebuild"),
+            developer_dir: Some(developer_dir),
+        });
+    }
+    candidates.push(XcodeLocation {
+        xcodebuild: PathBuf::from("/usr/bin/xcodebuild"),
+        developer_dir: None,
+    });
+
+    for candidate in candidates {
+        match xcode_version(&candidate).await {
+            Ok(version) => {
+                tracing::info!("Using {}", candidate.xcodebuild.display());
+                let _ = XCODE.set(candidate);
+                return Ok(version);
+            }
+            Err(e) => {
+                tracing::debug!("{} unusable: {}", candidate.xcodebuild.display(), e);
+            }
+        }
+    }
+
+    Err(XcbridgeError::XcodeNotFound)
+}
+
+/// Command for the discovered xcodebuild
+fn xcodebuild_command() -> Command {
+    match XCODE.get() {
+        Some(location) => command_for(location),
+        None => Command::new("xcodebuild"),
+    }
+}
+
+fn command_for(location: &XcodeLocation) -> Command {
+    tool_command(&location.xcodebuild, Some(location))
+}
+
+/// Command for a developer tool, run against `location`'s developer
+/// directory when it has one
+fn tool_command(program: impl AsRef<OsStr>, location: Option<&XcodeLocation>) -> Command {
+    let mut cmd = Command::new(program);
+    if let Some(developer_dir) = location.and_then(|l| l.developer_dir.as_ref()) {
+        cmd.env("DEVELOPER_DIR", developer_dir);
+    }
+    cmd
+}
+
+/// `xcrun` command using the same developer directory as xcodebuild
+pub fn xcrun() -> Command {
+    tool_command("xcrun", XCODE.get())
+}
 
 /// Get the installed Xcode version
 pub async fn get_xcode_version() -> Result<String> {
-    let output = Command::new("xcodebuild")
-        .arg("-version")
-        .output()
+    match XCODE.get() {
+        Some(location) => xcode_version(location).await,
+        None => {
+            xcode_version(&XcodeLocation {
+                xcodebuild: PathBuf::from("xcodebuild"),
+                developer_dir: None,
+            })
+            .await
+        }
+    }
+}
+
+async fn xcode_version(location: &XcodeLocation) -> Result<String> {
+    let output = runner::output(command_for(location).arg("-version"))
         .await
         .map_err(|_| XcbridgeError::XcodeNotFound)?;
 
@@ -29,21 +164,128 @@ pub async fn get_xcode_version() -> Result<String> {
     Ok(version)
 }
 
+/// Receipt of the standalone Command Line Tools package
+const CLT_PACKAGE: &str = "com.apple.pkg.CLTools_Executables";
+
+/// Version of the installed Command Line Tools, if they are installed
+pub async fn command_line_tools_version() -> Option<String> {
+    let output = runner::output(Command::new("pkgutil").arg(format!("--pkg-info={}", CLT_PACKAGE)))
+        .await
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    parse_pkg_version(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// The `version:` line of `pkgutil --pkg-info` output
+fn parse_pkg_version(pkg_info: &str) -> Option<String> {
+    pkg_info
+        .lines()
+        .find_map(|line| line.strip_prefix("version:"))
+        .map(|version| version.trim().to_string())
+        .filter(|version| !version.is_empty())
+}
+
+/// Directories searched for installed Swift toolchains
+fn toolchain_dirs() -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    if let Some(home) = std::env::var_os("HOME") {
+        dirs.push(PathBuf::from(home).join("Library/Developer/Toolchains"));
+    }
+    dirs.push(PathBuf::from("/Library/Developer/Toolchains"));
+    dirs
+}
+
+/// Find an installed toolchain by bundle name or identifier
+///
+/// Matches either the `.xctoolchain` bundle name (e.g. `swift-DEVELOPMENT-SNAPSHOT-2024-01-01-a`)
+/// or the `CFBundleIdentifier` in its `Info.plist` (e.g. `org.swift.59202401011a`).
+pub fn find_toolchain(toolchain: &str) -> Option<PathBuf> {
+    find_toolchain_in(&toolchain_dirs(), toolchain)
+}
+
+fn find_toolchain_in(dirs: &[PathBuf], toolchain: &str) -> Option<PathBuf> {
+    dirs.iter()
+        .filter_map(|dir| std::fs::read_dir(dir).ok())
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "xctoolchain"))
+        .find(|path| {
+            path.file_stem().is_some_and(|stem| stem == toolchain)
+                || toolchain_identifier(path).as_deref() == Some(toolchain)
+        })
+}
+
+/// Read the bundle identifier from a toolchain's Info.plist
+fn toolchain_identifier(path: &Path) -> Option<String> {
+    let plist = std::fs::read_to_string(path.join("Info.plist")).ok()?;
+    let (_, rest) = plist.split_once("<key>CFBundleIdentifier</key>")?;
+    let (_, rest) = rest.split_once("<string>")?;
+    let (identifier, _) = rest.split_once("</string>")?;
+    Some(identifier.trim().to_string())
+}
+
+/// Result bundle format requested from xcodebuild, the one `xcresulttool`
+/// reads build and test results from
+const RESULT_BUNDLE_VERSION: &str = "3";
+
 /// Parameters for a build operation
 #[derive(Debug, Clone)]
 pub struct BuildParams {
     pub project: Option<String>,
     pub workspace: Option<String>,
-    pub scheme: String,
+    pub scheme: Option<String>,
+    /// Target to build instead of a scheme (projects only)
+    pub target: Option<String>,
     pub configuration: String,
     pub destination: Option<String>,
+    /// Seconds to search for the destination (`-destination-timeout`)
+    pub destination_timeout: Option<u32>,
     pub derived_data_path: Option<String>,
+    pub result_bundle_path: Option<String>,
+    pub toolchain: Option<String>,
     pub extra_args: Vec<String>,
+    /// Environment variables set on the xcodebuild process
+    pub environment: HashMap<String, String>,
+    /// Directory xcodebuild runs in (defaults to the project's directory)
+    pub working_directory: Option<String>,
+    /// Pass `-skipPackagePluginValidation` and `-skipMacroValidation`
+    pub skip_plugin_validation: bool,
+    /// Concurrent build tasks (`-jobs`)
+    pub jobs: Option<u32>,
+    /// Pass `-showBuildTimingSummary`
+    pub timing_summary: bool,
 }
 
 impl BuildParams {
+    /// Scheme or target being built
+    pub fn name(&self) -> &str {
+        self.scheme
+            .as_deref()
+            .or(self.target.as_deref())
+            .unwrap_or_default()
+    }
+
     /// Convert to xcodebuild arguments
+    ///
+    /// Follows xcodebuild's `[options] [action ...] [setting=value ...]`
+    /// order, with `build` as the action unless `extra_args` names others.
     pub fn to_args(&self) -> Vec<String> {
+        let extra = ExtraArgs::split(&self.extra_args);
+        let mut args = self.pre_action_args();
+        if extra.actions.is_empty() {
+            args.push("build".to_string());
+        } else {
+            args.extend(extra.actions);
+        }
+        args.extend(self.post_action_args());
+        args
+    }
+
+    /// Options that go before the action, including those from `extra_args`
+    pub fn pre_action_args(&self) -> Vec<String> {
         let mut args = Vec::new();
 
         if let Some(project) = &self.project {
@@ -56,8 +298,15 @@ impl BuildParams {
             args.push(workspace.clone());
         }
 
-        args.push("-scheme".to_string());
-        args.push(self.scheme.clone());
+        if let Some(scheme) = &self.scheme {
+            args.push("-scheme".to_string());
+            args.push(scheme.clone());
+        }
+
+        if let Some(target) = &self.target {
+            args.push("-target".to_string());
+            args.push(target.clone());
+        }
 
         args.push("-configuration".to_string());
         args.push(self.configuration.clone());
@@ -67,15 +316,393 @@ impl BuildParams {
             args.push(destination.clone());
         }
 
-        if let Some(derived_data) = &self.derived_data_path {
+        if let Some(timeout) = self.destination_timeout {
+            args.push("-destination-timeout".to_string());
+            args.push(timeout.to_string());
+        }
+
+        // xcodebuild only takes -derivedDataPath with -scheme; target builds
+        // get the same layout from `target_build_settings`
+        if let Some(derived_data) = self
+            .derived_data_path
+            .as_ref()
+            .filter(|_| self.target.is_none())
+        {
             args.push("-derivedDataPath".to_string());
             args.push(derived_data.clone());
         }
 
-        args.extend(self.extra_args.clone());
+        if let Some(result_bundle) = &self.result_bundle_path {
+            args.push("-resultBundlePath".to_string());
+            args.push(result_bundle.clone());
+            args.push("-resultBundleVersion".to_string());
+            args.push(RESULT_BUNDLE_VERSION.to_string());
+        }
+
+        if let Some(toolchain) = &self.toolchain {
+            args.push("-toolchain".to_string());
+            args.push(toolchain.clone());
+        }
+
+        if self.skip_plugin_validation {
+            args.extend(PLUGIN_VALIDATION_ARGS.iter().map(|arg| arg.to_string()));
+        }
+
+        // An explicit `-jobs` in extra_args wins
+        if let Some(jobs) = self
+            .jobs
+            .filter(|_| !self.extra_args.iter().any(|a| a == "-jobs"))
+        {
+            args.push("-jobs".to_string());
+            args.push(jobs.to_string());
+        }
+
+        if self.timing_summary && !self.extra_args.iter().any(|a| a == TIMING_SUMMARY_ARG) {
+            args.push(TIMING_SUMMARY_ARG.to_string());
+        }
+
+        args.extend(ExtraArgs::split(&self.extra_args).options);
 
         args
     }
+
+    /// Arguments to rerun the build from clean, with `clean` ahead of its
+    /// actions, or `None` if the build already cleans
+    pub fn clean_build_args(&self) -> Option<Vec<String>> {
+        let extra = ExtraArgs::split(&self.extra_args);
+        if extra.actions.iter().any(|action| action == "clean") {
+            return None;
+        }
+
+        let mut args = self.pre_action_args();
+        args.push("clean".to_string());
+        if extra.actions.is_empty() {
+            args.push("build".to_string());
+        } else {
+            args.extend(extra.actions);
+        }
+        args.extend(self.post_action_args());
+        Some(args)
+    }
+
+    /// Build settings (`NAME=value`) from `extra_args`, which follow the action
+    pub fn post_action_args(&self) -> Vec<String> {
+        let mut settings = self.target_build_settings();
+        settings.extend(ExtraArgs::split(&self.extra_args).settings);
+        settings
+    }
+
+    /// `SYMROOT` and `OBJROOT` for a target build, so its products land in
+    /// `<derived_data_path>/Build/Products` like a scheme build's rather
+    /// than in the project's `build` directory. Settings given in
+    /// `extra_args` win.
+    fn target_build_settings(&self) -> Vec<String> {
+        let Some(derived_data) = self
+            .derived_data_path
+            .as_ref()
+            .filter(|_| self.target.is_some())
+        else {
+            return Vec::new();
+        };
+        let derived_data = Path::new(derived_data);
+        [
+            ("SYMROOT", "Build/Products"),
+            ("OBJROOT", "Build/Intermediates.noindex"),
+        ]
+        .into_iter()
+        .filter(|(name, _)| {
+            let prefix = format!("{}=", name);
+            !self.extra_args.iter().any(|arg| arg.starts_with(&prefix))
+        })
+        .map(|(name, dir)| format!("{}={}", name, derived_data.join(dir).display()))
+        .collect()
+    }
+
+    /// Arguments to resolve Swift package dependencies ahead of the build
+    ///
+    /// Uses the build's DerivedData, where xcodebuild looks for the checkouts.
+    pub fn resolve_packages_args(&self) -> Vec<String> {
+        let mut args = vec!["-resolvePackageDependencies".to_string()];
+        for (flag, value) in [
+            ("-project", &self.project),
+            ("-workspace", &self.workspace),
+            ("-scheme", &self.scheme),
+            (
+                "-derivedDataPath",
+                &self
+                    .derived_data_path
+                    .clone()
+                    .filter(|_| self.target.is_none()),
+            ),
+        ] {
+            if let Some(value) = value {
+                args.push(flag.to_string());
+                args.push(value.clone());
+            }
+        }
+        if self.skip_plugin_validation {
+            args.extend(PLUGIN_VALIDATION_ARGS.iter().map(|arg| arg.to_string()));
+        }
+        args
+    }
+}
+
+/// Flags that skip validation prompts for package plugins and macros, which
+/// would otherwise fail unattended builds
+const PLUGIN_VALIDATION_ARGS: &[&str] = &["-skipPackagePluginValidation", "-skipMacroValidation"];
+
+/// xcodebuild actions that may be passed in `extra_args`
+const ACTIONS: &[&str] = &[
+    "build",
+    "build-for-testing",
+    "analyze",
+    "archive",
+    "clean",
+    "install",
+    "installsrc",
+];
+
+/// xcodebuild options that take the following argument as their value
+const VALUE_OPTIONS: &[&str] = &[
+    "-project",
+    "-workspace",
+    "-scheme",
+    "-target",
+    "-configuration",
+    "-xcconfig",
+    "-arch",
+    "-sdk",
+    "-toolchain",
+    "-destination",
+    "-destination-timeout",
+    "-jobs",
+    "-derivedDataPath",
+    "-resultBundlePath",
+    "-resultBundleVersion",
+    "-archivePath",
+    "-exportPath",
+    "-exportOptionsPlist",
+    "-clonedSourcePackagesDirPath",
+    "-packageCachePath",
+    "-scmProvider",
+    "-xctestrun",
+    "-testProductsPath",
+    "-testPlan",
+    "-only-testing",
+    "-skip-testing",
+    "-only-test-configuration",
+    "-skip-test-configuration",
+    "-testLanguage",
+    "-testRegion",
+    "-test-iterations",
+    "-parallel-testing-enabled",
+    "-parallel-testing-worker-count",
+    "-maximum-parallel-testing-workers",
+    "-maximum-concurrent-test-device-destinations",
+    "-maximum-concurrent-test-simulator-destinations",
+    "-test-timeouts-enabled",
+    "-default-test-execution-time-allowance",
+    "-maximum-test-execution-time-allowance",
+    "-enableCodeCoverage",
+    "-enableAddressSanitizer",
+    "-enableThreadSanitizer",
+    "-enableUndefinedBehaviorSanitizer",
+    "-localizationPath",
+    "-exportLanguage",
+    "-authenticationKeyPath",
+    "-authenticationKeyID",
+    "-authenticationKeyIssuerID",
+];
+
+/// Caller-supplied arguments sorted by where they belong on the command line
+#[derive(Debug, Default)]
+struct ExtraArgs {
+    /// Options and their values, e.g. `-sdk iphonesimulator`
+    options: Vec<String>,
+    /// Actions, e.g. `clean`
+    actions: Vec<String>,
+    /// Build settings, e.g. `CODE_SIGNING_ALLOWED=NO`
+    settings: Vec<String>,
+}
+
+impl ExtraArgs {
+    fn split(extra_args: &[String]) -> Self {
+        let mut split = Self::default();
+        let mut args = extra_args.iter();
+        while let Some(arg) = args.next() {
+            if VALUE_OPTIONS.contains(&arg.as_str()) {
+                // The value stays with its option, whatever it looks like
+                split.options.push(arg.clone());
+                split.options.extend(args.next().cloned());
+            } else if ACTIONS.contains(&arg.as_str()) {
+                split.actions.push(arg.clone());
+            } else if is_build_setting(arg) {
+                split.settings.push(arg.clone());
+            } else {
+                // Options and their values keep their relative order
+                split.options.push(arg.clone());
+            }
+        }
+        split
+    }
+}
+
+/// Errors from stale DerivedData, i.e. modules or precompiled headers built
+/// from older sources or by another compiler, that a clean build fixes
+const STALE_BUILD_ERRORS: &[&str] = &[
+    "has been modified since the module file",
+    "has been modified since the precompiled header",
+    "malformed or corrupted AST file",
+    "compiled module was created by a different version of the compiler",
+    "module file out of date",
+    "but the path is currently",
+    // Only the standard library shims point at a stale module cache; other
+    // missing modules are usually a real dependency problem
+    "missing required module 'SwiftShims'",
+];
+
+/// Makes xcodebuild print time spent per kind of build task at the end
+const TIMING_SUMMARY_ARG: &str = "-showBuildTimingSummary";
+
+/// Time spent on one kind of build task, from `-showBuildTimingSummary`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BuildPhaseTiming {
+    /// Task kind, e.g. "SwiftCompile" or "PhaseScriptExecution"
+    pub phase: String,
+    pub tasks: u32,
+    pub seconds: f64,
+}
+
+/// Parse the "Build Timing Summary" block xcodebuild prints at the end of a
+/// build run with `-showBuildTimingSummary`, slowest phases first
+///
+/// ```text
+/// Build Timing Summary
+///
+/// SwiftCompile (35 tasks) | 38.611 seconds
+/// CompileC (1 task) | 0.356 seconds
+/// ```
+///
+/// Returns `None` if the log has no summary. When a build was rerun, the
+/// last summary is used.
+pub fn parse_timing_summary(logs: &[String]) -> Option<Vec<BuildPhaseTiming>> {
+    let start = logs
+        .iter()
+        .rposition(|line| line.trim() == "Build Timing Summary")?;
+
+    let mut timings: Vec<BuildPhaseTiming> = logs[start + 1..]
+        .iter()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty())
+        .map_while(parse_timing_line)
+        .collect();
+    timings.sort_by(|a, b| b.seconds.total_cmp(&a.seconds));
+    Some(timings)
+}
+
+/// `SwiftCompile (35 tasks) | 38.611 seconds`
+fn parse_timing_line(line: &str) -> Option<BuildPhaseTiming> {
+    let (task, time) = line.rsplit_once(" | ")?;
+    let seconds = time.strip_suffix(" seconds")?.trim().parse().ok()?;
+    let (phase, count) = task.rsplit_once(" (")?;
+    let tasks = count
+        .strip_suffix(" tasks)")
+        .or_else(|| count.strip_suffix(" task)"))?
+        .parse()
+        .ok()?;
+    Some(BuildPhaseTiming {
+        phase: phase.trim().to_string(),
+        tasks,
+        seconds,
+    })
+}
+
+/// Whether a failed build's log shows an error a clean build would fix
+pub fn is_stale_build_failure(logs: &[String]) -> bool {
+    logs.iter()
+        .any(|line| line.contains("error") && STALE_BUILD_ERRORS.iter().any(|e| line.contains(e)))
+}
+
+/// Whether `arg` is a `NAME=value` or `NAME[sdk=*]=value` build setting
+fn is_build_setting(arg: &str) -> bool {
+    let name_end = arg.find(['=', '[']).unwrap_or(0);
+    let name = &arg[..name_end];
+    !name.is_empty()
+        && !name.starts_with(|c: char| c.is_ascii_digit())
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Variables a build may not override, since xcbridge or the toolchain
+/// depend on them
+const PROTECTED_ENV_VARS: &[&str] = &[
+    "PATH",
+    "HOME",
+    "USER",
+    "SHELL",
+    "TMPDIR",
+    "DEVELOPER_DIR",
+    "SDKROOT",
+    "TOOLCHAINS",
+];
+
+/// Prefixes of variables that alter how processes are loaded
+const PROTECTED_ENV_PREFIXES: &[&str] = &["DYLD_", "LD_"];
+
+/// Check that build environment variables are well-formed and don't
+/// override protected variables
+pub fn validate_environment(environment: &HashMap<String, String>) -> Result<()> {
+    for (key, value) in environment {
+        if key.is_empty() || key.contains('=') || key.contains('\0') || value.contains('\0') {
+            return Err(XcbridgeError::InvalidRequest(format!(
+                "Invalid environment variable: {:?}",
+                key
+            )));
+        }
+
+        if PROTECTED_ENV_VARS.contains(&key.as_str())
+            || PROTECTED_ENV_PREFIXES
+                .iter()
+                .any(|prefix| key.starts_with(prefix))
+        {
+            return Err(XcbridgeError::InvalidRequest(format!(
+                "Environment variable {} cannot be overridden",
+                key
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Check that `-testLanguage` and `-testRegion` values look like locale
+/// identifiers: a language such as `de`, `zh-Hans` or `pt_BR`, and a region
+/// such as `DE` or `419`
+pub fn validate_test_locale(language: Option<&str>, region: Option<&str>) -> Result<()> {
+    if let Some(language) = language {
+        let mut subtags = language.split(['-', '_']);
+        let valid = subtags.next().is_some_and(|code| {
+            (2..=3).contains(&code.len()) && code.chars().all(|c| c.is_ascii_alphabetic())
+        }) && subtags.all(|subtag| {
+            (2..=8).contains(&subtag.len()) && subtag.chars().all(|c| c.is_ascii_alphanumeric())
+        });
+        if !valid {
+            return Err(XcbridgeError::InvalidRequest(format!(
+                "test_language is not a language identifier: {:?}",
+                language
+            )));
+        }
+    }
+
+    if let Some(region) = region {
+        let valid = (region.len() == 2 && region.chars().all(|c| c.is_ascii_alphabetic()))
+            || (region.len() == 3 && region.chars().all(|c| c.is_ascii_digit()));
+        if !valid {
+            return Err(XcbridgeError::InvalidRequest(format!(
+                "test_region is not a region code: {:?}",
+                region
+            )));
+        }
+    }
+    Ok(())
 }
 
 /// Parameters for a test operation
@@ -83,17 +710,41 @@ impl BuildParams {
 pub struct TestParams {
     pub project: Option<String>,
     pub workspace: Option<String>,
-    pub scheme: String,
+    pub scheme: Option<String>,
+    /// Run an `.xctestrun` with `test-without-building` instead of a scheme
+    pub xctestrun_path: Option<String>,
     pub destination: Option<String>,
+    /// Seconds to search for the destination (`-destination-timeout`)
+    pub destination_timeout: Option<u32>,
     pub test_plan: Option<String>,
+    /// Test plan configurations to run or skip
+    pub only_test_configuration: Vec<String>,
+    pub skip_test_configuration: Vec<String>,
+    /// Language and region to run the tests in (`-testLanguage`, `-testRegion`)
+    pub test_language: Option<String>,
+    pub test_region: Option<String>,
+    pub toolchain: Option<String>,
     pub only_testing: Vec<String>,
     pub skip_testing: Vec<String>,
+    pub parallel_testing: bool,
+    pub parallel_workers: Option<u32>,
+    pub result_bundle_path: Option<String>,
+    /// Concurrent build tasks (`-jobs`)
+    pub jobs: Option<u32>,
 }
 
 impl TestParams {
     /// Convert to xcodebuild test arguments
     pub fn to_args(&self) -> Vec<String> {
-        let mut args = vec!["test".to_string()];
+        let mut args = Vec::new();
+
+        if let Some(xctestrun) = &self.xctestrun_path {
+            args.push("test-without-building".to_string());
+            args.push("-xctestrun".to_string());
+            args.push(xctestrun.clone());
+        } else {
+            args.push("test".to_string());
+        }
 
         if let Some(project) = &self.project {
             args.push("-project".to_string());
@@ -105,19 +756,73 @@ impl TestParams {
             args.push(workspace.clone());
         }
 
-        args.push("-scheme".to_string());
-        args.push(self.scheme.clone());
+        if let Some(scheme) = &self.scheme {
+            args.push("-scheme".to_string());
+            args.push(scheme.clone());
+        }
 
         if let Some(destination) = &self.destination {
             args.push("-destination".to_string());
             args.push(destination.clone());
         }
 
+        if let Some(timeout) = self.destination_timeout {
+            args.push("-destination-timeout".to_string());
+            args.push(timeout.to_string());
+        }
+
         if let Some(test_plan) = &self.test_plan {
             args.push("-testPlan".to_string());
             args.push(test_plan.clone());
         }
 
+        for configuration in &self.only_test_configuration {
+            args.push("-only-test-configuration".to_string());
+            args.push(configuration.clone());
+        }
+
+        for configuration in &self.skip_test_configuration {
+            args.push("-skip-test-configuration".to_string());
+            args.push(configuration.clone());
+        }
+
+        if let Some(language) = &self.test_language {
+            args.push("-testLanguage".to_string());
+            args.push(language.clone());
+        }
+
+        if let Some(region) = &self.test_region {
+            args.push("-testRegion".to_string());
+            args.push(region.clone());
+        }
+
+        if self.parallel_testing {
+            args.push("-parallel-testing-enabled".to_string());
+            args.push("YES".to_string());
+
+            if let Some(workers) = self.parallel_workers {
+                args.push("-parallel-testing-worker-count".to_string());
+                args.push(workers.to_string());
+            }
+        }
+
+        if let Some(result_bundle) = &self.result_bundle_path {
+            args.push("-resultBundlePath".to_string());
+            args.push(result_bundle.clone());
+            args.push("-resultBundleVersion".to_string());
+            args.push(RESULT_BUNDLE_VERSION.to_string());
+        }
+
+        if let Some(toolchain) = &self.toolchain {
+            args.push("-toolchain".to_string());
+            args.push(toolchain.clone());
+        }
+
+        if let Some(jobs) = self.jobs {
+            args.push("-jobs".to_string());
+            args.push(jobs.to_string());
+        }
+
         for test in &self.only_testing {
             args.push("-only-testing".to_string());
             args.push(test.clone());
@@ -132,6 +837,88 @@ impl TestParams {
     }
 }
 
+/// Find the built `.app` bundle for a simulator build in a DerivedData directory
+pub fn find_simulator_app(derived_data: &Path, configuration: &str) -> Option<PathBuf> {
+    let products = derived_data
+        .join("Build/Products")
+        .join(format!("{}-iphonesimulator", configuration));
+
+    std::fs::read_dir(products)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .find(|path| path.extension().is_some_and(|ext| ext == "app"))
+}
+
+/// Directory containing the `-project` or `-workspace` in xcodebuild arguments
+fn default_working_directory(args: &[String]) -> Option<PathBuf> {
+    args.windows(2)
+        .find(|pair| pair[0] == "-project" || pair[0] == "-workspace")
+        .and_then(|pair| Path::new(&pair[1]).parent())
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .map(Path::to_path_buf)
+}
+
+/// How long a cancelled xcodebuild gets to stop after SIGINT before it is killed
+const CANCEL_GRACE: Duration = Duration::from_secs(10);
+
+/// Request to stop a running xcodebuild, shared between a job and its record
+#[derive(Debug, Clone)]
+pub struct CancelSignal(Arc<watch::Sender<bool>>);
+
+impl Default for CancelSignal {
+    fn default() -> Self {
+        Self(Arc::new(watch::channel(false).0))
+    }
+}
+
+impl CancelSignal {
+    pub fn cancel(&self) {
+        self.0.send_replace(true);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        *self.0.borrow()
+    }
+
+    /// Resolves once the job is cancelled (immediately if it already was)
+    pub async fn cancelled(&self) {
+        let _ = self.0.subscribe().wait_for(|cancelled| *cancelled).await;
+    }
+}
+
+/// Ask xcodebuild to stop as it would on Ctrl-C, letting it stop its own
+/// compiler and test processes
+fn interrupt(process: &BuildProcess) {
+    tracing::info!("Cancelling xcodebuild (pid {})", process.pid);
+    if let Err(e) = process.signal(libc::SIGINT) {
+        tracing::warn!(
+            "Failed to interrupt xcodebuild (pid {}): {}",
+            process.pid,
+            e
+        );
+    }
+}
+
+/// Start xcodebuild at `--build-nice`, so the compilers and tools it runs
+/// inherit the lower priority from their first instruction
+fn lower_priority(cmd: &mut std::process::Command) {
+    let nice = BUILD_NICE.load(Ordering::Relaxed);
+    if nice == 0 {
+        return;
+    }
+    // SAFETY: setpriority is async-signal-safe and nothing is allocated
+    unsafe {
+        cmd.pre_exec(move || {
+            if libc::setpriority(libc::PRIO_PROCESS, 0, nice as libc::c_int) == 0 {
+                Ok(())
+            } else {
+                Err(std::io::Error::last_os_error())
+            }
+        });
+    }
+}
+
 /// Output from a build operation
 #[derive(Debug)]
 pub struct BuildOutput {
@@ -139,35 +926,190 @@ pub struct BuildOutput {
     pub exit_code: i32,
     pub logs: Vec<String>,
     pub build_dir: Option<String>,
+    /// CPU time used by xcodebuild and the compilers and tools it ran
+    pub cpu_time: Option<CpuTime>,
 }
 
-/// Run xcodebuild with the given arguments, streaming output via callback
-pub async fn run_xcodebuild<F>(args: Vec<String>, mut on_line: F) -> Result<BuildOutput>
+/// CPU time used by a process and the descendants it waited for
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct CpuTime {
+    pub user_secs: f64,
+    pub system_secs: f64,
+}
+
+impl std::ops::AddAssign for CpuTime {
+    fn add_assign(&mut self, other: Self) {
+        self.user_secs += other.user_secs;
+        self.system_secs += other.system_secs;
+    }
+}
+
+/// An xcodebuild process spawned through std and reaped only by us, so
+/// `wait4` can read its CPU time without racing tokio's child reaping
+///
+/// Until it is reaped its pid cannot be reused, so signalling it is always
+/// safe. A process dropped before it is waited on, e.g. when its request
+/// times out, is killed and reaped in the background.
+struct BuildProcess {
+    pid: libc::pid_t,
+    reaped: bool,
+}
+
+impl BuildProcess {
+    fn spawn(cmd: &mut std::process::Command) -> std::io::Result<(Self, ChildStdout, ChildStderr)> {
+        let mut child = cmd.spawn()?;
+        let process = Self {
+            pid: child.id() as libc::pid_t,
+            reaped: false,
+        };
+        let stdout = ChildStdout::from_std(child.stdout.take().unwrap())?;
+        let stderr = ChildStderr::from_std(child.stderr.take().unwrap())?;
+        Ok((process, stdout, stderr))
+    }
+
+    fn signal(&self, signal: libc::c_int) -> std::io::Result<()> {
+        // SAFETY: kill has no memory safety requirements, and the pid is
+        // still ours because the process has not been reaped
+        if unsafe { libc::kill(self.pid, signal) } == 0 {
+            Ok(())
+        } else {
+            Err(std::io::Error::last_os_error())
+        }
+    }
+
+    /// Wait for the process to exit, reaping it with `wait4` to read its
+    /// CPU time
+    async fn wait(mut self) -> std::io::Result<(ExitStatus, CpuTime)> {
+        let pid = self.pid;
+        // The blocking task reaps the process even if this future is dropped
+        self.reaped = true;
+        tokio::task::spawn_blocking(move || wait4(pid))
+            .await
+            .map_err(std::io::Error::other)?
+    }
+}
+
+impl Drop for BuildProcess {
+    fn drop(&mut self) {
+        if self.reaped {
+            return;
+        }
+        let _ = self.signal(libc::SIGKILL);
+        let pid = self.pid;
+        std::thread::spawn(move || wait4(pid));
+    }
+}
+
+fn wait4(pid: libc::pid_t) -> std::io::Result<(ExitStatus, CpuTime)> {
+    let mut status = 0;
+    let mut usage = std::mem::MaybeUninit::<libc::rusage>::zeroed();
+    loop {
+        // SAFETY: `status` and `usage` are valid for writes during the call
+        let reaped = unsafe { libc::wait4(pid, &mut status, 0, usage.as_mut_ptr()) };
+        if reaped == pid {
+            break;
+        }
+        let error = std::io::Error::last_os_error();
+        if error.kind() != std::io::ErrorKind::Interrupted {
+            return Err(error);
+        }
+    }
+    // SAFETY: wait4 filled in `usage` for the child it reaped
+    let usage = unsafe { usage.assume_init() };
+    let secs = |time: libc::timeval| time.tv_sec as f64 + time.tv_usec as f64 / 1_000_000.0;
+    Ok((
+        ExitStatus::from_raw(status),
+        CpuTime {
+            user_secs: secs(usage.ru_utime),
+            system_secs: secs(usage.ru_stime),
+        },
+    ))
+}
+
+/// Run xcodebuild with extra environment variables, streaming output via callback
+///
+/// Runs in `working_directory`, or the directory containing the project or
+/// workspace when none is given, so build scripts can use relative paths.
+///
+/// When `cancel` fires, xcodebuild is interrupted and given [`CANCEL_GRACE`]
+/// to exit before it is killed.
+pub async fn run_xcodebuild_with_env<F>(
+    args: Vec<String>,
+    environment: &HashMap<String, String>,
+    working_directory: Option<&Path>,
+    cancel: &CancelSignal,
+    mut on_line: F,
+) -> Result<BuildOutput>
 where
     F: FnMut(String),
 {
-    let mut cmd = Command::new("xcodebuild");
+    let mut cmd = xcodebuild_command();
     cmd.args(&args)
+        .envs(environment)
         .stdout(Stdio::piped())
         .stderr(Stdio::piped());
 
+    let working_directory = working_directory
+        .map(Path::to_path_buf)
+        .or_else(|| default_working_directory(&args));
+    if let Some(dir) = &working_directory {
+        cmd.current_dir(dir);
+    }
+
+    if !environment.is_empty() {
+        let mut keys: Vec<_> = environment.keys().map(String::as_str).collect();
+        keys.sort_unstable();
+        tracing::info!("Build environment: {}", keys.join(", "));
+    }
+
     tracing::info!("Running: xcodebuild {}", args.join(" "));
 
-    let mut child = cmd
-        .spawn()
-        .map_err(|e| XcbridgeError::CommandFailed(format!("Failed to spawn xcodebuild: {}", e)))?;
+    // A task's own runner, as in tests, answers in one go rather than
+    // streaming from a spawned process
+    if runner::has_task_runner() {
+        let output = runner::output(&mut cmd).await.map_err(|e| {
+            XcbridgeError::CommandFailed(format!("Failed to run xcodebuild: {}", e))
+        })?;
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let logs: Vec<String> = stdout
+            .lines()
+            .chain(stderr.lines())
+            .map(String::from)
+            .collect();
+        logs.iter().cloned().for_each(&mut on_line);
+        return Ok(BuildOutput {
+            success: output.status.success(),
+            exit_code: output.status.code().unwrap_or(-1),
+            logs,
+            build_dir: None,
+            cpu_time: None,
+        });
+    }
 
-    let stdout = child.stdout.take().unwrap();
-    let stderr = child.stderr.take().unwrap();
+    lower_priority(cmd.as_std_mut());
+    let (process, stdout, stderr) = BuildProcess::spawn(cmd.as_std_mut())
+        .map_err(|e| XcbridgeError::CommandFailed(format!("Failed to spawn xcodebuild: {}", e)))?;
 
     let mut stdout_reader = BufReader::new(stdout).lines();
     let mut stderr_reader = BufReader::new(stderr).lines();
 
     let mut logs = Vec::new();
     let mut build_dir = None;
+    let mut kill_deadline: Option<Instant> = None;
 
     loop {
         tokio::select! {
+            _ = cancel.cancelled(), if kill_deadline.is_none() => {
+                interrupt(&process);
+                kill_deadline = Some(Instant::now() + CANCEL_GRACE);
+            }
+            _ = tokio::time::sleep_until(kill_deadline.unwrap_or_else(Instant::now)),
+                if kill_deadline.is_some() => {
+                tracing::warn!("xcodebuild did not stop after SIGINT, killing it");
+                let _ = process.signal(libc::SIGKILL);
+                break;
+            }
             line = stdout_reader.next_line() => {
                 match line {
                     Ok(Some(line)) => {
@@ -202,10 +1144,9 @@ where
         }
     }
 
-    let status = child
-        .wait()
-        .await
-        .map_err(|e| XcbridgeError::CommandFailed(format!("Failed to wait for xcodebuild: {}", e)))?;
+    let (status, cpu_time) = process.wait().await.map_err(|e| {
+        XcbridgeError::CommandFailed(format!("Failed to wait for xcodebuild: {}", e))
+    })?;
 
     let exit_code = status.code().unwrap_or(-1);
 
@@ -214,14 +1155,71 @@ where
         exit_code,
         logs,
         build_dir,
+        cpu_time: Some(cpu_time),
     })
 }
 
+/// Platforms whose simulator runtimes xcodebuild can download
+pub const DOWNLOADABLE_PLATFORMS: &[&str] = &["iOS", "watchOS", "tvOS", "visionOS"];
+
+/// Arguments to download a simulator runtime with `-downloadPlatform`
+pub fn download_platform_args(platform: &str, version: Option<&str>) -> Vec<String> {
+    let mut args = vec!["-downloadPlatform".to_string(), platform.to_string()];
+    if let Some(version) = version {
+        args.push("-buildVersion".to_string());
+        args.push(version.to_string());
+    }
+    args
+}
+
+/// Arguments to export a project's strings as .xcloc bundles, one per
+/// language, into `localization_path`
+pub fn export_localizations_args(
+    project: &str,
+    localization_path: &str,
+    languages: &[String],
+    include_screenshots: bool,
+) -> Vec<String> {
+    let mut args = vec![
+        "-exportLocalizations".to_string(),
+        "-project".to_string(),
+        project.to_string(),
+        "-localizationPath".to_string(),
+        localization_path.to_string(),
+    ];
+    for language in languages {
+        args.push("-exportLanguage".to_string());
+        args.push(language.clone());
+    }
+    if include_screenshots {
+        args.push("-includeScreenshots".to_string());
+    }
+    args
+}
+
+/// Arguments to import a translated .xcloc bundle (or .xliff file) into a
+/// project
+pub fn import_localizations_args(
+    project: &str,
+    localization_path: &str,
+    merge: bool,
+) -> Vec<String> {
+    let mut args = vec![
+        "-importLocalizations".to_string(),
+        "-project".to_string(),
+        project.to_string(),
+        "-localizationPath".to_string(),
+        localization_path.to_string(),
+    ];
+    if merge {
+        args.push("-mergeImport".to_string());
+    }
+    args
+}
+
 /// Run a simple xcodebuild command and return output
 pub async fn xcodebuild(args: &[&str]) -> Result<String> {
-    let output = Command::new("xcodebuild")
-        .args(args)
-        .output()
+    let output = runner::output(xcodebuild_command().args(args))
         .await
         .map_err(|e| XcbridgeError::CommandFailed(format!("xcodebuild failed: {}", e)))?;
 
@@ -234,14 +1232,817 @@ pub async fn xcodebuild(args: &[&str]) -> Result<String> {
     }
 }
 
-/// List available SDKs
-pub async fn list_sdks() -> Result<Vec<String>> {
-    let output = xcodebuild(&["-showsdks"]).await?;
-    let sdks: Vec<String> = output
+/// A valid destination for a scheme, from `xcodebuild -showdestinations`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Destination {
+    pub platform: String,
+    pub id: Option<String>,
+    pub name: Option<String>,
+    #[serde(rename = "os")]
+    pub os_version: Option<String>,
+    pub arch: Option<String>,
+    pub variant: Option<String>,
+    /// Ready-to-use `-destination` argument
+    pub destination: String,
+}
+
+/// List the available destinations for a scheme
+pub async fn show_destinations(
+    project: Option<&str>,
+    workspace: Option<&str>,
+    scheme: &str,
+) -> Result<Vec<Destination>> {
+    let mut args = vec!["-showdestinations", "-scheme", scheme];
+    if let Some(project) = project {
+        args.extend(["-project", project]);
+    }
+    if let Some(workspace) = workspace {
+        args.extend(["-workspace", workspace]);
+    }
+
+    let output = xcodebuild(&args).await?;
+    Ok(parse_destinations(&output))
+}
+
+/// Parse the "Available destinations" section of `-showdestinations` output
+///
+/// Lines look like:
+/// `{ platform:iOS Simulator, id:5A3C..., OS:17.2, name:iPhone 15 }`
+fn parse_destinations(output: &str) -> Vec<Destination> {
+    output
         .lines()
-        .filter(|line| line.contains("-sdk"))
-        .filter_map(|line| line.split("-sdk").nth(1))
-        .map(|s| s.trim().to_string())
+        .skip_while(|line| !line.contains("Available destinations"))
+        .take_while(|line| !line.contains("Ineligible destinations"))
+        .filter_map(parse_destination_line)
+        .collect()
+}
+
+fn parse_destination_line(line: &str) -> Option<Destination> {
+    let inner = line.trim().strip_prefix('{')?.strip_suffix('}')?.trim();
+
+    let mut destination = Destination {
+        platform: String::new(),
+        id: None,
+        name: None,
+        os_version: None,
+        arch: None,
+        variant: None,
+        destination: String::new(),
+    };
+
+    for field in inner.split(", ") {
+        let Some((key, value)) = field.split_once(':') else {
+            continue;
+        };
+        let value = value.trim().to_string();
+        match key.trim() {
+            "platform" => destination.platform = value,
+            "id" => destination.id = Some(value),
+            "name" => destination.name = Some(value),
+            "OS" => destination.os_version = Some(value),
+            "arch" => destination.arch = Some(value),
+            "variant" => destination.variant = Some(value),
+            _ => {}
+        }
+    }
+
+    if destination.platform.is_empty() {
+        return None;
+    }
+
+    destination.destination = match (&destination.id, &destination.name) {
+        (Some(id), _) => format!("platform={},id={}", destination.platform, id),
+        (None, Some(name)) => format!("platform={},name={}", destination.platform, name),
+        (None, None) => format!("generic/platform={}", destination.platform),
+    };
+
+    Some(destination)
+}
+
+/// An installed SDK, from `xcodebuild -showsdks -json`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all(deserialize = "camelCase"))]
+pub struct Sdk {
+    /// Name for `-sdk`, e.g. "iphonesimulator17.2"
+    pub canonical_name: String,
+    /// e.g. "Simulator - iOS 17.2"
+    pub display_name: String,
+    /// e.g. "iphonesimulator"
+    pub platform: String,
+    #[serde(default)]
+    pub sdk_version: Option<String>,
+}
+
+/// List the installed SDKs
+pub async fn list_sdks() -> Result<Vec<Sdk>> {
+    let output = xcodebuild(&["-showsdks", "-json"]).await?;
+    serde_json::from_str(&output)
+        .map_err(|e| XcbridgeError::Internal(format!("Failed to parse SDK list: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::xcode::runner::MockRunner;
+
+    #[tokio::test]
+    async fn test_command_line_tools_version() {
+        let pkg_info = "package-id: com.apple.pkg.CLTools_Executables
+version: 15.3.0.0.1.1708646388
+volume: /
+location: /
+install-time: 1710000000
+";
+        let mock = Arc::new(MockRunner::new().on(&["pkgutil"], pkg_info));
+        let version = runner::with_runner(mock, command_line_tools_version()).await;
+        assert_eq!(version.as_deref(), Some("15.3.0.0.1.1708646388"));
+
+        let mock = Arc::new(MockRunner::new().fail(
+            &["pkgutil"],
+            "No receipt for 'com.apple.pkg.CLTools_Executables' found at '/'.",
+        ));
+        assert!(runner::with_runner(mock, command_line_tools_version())
+            .await
+            .is_none());
+    }
+
+    #[test]
+    fn test_parse_timing_summary() {
+        let logs: Vec<String> = [
+            "** BUILD SUCCEEDED ** [12.3 sec]",
+            "",
+            "Build Timing Summary",
+            "",
+            "CompileC (1 task) | 0.356 seconds",
+            "",
+            "SwiftCompile (35 tasks) | 38.611 seconds",
+            "",
+            "PhaseScriptExecution Run\\ SwiftLint (2 tasks) | 4.002 seconds",
+            "",
+            "Test session results, code coverage, and logs:",
+        ]
+        .iter()
+        .map(|s| s.to_string())
         .collect();
-    Ok(sdks)
+
+        let timings = parse_timing_summary(&logs).unwrap();
+        assert_eq!(timings.len(), 3);
+        assert_eq!(timings[0].phase, "SwiftCompile");
+        assert_eq!(timings[0].tasks, 35);
+        assert_eq!(timings[0].seconds, 38.611);
+        assert_eq!(timings[1].phase, "PhaseScriptExecution Run\\ SwiftLint");
+        assert_eq!(timings[2].tasks, 1);
+
+        assert_eq!(parse_timing_summary(&logs[..2]), None);
+    }
+
+    fn scheme_params(extra_args: &[&str]) -> BuildParams {
+        BuildParams {
+            project: None,
+            workspace: Some("App.xcworkspace".to_string()),
+            scheme: Some("App".to_string()),
+            target: None,
+            configuration: "Debug".to_string(),
+            destination: Some("platform=iOS Simulator,name=iPhone 15".to_string()),
+            destination_timeout: None,
+            derived_data_path: Some("/tmp/dd".to_string()),
+            result_bundle_path: None,
+            toolchain: None,
+            extra_args: extra_args.iter().map(|s| s.to_string()).collect(),
+            environment: HashMap::new(),
+            working_directory: None,
+            skip_plugin_validation: false,
+            jobs: None,
+            timing_summary: false,
+        }
+    }
+
+    #[test]
+    fn test_build_args_for_target() {
+        let params = BuildParams {
+            project: Some("MyLib.xcodeproj".to_string()),
+            workspace: None,
+            scheme: None,
+            target: Some("MyLib".to_string()),
+            configuration: "Release".to_string(),
+            destination: None,
+            destination_timeout: None,
+            derived_data_path: None,
+            result_bundle_path: None,
+            toolchain: None,
+            extra_args: vec![],
+            environment: HashMap::new(),
+            working_directory: None,
+            skip_plugin_validation: false,
+            jobs: Some(4),
+            timing_summary: true,
+        };
+
+        assert_eq!(
+            params.to_args(),
+            [
+                "-project",
+                "MyLib.xcodeproj",
+                "-target",
+                "MyLib",
+                "-configuration",
+                "Release",
+                "-jobs",
+                "4",
+                "-showBuildTimingSummary",
+                "build"
+            ]
+        );
+        assert_eq!(params.name(), "MyLib");
+    }
+
+    #[test]
+    fn test_target_build_products_go_to_derived_data() {
+        let dir = std::env::temp_dir().join(format!("xcbridge-{}", uuid::Uuid::new_v4()));
+        let mut params = BuildParams {
+            project: Some("MyLib.xcodeproj".to_string()),
+            workspace: None,
+            scheme: None,
+            target: Some("MyLib".to_string()),
+            configuration: "Release".to_string(),
+            destination: None,
+            destination_timeout: None,
+            derived_data_path: Some(dir.to_string_lossy().to_string()),
+            result_bundle_path: None,
+            toolchain: None,
+            extra_args: vec!["OTHER_SWIFT_FLAGS=-DCI".to_string()],
+            environment: HashMap::new(),
+            working_directory: None,
+            skip_plugin_validation: false,
+            jobs: None,
+            timing_summary: false,
+        };
+
+        // xcodebuild refuses -derivedDataPath without -scheme
+        let args = params.to_args();
+        assert!(!args.contains(&"-derivedDataPath".to_string()));
+        assert!(!params
+            .resolve_packages_args()
+            .contains(&"-derivedDataPath".to_string()));
+        let symroot = format!("SYMROOT={}", dir.join("Build/Products").display());
+        let objroot = format!(
+            "OBJROOT={}",
+            dir.join("Build/Intermediates.noindex").display()
+        );
+        assert_eq!(
+            args[args.len() - 3..],
+            [
+                symroot.clone(),
+                objroot,
+                "OTHER_SWIFT_FLAGS=-DCI".to_string()
+            ]
+        );
+
+        // Products built under SYMROOT are found like a scheme build's
+        let framework = dir.join("Build/Products/Release/MyLib.framework");
+        std::fs::create_dir_all(&framework).unwrap();
+        assert_eq!(crate::xcode::bundle::find_products(&dir), [framework]);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        // An explicit SYMROOT wins
+        params.extra_args = vec!["SYMROOT=/tmp/symroot".to_string()];
+        let args = params.to_args();
+        assert!(!args.contains(&symroot));
+        assert!(args.contains(&"SYMROOT=/tmp/symroot".to_string()));
+
+        // Scheme builds keep -derivedDataPath
+        params.scheme = Some("MyLib".to_string());
+        params.target = None;
+        assert!(params.to_args().contains(&"-derivedDataPath".to_string()));
+    }
+
+    #[test]
+    fn test_clean_build_args() {
+        let params = scheme_params(&["-quiet", "CODE_SIGNING_ALLOWED=NO"]);
+        let args = params.clean_build_args().unwrap();
+        assert_eq!(
+            args[args.len() - 4..],
+            ["-quiet", "clean", "build", "CODE_SIGNING_ALLOWED=NO"]
+        );
+
+        assert!(scheme_params(&["clean", "build"])
+            .clean_build_args()
+            .is_none());
+    }
+
+    #[test]
+    fn test_is_stale_build_failure() {
+        let stale = "/src/App/Bridging.h:1:9: fatal error: file '/src/Lib/Lib.h' has been modified since the precompiled header '/dd/App-Bridging.pch' was built";
+        assert!(is_stale_build_failure(&[
+            "CompileSwift normal arm64".to_string(),
+            stale.to_string(),
+        ]));
+        assert!(!is_stale_build_failure(&[
+            "/src/App/View.swift:3:5: error: cannot find 'foo' in scope".to_string()
+        ]));
+        assert!(is_stale_build_failure(&[
+            "<unknown>:0: error: missing required module 'SwiftShims'".to_string()
+        ]));
+        assert!(!is_stale_build_failure(&[
+            "/src/App/Net.swift:1:8: error: missing required module 'Alamofire'".to_string()
+        ]));
+    }
+
+    #[test]
+    fn test_build_args_for_scheme() {
+        let mut params = scheme_params(&[]);
+        params.result_bundle_path = Some("/tmp/r.xcresult".to_string());
+        params.toolchain = Some("swift-5.10".to_string());
+
+        assert_eq!(
+            params.to_args(),
+            [
+                "-workspace",
+                "App.xcworkspace",
+                "-scheme",
+                "App",
+                "-configuration",
+                "Debug",
+                "-destination",
+                "platform=iOS Simulator,name=iPhone 15",
+                "-derivedDataPath",
+                "/tmp/dd",
+                "-resultBundlePath",
+                "/tmp/r.xcresult",
+                "-resultBundleVersion",
+                RESULT_BUNDLE_VERSION,
+                "-toolchain",
+                "swift-5.10",
+                "build"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_build_args_place_extra_args_around_action() {
+        let mut params = scheme_params(&[
+            "CODE_SIGNING_ALLOWED=NO",
+            "-sdk",
+            "iphonesimulator",
+            "-quiet",
+            "SWIFT_ACTIVE_COMPILATION_CONDITIONS=CI",
+            "-jobs",
+            "4",
+        ]);
+        // Ignored in favour of the `-jobs` in extra_args
+        params.jobs = Some(2);
+
+        assert_eq!(
+            params.pre_action_args()[10..],
+            ["-sdk", "iphonesimulator", "-quiet", "-jobs", "4"]
+        );
+        assert_eq!(
+            params.post_action_args(),
+            [
+                "CODE_SIGNING_ALLOWED=NO",
+                "SWIFT_ACTIVE_COMPILATION_CONDITIONS=CI"
+            ]
+        );
+        assert_eq!(
+            params.to_args()[10..],
+            [
+                "-sdk",
+                "iphonesimulator",
+                "-quiet",
+                "-jobs",
+                "4",
+                "build",
+                "CODE_SIGNING_ALLOWED=NO",
+                "SWIFT_ACTIVE_COMPILATION_CONDITIONS=CI"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_build_args_with_explicit_actions() {
+        let params = scheme_params(&["clean", "build", "-allowProvisioningUpdates"]);
+        assert_eq!(
+            params.to_args()[10..],
+            ["-allowProvisioningUpdates", "clean", "build"]
+        );
+
+        let params = scheme_params(&["-xcconfig", "Configs/CI.xcconfig", "analyze"]);
+        assert_eq!(
+            params.to_args()[10..],
+            ["-xcconfig", "Configs/CI.xcconfig", "analyze"]
+        );
+
+        // Conditional settings, and `-option=value` user defaults
+        let params = scheme_params(&[
+            "-IDEBuildOperationMaxNumberOfConcurrentCompileTasks=4",
+            "ARCHS[sdk=iphonesimulator*]=arm64",
+        ]);
+        assert_eq!(
+            params.to_args()[10..],
+            [
+                "-IDEBuildOperationMaxNumberOfConcurrentCompileTasks=4",
+                "build",
+                "ARCHS[sdk=iphonesimulator*]=arm64"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_build_args_keep_option_values_with_options() {
+        let params = scheme_params(&[
+            "-destination",
+            "platform=iOS Simulator,name=iPhone 15",
+            "CODE_SIGNING_ALLOWED=NO",
+            "-configuration",
+            "build",
+        ]);
+        assert_eq!(
+            params.to_args()[10..],
+            [
+                "-destination",
+                "platform=iOS Simulator,name=iPhone 15",
+                "-configuration",
+                "build",
+                "build",
+                "CODE_SIGNING_ALLOWED=NO"
+            ]
+        );
+
+        let params = scheme_params(&["-scheme", "test", "clean"]);
+        assert_eq!(params.to_args()[10..], ["-scheme", "test", "clean"]);
+    }
+
+    #[test]
+    fn test_localization_args() {
+        let args = export_localizations_args(
+            "/src/App.xcodeproj",
+            "/tmp/loc",
+            &["fr".to_string(), "de".to_string()],
+            true,
+        );
+        assert_eq!(
+            args,
+            [
+                "-exportLocalizations",
+                "-project",
+                "/src/App.xcodeproj",
+                "-localizationPath",
+                "/tmp/loc",
+                "-exportLanguage",
+                "fr",
+                "-exportLanguage",
+                "de",
+                "-includeScreenshots"
+            ]
+        );
+        assert_eq!(
+            default_working_directory(&args),
+            Some(PathBuf::from("/src"))
+        );
+
+        let args = import_localizations_args("/src/App.xcodeproj", "/tmp/loc/fr.xcloc", false);
+        assert_eq!(
+            args,
+            [
+                "-importLocalizations",
+                "-project",
+                "/src/App.xcodeproj",
+                "-localizationPath",
+                "/tmp/loc/fr.xcloc"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_resolve_packages_args() {
+        let mut params = scheme_params(&["-sdk", "iphonesimulator"]);
+        params.result_bundle_path = Some("/tmp/r.xcresult".to_string());
+        params.skip_plugin_validation = true;
+
+        assert_eq!(
+            params.resolve_packages_args(),
+            [
+                "-resolvePackageDependencies",
+                "-workspace",
+                "App.xcworkspace",
+                "-scheme",
+                "App",
+                "-derivedDataPath",
+                "/tmp/dd",
+                "-skipPackagePluginValidation",
+                "-skipMacroValidation"
+            ]
+        );
+        assert!(params
+            .to_args()
+            .contains(&"-skipPackagePluginValidation".to_string()));
+    }
+
+    #[test]
+    fn test_test_args() {
+        let params = TestParams {
+            project: Some("App.xcodeproj".to_string()),
+            workspace: None,
+            scheme: Some("AppTests".to_string()),
+            xctestrun_path: None,
+            destination: Some("id=AAAA".to_string()),
+            destination_timeout: Some(30),
+            test_plan: Some("Localizations".to_string()),
+            only_test_configuration: vec!["German".to_string()],
+            skip_test_configuration: vec!["Arabic".to_string()],
+            test_language: Some("de".to_string()),
+            test_region: Some("AT".to_string()),
+            toolchain: None,
+            only_testing: vec!["AppTests/LoginTests".to_string()],
+            skip_testing: vec!["AppTests/SlowTests".to_string()],
+            parallel_testing: true,
+            parallel_workers: Some(2),
+            result_bundle_path: None,
+            jobs: Some(2),
+        };
+
+        assert_eq!(
+            params.to_args(),
+            [
+                "test",
+                "-project",
+                "App.xcodeproj",
+                "-scheme",
+                "AppTests",
+                "-destination",
+                "id=AAAA",
+                "-destination-timeout",
+                "30",
+                "-testPlan",
+                "Localizations",
+                "-only-test-configuration",
+                "German",
+                "-skip-test-configuration",
+                "Arabic",
+                "-testLanguage",
+                "de",
+                "-testRegion",
+                "AT",
+                "-parallel-testing-enabled",
+                "YES",
+                "-parallel-testing-worker-count",
+                "2",
+                "-jobs",
+                "2",
+                "-only-testing",
+                "AppTests/LoginTests",
+                "-skip-testing",
+                "AppTests/SlowTests"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_test_args_for_xctestrun() {
+        let params = TestParams {
+            project: None,
+            workspace: None,
+            scheme: None,
+            xctestrun_path: Some("/tmp/App_iphonesimulator17.2-arm64.xctestrun".to_string()),
+            destination: Some("id=AAAA".to_string()),
+            destination_timeout: None,
+            test_plan: None,
+            only_test_configuration: vec![],
+            skip_test_configuration: vec![],
+            test_language: None,
+            test_region: None,
+            toolchain: None,
+            only_testing: vec![],
+            skip_testing: vec![],
+            parallel_testing: false,
+            parallel_workers: None,
+            result_bundle_path: Some("/tmp/run.xcresult".to_string()),
+            jobs: None,
+        };
+
+        assert_eq!(
+            params.to_args(),
+            [
+                "test-without-building",
+                "-xctestrun",
+                "/tmp/App_iphonesimulator17.2-arm64.xctestrun",
+                "-destination",
+                "id=AAAA",
+                "-resultBundlePath",
+                "/tmp/run.xcresult",
+                "-resultBundleVersion",
+                RESULT_BUNDLE_VERSION,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate_environment() {
+        let env = |key: &str| HashMap::from([(key.to_string(), "1".to_string())]);
+
+        assert!(validate_environment(&env("CI")).is_ok());
+        assert!(validate_environment(&env("GIT_COMMIT")).is_ok());
+        assert!(validate_environment(&env("PATH")).is_err());
+        assert!(validate_environment(&env("DYLD_INSERT_LIBRARIES")).is_err());
+        assert!(validate_environment(&env("A=B")).is_err());
+        assert!(validate_environment(&env("")).is_err());
+    }
+
+    #[test]
+    fn test_validate_test_locale() {
+        for language in ["de", "fil", "zh-Hans", "pt_BR", "sr-Latn-RS"] {
+            assert!(
+                validate_test_locale(Some(language), None).is_ok(),
+                "{}",
+                language
+            );
+        }
+        for language in ["", "d", "german", "de-", "en US", "-testRegion"] {
+            assert!(
+                validate_test_locale(Some(language), None).is_err(),
+                "{}",
+                language
+            );
+        }
+        for region in ["AT", "us", "419"] {
+            assert!(
+                validate_test_locale(None, Some(region)).is_ok(),
+                "{}",
+                region
+            );
+        }
+        for region in ["", "A", "AUT", "41", "en_US"] {
+            assert!(
+                validate_test_locale(None, Some(region)).is_err(),
+                "{}",
+                region
+            );
+        }
+    }
+
+    #[test]
+    fn test_tool_command_uses_developer_dir() {
+        let location = XcodeLocation {
+            xcodebuild: PathBuf::from(
+                "/Applications/Xcode-15.4.app/Contents/Developer/usr/bin/xcodebuild",
+            ),
+            developer_dir: Some(PathBuf::from(
+                "/Applications/Xcode-15.4.app/Contents/Developer",
+            )),
+        };
+        let developer_dir = |cmd: &Command| {
+            cmd.as_std()
+                .get_envs()
+                .find(|(name, _)| *name == "DEVELOPER_DIR")
+                .and_then(|(_, value)| value.map(PathBuf::from))
+        };
+
+        for cmd in [
+            command_for(&location),
+            tool_command("xcrun", Some(&location)),
+        ] {
+            assert_eq!(developer_dir(&cmd), location.developer_dir);
+        }
+        assert_eq!(
+            command_for(&location).as_std().get_program(),
+            location.xcodebuild
+        );
+        assert_eq!(developer_dir(&tool_command("xcode-select", None)), None);
+    }
+
+    #[test]
+    fn test_developer_dir_for() {
+        assert_eq!(
+            developer_dir_for(Path::new("/Applications/Xcode-15.4.app")),
+            PathBuf::from("/Applications/Xcode-15.4.app/Contents/Developer")
+        );
+        assert_eq!(
+            developer_dir_for(Path::new("/Applications/Xcode.app/Contents/Developer")),
+            PathBuf::from("/Applications/Xcode.app/Contents/Developer")
+        );
+    }
+
+    #[test]
+    fn test_parse_destinations() {
+        let output = "\
+Command line invocation:
+    /usr/bin/xcodebuild -showdestinations -scheme MyApp
+
+\tAvailable destinations for the \"MyApp\" scheme:
+\t\t{ platform:macOS, arch:arm64, variant:Designed for [iPad,iPhone], id:00006000-001A, name:My Mac }
+\t\t{ platform:iOS Simulator, id:5A3C1E2F-0000-4D8B-9C7A-1234567890AB, OS:17.2, name:iPhone 15 }
+
+\tIneligible destinations for the \"MyApp\" scheme:
+\t\t{ platform:iOS, id:dvtdevice-DVTiPhonePlaceholder-iphoneos:placeholder, name:Any iOS Device, error:iOS 17.2 is not installed. }
+";
+        let destinations = parse_destinations(output);
+
+        assert_eq!(destinations.len(), 2);
+        assert_eq!(destinations[0].platform, "macOS");
+        assert_eq!(destinations[0].arch.as_deref(), Some("arm64"));
+        assert_eq!(
+            destinations[0].variant.as_deref(),
+            Some("Designed for [iPad,iPhone]")
+        );
+        assert_eq!(destinations[1].name.as_deref(), Some("iPhone 15"));
+        assert_eq!(destinations[1].os_version.as_deref(), Some("17.2"));
+        assert_eq!(
+            destinations[1].destination,
+            "platform=iOS Simulator,id=5A3C1E2F-0000-4D8B-9C7A-1234567890AB"
+        );
+    }
+
+    #[test]
+    fn test_find_toolchain_by_name_and_identifier() {
+        let dir = std::env::temp_dir().join(format!("xcbridge-{}", uuid::Uuid::new_v4()));
+        let bundle = dir.join("swift-DEVELOPMENT-SNAPSHOT-2024-01-01-a.xctoolchain");
+        std::fs::create_dir_all(&bundle).unwrap();
+        std::fs::write(
+            bundle.join("Info.plist"),
+            "<dict>\n<key>CFBundleIdentifier</key>\n<string>org.swift.59202401011a</string>\n</dict>",
+        )
+        .unwrap();
+        let dirs = vec![dir.clone()];
+
+        assert_eq!(
+            find_toolchain_in(&dirs, "swift-DEVELOPMENT-SNAPSHOT-2024-01-01-a"),
+            Some(bundle.clone())
+        );
+        assert_eq!(
+            find_toolchain_in(&dirs, "org.swift.59202401011a"),
+            Some(bundle)
+        );
+        assert_eq!(find_toolchain_in(&dirs, "org.swift.missing"), None);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_build_process_wait_reads_cpu_time() {
+        let (process, _stdout, _stderr) = BuildProcess::spawn(
+            std::process::Command::new("sh")
+                .args([
+                    "-c",
+                    "i=0; while [ $i -lt 20000 ]; do i=$((i+1)); done; exit 3",
+                ])
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped()),
+        )
+        .unwrap();
+
+        let (status, cpu_time) = process.wait().await.unwrap();
+        assert_eq!(status.code(), Some(3));
+        assert!(cpu_time.user_secs + cpu_time.system_secs > 0.0);
+    }
+
+    #[tokio::test]
+    async fn test_list_sdks_with_mock_runner() {
+        let output = r#"[
+            {"canonicalName": "iphoneos17.2", "displayName": "iOS 17.2", "platform": "iphoneos", "sdkVersion": "17.2", "isBaseSdk": true},
+            {"canonicalName": "iphonesimulator17.2", "displayName": "Simulator - iOS 17.2", "platform": "iphonesimulator", "sdkVersion": "17.2"}
+        ]"#;
+        let mock = Arc::new(MockRunner::new().on(&["xcodebuild", "-showsdks", "-json"], output));
+
+        let sdks = runner::with_runner(mock, list_sdks()).await.unwrap();
+        assert_eq!(sdks.len(), 2);
+        assert_eq!(sdks[1].canonical_name, "iphonesimulator17.2");
+        assert_eq!(sdks[1].platform, "iphonesimulator");
+        assert_eq!(sdks[1].sdk_version.as_deref(), Some("17.2"));
+    }
+
+    #[test]
+    fn test_download_platform_args() {
+        assert_eq!(
+            download_platform_args("iOS", None),
+            ["-downloadPlatform", "iOS"]
+        );
+        assert_eq!(
+            download_platform_args("visionOS", Some("2.0")),
+            ["-downloadPlatform", "visionOS", "-buildVersion", "2.0"]
+        );
+    }
+
+    #[test]
+    fn test_default_working_directory() {
+        let args = |args: &[&str]| args.iter().map(|a| a.to_string()).collect::<Vec<_>>();
+
+        assert_eq!(
+            default_working_directory(&args(&["-project", "/src/MyApp/MyApp.xcodeproj"])),
+            Some(PathBuf::from("/src/MyApp"))
+        );
+        assert_eq!(
+            default_working_directory(&args(&[
+                "-scheme",
+                "MyApp",
+                "-workspace",
+                "/src/App.xcworkspace"
+            ])),
+            Some(PathBuf::from("/src"))
+        );
+        assert_eq!(
+            default_working_directory(&args(&["-project", "MyApp.xcodeproj"])),
+            None
+        );
+        assert_eq!(default_working_directory(&args(&["-version"])), None);
+    }
 }
@@ -0,0 +1,583 @@
+// Copyright 2026 Aptove
+// SPDX-License-Identifier: Apache-2.0
+
+//! xcresulttool wrapper for reading result bundles
+
+use crate::error::{Result, XcbridgeError};
+use crate::xcode::xcodebuild;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Structured build outcome read from a result bundle
+#[derive(Debug, Clone, Serialize)]
+pub struct BuildDiagnostics {
+    pub error_count: u32,
+    pub warning_count: u32,
+    pub analyzer_warning_count: u32,
+    pub errors: Vec<BuildIssue>,
+    pub warnings: Vec<BuildIssue>,
+    /// Build duration in seconds, as recorded by xcodebuild
+    pub duration_secs: Option<f64>,
+}
+
+/// A compiler or build system issue
+#[derive(Debug, Clone, Serialize)]
+pub struct BuildIssue {
+    /// e.g. "Swift Compiler Error"
+    pub issue_type: String,
+    pub message: String,
+    pub file: Option<String>,
+    pub line: Option<u32>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BuildResults {
+    #[serde(default)]
+    error_count: u32,
+    #[serde(default)]
+    warning_count: u32,
+    #[serde(default)]
+    analyzer_warning_count: u32,
+    #[serde(default)]
+    errors: Vec<Issue>,
+    #[serde(default)]
+    warnings: Vec<Issue>,
+    start_time: Option<f64>,
+    end_time: Option<f64>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Issue {
+    #[serde(default)]
+    issue_type: String,
+    message: String,
+    #[serde(rename = "sourceURL")]
+    source_url: Option<String>,
+}
+
+impl From<Issue> for BuildIssue {
+    fn from(issue: Issue) -> Self {
+        let (file, line) = issue
+            .source_url
+            .as_deref()
+            .map(parse_source_url)
+            .unwrap_or_default();
+        BuildIssue {
+            issue_type: issue.issue_type,
+            message: issue.message,
+            file,
+            line,
+        }
+    }
+}
+
+/// Split a result bundle source URL into its file path and (1-based) line
+///
+/// `file:///src/App.swift#EndingLineNumber=41&StartingColumnNumber=8&StartingLineNumber=41`
+fn parse_source_url(url: &str) -> (Option<String>, Option<u32>) {
+    let (path, fragment) = url.split_once('#').unwrap_or((url, ""));
+    let file = path.strip_prefix("file://").unwrap_or(path).to_string();
+
+    // Line numbers in result bundles are zero-based
+    let line = fragment
+        .split('&')
+        .find_map(|param| param.strip_prefix("StartingLineNumber="))
+        .and_then(|line| line.parse::<u32>().ok())
+        .map(|line| line + 1);
+
+    (Some(file), line)
+}
+
+/// Aggregate test outcome read from a result bundle
+///
+/// Unlike counts scraped from the log, this stays accurate when parallel
+/// test workers interleave their output.
+#[derive(Debug, Clone, Serialize)]
+pub struct TestSummary {
+    pub total: u32,
+    pub passed: u32,
+    pub failed: u32,
+    pub skipped: u32,
+    pub expected_failures: u32,
+    /// Test run duration in seconds
+    pub duration_secs: Option<f64>,
+    pub failures: Vec<TestSummaryFailure>,
+    /// Every test case that ran
+    pub tests: Vec<TestCaseRun>,
+}
+
+/// A test case that ran, from the result bundle's test tree
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TestCaseRun {
+    /// e.g. "LoginTests/testValidLogin()"
+    pub identifier: String,
+    /// "Passed", "Failed", "Skipped" or "Expected Failure"
+    pub result: String,
+    pub duration_secs: Option<f64>,
+}
+
+/// A failed test from the result bundle summary
+#[derive(Debug, Clone, Serialize)]
+pub struct TestSummaryFailure {
+    pub test_name: String,
+    pub target_name: Option<String>,
+    pub message: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TestResultsSummary {
+    #[serde(default)]
+    total_test_count: u32,
+    #[serde(default)]
+    passed_tests: u32,
+    #[serde(default)]
+    failed_tests: u32,
+    #[serde(default)]
+    skipped_tests: u32,
+    #[serde(default)]
+    expected_failures: u32,
+    start_time: Option<f64>,
+    finish_time: Option<f64>,
+    #[serde(default)]
+    test_failures: Vec<TestFailureEntry>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TestFailureEntry {
+    test_name: String,
+    target_name: Option<String>,
+    #[serde(default)]
+    failure_text: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TestResultsTests {
+    #[serde(default)]
+    test_nodes: Vec<TestNode>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TestNode {
+    name: String,
+    node_type: String,
+    node_identifier: Option<String>,
+    result: Option<String>,
+    duration_in_seconds: Option<f64>,
+    #[serde(default)]
+    children: Vec<TestNode>,
+}
+
+/// A file a test attached with `XCTAttachment`, exported from a result bundle
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TestAttachment {
+    /// Test that added it, e.g. "LoginTests/testValidLogin()"
+    pub test_identifier: String,
+    /// Name given to the attachment in the test
+    pub name: String,
+    /// Exported file name, within the export directory
+    pub file: String,
+    /// Added as part of a test failure
+    pub associated_with_failure: bool,
+    /// When it was added (seconds since the Unix epoch)
+    pub timestamp: Option<f64>,
+    /// Device the test ran on
+    pub device_name: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AttachmentManifestEntry {
+    test_identifier: String,
+    #[serde(default)]
+    attachments: Vec<AttachmentManifestItem>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AttachmentManifestItem {
+    exported_file_name: String,
+    suggested_human_readable_name: Option<String>,
+    #[serde(default)]
+    is_associated_with_failure: bool,
+    timestamp: Option<f64>,
+    device_name: Option<String>,
+}
+
+/// Read the build results from a result bundle
+pub async fn build_results(path: &Path) -> Result<BuildDiagnostics> {
+    let output = xcodebuild::xcrun()
+        .args([
+            "xcresulttool",
+            "get",
+            "build-results",
+            "--compact",
+            "--path",
+        ])
+        .arg(path)
+        .output()
+        .await
+        .map_err(|e| XcbridgeError::CommandFailed(format!("xcresulttool failed: {}", e)))?;
+
+    if !output.status.success() {
+        return Err(XcbridgeError::CommandFailed(
+            String::from_utf8_lossy(&output.stderr).to_string(),
+        ));
+    }
+
+    parse_build_results(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// Read the test summary from a result bundle
+pub async fn test_summary(path: &Path) -> Result<TestSummary> {
+    let output = xcodebuild::xcrun()
+        .args([
+            "xcresulttool",
+            "get",
+            "test-results",
+            "summary",
+            "--compact",
+            "--path",
+        ])
+        .arg(path)
+        .output()
+        .await
+        .map_err(|e| XcbridgeError::CommandFailed(format!("xcresulttool failed: {}", e)))?;
+
+    if !output.status.success() {
+        return Err(XcbridgeError::CommandFailed(
+            String::from_utf8_lossy(&output.stderr).to_string(),
+        ));
+    }
+
+    let mut summary = parse_test_summary(&String::from_utf8_lossy(&output.stdout))?;
+    match test_cases(path).await {
+        Ok(tests) => summary.tests = tests,
+        Err(e) => tracing::warn!("Failed to read tests from result bundle: {}", e),
+    }
+    Ok(summary)
+}
+
+/// Read the test cases that ran from a result bundle
+async fn test_cases(path: &Path) -> Result<Vec<TestCaseRun>> {
+    let output = xcodebuild::xcrun()
+        .args([
+            "xcresulttool",
+            "get",
+            "test-results",
+            "tests",
+            "--compact",
+            "--path",
+        ])
+        .arg(path)
+        .output()
+        .await
+        .map_err(|e| XcbridgeError::CommandFailed(format!("xcresulttool failed: {}", e)))?;
+
+    if !output.status.success() {
+        return Err(XcbridgeError::CommandFailed(
+            String::from_utf8_lossy(&output.stderr).to_string(),
+        ));
+    }
+
+    parse_test_cases(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// Export the attachments in a result bundle to `output`
+///
+/// xcresulttool writes each attachment to `output` along with a
+/// `manifest.json` describing them, which is read back here.
+pub async fn export_attachments(path: &Path, output: &Path) -> Result<Vec<TestAttachment>> {
+    let result = xcodebuild::xcrun()
+        .args(["xcresulttool", "export", "attachments", "--path"])
+        .arg(path)
+        .arg("--output-path")
+        .arg(output)
+        .output()
+        .await
+        .map_err(|e| XcbridgeError::CommandFailed(format!("xcresulttool failed: {}", e)))?;
+
+    if !result.status.success() {
+        return Err(XcbridgeError::CommandFailed(
+            String::from_utf8_lossy(&result.stderr).to_string(),
+        ));
+    }
+
+    read_attachment_manifest(output).await
+}
+
+/// Read the `manifest.json` of an attachment export
+pub async fn read_attachment_manifest(output: &Path) -> Result<Vec<TestAttachment>> {
+    let json = tokio::fs::read_to_string(output.join("manifest.json"))
+        .await
+        .map_err(|e| {
+            XcbridgeError::Internal(format!("Failed to read attachment manifest: {}", e))
+        })?;
+    parse_attachment_manifest(&json)
+}
+
+fn parse_attachment_manifest(json: &str) -> Result<Vec<TestAttachment>> {
+    let manifest: Vec<AttachmentManifestEntry> = serde_json::from_str(json).map_err(|e| {
+        XcbridgeError::Internal(format!("Failed to parse attachment manifest: {}", e))
+    })?;
+
+    Ok(manifest
+        .into_iter()
+        .flat_map(|entry| {
+            let test_identifier = entry.test_identifier;
+            entry
+                .attachments
+                .into_iter()
+                .map(move |attachment| TestAttachment {
+                    test_identifier: test_identifier.clone(),
+                    name: attachment
+                        .suggested_human_readable_name
+                        .unwrap_or_else(|| attachment.exported_file_name.clone()),
+                    file: attachment.exported_file_name,
+                    associated_with_failure: attachment.is_associated_with_failure,
+                    timestamp: attachment.timestamp,
+                    device_name: attachment.device_name,
+                })
+        })
+        .collect())
+}
+
+fn parse_test_cases(json: &str) -> Result<Vec<TestCaseRun>> {
+    let tests: TestResultsTests = serde_json::from_str(json)
+        .map_err(|e| XcbridgeError::Internal(format!("Failed to parse test results: {}", e)))?;
+
+    fn collect(nodes: Vec<TestNode>, cases: &mut Vec<TestCaseRun>) {
+        for node in nodes {
+            if node.node_type == "Test Case" {
+                cases.push(TestCaseRun {
+                    identifier: node.node_identifier.unwrap_or(node.name),
+                    result: node.result.unwrap_or_default(),
+                    duration_secs: node.duration_in_seconds,
+                });
+            } else {
+                collect(node.children, cases);
+            }
+        }
+    }
+
+    let mut cases = Vec::new();
+    collect(tests.test_nodes, &mut cases);
+    Ok(cases)
+}
+
+fn parse_test_summary(json: &str) -> Result<TestSummary> {
+    let summary: TestResultsSummary = serde_json::from_str(json)
+        .map_err(|e| XcbridgeError::Internal(format!("Failed to parse test summary: {}", e)))?;
+
+    let duration_secs = match (summary.start_time, summary.finish_time) {
+        (Some(start), Some(finish)) => Some(finish - start),
+        _ => None,
+    };
+
+    Ok(TestSummary {
+        total: summary.total_test_count,
+        passed: summary.passed_tests,
+        failed: summary.failed_tests,
+        skipped: summary.skipped_tests,
+        expected_failures: summary.expected_failures,
+        duration_secs,
+        failures: summary
+            .test_failures
+            .into_iter()
+            .map(|failure| TestSummaryFailure {
+                test_name: failure.test_name,
+                target_name: failure.target_name,
+                message: failure.failure_text,
+            })
+            .collect(),
+        tests: Vec::new(),
+    })
+}
+
+fn parse_build_results(json: &str) -> Result<BuildDiagnostics> {
+    let results: BuildResults = serde_json::from_str(json)
+        .map_err(|e| XcbridgeError::Internal(format!("Failed to parse build results: {}", e)))?;
+
+    let duration_secs = match (results.start_time, results.end_time) {
+        (Some(start), Some(end)) => Some(end - start),
+        _ => None,
+    };
+
+    Ok(BuildDiagnostics {
+        error_count: results.error_count,
+        warning_count: results.warning_count,
+        analyzer_warning_count: results.analyzer_warning_count,
+        errors: results.errors.into_iter().map(Into::into).collect(),
+        warnings: results.warnings.into_iter().map(Into::into).collect(),
+        duration_secs,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_build_results() {
+        let json = r#"{
+            "actionTitle": "Build \"MyApp\"",
+            "status": "failed",
+            "startTime": 1718000000.5,
+            "endTime": 1718000042.0,
+            "errorCount": 1,
+            "warningCount": 1,
+            "analyzerWarningCount": 0,
+            "errors": [{
+                "issueType": "Swift Compiler Error",
+                "message": "Cannot find 'foo' in scope",
+                "targetName": "MyApp",
+                "sourceURL": "file:///src/MyApp/ContentView.swift#EndingColumnNumber=12&EndingLineNumber=9&StartingColumnNumber=9&StartingLineNumber=9"
+            }],
+            "warnings": [{
+                "issueType": "Deprecation",
+                "message": "'foo()' is deprecated"
+            }]
+        }"#;
+
+        let diagnostics = parse_build_results(json).unwrap();
+
+        assert_eq!(diagnostics.error_count, 1);
+        assert_eq!(diagnostics.warning_count, 1);
+        assert_eq!(diagnostics.duration_secs, Some(41.5));
+        assert_eq!(diagnostics.errors[0].issue_type, "Swift Compiler Error");
+        assert_eq!(
+            diagnostics.errors[0].file.as_deref(),
+            Some("/src/MyApp/ContentView.swift")
+        );
+        assert_eq!(diagnostics.errors[0].line, Some(10));
+        assert_eq!(diagnostics.warnings[0].file, None);
+    }
+
+    #[test]
+    fn test_parse_test_cases() {
+        let json = r#"{
+            "devices": [],
+            "testNodes": [{
+                "name": "MyApp",
+                "nodeType": "Test Plan",
+                "result": "Failed",
+                "children": [{
+                    "name": "MyAppTests",
+                    "nodeType": "Unit test bundle",
+                    "result": "Failed",
+                    "children": [{
+                        "name": "LoginTests",
+                        "nodeType": "Test Suite",
+                        "result": "Failed",
+                        "children": [
+                            {
+                                "name": "testValidLogin()",
+                                "nodeIdentifier": "LoginTests/testValidLogin()",
+                                "nodeType": "Test Case",
+                                "result": "Passed",
+                                "duration": "0.012s",
+                                "durationInSeconds": 0.012
+                            },
+                            {
+                                "name": "testLockout()",
+                                "nodeIdentifier": "LoginTests/testLockout()",
+                                "nodeType": "Test Case",
+                                "result": "Failed",
+                                "children": [
+                                    {"name": "XCTAssertEqual failed", "nodeType": "Failure Message", "result": "Failed"}
+                                ]
+                            }
+                        ]
+                    }]
+                }]
+            }]
+        }"#;
+
+        let cases = parse_test_cases(json).unwrap();
+        assert_eq!(
+            cases,
+            [
+                TestCaseRun {
+                    identifier: "LoginTests/testValidLogin()".to_string(),
+                    result: "Passed".to_string(),
+                    duration_secs: Some(0.012),
+                },
+                TestCaseRun {
+                    identifier: "LoginTests/testLockout()".to_string(),
+                    result: "Failed".to_string(),
+                    duration_secs: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_attachment_manifest() {
+        let json = r#"[
+            {
+                "testIdentifier": "LoginUITests/testLogin()",
+                "testIdentifierURL": "test://com.apple.xcode/MyApp/MyAppUITests/LoginUITests/testLogin",
+                "attachments": [
+                    {
+                        "exportedFileName": "0C1A9A7E-5B7D-4C4B-9E0B-1F0D6E0A1C2B.png",
+                        "suggestedHumanReadableName": "Login screen_0_0C1A9A7E.png",
+                        "isAssociatedWithFailure": true,
+                        "timestamp": 1718000003.5,
+                        "configurationName": "Test Scheme Action",
+                        "deviceName": "iPhone 15",
+                        "deviceId": "AAAA"
+                    },
+                    {
+                        "exportedFileName": "8E2F.txt",
+                        "isAssociatedWithFailure": false
+                    }
+                ]
+            }
+        ]"#;
+
+        let attachments = parse_attachment_manifest(json).unwrap();
+        assert_eq!(attachments.len(), 2);
+        assert_eq!(attachments[0].test_identifier, "LoginUITests/testLogin()");
+        assert_eq!(attachments[0].name, "Login screen_0_0C1A9A7E.png");
+        assert!(attachments[0].associated_with_failure);
+        assert_eq!(attachments[0].device_name.as_deref(), Some("iPhone 15"));
+        assert_eq!(attachments[1].name, "8E2F.txt");
+        assert_eq!(attachments[1].timestamp, None);
+    }
+
+    #[test]
+    fn test_parse_test_summary() {
+        let json = r#"{
+            "title": "Test - MyApp",
+            "result": "Failed",
+            "startTime": 1718000000.0,
+            "finishTime": 1718000012.25,
+            "totalTestCount": 12,
+            "passedTests": 9,
+            "failedTests": 1,
+            "skippedTests": 2,
+            "expectedFailures": 0,
+            "testFailures": [{
+                "testName": "testInvalidLogin()",
+                "targetName": "MyAppTests",
+                "failureText": "XCTAssertTrue failed",
+                "testIdentifier": 3
+            }]
+        }"#;
+
+        let summary = parse_test_summary(json).unwrap();
+
+        assert_eq!(summary.total, 12);
+        assert_eq!(summary.passed, 9);
+        assert_eq!(summary.failed, 1);
+        assert_eq!(summary.skipped, 2);
+        assert_eq!(summary.duration_secs, Some(12.25));
+        assert_eq!(summary.failures[0].test_name, "testInvalidLogin()");
+        assert_eq!(summary.failures[0].message, "XCTAssertTrue failed");
+    }
+}